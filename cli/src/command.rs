@@ -1,9 +1,21 @@
-use std::{path::Path, process::Command, thread::sleep, time::Duration};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    thread::sleep,
+    time::Duration,
+};
 
-use crate::{errors::AppResult, imp::PPDrive};
-use clap::{Parser, Subcommand, ValueEnum};
+use crate::{
+    errors::{AppResult, Error},
+    imp::PPDrive,
+};
+use clap::{ArgMatches, Args, Parser, Subcommand, ValueEnum};
 use ppd_shared::{
-    opts::{ServiceAuthConfig, ServiceBaseConfig, ServiceConfig, ServiceType},
+    config::{CONFIG_FILE_ENV, load_config},
+    opts::{
+        ClientScope, NewClientKey, ServiceAuthConfig, ServiceBaseConfig, ServiceConfig,
+        ServiceType,
+    },
     tools::root_dir,
 };
 
@@ -20,11 +32,19 @@ pub struct Cli {
 }
 
 impl Cli {
-    pub fn run(self) -> AppResult<()> {
+    /// run the command. `matches` are the matches the command was parsed from, used to
+    /// tell flags set by users from default values.
+    pub fn run(self, matches: &ArgMatches) -> AppResult<()> {
         let port = self.port.unwrap_or(5025);
 
         match self.command {
-            CliCommand::Start => {
+            CliCommand::Start {
+                service_id: Some(id),
+            } => {
+                PPDrive::launch_saved(id, port)?;
+            }
+
+            CliCommand::Start { service_id: None } => {
                 tracing::info!("start ppdrive manager...");
                 start_manager::<String>(port, None)?;
 
@@ -44,24 +64,31 @@ impl Cli {
             }
 
             CliCommand::Launch {
-                svc,
-                base_config,
-                auth_config,
+                service,
                 yes_auto_install: auto_install,
                 remove_deps: reload,
+                save,
+                no_autostart,
             } => {
-                let config = ServiceConfig {
-                    ty: svc,
-                    base: base_config,
-                    auth: auth_config,
-                    auto_install,
-                    reload_deps: reload,
-                };
-
-                PPDrive::add(config, port)?;
+                let matches = subcommand_matches(matches, &["launch"])?;
+                let mut config = service.load(matches)?;
+                config.auto_install = auto_install;
+                config.reload_deps = reload;
+
+                if save {
+                    PPDrive::save(config, port)?;
+                } else {
+                    PPDrive::add(config, !no_autostart, port)?;
+                }
             }
-            CliCommand::Stop { id } => match id {
-                Some(id) => PPDrive::cancel(id, port)?,
+            CliCommand::Config { command } => match command {
+                ConfigCommand::Show { service } => {
+                    let matches = subcommand_matches(matches, &["config", "show"])?;
+                    PPDrive::show_config(service.load(matches)?)?;
+                }
+            },
+            CliCommand::Stop { id, remove } => match id {
+                Some(id) => PPDrive::cancel(id, remove, port)?,
                 None => PPDrive::stop(port)?,
             },
             CliCommand::List => {
@@ -84,6 +111,49 @@ impl Cli {
                 ClientCommand::List { service_id } => {
                     PPDrive::get_client_list(port, service_id)?;
                 }
+                ClientCommand::Key { command } => match command {
+                    ClientKeyCommand::Add {
+                        service_id,
+                        client_id,
+                        name,
+                        scopes,
+                        expires_in_days,
+                    } => {
+                        let opts = NewClientKey {
+                            name,
+                            scopes,
+                            expires_in_days,
+                        };
+
+                        PPDrive::add_client_key(port, service_id, client_id, opts)?;
+                    }
+                    ClientKeyCommand::List {
+                        service_id,
+                        client_id,
+                    } => {
+                        PPDrive::list_client_keys(port, service_id, client_id)?;
+                    }
+                    ClientKeyCommand::Revoke { service_id, key_id } => {
+                        PPDrive::revoke_client_key(port, service_id, key_id)?;
+                    }
+                },
+            },
+            CliCommand::Secrets { command } => match command {
+                SecretsCommand::Rotate { keep } => {
+                    PPDrive::rotate_secrets(port, keep)?;
+                }
+                SecretsCommand::Generate { output } => {
+                    PPDrive::generate_secrets(output)?;
+                }
+            },
+            CliCommand::Admin { command } => match command {
+                AdminCommand::Create {
+                    service_id,
+                    username,
+                    password,
+                } => {
+                    PPDrive::create_admin(port, service_id, username, password)?;
+                }
             },
             _ => unimplemented!("this command is not supported"),
         }
@@ -94,21 +164,21 @@ impl Cli {
 
 #[derive(Subcommand, Debug)]
 enum CliCommand {
-    /// start ppdrive service manager
-    Start,
+    /// start ppdrive service manager. Recorded services with autostart are launched
+    /// with it.
+    Start {
+        /// start a recorded service on the running manager instead.
+        #[arg(long("svc-id"))]
+        service_id: Option<u8>,
+    },
 
     /// check whether ppdrive instance is running (on the specified port).
     Status,
 
     /// launch a ppdrive service
     Launch {
-        svc: ServiceType,
-
-        #[command(flatten)]
-        base_config: ServiceBaseConfig,
-
         #[command(flatten)]
-        auth_config: ServiceAuthConfig,
+        service: ServiceArgs,
 
         /// automatically install missing plugins and dependencies
         #[arg(default_value_t = false, short)]
@@ -119,11 +189,27 @@ enum CliCommand {
         /// `ppdrive launch rest -ry`
         #[arg(default_value_t = false, short)]
         remove_deps: bool,
+
+        /// record the service without starting it. It's launched the next time the manager
+        /// starts, or with `ppdrive start --svc-id`.
+        #[arg(long, default_value_t = false)]
+        save: bool,
+
+        /// don't launch the service again when the manager restarts.
+        #[arg(long, default_value_t = false, conflicts_with = "save")]
+        no_autostart: bool,
     },
 
     /// stop ppdrive or a running service.
     /// if id is provided, this will try to stop a running service else, the manager will stopped.
-    Stop { id: Option<u8> },
+    /// stopped services stay recorded and can be started again with `start --svc-id`.
+    Stop {
+        id: Option<u8>,
+
+        /// remove the service from recorded services as well.
+        #[arg(long, requires = "id")]
+        remove: bool,
+    },
 
     /// create a new client for the specified service
     // CreateClient { svc_id: u8, client_name: String },
@@ -131,6 +217,25 @@ enum CliCommand {
         #[command(subcommand)]
         command: ClientCommand,
     },
+
+    /// manage admins of the specified service
+    Admin {
+        #[command(subcommand)]
+        command: AdminCommand,
+    },
+
+    /// manage the secret keys used to issue tokens
+    Secrets {
+        #[command(subcommand)]
+        command: SecretsCommand,
+    },
+
+    /// inspect the configuration of services
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
     /// list services running in service manager
     List,
 
@@ -138,6 +243,51 @@ enum CliCommand {
     Install,
 }
 
+/// options of a service. Values are read from the config file, `PPDRIVE_*` environment
+/// variables and flags, in increasing order of precedence.
+#[derive(Args, Debug)]
+struct ServiceArgs {
+    #[arg(value_enum, default_value_t)]
+    svc: ServiceType,
+
+    /// path of the config file. `ppd_config.toml` is read if it exists in the working
+    /// directory and no path is provided.
+    #[arg(long, env = CONFIG_FILE_ENV)]
+    config: Option<PathBuf>,
+
+    #[command(flatten)]
+    base_config: ServiceBaseConfig,
+
+    #[command(flatten)]
+    auth_config: Box<ServiceAuthConfig>,
+}
+
+impl ServiceArgs {
+    /// build the effective config of the service. `matches` are the matches of the
+    /// subcommand the args were parsed from.
+    fn load(self, matches: &ArgMatches) -> AppResult<ServiceConfig> {
+        let parsed = ServiceConfig {
+            ty: self.svc,
+            base: self.base_config,
+            auth: *self.auth_config,
+            ..Default::default()
+        };
+
+        let config = load_config(&parsed, matches, self.config.as_deref())?;
+        Ok(config)
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// print the effective config of a service, merged from the config file, environment
+    /// variables and flags.
+    Show {
+        #[command(flatten)]
+        service: ServiceArgs,
+    },
+}
+
 #[derive(Subcommand, Debug)]
 enum ClientCommand {
     /// create a new client and receive the client token.
@@ -166,6 +316,87 @@ enum ClientCommand {
         #[arg(long("svc-id"))]
         service_id: u8,
     },
+
+    /// manage named keys of a client.
+    Key {
+        #[command(subcommand)]
+        command: ClientKeyCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ClientKeyCommand {
+    /// create a new key for the client and receive the key's id and token.
+    Add {
+        #[arg(long("svc-id"))]
+        service_id: u8,
+
+        #[arg(long("client-id"))]
+        client_id: String,
+
+        #[arg(long)]
+        name: String,
+
+        /// scopes granted to the key. The key has all scopes if none is provided.
+        #[arg(long, value_delimiter(','))]
+        scopes: Vec<ClientScope>,
+
+        /// number of days after which the key expires.
+        #[arg(long)]
+        expires_in_days: Option<u32>,
+    },
+
+    /// list keys of the client.
+    List {
+        #[arg(long("svc-id"))]
+        service_id: u8,
+
+        #[arg(long("client-id"))]
+        client_id: String,
+    },
+
+    /// revoke a key. Requests made with the key are rejected immediately.
+    Revoke {
+        #[arg(long("svc-id"))]
+        service_id: u8,
+
+        #[arg(long("key-id"))]
+        key_id: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SecretsCommand {
+    /// make a new key current and print client tokens re-issued with it. Tokens issued with
+    /// the previous keys are accepted until the keys are dropped by later rotations.
+    Rotate {
+        /// number of previous keys to keep.
+        #[arg(long, default_value_t = 1)]
+        keep: u8,
+    },
+
+    /// generate new secrets. They are printed as environment variables unless `--output`
+    /// is provided.
+    Generate {
+        /// path of a secrets file to create.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AdminCommand {
+    /// create an admin user who can log in to the service's admin router.
+    Create {
+        #[arg(long("svc-id"))]
+        service_id: u8,
+
+        #[arg(long)]
+        username: String,
+
+        #[arg(long)]
+        password: String,
+    },
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
@@ -173,6 +404,7 @@ enum StartOptions {
     Manager,
     Rest,
     Grpc,
+    S3,
 }
 
 impl From<StartOptions> for ServiceType {
@@ -180,11 +412,21 @@ impl From<StartOptions> for ServiceType {
         match value {
             StartOptions::Grpc => ServiceType::Grpc,
             StartOptions::Rest => ServiceType::Rest,
+            StartOptions::S3 => ServiceType::S3,
             _ => unreachable!("service unknown"),
         }
     }
 }
 
+/// matches of the nested subcommand at `path`.
+fn subcommand_matches<'a>(matches: &'a ArgMatches, path: &[&str]) -> AppResult<&'a ArgMatches> {
+    path.iter().try_fold(matches, |matches, name| {
+        matches
+            .subcommand_matches(name)
+            .ok_or_else(|| Error::Internal(format!("'{name}' command was not parsed")))
+    })
+}
+
 /// start the manager by running appropriate command based on environments. `current_dir`
 /// is the directory from which we run the command.
 pub fn start_manager<P>(port: u16, current_dir: Option<P>) -> AppResult<()>
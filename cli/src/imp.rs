@@ -1,13 +1,16 @@
 use bincode::{Decode, Encode, config};
 
 use ppd_shared::opts::{
-    ClientDetails, ClientInfo, Response, ServiceConfig, ServiceInfo, ServiceRequest,
+    ClientDetails, ClientInfo, ClientKeyInfo, NewClientKey, ReissuedToken, Response,
+    ServiceConfig, ServiceInfo, ServiceRequest,
 };
 
 use crate::errors::{AppResult, Error};
+use ppd_shared::{config::to_toml, tools::AppSecrets};
 use ppdrive::plugin::service::Service;
 use std::{
     io::{Read, Write},
+    path::PathBuf,
     net::TcpStream,
     time::Duration,
 };
@@ -16,12 +19,16 @@ use std::{
 pub struct PPDrive;
 
 impl PPDrive {
-    /// add a new service to the manager
-    pub fn add(config: ServiceConfig, port: u16) -> AppResult<u8> {
+    /// add a new service to the manager. the service is launched again when the manager
+    /// restarts if `autostart` is set.
+    pub fn add(config: ServiceConfig, autostart: bool, port: u16) -> AppResult<u8> {
         let svc = Service::from(&config);
         svc.init()?;
 
-        let resp = Self::send_request::<u8>(ServiceRequest::Add(config.clone()), port)?;
+        let resp = Self::send_request::<u8>(
+            ServiceRequest::Add(Box::new(config.clone()), autostart),
+            port,
+        )?;
         resp.log();
 
         tracing::info!("waiting to validate service startup...");
@@ -36,9 +43,28 @@ impl PPDrive {
         Ok(*resp.body())
     }
 
+    /// record a service in the manager without starting it
+    pub fn save(config: ServiceConfig, port: u16) -> AppResult<u8> {
+        let svc = Service::from(&config);
+        svc.init()?;
+
+        let resp = Self::send_request::<u8>(ServiceRequest::Save(Box::new(config)), port)?;
+        resp.log();
+
+        Ok(*resp.body())
+    }
+
+    /// start a recorded service
+    pub fn launch_saved(id: u8, port: u16) -> AppResult<()> {
+        let resp = Self::send_request::<()>(ServiceRequest::Launch(id), port)?;
+        resp.log();
+
+        Ok(())
+    }
+
     /// cancel a service in the manager
-    pub fn cancel(id: u8, port: u16) -> AppResult<()> {
-        let resp = Self::send_request::<()>(ServiceRequest::Cancel(id), port)?;
+    pub fn cancel(id: u8, remove: bool, port: u16) -> AppResult<()> {
+        let resp = Self::send_request::<()>(ServiceRequest::Cancel(id, remove), port)?;
         resp.log();
 
         Ok(())
@@ -50,22 +76,26 @@ impl PPDrive {
 
         resp.log();
         if !list.is_empty() {
-            println!(" ID\t | Port\t | Type\t | Auth-modes");
+            println!(" ID\t | Port\t | Type\t | Status\t | Autostart\t | Auth-modes");
             for svc in list {
                 let ServiceInfo {
                     id,
                     port,
                     auth_modes,
                     ty,
+                    running,
+                    autostart,
                 } = svc;
 
                 let modes: Vec<String> = auth_modes.iter().map(|m| format!("{m}")).collect();
                 let modes: String = modes.join(", ");
+                let status = if *running { "running" } else { "stopped" };
+                let autostart = if *autostart { "yes" } else { "no" };
 
-                println!(" {id}\t | {port}\t | {ty}\t | {modes}")
+                println!(" {id}\t | {port}\t | {ty}\t | {status}\t | {autostart}\t\t | {modes}")
             }
         } else {
-            println!("no service running");
+            println!("no service recorded");
         }
 
         Ok(())
@@ -91,14 +121,28 @@ impl PPDrive {
     }
 
     pub fn refresh_client_token(port: u16, svc_id: u8, client_id: String) -> AppResult<()> {
-        let resp = Self::send_request::<Option<String>>(
+        let resp = Self::send_request::<Option<ClientDetails>>(
             ServiceRequest::RefreshClientToken(svc_id, client_id),
             port,
         )?;
 
         resp.log();
-        if let Some(token) = resp.body() {
-            println!("{token}");
+        if let Some(client) = resp.body() {
+            println!("{client}");
+        }
+
+        Ok(())
+    }
+
+    pub fn create_admin(port: u16, svc_id: u8, username: String, password: String) -> AppResult<()> {
+        let resp = Self::send_request::<Option<String>>(
+            ServiceRequest::CreateAdmin(svc_id, username, password),
+            port,
+        )?;
+
+        resp.log();
+        if let Some(id) = resp.body() {
+            println!("{id}");
         }
 
         Ok(())
@@ -132,6 +176,130 @@ impl PPDrive {
         Ok(())
     }
 
+    pub fn add_client_key(
+        port: u16,
+        svc_id: u8,
+        client_id: String,
+        opts: NewClientKey,
+    ) -> AppResult<()> {
+        let resp = Self::send_request::<Option<ClientDetails>>(
+            ServiceRequest::AddClientKey(svc_id, client_id, opts),
+            port,
+        )?;
+
+        resp.log();
+        if let Some(key) = resp.body() {
+            println!("{key}");
+        }
+
+        Ok(())
+    }
+
+    pub fn list_client_keys(port: u16, svc_id: u8, client_id: String) -> AppResult<()> {
+        let resp = Self::send_request::<Vec<ClientKeyInfo>>(
+            ServiceRequest::ListClientKeys(svc_id, client_id),
+            port,
+        )?;
+        resp.log();
+
+        let keys = resp.body();
+        if !keys.is_empty() {
+            println!(" ID\t\t\t\t | Name\t | Scopes\t | Expires At\t | Last Used\t | Date Created");
+            for key in keys {
+                let ClientKeyInfo {
+                    id,
+                    name,
+                    scopes,
+                    expires_at,
+                    last_used,
+                    created_at,
+                } = key;
+
+                let scopes = if scopes.is_empty() {
+                    "all".to_string()
+                } else {
+                    let scopes: Vec<String> = scopes.iter().map(|s| s.to_string()).collect();
+                    scopes.join(",")
+                };
+
+                let expires_at = timestamp(expires_at, "never");
+                let last_used = timestamp(last_used, "never");
+
+                println!(" {id}\t | {name}\t | {scopes}\t | {expires_at}\t | {last_used}\t | {created_at}");
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn revoke_client_key(port: u16, svc_id: u8, key_id: String) -> AppResult<()> {
+        let resp =
+            Self::send_request::<()>(ServiceRequest::RevokeClientKey(svc_id, key_id), port)?;
+        resp.log();
+
+        Ok(())
+    }
+
+    pub fn rotate_secrets(port: u16, keep: u8) -> AppResult<()> {
+        let resp =
+            Self::send_request::<Vec<ReissuedToken>>(ServiceRequest::RotateSecrets(keep), port)?;
+        resp.log();
+
+        let tokens = resp.body();
+        if !tokens.is_empty() {
+            println!(" Service\t | Client ID\t\t\t | Key ID\t\t\t | Token\t | S3 Secret");
+            for reissued in tokens {
+                let ReissuedToken {
+                    service_id,
+                    client_id,
+                    key_id,
+                    token,
+                    s3_secret,
+                } = reissued;
+
+                let key_id = key_id.as_deref().unwrap_or("-");
+                println!(" {service_id}\t | {client_id}\t | {key_id}\t | {token}\t | {s3_secret}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// generate secrets into a new secrets file at `output`, or print them as environment
+    /// variables.
+    pub fn generate_secrets(output: Option<PathBuf>) -> AppResult<()> {
+        let secrets = AppSecrets::generate();
+
+        match output {
+            Some(path) => {
+                if path.exists() {
+                    return Err(Error::Internal(format!(
+                        "{} already exists",
+                        path.display()
+                    )));
+                }
+
+                let mut opts = std::fs::OpenOptions::new();
+                opts.create_new(true).write(true);
+
+                #[cfg(unix)]
+                std::os::unix::fs::OpenOptionsExt::mode(&mut opts, 0o600);
+
+                opts.open(&path)?.write_all(&secrets.to_bytes())?;
+                tracing::info!("secrets saved to {}", path.display());
+            }
+            None => println!("{}", secrets.to_env()),
+        }
+
+        Ok(())
+    }
+
+    /// print the effective config of a service in the config file format.
+    pub fn show_config(config: ServiceConfig) -> AppResult<()> {
+        println!("# service: {}\n{}", config.ty, to_toml(&config)?);
+        Ok(())
+    }
+
     /// check if ppdrive instance is running on a given port. we do this by attempting to read
     /// list of exisiting services. request failure most likely means ppdrive is not running.
     pub fn check_status(port: u16) -> AppResult<()> {
@@ -159,7 +327,7 @@ impl PPDrive {
         cmd: ServiceRequest,
         port: u16,
     ) -> AppResult<Response<T>> {
-        match bincode::encode_to_vec(cmd, config::standard()) {
+        match cmd.to_frame() {
             Ok(data) => {
                 let addr = Self::addr(port);
                 let mut stream = TcpStream::connect(addr)?;
@@ -181,3 +349,11 @@ impl PPDrive {
         format!("0.0.0.0:{port}")
     }
 }
+
+/// format a unix timestamp for display.
+fn timestamp(value: &Option<i64>, none: &str) -> String {
+    value
+        .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or(none.to_string())
+}
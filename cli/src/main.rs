@@ -1,5 +1,5 @@
 use crate::{command::Cli, errors::AppResult};
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
 use ppd_shared::start_logger;
 
 mod command;
@@ -10,10 +10,11 @@ mod imp;
 mod tests;
 
 fn main() -> AppResult<()> {
-    let cli = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
     let _guard = start_logger("ppdrive=debug,ppd_shared=debug")?;
 
-    if let Err(err) = cli.run() {
+    if let Err(err) = cli.run(&matches) {
         tracing::error!("{err}")
     }
 
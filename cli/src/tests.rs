@@ -9,7 +9,7 @@ use crate::{command::start_manager, errors::AppResult, imp::PPDrive};
 fn test_create_client() -> AppResult<()> {
     // start manager
     let port = 5025;
-    let port_clone = port.clone();
+    let port_clone = port;
     start_manager(port_clone, Some("../")).expect("cannot start manager");
 
     // wait till we're able to establish connection with manager
@@ -37,9 +37,10 @@ fn test_create_client() -> AppResult<()> {
 
     // create a service, create token and stop manager
     let config = ServiceConfig::default();
-    let id = PPDrive::add(config, port)?;
+    let id = PPDrive::add(config, false, port)?;
 
     PPDrive::create_client(port, id, "Test Client".to_string(), None)?;
+    PPDrive::cancel(id, true, port)?;
     PPDrive::stop(port)?;
 
     Ok(())
@@ -19,6 +19,7 @@ pub enum HandlerError {
     IOError(String),
     NotFound(String),
     PermissionError(String),
+    BadRequest(String),
 }
 
 impl Display for HandlerError {
@@ -33,6 +34,7 @@ impl Display for HandlerError {
             HandlerError::IOError(msg) => write!(f, "{msg}"),
             HandlerError::NotFound(msg) => write!(f, "{msg}"),
             HandlerError::PermissionError(msg) => write!(f, "{msg}"),
+            HandlerError::BadRequest(msg) => write!(f, "{msg}"),
         }
     }
 }
@@ -73,6 +75,7 @@ impl IntoResponse for HandlerError {
             HandlerError::AuthorizationError(msg) => (StatusCode::UNAUTHORIZED, msg),
             HandlerError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
             HandlerError::PermissionError(msg) => (StatusCode::FORBIDDEN, msg),
+            HandlerError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
             _ => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
         };
 
@@ -2,17 +2,32 @@ use std::str::from_utf8;
 
 use axum::http::HeaderValue;
 use chrono::Utc;
-use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, decode_header, encode,
+};
+use ppd_bk::{
+    RBatis,
+    models::{
+        token::{NewRefreshToken, RefreshTokens},
+        user::Users,
+    },
+};
 use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use uuid::Uuid;
 
 use crate::{HandlerResult, errors::HandlerError};
 
-use ppd_shared::{api::LoginTokens, opts::ServiceConfig};
+use ppd_shared::{
+    api::LoginTokens,
+    opts::ServiceConfig,
+    tools::{AppSecrets, LEGACY_KEY_ID},
+};
 
 pub const BEARER_KEY: &str = "PPDRIVE_BEARER_KEY";
 pub const BEARER_VALUE: &str = "Bearer";
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, PartialEq)]
 pub enum TokenType {
     Access,
     Refresh,
@@ -23,7 +38,11 @@ pub struct Claims {
     sub: u64,
     exp: i64,
     ty: TokenType,
-    user_bucket_size: Option<f64>
+    user_bucket_size: Option<f64>,
+
+    /// unique id of refresh tokens, so tokens issued in the same second don't share a hash.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    jti: Option<String>,
 }
 
 impl Claims {
@@ -46,21 +65,46 @@ impl Claims {
 
 pub(crate) fn decode_jwt(
     header_value: &HeaderValue,
-    secret: &[u8],
+    secrets: &AppSecrets,
     config: &ServiceConfig,
 ) -> Result<Claims, HandlerError> {
     let token = extract_jwt(header_value, &config.auth.bearer)?;
+    decode_token(&token, secrets, TokenType::Access)
+}
+
+/// decode a PPDRIVE token, rejecting tokens that are not of type `ty`. The token is verified
+/// with the key named by its `kid` header, or the legacy key for tokens without one.
+pub fn decode_token(token: &str, secrets: &AppSecrets, ty: TokenType) -> HandlerResult<Claims> {
+    let header = decode_header(token)
+        .map_err(|err| HandlerError::AuthorizationError(format!("invalid token: {err}")))?;
+
+    let key_id = match header.kid {
+        Some(kid) => kid.parse().ok(),
+        None => Some(LEGACY_KEY_ID),
+    };
+
+    let key = key_id
+        .and_then(|id| secrets.key(id))
+        .ok_or(HandlerError::AuthorizationError(
+            "invalid token: unknown signing key".to_string(),
+        ))?;
 
     let mut validation = Validation::default();
     validation.algorithms = vec![Algorithm::HS512];
 
-    let decoded = decode::<Claims>(&token, &DecodingKey::from_secret(secret), &validation)
+    let decoded = decode::<Claims>(token, &DecodingKey::from_secret(key.jwt_secret()), &validation)
         .map_err(|err| HandlerError::AuthorizationError(format!("invalid token: {err}")))?;
 
+    if decoded.claims.ty != ty {
+        return Err(HandlerError::AuthorizationError(
+            "invalid token: unexpected token type".to_string(),
+        ));
+    }
+
     Ok(decoded.claims)
 }
 
-fn extract_jwt(header_value: &HeaderValue, bearer: &str) -> Result<String, HandlerError> {
+pub(crate) fn extract_jwt(header_value: &HeaderValue, bearer: &str) -> Result<String, HandlerError> {
     let bearer = format!("{bearer} ");
     let bearer = bearer.as_str();
 
@@ -81,9 +125,10 @@ fn extract_jwt(header_value: &HeaderValue, bearer: &str) -> Result<String, Handl
     }
 }
 
+/// create a token signed with the current key.
 pub fn create_jwt(
     user_id: &u64,
-    secret: &[u8],
+    secrets: &AppSecrets,
     exp: i64,
     ty: TokenType,
     user_bucket_size: Option<f64>
@@ -93,21 +138,32 @@ pub fn create_jwt(
         .expect("Invalid timestamp")
         .timestamp();
 
+    let jti = match ty {
+        TokenType::Refresh => Some(Uuid::new_v4().to_string()),
+        TokenType::Access => None,
+    };
+
     let claims = Claims {
         sub: user_id.to_owned(),
         exp,
         ty,
-        user_bucket_size
+        user_bucket_size,
+        jti,
+    };
+
+    let key = secrets.current();
+    let header = Header {
+        kid: Some(key.id().to_string()),
+        ..Header::new(Algorithm::HS512)
     };
 
-    let header = Header::new(Algorithm::HS512);
-    encode(&header, &claims, &EncodingKey::from_secret(secret))
+    encode(&header, &claims, &EncodingKey::from_secret(key.jwt_secret()))
         .map_err(|err| HandlerError::AuthorizationError(format!("unable to create token: {err}")))
 }
 
 pub struct LoginOpts<'a> {
     pub config: &'a ServiceConfig,
-    pub jwt_secret: &'a [u8],
+    pub secrets: &'a AppSecrets,
     pub access_exp: Option<i64>,
     pub refresh_exp: Option<i64>,
     pub user_id: &'a u64,
@@ -115,10 +171,17 @@ pub struct LoginOpts<'a> {
 }
 
 impl<'a> LoginOpts<'a> {
-    pub fn tokens(self) -> HandlerResult<LoginTokens> {
+    /// issue tokens for a new login. The refresh token is saved (hashed) so it can be
+    /// exchanged with [refresh_tokens] and revoked.
+    pub async fn tokens(self, db: &RBatis) -> HandlerResult<LoginTokens> {
+        let family = Uuid::new_v4().to_string();
+        self.issue(db, family).await
+    }
+
+    async fn issue(self, db: &RBatis, family: String) -> HandlerResult<LoginTokens> {
         let LoginOpts {
             config,
-            jwt_secret,
+            secrets,
             access_exp,
             refresh_exp,
             user_id,
@@ -132,7 +195,7 @@ impl<'a> LoginOpts<'a> {
         let refresh_exp = refresh_exp.unwrap_or(default_refresh);
 
         let access = if access_exp > 0 {
-            let access_token = create_jwt(user_id, jwt_secret, access_exp, TokenType::Access, user_max_bucket)?;
+            let access_token = create_jwt(user_id, secrets, access_exp, TokenType::Access, user_max_bucket)?;
 
             Some((access_token, access_exp))
         } else {
@@ -140,8 +203,18 @@ impl<'a> LoginOpts<'a> {
         };
 
         let refresh = if refresh_exp > 0 {
-            let refresh_token = create_jwt(user_id, jwt_secret, access_exp, TokenType::Refresh, user_max_bucket)?;
+            let refresh_token = create_jwt(user_id, secrets, refresh_exp, TokenType::Refresh, user_max_bucket)?;
 
+            let stored = NewRefreshToken {
+                token_hash: hash_token(&refresh_token),
+                user_id: *user_id,
+                family,
+                access_exp,
+                refresh_exp,
+            };
+
+            RefreshTokens::delete_expired(db, user_id).await?;
+            RefreshTokens::create(db, stored).await?;
             Some((refresh_token, refresh_exp))
         } else {
             None
@@ -150,3 +223,72 @@ impl<'a> LoginOpts<'a> {
         Ok(LoginTokens { access, refresh })
     }
 }
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha3_256::digest(token.as_bytes()))
+}
+
+/// exchange a refresh token for new tokens. The refresh token is rotated: using it again
+/// is treated as token theft and revokes every token issued from the same login.
+pub async fn refresh_tokens(
+    db: &RBatis,
+    config: &ServiceConfig,
+    secrets: &AppSecrets,
+    token: &str,
+) -> HandlerResult<LoginTokens> {
+    let claims = decode_token(token, secrets, TokenType::Refresh)?;
+    let mut stored = RefreshTokens::find(db, &hash_token(token))
+        .await?
+        .ok_or(HandlerError::AuthorizationError(
+            "refresh token has been revoked".to_string(),
+        ))?;
+
+    // the token is marked as used only if no other request did it first, so a token can't
+    // be exchanged twice by concurrent requests
+    if stored.used() || stored.user_id() != claims.sub() || !stored.mark_used(db).await? {
+        RefreshTokens::delete_family(db, stored.family()).await?;
+        return Err(HandlerError::AuthorizationError(
+            "refresh token has already been used. Please log in again".to_string(),
+        ));
+    }
+
+    let user = Users::get(db, claims.sub()).await?;
+
+    let login = LoginOpts {
+        config,
+        secrets,
+        access_exp: Some(*stored.access_exp()),
+        refresh_exp: Some(*stored.refresh_exp()),
+        user_id: &user.id(),
+        user_max_bucket: *user.max_bucket_size(),
+    };
+
+    login.issue(db, stored.family().to_string()).await
+}
+
+/// revoke the login `token` was issued from (logout). The token must belong to `user_id`.
+pub async fn revoke_login(
+    db: &RBatis,
+    secrets: &AppSecrets,
+    token: &str,
+    user_id: &u64,
+) -> HandlerResult<()> {
+    let claims = decode_token(token, secrets, TokenType::Refresh)?;
+    if claims.sub() != user_id {
+        return Err(HandlerError::PermissionError(
+            "refresh token belongs to another user".to_string(),
+        ));
+    }
+
+    if let Some(stored) = RefreshTokens::find(db, &hash_token(token)).await? {
+        RefreshTokens::delete_family(db, stored.family()).await?;
+    }
+
+    Ok(())
+}
+
+/// revoke all refresh tokens of the user (logout everywhere).
+pub async fn revoke_all_logins(db: &RBatis, user_id: &u64) -> HandlerResult<()> {
+    RefreshTokens::delete_for_user(db, user_id).await?;
+    Ok(())
+}
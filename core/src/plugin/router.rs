@@ -6,7 +6,7 @@ use std::{str, sync::Arc};
 use axum::Router;
 use libloading::{Library, Symbol};
 use ppd_shared::{
-    opts::{ServiceAuthMode, ServiceConfig, ServiceType},
+    opts::{ServiceAuthMode, ServiceConfig},
     plugin::Plugin,
 };
 
@@ -40,22 +40,27 @@ impl Routers {
         Self::get_router(&self.direct)
     }
 
+    pub fn admin(&self) -> RouterType {
+        Self::get_router(&self.admin)
+    }
+
+    pub fn zero(&self) -> RouterType {
+        Self::get_router(&self.zero)
+    }
+
     pub fn load(mut self) -> HandlerResult<Self> {
         let config = self.config.clone();
         let modes = &config.auth.modes;
-        let svc_type = config.ty;
 
         for mode in modes {
-            let router = ServiceRouter {
-                svc_type,
-                auth_mode: *mode,
-            };
+            let router = ServiceRouter { auth_mode: *mode };
 
             let ptr = router.get(self.config.clone())?;
             match mode {
                 ServiceAuthMode::Client => self.client = ptr,
-                ServiceAuthMode::Direct => self.client = ptr,
-                _ => unimplemented!(),
+                ServiceAuthMode::Direct => self.direct = ptr,
+                ServiceAuthMode::Admin => self.admin = ptr,
+                ServiceAuthMode::Zero => self.zero = ptr,
             }
         }
 
@@ -99,9 +104,10 @@ impl Drop for Routers {
     }
 }
 
+/// router of a REST service for an auth mode. Other service types have their own routes and
+/// don't load routers.
 #[derive(Default)]
 pub struct ServiceRouter {
-    pub svc_type: ServiceType,
     pub auth_mode: ServiceAuthMode,
 }
 
@@ -126,15 +132,12 @@ impl ServiceRouter {
 impl Plugin for ServiceRouter {
     fn package_name(&self) -> &'static str {
         use ServiceAuthMode::*;
-        use ServiceType::*;
-
-        match self.svc_type {
-            Rest => match self.auth_mode {
-                Client => "rest-client",
-                Direct => "rest-direct",
-                _ => unimplemented!("loading plugin for this auth_mode is not supported"),
-            },
-            Grpc => unimplemented!("loading plugin for a grpc server is not implemented."),
+
+        match self.auth_mode {
+            Client => "rest-client",
+            Direct => "rest-direct",
+            Admin => "rest-admin",
+            Zero => "rest-zero",
         }
     }
 }
@@ -101,20 +101,23 @@ impl<'a> Plugin for Service<'a> {
         match &self.ty {
             ServiceType::Rest => "ppd-rest",
             ServiceType::Grpc => "ppd-grpc",
+            ServiceType::S3 => "ppd-s3",
         }
     }
 }
 
 impl<'a> Module for Service<'a> {
     fn dependecies(&self) -> Vec<Box<dyn Plugin>> {
+        // gRPC and S3 services have their own routes and authentication schemes
+        if let ServiceType::Grpc | ServiceType::S3 = self.ty {
+            return Vec::new();
+        }
+
         let routers: Vec<Box<dyn Plugin>> = self
             .modes
             .iter()
             .map(|mode| {
-                Box::new(ServiceRouter {
-                    svc_type: *self.ty,
-                    auth_mode: *mode,
-                }) as Box<dyn Plugin>
+                Box::new(ServiceRouter { auth_mode: *mode }) as Box<dyn Plugin>
             })
             .collect();
 
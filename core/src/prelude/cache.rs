@@ -0,0 +1,37 @@
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+/// An in-memory cache whose entries expire after `ttl`.
+pub struct TtlCache<V> {
+    ttl: Duration,
+    entries: RwLock<HashMap<String, (V, Instant)>>,
+}
+
+impl<V: Clone> TtlCache<V> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// returns `None` if there's no entry for `key` or the entry has expired.
+    pub fn get(&self, key: &str) -> Option<V> {
+        let entries = self.entries.read().ok()?;
+        entries
+            .get(key)
+            .filter(|(_, inserted)| inserted.elapsed() < self.ttl)
+            .map(|(value, _)| value.clone())
+    }
+
+    /// insert a value, removing expired entries.
+    pub fn insert(&self, key: String, value: V) {
+        if let Ok(mut entries) = self.entries.write() {
+            entries.retain(|_, (_, inserted)| inserted.elapsed() < self.ttl);
+            entries.insert(key, (value, Instant::now()));
+        }
+    }
+}
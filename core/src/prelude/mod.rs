@@ -1,2 +1,3 @@
 // pub mod opts;
-pub mod state;
\ No newline at end of file
+pub mod cache;
+pub mod state;
@@ -1,25 +1,54 @@
-use crate::errors::HandlerError;
+use crate::{errors::HandlerError, prelude::cache::TtlCache};
 use ppd_bk::RBatis;
 use ppd_shared::{opts::ServiceConfig, tools::AppSecrets};
-use std::sync::Arc;
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+/// how often secrets are read again, so keys rotated with `ppdrive secrets rotate` are picked up
+/// by running services.
+const SECRETS_RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+/// id and maximum bucket size of a user.
+pub type CachedUser = (u64, Option<f64>);
+
+/// JWKS of an OIDC issuer and when it was loaded.
+#[cfg(feature = "jwt")]
+pub type CachedJwks = (Arc<jsonwebtoken::jwk::JwkSet>, std::time::Instant);
 
 #[derive(Clone)]
 pub struct HandlerState {
     db: Arc<RBatis>,
-    secrets: Arc<AppSecrets>,
+    secrets: Arc<RwLock<Arc<AppSecrets>>>,
     config: Arc<ServiceConfig>,
+
+    /// users resolved by the external authentication service, by token.
+    auth_cache: Arc<TtlCache<CachedUser>>,
+
+    /// JWKS of OIDC issuers, by issuer.
+    #[cfg(feature = "jwt")]
+    jwks_cache: Arc<TtlCache<CachedJwks>>,
 }
 
 impl HandlerState {
     pub async fn new(config: &ServiceConfig, db: Arc<RBatis>) -> Result<Self, HandlerError> {
         let secrets = AppSecrets::read().await?;
-        let secrets = Arc::new(secrets);
+        let secrets = Arc::new(RwLock::new(Arc::new(secrets)));
+        Self::reload_secrets(Arc::downgrade(&secrets));
+
+        let auth_cache = Arc::new(TtlCache::new(Duration::from_secs(config.auth.url_ttl)));
+        #[cfg(feature = "jwt")]
+        let jwks_cache = Arc::new(TtlCache::new(Duration::from_secs(config.auth.oidc.jwks_ttl)));
         let config = Arc::new(config.clone());
 
         let s = Self {
             db,
             secrets,
             config,
+            auth_cache,
+            #[cfg(feature = "jwt")]
+            jwks_cache,
         };
 
         Ok(s)
@@ -30,10 +59,44 @@ impl HandlerState {
     }
 
     pub fn secrets(&self) -> Arc<AppSecrets> {
-        self.secrets.clone()
+        match self.secrets.read() {
+            Ok(secrets) => secrets.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// periodically read secrets again until the state is dropped.
+    fn reload_secrets(secrets: std::sync::Weak<RwLock<Arc<AppSecrets>>>) {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(SECRETS_RELOAD_INTERVAL).await;
+
+                let Some(secrets) = secrets.upgrade() else {
+                    break;
+                };
+
+                match AppSecrets::read().await {
+                    Ok(loaded) => {
+                        if let Ok(mut current) = secrets.write() {
+                            *current = Arc::new(loaded);
+                        }
+                    }
+                    Err(err) => tracing::error!("unable to reload secrets: {err}"),
+                }
+            }
+        });
     }
 
     pub fn config(&self) -> Arc<ServiceConfig> {
         self.config.clone()
     }
+
+    pub fn auth_cache(&self) -> &TtlCache<CachedUser> {
+        &self.auth_cache
+    }
+
+    #[cfg(feature = "jwt")]
+    pub fn jwks_cache(&self) -> &TtlCache<CachedJwks> {
+        &self.jwks_cache
+    }
 }
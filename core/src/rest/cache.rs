@@ -0,0 +1,73 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::http::{
+    HeaderMap,
+    header::{CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    response::Builder,
+};
+use ppd_fs::FileBody;
+
+/// `Cache-Control` value for private assets.
+const PRIVATE_CACHE_CONTROL: &str = "private, no-store";
+
+/// Adds validators (`ETag`, `Last-Modified`) and `Cache-Control` headers to a file response.
+pub fn cache_headers(builder: Builder, file: &FileBody, cache_control: &str) -> Builder {
+    let cache_control = if *file.public() {
+        cache_control
+    } else {
+        PRIVATE_CACHE_CONTROL
+    };
+
+    let builder = builder
+        .header(ETAG, file.etag())
+        .header(CACHE_CONTROL, cache_control);
+
+    match file.modified() {
+        Some(modified) => builder.header(LAST_MODIFIED, httpdate::fmt_http_date(*modified)),
+        None => builder,
+    }
+}
+
+/// Checks `If-None-Match` and `If-Modified-Since` headers. Returns true if the client's
+/// copy of the file is still valid and a `304 Not Modified` should be sent.
+pub fn not_modified(headers: &HeaderMap, file: &FileBody) -> bool {
+    // If-None-Match takes precedence over If-Modified-Since
+    if let Some(value) = headers.get(IF_NONE_MATCH) {
+        return match value.to_str() {
+            Ok(value) => etag_matches(value, &file.etag(), false),
+            Err(_) => false,
+        };
+    }
+
+    let since = headers
+        .get(IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| httpdate::parse_http_date(v).ok());
+
+    match (since, file.modified()) {
+        (Some(since), Some(modified)) => unix_secs(modified) <= unix_secs(&since),
+        _ => false,
+    }
+}
+
+/// Compares a list of entity tags (e.g from `If-None-Match`) against `etag`. Weak
+/// comparison ignores the `W/` prefix, strong comparison requires both tags to be strong.
+pub fn etag_matches(value: &str, etag: &str, strong: bool) -> bool {
+    if value.trim() == "*" {
+        return true;
+    }
+
+    value.split(',').map(|tag| tag.trim()).any(|tag| {
+        if strong {
+            tag == etag
+        } else {
+            tag.trim_start_matches("W/") == etag.trim_start_matches("W/")
+        }
+    })
+}
+
+fn unix_secs(time: &SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,70 @@
+//! delegates validation of user tokens to an external authentication service (`--auth-url`).
+
+use std::{sync::LazyLock, time::Duration};
+
+use axum::http::{HeaderValue, header::AUTHORIZATION};
+use ppd_bk::models::user::Users;
+use reqwest::Client;
+use serde::Deserialize;
+use sha3::{Digest, Sha3_256};
+
+use crate::{
+    HandlerResult,
+    errors::HandlerError,
+    prelude::state::{CachedUser, HandlerState},
+};
+
+pub(super) static HTTP_CLIENT: LazyLock<Client> = LazyLock::new(|| {
+    Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap_or_default()
+});
+
+/// The response expected from the external authentication service when a token is valid.
+#[derive(Deserialize)]
+struct ExternalUser {
+    /// id of the user in the external service.
+    id: String,
+
+    /// maximum accumulated size of buckets the user can create. Only applied when the user
+    /// is first seen.
+    max_bucket: Option<f64>,
+}
+
+/// forward the authorization header to `url` and map the external user to a PPDRIVE user,
+/// creating it on first sight. Results are cached with the service's `url_ttl`.
+pub(crate) async fn get_external_user(
+    state: &HandlerState,
+    header: &HeaderValue,
+    url: &str,
+) -> HandlerResult<CachedUser> {
+    let key = hex::encode(Sha3_256::digest(header.as_bytes()));
+    if let Some(user) = state.auth_cache().get(&key) {
+        return Ok(user);
+    }
+
+    let resp = HTTP_CLIENT
+        .get(url)
+        .header(AUTHORIZATION, header)
+        .send()
+        .await
+        .map_err(|err| HandlerError::InternalError(format!("auth service unreachable: {err}")))?;
+
+    if !resp.status().is_success() {
+        return Err(HandlerError::AuthorizationError(format!(
+            "token rejected by auth service ({})",
+            resp.status()
+        )));
+    }
+
+    let external: ExternalUser = resp.json().await.map_err(|err| {
+        HandlerError::InternalError(format!("invalid auth service response: {err}"))
+    })?;
+
+    let user = Users::get_or_create_external(state.db(), &external.id, external.max_bucket).await?;
+    let cached = (user.id(), *user.max_bucket_size());
+    state.auth_cache().insert(key, cached);
+
+    Ok(cached)
+}
@@ -1,22 +1,36 @@
 use std::ops::Deref;
 
-use crate::tools::verify_client;
+use crate::rest::external::get_external_user;
+use crate::rest::oidc::{get_oidc_user, is_oidc_token};
+use crate::tools::{check_scope, verify_client};
 use crate::{HandlerResult, errors::HandlerError};
 use crate::{jwt::decode_jwt, prelude::state::HandlerState};
 use axum::{
     async_trait,
     extract::{FromRef, FromRequestParts},
-    http::{HeaderValue, header::AUTHORIZATION, request::Parts},
+    http::{
+        HeaderValue,
+        header::{AUTHORIZATION, CONTENT_LENGTH},
+        request::Parts,
+    },
 };
 use ppd_bk::RBatis;
 use ppd_bk::models::bucket::Buckets;
-use ppd_bk::models::user::Users;
-use ppd_shared::opts::ServiceConfig;
+use ppd_bk::models::user::{UserRole, Users};
+use ppd_shared::opts::{ClientScope, ServiceConfig};
 
 /// A middleware that accepts client token, validates it and return the client's id
 pub struct ClientExtractor {
     id: u64,
-    max_bucket_size: Option<f64>
+    max_bucket_size: Option<f64>,
+    scopes: Vec<ClientScope>,
+}
+
+impl ClientExtractor {
+    /// reject the request if the client key used doesn't grant `scope`.
+    pub fn require(&self, scope: ClientScope) -> HandlerResult<()> {
+        check_scope(&self.scopes, scope)
+    }
 }
 
 impl BucketSizeValidator for ClientExtractor {
@@ -58,11 +72,15 @@ where
         let state = HandlerState::from_ref(state);
         let secrets = state.secrets();
 
-        let (id, max_bucket_size) = verify_client(state.db(), secrets.deref(), token)
+        let client = verify_client(state.db(), secrets.deref(), token)
             .await
             .map_err(|err| HandlerError::AuthorizationError(err.to_string()))?;
 
-        Ok(ClientExtractor { id, max_bucket_size })
+        Ok(ClientExtractor {
+            id: client.id,
+            max_bucket_size: client.max_bucket_size,
+            scopes: client.scopes,
+        })
     }
 }
 
@@ -72,6 +90,14 @@ where
 pub struct ClientUserExtractor {
     id: u64,
     max_bucket_size: Option<f64>,
+    scopes: Vec<ClientScope>,
+}
+
+impl ClientUserExtractor {
+    /// reject the request if the client key used doesn't grant `scope`.
+    pub fn require(&self, scope: ClientScope) -> HandlerResult<()> {
+        check_scope(&self.scopes, scope)
+    }
 }
 
 impl BucketSizeValidator for ClientUserExtractor {
@@ -126,6 +152,7 @@ where
         Ok(ClientUserExtractor {
             id: user.id(),
             max_bucket_size: *user.max_bucket_size(),
+            scopes: client.scopes,
         })
     }
 }
@@ -165,11 +192,17 @@ where
                 let state = HandlerState::from_ref(state);
                 let config = state.config();
 
-                match &config.auth.url {
-                    Some(_url) => {
-                        unimplemented!("external url feature not implemented.")
+                match (&config.auth.url, &config.auth.oidc.issuer) {
+                    (Some(url), _) => {
+                        let (id, max_bucket_size) = get_external_user(&state, auth, url).await?;
+                        Ok(UserExtractor { id, max_bucket_size })
                     }
-                    None => {
+                    (None, Some(issuer)) if is_oidc_token(auth, &config) => {
+                        let (id, max_bucket_size) =
+                            get_oidc_user(&state, auth, &config, issuer).await?;
+                        Ok(UserExtractor { id, max_bucket_size })
+                    }
+                    _ => {
                         let user = get_local_user(&state, auth, &config).await?;
                         Ok(user)
                     }
@@ -188,11 +221,106 @@ async fn get_local_user(
     config: &ServiceConfig,
 ) -> HandlerResult<UserExtractor> {
     let secrets = state.secrets();
-    let claims = decode_jwt(header, secrets.jwt_secret(), config)?;
+    let claims = decode_jwt(header, &secrets, config)?;
 
     Ok(UserExtractor { id: *claims.sub(), max_bucket_size: *claims.user_bucket_size() })
 }
 
+/// An extractor that verifies the user's authorization token like [UserExtractor] and
+/// accepts only admin users.
+pub struct AdminExtractor {
+    id: u64,
+}
+
+impl AdminExtractor {
+    pub fn id(&self) -> &u64 {
+        &self.id
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AdminExtractor
+where
+    HandlerState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = HandlerError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = UserExtractor::from_request_parts(parts, state).await?;
+        let state = HandlerState::from_ref(state);
+
+        let admin = Users::get(state.db(), user.id())
+            .await
+            .map_err(|err| HandlerError::AuthorizationError(err.to_string()))?;
+
+        match admin.role()? {
+            UserRole::Admin => Ok(AdminExtractor { id: admin.id() }),
+            _ => Err(HandlerError::PermissionError(
+                "admin access required".to_string(),
+            )),
+        }
+    }
+}
+
+/// Header carrying the offset at which an upload chunk should be written.
+pub const UPLOAD_OFFSET_HEADER: &str = "upload-offset";
+
+/// An extractor that reads the offset of an upload chunk from [UPLOAD_OFFSET_HEADER].
+pub struct UploadOffset(pub u64);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for UploadOffset
+where
+    S: Send + Sync,
+{
+    type Rejection = HandlerError;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        let offset = parts
+            .headers
+            .get(UPLOAD_OFFSET_HEADER)
+            .ok_or(HandlerError::BadRequest(format!(
+                "missing '{UPLOAD_OFFSET_HEADER}' in headers"
+            )))?;
+
+        let offset = offset
+            .to_str()
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .ok_or(HandlerError::BadRequest(format!(
+                "'{UPLOAD_OFFSET_HEADER}' must be a positive integer"
+            )))?;
+
+        Ok(UploadOffset(offset))
+    }
+}
+
+/// An extractor that reads the size of a request body from its `Content-Length` header, for
+/// bodies that are streamed into a range claimed beforehand (i.e upload chunks).
+pub struct ContentLength(pub u64);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ContentLength
+where
+    S: Send + Sync,
+{
+    type Rejection = HandlerError;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        let len = parts
+            .headers
+            .get(CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok())
+            .ok_or(HandlerError::BadRequest(
+                "a valid 'content-length' header is required".to_string(),
+            ))?;
+
+        Ok(ContentLength(len))
+    }
+}
+
 pub trait BucketSizeValidator {
     fn id(&self) -> &u64;
     fn max_bucket_size(&self) -> &Option<f64>;
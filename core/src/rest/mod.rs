@@ -1,23 +1,44 @@
 use axum::{
     body::Body,
-    extract::{Path, State},
-    http::header::CONTENT_TYPE,
+    extract::{Path, Query, State},
+    http::{
+        HeaderMap,
+        header::{ACCEPT, CONTENT_TYPE, VARY},
+    },
     response::Response,
 };
 use axum_macros::debug_handler;
 use ppd_bk::models::asset::AssetType;
-use ppd_fs::{AssetBody, read_asset};
-use ppd_shared::tools::SECRETS_FILENAME;
+use ppd_fs::{AssetBody, read_asset, versions::read_version};
+use ppd_shared::{
+    api::{FolderListing, ListFolderOptions, ReadAssetOptions},
+    tools::SECRETS_FILENAME,
+};
 
 use crate::{errors::HandlerError, prelude::state::HandlerState, rest::extractors::{BucketSizeValidator, UserExtractor}};
 
+mod cache;
+mod external;
 pub mod extractors;
+pub mod notifier;
+mod oidc;
+mod range;
+mod upload;
+
+pub use range::file_response;
+pub use upload::{AssetUpload, read_asset_upload};
 
+/// read an asset. Folders are listed as JSON when the `Accept` header prefers it over
+/// HTML. Listings are paginated and filtered with [ListFolderOptions]. Previous versions of
+/// files are read with [ReadAssetOptions].
 #[debug_handler]
 pub async fn get_asset(
     Path((asset_type, mut asset_path)): Path<(AssetType, String)>,
+    Query(list): Query<ListFolderOptions>,
+    Query(read): Query<ReadAssetOptions>,
     State(state): State<HandlerState>,
     user: Option<UserExtractor>,
+    headers: HeaderMap,
 ) -> Result<Response<Body>, HandlerError> {
     
     if asset_path.ends_with("/") {
@@ -28,19 +49,92 @@ pub async fn get_asset(
         return Err(HandlerError::PermissionError("access denied".to_string()));
     }
 
+    list.after()
+        .map_err(|err| HandlerError::BadRequest(err.to_string()))?;
+
     let db = state.db();
     let user_id = user.map(|u| *u.id());
-    let body = read_asset(db, &asset_path, &asset_type, &user_id).await?;
-
-    let body = match body {
-        AssetBody::File(mime, content) => Response::builder()
-            .header(CONTENT_TYPE, mime.to_string())
-            .body(Body::from(content)),
-        AssetBody::Folder(content) => Response::builder()
-            .header(CONTENT_TYPE, "text/html")
-            .body(Body::from(content)),
+
+    if let Some(version) = read.version {
+        let AssetType::File = asset_type else {
+            return Err(HandlerError::BadRequest("only files have versions".to_string()));
+        };
+
+        let file = read_version(db, &asset_path, &user_id, &version).await?;
+        let config = state.config();
+        return range::file_response(&headers, file, &config.base.cache_control).await;
+    }
+
+    let body = read_asset(db, &asset_path, &asset_type, &user_id, &list).await?;
+
+    match body {
+        AssetBody::File(file) => {
+            let config = state.config();
+            range::file_response(&headers, file, &config.base.cache_control).await
+        }
+        AssetBody::Folder(listing) => folder_response(&headers, &listing),
+    }
+}
+
+/// respond with a folder listing, as JSON or HTML depending on the `Accept` header.
+pub fn folder_response(
+    headers: &HeaderMap,
+    listing: &FolderListing,
+) -> Result<Response<Body>, HandlerError> {
+    let (content_type, body) = if prefers_json(headers) {
+        let json = serde_json::to_string(listing)
+            .map_err(|err| HandlerError::InternalError(err.to_string()))?;
+
+        ("application/json", json)
+    } else {
+        ("text/html", listing.to_html())
     };
 
-    let resp = body.map_err(|err| HandlerError::InternalError(err.to_string()))?;
-    Ok(resp)
+    Response::builder()
+        .header(CONTENT_TYPE, content_type)
+        .header(VARY, "Accept")
+        .body(Body::from(body))
+        .map_err(|err| HandlerError::InternalError(err.to_string()))
+}
+
+/// whether `application/json` has a higher quality than `text/html` in the `Accept` header.
+/// HTML is preferred when the header is missing or both have the same quality.
+fn prefers_json(headers: &HeaderMap) -> bool {
+    let Some(accept) = headers.get(ACCEPT).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+
+    accept_quality(accept, "application/json") > accept_quality(accept, "text/html")
+}
+
+/// quality of `mime` in an `Accept` header, taken from the most specific matching range.
+fn accept_quality(accept: &str, mime: &str) -> f32 {
+    let group = mime.split('/').next().unwrap_or_default();
+    let mut best: Option<(u8, f32)> = None;
+
+    for range in accept.split(',') {
+        let mut params = range.split(';');
+        let media = params.next().unwrap_or_default().trim();
+
+        let specificity = if media.eq_ignore_ascii_case(mime) {
+            2
+        } else if media.strip_suffix("/*").is_some_and(|g| g.eq_ignore_ascii_case(group)) {
+            1
+        } else if media == "*/*" {
+            0
+        } else {
+            continue;
+        };
+
+        let quality = params
+            .find_map(|p| p.trim().strip_prefix("q="))
+            .and_then(|q| q.parse().ok())
+            .unwrap_or(1.0);
+
+        if best.is_none_or(|(s, _)| specificity > s) {
+            best = Some((specificity, quality));
+        }
+    }
+
+    best.map(|(_, q)| q).unwrap_or_default()
 }
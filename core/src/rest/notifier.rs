@@ -0,0 +1,92 @@
+//! delivery of password reset tokens (`--reset-notifier`).
+
+use std::path::PathBuf;
+
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+
+use crate::{HandlerResult, errors::HandlerError, rest::external::HTTP_CLIENT};
+
+/// A password reset token to be delivered to a user.
+#[derive(Serialize)]
+pub struct ResetNotice<'a> {
+    /// public id of the user.
+    pub user_id: &'a str,
+    pub username: &'a str,
+    pub token: &'a str,
+
+    /// unix timestamp after which the token can't be used.
+    pub expires_at: i64,
+}
+
+/// Where password reset tokens are delivered.
+pub enum ResetNotifier {
+    /// written to the service's log. Only suitable for development.
+    Log,
+
+    /// appended to a file as JSON lines.
+    File(PathBuf),
+
+    /// posted as JSON to a url, i.e a service that emails the token to the user.
+    Webhook(String),
+}
+
+impl ResetNotifier {
+    /// parse the `--reset-notifier` option: `log`, `file:<path>` or a http(s) url.
+    pub fn from_config(value: &str) -> HandlerResult<Self> {
+        if value == "log" {
+            return Ok(ResetNotifier::Log);
+        }
+
+        if let Some(path) = value.strip_prefix("file:") {
+            return Ok(ResetNotifier::File(PathBuf::from(path)));
+        }
+
+        if value.starts_with("http://") || value.starts_with("https://") {
+            return Ok(ResetNotifier::Webhook(value.to_string()));
+        }
+
+        Err(HandlerError::InternalError(format!(
+            "unsupported reset notifier '{value}'"
+        )))
+    }
+
+    pub async fn notify(&self, notice: &ResetNotice<'_>) -> HandlerResult<()> {
+        match self {
+            ResetNotifier::Log => {
+                tracing::info!(
+                    "password reset token for '{}': {} (expires at {})",
+                    notice.username,
+                    notice.token,
+                    notice.expires_at
+                );
+            }
+            ResetNotifier::File(path) => {
+                let mut line = serde_json::to_string(notice)
+                    .map_err(|err| HandlerError::InternalError(err.to_string()))?;
+                line.push('\n');
+
+                let mut file = tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await?;
+
+                file.write_all(line.as_bytes()).await?;
+            }
+            ResetNotifier::Webhook(url) => {
+                HTTP_CLIENT
+                    .post(url)
+                    .json(notice)
+                    .send()
+                    .await
+                    .and_then(|resp| resp.error_for_status())
+                    .map_err(|err| {
+                        HandlerError::InternalError(format!("unable to deliver reset token: {err}"))
+                    })?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+//! validation of user tokens issued by an OpenID Connect provider (`--oidc-issuer`).
+
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::http::HeaderValue;
+use jsonwebtoken::{
+    Algorithm, DecodingKey, Validation, decode, decode_header,
+    jwk::{Jwk, JwkSet, KeyAlgorithm},
+};
+use ppd_bk::models::user::Users;
+use ppd_shared::opts::{OidcConfig, ServiceConfig};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    HandlerResult,
+    errors::HandlerError,
+    jwt::extract_jwt,
+    prelude::state::{CachedUser, HandlerState},
+    rest::external::HTTP_CLIENT,
+};
+
+/// minimum age of a cached JWKS before an unknown key id triggers a reload. Keeps tokens
+/// with made-up key ids from hammering the issuer.
+const MIN_JWKS_REFRESH: Duration = Duration::from_secs(60);
+
+/// The part of the issuer's discovery document we need.
+#[derive(Deserialize)]
+struct ProviderMetadata {
+    jwks_uri: String,
+}
+
+/// returns `true` if the token in `header` is signed with an algorithm accepted from the
+/// OIDC issuer. PPDRIVE's own tokens (HS512) are left to the local validation.
+pub(crate) fn is_oidc_token(header: &HeaderValue, config: &ServiceConfig) -> bool {
+    extract_jwt(header, &config.auth.bearer)
+        .ok()
+        .and_then(|token| decode_header(&token).ok())
+        .is_some_and(|header| matches!(header.alg, Algorithm::RS256 | Algorithm::ES256))
+}
+
+/// validate a token issued by `issuer` against its JWKS and map the configured claim to a
+/// PPDRIVE user, creating it on first sight.
+pub(crate) async fn get_oidc_user(
+    state: &HandlerState,
+    header: &HeaderValue,
+    config: &ServiceConfig,
+    issuer: &str,
+) -> HandlerResult<CachedUser> {
+    let oidc = &config.auth.oidc;
+    let token = extract_jwt(header, &config.auth.bearer)?;
+
+    let token_header = decode_header(&token)
+        .map_err(|err| HandlerError::AuthorizationError(format!("invalid token: {err}")))?;
+
+    let kid = token_header.kid.ok_or(HandlerError::AuthorizationError(
+        "token has no key id".to_string(),
+    ))?;
+
+    let jwk = find_key(state, oidc, issuer, &kid).await?;
+    let alg_matches = matches!(
+        (jwk.common.key_algorithm, token_header.alg),
+        (None, _)
+            | (Some(KeyAlgorithm::RS256), Algorithm::RS256)
+            | (Some(KeyAlgorithm::ES256), Algorithm::ES256)
+    );
+
+    if !alg_matches {
+        return Err(HandlerError::AuthorizationError(format!(
+            "key '{kid}' can not be used with {:?}",
+            token_header.alg
+        )));
+    }
+
+    let key = DecodingKey::from_jwk(&jwk)
+        .map_err(|err| HandlerError::AuthorizationError(format!("invalid key '{kid}': {err}")))?;
+
+    let mut validation = Validation::new(token_header.alg);
+    validation.set_issuer(&[issuer]);
+    match &oidc.audience {
+        Some(audience) => validation.set_audience(&[audience]),
+        None => validation.validate_aud = false,
+    }
+
+    let claims = decode::<HashMap<String, Value>>(&token, &key, &validation)
+        .map_err(|err| HandlerError::AuthorizationError(format!("invalid token: {err}")))?
+        .claims;
+
+    let subject = match claims.get(&oidc.user_claim) {
+        Some(Value::String(v)) => v.clone(),
+        Some(Value::Number(v)) => v.to_string(),
+        _ => {
+            return Err(HandlerError::AuthorizationError(format!(
+                "token has no '{}' claim",
+                oidc.user_claim
+            )));
+        }
+    };
+
+    // namespaced by issuer so users of different providers never collide.
+    let external_id = format!("{issuer}|{subject}");
+    let user = Users::get_or_create_external(state.db(), &external_id, None).await?;
+
+    Ok((user.id(), *user.max_bucket_size()))
+}
+
+/// find the key with `kid` in the issuer's JWKS. The JWKS is reloaded when the key is
+/// unknown, so keys rotated by the issuer are picked up before the cache expires.
+async fn find_key(
+    state: &HandlerState,
+    oidc: &OidcConfig,
+    issuer: &str,
+    kid: &str,
+) -> HandlerResult<Jwk> {
+    let cached = state.jwks_cache().get(issuer);
+    if let Some((jwks, _)) = &cached
+        && let Some(jwk) = jwks.find(kid)
+    {
+        return Ok(jwk.clone());
+    }
+
+    let unknown_key = || HandlerError::AuthorizationError(format!("unknown key id '{kid}'"));
+    if let Some((_, loaded)) = &cached
+        && loaded.elapsed() < MIN_JWKS_REFRESH
+    {
+        return Err(unknown_key());
+    }
+
+    let jwks = Arc::new(load_jwks(oidc, issuer).await?);
+    state
+        .jwks_cache()
+        .insert(issuer.to_string(), (jwks.clone(), Instant::now()));
+
+    jwks.find(kid).cloned().ok_or_else(unknown_key)
+}
+
+/// load the JWKS from `--oidc-jwks` (url or file path), or from the `jwks_uri` in the
+/// issuer's discovery document.
+async fn load_jwks(oidc: &OidcConfig, issuer: &str) -> HandlerResult<JwkSet> {
+    let source = match &oidc.jwks {
+        Some(jwks) => jwks.clone(),
+        None => {
+            let url = format!(
+                "{}/.well-known/openid-configuration",
+                issuer.trim_end_matches('/')
+            );
+
+            let metadata: ProviderMetadata = serde_json::from_str(&fetch(&url).await?)
+                .map_err(|err| {
+                    HandlerError::InternalError(format!("invalid discovery document: {err}"))
+                })?;
+
+            metadata.jwks_uri
+        }
+    };
+
+    let content = if source.starts_with("http://") || source.starts_with("https://") {
+        fetch(&source).await?
+    } else {
+        tokio::fs::read_to_string(&source).await.map_err(|err| {
+            HandlerError::InternalError(format!("unable to read JWKS '{source}': {err}"))
+        })?
+    };
+
+    serde_json::from_str(&content)
+        .map_err(|err| HandlerError::InternalError(format!("invalid JWKS '{source}': {err}")))
+}
+
+async fn fetch(url: &str) -> HandlerResult<String> {
+    let resp = HTTP_CLIENT
+        .get(url)
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|err| HandlerError::InternalError(format!("unable to fetch '{url}': {err}")))?;
+
+    resp.text()
+        .await
+        .map_err(|err| HandlerError::InternalError(format!("unable to read '{url}': {err}")))
+}
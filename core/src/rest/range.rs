@@ -0,0 +1,215 @@
+use std::{
+    io::Cursor,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    body::Body,
+    http::{
+        HeaderMap, StatusCode,
+        header::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, IF_RANGE, RANGE},
+    },
+    response::Response,
+};
+use ppd_fs::{AssetReader, FileBody};
+use tokio::io::AsyncReadExt;
+use tokio_util::io::ReaderStream;
+
+use crate::{errors::HandlerError, rest::cache};
+
+/// Maximum number of ranges served in a single request. Requests with more
+/// ranges receive the full file.
+const MAX_RANGES: usize = 32;
+
+/// An inclusive byte range within a file.
+struct ByteRange {
+    start: u64,
+    end: u64,
+}
+
+impl ByteRange {
+    fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    fn content_range(&self, size: u64) -> String {
+        format!("bytes {}-{}/{size}", self.start, self.end)
+    }
+}
+
+enum RangeRequest {
+    Full,
+    Partial(Vec<ByteRange>),
+    Unsatisfiable,
+}
+
+/// Parses a `Range` header value against a file of `size` bytes. Header values we
+/// cannot understand are ignored and the full file is served.
+fn parse_range(value: &str, size: u64) -> RangeRequest {
+    let Some(specs) = value.trim().strip_prefix("bytes=") else {
+        return RangeRequest::Full;
+    };
+
+    let mut ranges = Vec::new();
+    for spec in specs.split(',') {
+        let Some((start, end)) = spec.trim().split_once('-') else {
+            return RangeRequest::Full;
+        };
+
+        let (start, end) = (start.trim(), end.trim());
+        let range = if start.is_empty() {
+            // suffix range, i.e the last `n` bytes
+            let Ok(n) = end.parse::<u64>() else {
+                return RangeRequest::Full;
+            };
+
+            if n == 0 || size == 0 {
+                continue;
+            }
+
+            ByteRange {
+                start: size.saturating_sub(n),
+                end: size - 1,
+            }
+        } else {
+            let Ok(start) = start.parse::<u64>() else {
+                return RangeRequest::Full;
+            };
+
+            let end = if end.is_empty() {
+                size.saturating_sub(1)
+            } else {
+                match end.parse::<u64>() {
+                    Ok(end) if end >= start => end.min(size.saturating_sub(1)),
+                    _ => return RangeRequest::Full,
+                }
+            };
+
+            if start >= size {
+                continue;
+            }
+
+            ByteRange { start, end }
+        };
+
+        ranges.push(range);
+    }
+
+    if ranges.is_empty() {
+        RangeRequest::Unsatisfiable
+    } else if ranges.len() > MAX_RANGES {
+        RangeRequest::Full
+    } else {
+        RangeRequest::Partial(ranges)
+    }
+}
+
+/// `If-Range` makes a range request conditional. The range is only served if the
+/// validator (an entity tag or date) still matches the file, otherwise the full file
+/// is returned.
+fn if_range_matches(headers: &HeaderMap, file: &FileBody) -> bool {
+    let Some(value) = headers.get(IF_RANGE) else {
+        return true;
+    };
+
+    match (value.to_str(), file.modified()) {
+        (Ok(value), _) if value.trim_start().starts_with(['"', 'W']) => {
+            cache::etag_matches(value, &file.etag(), true)
+        }
+        (Ok(value), Some(modified)) => httpdate::fmt_http_date(*modified) == value,
+        _ => false,
+    }
+}
+
+/// Builds a streamed response for a file asset, honoring conditional (`If-None-Match`,
+/// `If-Modified-Since`) and range (`Range`, `If-Range`) headers.
+pub async fn file_response(
+    headers: &HeaderMap,
+    file: FileBody,
+    cache_control: &str,
+) -> Result<Response<Body>, HandlerError> {
+    let builder = Response::builder().header(ACCEPT_RANGES, "bytes");
+    let builder = cache::cache_headers(builder, &file, cache_control);
+
+    if cache::not_modified(headers, &file) {
+        return builder
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Body::empty())
+            .map_err(|err| HandlerError::InternalError(err.to_string()));
+    }
+
+    let size = *file.size();
+    let range = match headers.get(RANGE).map(|v| v.to_str()) {
+        Some(Ok(value)) if if_range_matches(headers, &file) => parse_range(value, size),
+        _ => RangeRequest::Full,
+    };
+
+    let resp = match range {
+        RangeRequest::Full => {
+            let reader = file.reader().await?;
+            builder
+                .header(CONTENT_TYPE, file.mime().to_string())
+                .header(CONTENT_LENGTH, size)
+                .body(stream_body(reader))
+        }
+        RangeRequest::Unsatisfiable => builder
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(CONTENT_RANGE, format!("bytes */{size}"))
+            .body(Body::empty()),
+        RangeRequest::Partial(ranges) if ranges.len() == 1 => {
+            let range = &ranges[0];
+            let reader = file.range_reader(range.start, range.len()).await?;
+
+            builder
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(CONTENT_TYPE, file.mime().to_string())
+                .header(CONTENT_RANGE, range.content_range(size))
+                .header(CONTENT_LENGTH, range.len())
+                .body(stream_body(reader))
+        }
+        RangeRequest::Partial(ranges) => {
+            let boundary = format!(
+                "ppdrive-{:x}",
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos()
+            );
+
+            let mut length = 0;
+            let mut reader: AssetReader = Box::pin(tokio::io::empty());
+
+            // each range is sent as a part of a multipart/byteranges body
+            for range in &ranges {
+                let head = format!(
+                    "\r\n--{boundary}\r\n{CONTENT_TYPE}: {}\r\n{CONTENT_RANGE}: {}\r\n\r\n",
+                    file.mime(),
+                    range.content_range(size)
+                );
+
+                let part = file.range_reader(range.start, range.len()).await?;
+                length += head.len() as u64 + range.len();
+                reader = Box::pin(reader.chain(Cursor::new(head.into_bytes())).chain(part));
+            }
+
+            let tail = format!("\r\n--{boundary}--\r\n");
+            length += tail.len() as u64;
+            reader = Box::pin(reader.chain(Cursor::new(tail.into_bytes())));
+
+            builder
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(
+                    CONTENT_TYPE,
+                    format!("multipart/byteranges; boundary={boundary}"),
+                )
+                .header(CONTENT_LENGTH, length)
+                .body(stream_body(reader))
+        }
+    };
+
+    resp.map_err(|err| HandlerError::InternalError(err.to_string()))
+}
+
+fn stream_body(reader: AssetReader) -> Body {
+    Body::from_stream(ReaderStream::new(reader))
+}
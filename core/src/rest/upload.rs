@@ -0,0 +1,62 @@
+use std::path::PathBuf;
+
+use axum::extract::{Multipart, multipart::MultipartError};
+use ppd_fs::{
+    errors::Error as FsError,
+    opts::CreateAssetOptions,
+    upload::{TmpFile, upload_limit},
+};
+use ppd_shared::tools::mb_to_bytes;
+
+use crate::prelude::state::HandlerState;
+
+/// An asset sent as a multipart form, with an `options` field followed by an optional `file`.
+#[derive(Default)]
+pub struct AssetUpload {
+    pub opts: CreateAssetOptions,
+    pub tmp_file: Option<PathBuf>,
+    pub filesize: Option<u64>,
+}
+
+/// read an [AssetUpload] from `multipart`. The file is streamed to a temporary file, and
+/// the upload is aborted as soon as the bucket's space or the upload limit is exceeded.
+pub async fn read_asset_upload<E>(
+    state: &HandlerState,
+    mut multipart: Multipart,
+) -> Result<AssetUpload, E>
+where
+    E: From<MultipartError> + From<FsError> + From<serde_json::Error>,
+{
+    let db = state.db();
+    let max_upload = mb_to_bytes(state.config().base.max_upload_size) as u64;
+    let mut upload = AssetUpload::default();
+
+    while let Some(mut field) = multipart.next_field().await? {
+        let name = field.name().unwrap_or("").to_string();
+
+        if name == "options" {
+            let data = field.text().await?;
+            upload.opts = serde_json::from_str(&data)?;
+        } else if name == "file" {
+            let limit = upload_limit(db, &upload.opts.bucket, max_upload).await?;
+            let mut tmp = TmpFile::create(limit).await?;
+
+            loop {
+                match field.chunk().await {
+                    Ok(Some(chunk)) => tmp.write_chunk(&chunk).await?,
+                    Ok(None) => break,
+                    Err(err) => {
+                        tmp.discard().await;
+                        return Err(err.into());
+                    }
+                }
+            }
+
+            let (path, size) = tmp.finish().await?;
+            upload.filesize = Some(size);
+            upload.tmp_file = Some(path);
+        }
+    }
+
+    Ok(upload)
+}
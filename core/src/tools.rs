@@ -1,24 +1,120 @@
 use crate::{HandlerResult, errors::HandlerError};
-use chacha20poly1305::{Error as XError, KeyInit, XChaCha20Poly1305, XNonce, aead::Aead};
-use ppd_bk::{RBatis, models::client::Clients};
+use argon2::{
+    Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version,
+    password_hash::SaltString,
+};
+use chacha20poly1305::{
+    Error as XError, KeyInit, XChaCha20Poly1305, XNonce,
+    aead::{Aead, AeadCore, OsRng, rand_core::RngCore},
+};
+use chrono::Utc;
+use ppd_bk::{
+    RBatis,
+    models::{
+        client::{ClientKeys, Clients},
+        token::{PasswordResets, RefreshTokens},
+        user::Users,
+    },
+};
 use ppd_shared::{
-    opts::{ClientDetails, ClientInfo},
-    tools::AppSecrets,
+    opts::{
+        ClientDetails, ClientInfo, ClientKeyInfo, ClientScope, NewClientKey, PasswordHashConfig,
+        ReissuedToken,
+    },
+    tools::{AppSecrets, LEGACY_KEY_ID, SecretKey},
 };
 use sha3::{Digest, Sha3_256};
 
+/// version prefix of client tokens carrying a key id and nonce: `v2.<key id>.<hex(nonce | ciphertext)>`.
+/// Tokens without the prefix were issued by earlier versions with the legacy key's fixed nonce.
+const TOKEN_VERSION: &str = "v2";
+
+/// context of the [s3_secret] derivation, so S3 secrets never match another use of the key.
+const S3_SECRET_CONTEXT: &[u8] = b"ppdrive s3 secret";
+
 /// generate a token for client's id
 fn generate_token(secrets: &AppSecrets, client_key: &str) -> HandlerResult<String> {
-    let key = secrets.secret_key();
-    let nonce_key = secrets.nonce();
+    key_token(secrets.current(), client_key)
+}
+
+/// encrypt a client key with `key`, using a random nonce.
+fn key_token(key: &SecretKey, client_key: &str) -> HandlerResult<String> {
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let cipher = XChaCha20Poly1305::new(key.secret_key().into());
+
+    let mut data = nonce.to_vec();
+    data.extend(cipher.encrypt(&nonce, client_key.as_bytes())?);
+
+    Ok(format!("{TOKEN_VERSION}.{}.{}", key.id(), hex::encode(&data)))
+}
+
+/// derive the secret that signs S3 requests of a client key. Client tokens can't be issued
+/// again, so the secret is derived from the client's key and `key` instead.
+fn s3_secret(key: &SecretKey, client_key: &str) -> String {
+    let mut hasher = Sha3_256::new();
+    hasher.update(S3_SECRET_CONTEXT);
+    hasher.update(key.secret_key());
+    hasher.update(client_key.as_bytes());
+
+    hex::encode(hasher.finalize())
+}
+
+/// all S3 secrets of a client key that are currently accepted, the current key's secret first.
+/// Legacy tokens were used as S3 secrets and are accepted as long as the legacy key is kept.
+fn accepted_s3_secrets(secrets: &AppSecrets, client_key: &str) -> HandlerResult<Vec<String>> {
+    let mut accepted = Vec::new();
+
+    for key in secrets.keys() {
+        accepted.push(s3_secret(key, client_key));
 
-    let nonce = XNonce::from_slice(nonce_key);
-    let cipher = XChaCha20Poly1305::new(key.into());
+        if let Some(nonce) = key.nonce() {
+            let cipher = XChaCha20Poly1305::new(key.secret_key().into());
+            let encrypt = cipher.encrypt(XNonce::from_slice(nonce), client_key.as_bytes())?;
+            accepted.push(hex::encode(&encrypt));
+        }
+    }
+
+    Ok(accepted)
+}
+
+/// decrypt a client token and return the client key it holds.
+fn decrypt_token(secrets: &AppSecrets, token: &str) -> HandlerResult<String> {
+    let invalid = |msg: &str| HandlerError::AuthorizationError(format!("invalid client token: {msg}"));
+
+    let (key, data, versioned) = match token.split('.').collect::<Vec<_>>().as_slice() {
+        [TOKEN_VERSION, id, data] => {
+            let key = id
+                .parse()
+                .ok()
+                .and_then(|id| secrets.key(id))
+                .ok_or_else(|| invalid("unknown key"))?;
+
+            (key, *data, true)
+        }
+        [data] => {
+            let key = secrets
+                .key(LEGACY_KEY_ID)
+                .ok_or_else(|| invalid("unknown key"))?;
+
+            (key, *data, false)
+        }
+        _ => return Err(invalid("unsupported format")),
+    };
+
+    let mut data = hex::decode(data).map_err(|err| invalid(&err.to_string()))?;
+    let nonce = match (versioned, key.nonce()) {
+        (true, _) if data.len() > 24 => data.drain(..24).collect(),
+        (true, _) => return Err(invalid("token is too short")),
+        (false, Some(nonce)) => nonce.to_vec(),
+        (false, None) => return Err(invalid("unknown key")),
+    };
 
-    let encrypt = cipher.encrypt(nonce, client_key.as_bytes())?;
-    let encode = hex::encode(&encrypt);
+    let cipher = XChaCha20Poly1305::new(key.secret_key().into());
+    let decrypt = cipher
+        .decrypt(XNonce::from_slice(&nonce), data.as_slice())
+        .map_err(|err| invalid(&err.to_string()))?;
 
-    Ok(encode)
+    String::from_utf8(decrypt).map_err(|err| invalid(&err.to_string()))
 }
 
 /// creates a new client and returns the client's key
@@ -30,42 +126,217 @@ pub async fn create_client(
 ) -> HandlerResult<ClientDetails> {
     let client_key = Clients::new_key();
     let token = generate_token(secrets, &client_key)?;
+    let s3_secret = s3_secret(secrets.current(), &client_key);
 
     let id = Clients::create(rb, client_key, name.to_string(), max_bucket_size).await?;
-    Ok((id, token).into())
+    Ok((id, token, s3_secret).into())
 }
 
-/// validate that a given client token exists
-pub async fn verify_client(rb: &RBatis, secrets: &AppSecrets, token: &str) -> HandlerResult<(u64, Option<f64>)> {
-    let decode =
-        hex::decode(token).map_err(|err| HandlerError::AuthorizationError(err.to_string()))?;
+/// how often (seconds) the last use of a client key is recorded.
+const KEY_USAGE_INTERVAL: i64 = 60;
 
-    let key = secrets.secret_key();
-    let nonce_key = secrets.nonce();
+/// A client authenticated with its token.
+pub struct VerifiedClient {
+    pub id: u64,
+    pub max_bucket_size: Option<f64>,
 
-    let cipher = XChaCha20Poly1305::new(key.into());
-    let nonce = XNonce::from_slice(nonce_key);
+    /// scopes of the key used. Empty if the key has all scopes.
+    pub scopes: Vec<ClientScope>,
+}
 
-    let decrypt = cipher.decrypt(nonce, decode.as_slice())?;
+/// validate that a given client token exists
+pub async fn verify_client(rb: &RBatis, secrets: &AppSecrets, token: &str) -> HandlerResult<VerifiedClient> {
+    let key = decrypt_token(secrets, token)?;
 
-    let key = String::from_utf8(decrypt)
-        .map_err(|err| HandlerError::AuthorizationError(err.to_string()))?;
+    if let Some(mut client_key) = ClientKeys::find_with_key(rb, &key).await? {
+        let scopes = use_client_key(rb, &mut client_key).await?;
+        let client = Clients::get_by_id(rb, client_key.client_id()).await?;
+
+        return Ok(VerifiedClient {
+            id: client.id(),
+            max_bucket_size: *client.max_bucket_size(),
+            scopes,
+        });
+    }
 
     let client = Clients::get_with_key(rb, &key).await?;
-    Ok((client.id(), *client.max_bucket_size()))
+    Ok(VerifiedClient {
+        id: client.id(),
+        max_bucket_size: *client.max_bucket_size(),
+        scopes: vec![],
+    })
+}
+
+/// reject expired keys and record the key's use. Returns the key's scopes.
+async fn use_client_key(rb: &RBatis, client_key: &mut ClientKeys) -> HandlerResult<Vec<ClientScope>> {
+    let now = Utc::now().timestamp();
+    if let Some(expires_at) = client_key.expires_at()
+        && *expires_at <= now
+    {
+        return Err(HandlerError::AuthorizationError(
+            "client key has expired".to_string(),
+        ));
+    }
+
+    if client_key
+        .last_used()
+        .is_none_or(|last_used| now - last_used >= KEY_USAGE_INTERVAL)
+    {
+        client_key.touch(rb, now).await?;
+    }
+
+    Ok(client_key.scopes())
+}
+
+/// check that `scopes` (of a client key) grants `scope`.
+pub fn check_scope(scopes: &[ClientScope], scope: ClientScope) -> HandlerResult<()> {
+    if scopes.is_empty() || scopes.contains(&scope) {
+        Ok(())
+    } else {
+        Err(HandlerError::PermissionError(format!(
+            "client key is missing the '{scope}' scope"
+        )))
+    }
+}
+
+/// Retrieve a client and its S3 secrets, used to verify S3 request signatures. `access_id`
+/// is the id of a client key or, for the client's primary key, the client's id. Returns every
+/// secret accepted for the key, so requests signed with a secret issued before a rotation are
+/// still verified, and the key's scopes.
+pub async fn get_client_with_s3_secrets(
+    db: &RBatis,
+    secrets: &AppSecrets,
+    access_id: &str,
+) -> HandlerResult<(Clients, Vec<String>, Vec<ClientScope>)> {
+    if let Ok(mut client_key) = ClientKeys::get(db, access_id).await {
+        let scopes = use_client_key(db, &mut client_key).await?;
+        let client = Clients::get_by_id(db, client_key.client_id()).await?;
+        let accepted = accepted_s3_secrets(secrets, client_key.key())?;
+
+        return Ok((client, accepted, scopes));
+    }
+
+    let client = Clients::get(db, access_id).await?;
+    let accepted = accepted_s3_secrets(secrets, client.key())?;
+
+    Ok((client, accepted, vec![]))
+}
+
+/// issue the tokens of every client and client key again with the current secret key. Tokens
+/// issued with previous keys keep working until their key is dropped.
+pub async fn reissue_tokens(
+    db: &RBatis,
+    secrets: &AppSecrets,
+    service_id: u8,
+) -> HandlerResult<Vec<ReissuedToken>> {
+    let clients = Clients::select_all(db)
+        .await
+        .map_err(|err| HandlerError::InternalError(err.to_string()))?;
+
+    let mut tokens = Vec::new();
+    for client in clients {
+        tokens.push(ReissuedToken {
+            service_id,
+            client_id: client.pid().to_string(),
+            key_id: None,
+            token: generate_token(secrets, client.key())?,
+            s3_secret: s3_secret(secrets.current(), client.key()),
+        });
+
+        for key in ClientKeys::list(db, &client.id()).await? {
+            tokens.push(ReissuedToken {
+                service_id,
+                client_id: client.pid().to_string(),
+                key_id: Some(key.pid().to_string()),
+                token: generate_token(secrets, key.key())?,
+                s3_secret: s3_secret(secrets.current(), key.key()),
+            });
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// create a named key for a client and return the key's id and token.
+pub async fn create_client_key(
+    db: &RBatis,
+    secrets: &AppSecrets,
+    client_id: &str,
+    opts: &NewClientKey,
+) -> HandlerResult<ClientDetails> {
+    let client = Clients::get(db, client_id).await?;
+    let key = Clients::new_key();
+    let token = generate_token(secrets, &key)?;
+    let s3_secret = s3_secret(secrets.current(), &key);
+
+    let expires_at = opts
+        .expires_in_days
+        .map(|days| Utc::now().timestamp() + i64::from(days) * 86400);
+
+    let id = ClientKeys::create(
+        db,
+        client.id(),
+        key,
+        opts.name.clone(),
+        &opts.scopes,
+        expires_at,
+    )
+    .await?;
+
+    Ok((id, token, s3_secret).into())
 }
 
-/// Regenerate token for a given client.
+pub async fn get_client_keys(db: &RBatis, client_id: &str) -> HandlerResult<Vec<ClientKeyInfo>> {
+    let client = Clients::get(db, client_id).await?;
+    let keys = ClientKeys::list(db, &client.id()).await?;
+
+    Ok(keys.iter().map(|k| k.into()).collect())
+}
+
+/// revoke a client key. Requests made with the key are rejected immediately.
+pub async fn revoke_client_key(db: &RBatis, key_id: &str) -> HandlerResult<()> {
+    let client_key = ClientKeys::get(db, key_id).await?;
+    client_key.delete(db).await?;
+
+    Ok(())
+}
+
+/// Regenerate token and S3 secret for a given client.
 pub async fn regenerate_token(
     db: &RBatis,
     secrets: &AppSecrets,
     client_id: &str,
-) -> HandlerResult<String> {
+) -> HandlerResult<ClientDetails> {
     let mut client = Clients::get(db, client_id).await?;
     client.update_key(db).await?;
 
     let token = generate_token(secrets, client.key())?;
-    Ok(token)
+    let s3_secret = s3_secret(secrets.current(), client.key());
+
+    Ok((client_id.to_string(), token, s3_secret).into())
+}
+
+/// creates an admin user and returns the user's id
+pub async fn create_admin(
+    db: &RBatis,
+    username: &str,
+    password: &str,
+    config: &PasswordHashConfig,
+) -> HandlerResult<String> {
+    let exists = Users::get_by_key(db, "username", username)
+        .await
+        .map_err(|err| HandlerError::InternalError(err.to_string()))?;
+
+    if exists.is_some() {
+        return Err(HandlerError::PermissionError(
+            "user with this username already exists".to_string(),
+        ));
+    }
+
+    let password = make_password(password, config)?;
+    let id = Users::create_admin(db, username.to_string(), password).await?;
+
+    Ok(id)
 }
 
 pub async fn get_clients(db: &RBatis) -> HandlerResult<Vec<ClientInfo>> {
@@ -76,21 +347,134 @@ pub async fn get_clients(db: &RBatis) -> HandlerResult<Vec<ClientInfo>> {
     Ok(results)
 }
 
-pub fn make_password(password: &str) -> String {
-    let hash_pass = Sha3_256::digest(password.to_string().as_bytes());
-    hex::encode(hash_pass)
+fn argon2(config: &PasswordHashConfig) -> HandlerResult<Argon2<'static>> {
+    let params = Params::new(config.memory, config.iterations, config.parallelism, None)
+        .map_err(|err| HandlerError::InternalError(format!("invalid argon2 parameters: {err}")))?;
+
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
 }
 
-pub fn check_password(password: &str, hashed: &str) -> HandlerResult<String> {
-    let h = make_password(password);
+/// hash a password into an Argon2id PHC string.
+pub fn make_password(password: &str, config: &PasswordHashConfig) -> HandlerResult<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = argon2(config)?
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|err| HandlerError::InternalError(format!("unable to hash password: {err}")))?;
 
-    if *hashed != h {
-        return Err(HandlerError::AuthorizationError(
-            "wrong password!".to_string(),
-        ));
+    Ok(hash.to_string())
+}
+
+/// verify a password against a hash created by [make_password] or a legacy (unsalted SHA3)
+/// hash. Returns `true` if the hash is outdated and should be replaced.
+pub fn check_password(
+    password: &str,
+    hashed: &str,
+    config: &PasswordHashConfig,
+) -> HandlerResult<bool> {
+    let wrong_password = || HandlerError::AuthorizationError("wrong password!".to_string());
+
+    match PasswordHash::new(hashed) {
+        Ok(hash) => {
+            // parameters are read from the hash, so older hashes can still be verified.
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .map_err(|_| wrong_password())?;
+
+            let outdated = hash.algorithm != Algorithm::Argon2id.ident()
+                || Params::try_from(&hash).map_or(true, |params| {
+                    params.m_cost() != config.memory
+                        || params.t_cost() != config.iterations
+                        || params.p_cost() != config.parallelism
+                });
+
+            Ok(outdated)
+        }
+        Err(_) => {
+            let legacy = hex::encode(Sha3_256::digest(password.as_bytes()));
+            if !constant_time_eq(legacy.as_bytes(), hashed.as_bytes()) {
+                return Err(wrong_password());
+            }
+
+            Ok(true)
+        }
+    }
+}
+
+/// verify the user's password, upgrading an outdated hash in place.
+pub async fn verify_password(
+    db: &RBatis,
+    user: &mut Users,
+    password: &str,
+    config: &PasswordHashConfig,
+) -> HandlerResult<()> {
+    let hashed = user.password().clone().unwrap_or_default();
+
+    if check_password(password, &hashed, config)? {
+        let rehashed = make_password(password, config)?;
+        user.update_password(db, rehashed).await?;
+    }
+
+    Ok(())
+}
+
+/// replace the user's password and revoke their refresh tokens.
+pub async fn set_password(
+    db: &RBatis,
+    user: &mut Users,
+    password: &str,
+    config: &PasswordHashConfig,
+) -> HandlerResult<()> {
+    let hashed = make_password(password, config)?;
+    user.update_password(db, hashed).await?;
+    RefreshTokens::delete_for_user(db, &user.id()).await?;
+
+    Ok(())
+}
+
+fn hash_reset_token(token: &str) -> String {
+    hex::encode(Sha3_256::digest(token.as_bytes()))
+}
+
+/// create a password reset token valid for `exp` seconds. Only the token's hash is saved and
+/// earlier tokens of the user are discarded. Returns the token and its expiry timestamp.
+pub async fn create_reset_token(db: &RBatis, user_id: &u64, exp: i64) -> HandlerResult<(String, i64)> {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+
+    let token = hex::encode(bytes);
+    let expires_at = Utc::now().timestamp() + exp;
+    PasswordResets::create(db, hash_reset_token(&token), *user_id, expires_at).await?;
+
+    Ok((token, expires_at))
+}
+
+/// set a new password for the user a reset token was issued to. The token can be used once.
+pub async fn reset_password(
+    db: &RBatis,
+    token: &str,
+    password: &str,
+    config: &PasswordHashConfig,
+) -> HandlerResult<()> {
+    let invalid_token =
+        || HandlerError::AuthorizationError("invalid or expired reset token".to_string());
+
+    let token_hash = hash_reset_token(token);
+    let reset = PasswordResets::find(db, &token_hash)
+        .await?
+        .ok_or_else(invalid_token)?;
+
+    if !PasswordResets::consume(db, &token_hash).await? {
+        // the token has expired, or was redeemed by a concurrent request
+        reset.delete(db).await?;
+        return Err(invalid_token());
     }
 
-    Ok(h)
+    let mut user = Users::get(db, reset.user_id()).await?;
+    set_password(db, &mut user, password, config).await
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
 }
 
 impl From<XError> for HandlerError {
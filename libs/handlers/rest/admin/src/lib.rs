@@ -0,0 +1,376 @@
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    routing::{get, post},
+};
+use axum_macros::debug_handler;
+use ppd_bk::{
+    models::{
+        IntoSerializer,
+        asset::{AssetSerializer, AssetType, Assets},
+        bucket::{BucketSerializer, Buckets},
+        client::Clients,
+        user::{UserRole, UserSerializer, Users},
+    },
+};
+use ppd_fs::{
+    auth::{delete_asset as remove_asset, delete_bucket as remove_bucket},
+    storage::{Storage, StorageBackend},
+};
+use ppd_shared::{
+    api::{ListPage, LoginTokens, PageOptions, UpdateQuota, UsageInfo, UserCredentials},
+    opts::{ClientInfo, ServiceConfig},
+};
+use ppdrive::{
+    jwt::LoginOpts, prelude::state::HandlerState, rest::extractors::AdminExtractor,
+    tools::verify_password,
+};
+use serde::Serialize;
+
+use crate::errors::ServerError;
+
+mod errors;
+
+/// A bucket and the size (in bytes) of its assets.
+#[derive(Serialize)]
+pub struct BucketUsage {
+    #[serde(flatten)]
+    bucket: BucketSerializer,
+    size: u64,
+}
+
+async fn serialize_all<M: IntoSerializer>(
+    state: &HandlerState,
+    models: Vec<M>,
+) -> Result<Vec<M::Serializer>, ServerError> {
+    let mut results = Vec::with_capacity(models.len());
+    for model in models {
+        results.push(model.into_serializer(state.db()).await?);
+    }
+
+    Ok(results)
+}
+
+fn list_page<T>(opts: &PageOptions, items: Vec<T>, total: u64) -> ListPage<T> {
+    ListPage {
+        items,
+        page: opts.page(),
+        limit: opts.limit(),
+        total,
+    }
+}
+
+#[debug_handler]
+async fn login_admin(
+    State(state): State<HandlerState>,
+    Json(data): Json<UserCredentials>,
+) -> Result<Json<LoginTokens>, ServerError> {
+    let db = state.db();
+    let config = state.config();
+    let secrets = state.secrets();
+
+    let UserCredentials { username, password } = data;
+    let mut user = Users::get_by_key(db, "username", &username)
+        .await
+        .map_err(|err| ServerError::InternalError(err.to_string()))?
+        .ok_or(ServerError::AuthorizationError(format!(
+            "user with username '{username}' does not exist"
+        )))?;
+
+    verify_password(db, &mut user, &password, &config.auth.password).await?;
+
+    if !matches!(user.role()?, UserRole::Admin) {
+        return Err(ServerError::PermissionDenied(
+            "admin access required".to_string(),
+        ));
+    }
+
+    let login = LoginOpts {
+        user_id: &user.id(),
+        config: &config,
+        secrets: &secrets,
+        access_exp: None,
+        refresh_exp: None,
+        user_max_bucket: *user.max_bucket_size(),
+    };
+
+    let tokens = login.tokens(db).await?;
+    Ok(Json(tokens))
+}
+
+#[debug_handler]
+async fn list_clients(
+    Query(opts): Query<PageOptions>,
+    State(state): State<HandlerState>,
+    _: AdminExtractor,
+) -> Result<Json<ListPage<ClientInfo>>, ServerError> {
+    let (clients, total) = Clients::get_page(state.db(), opts.page(), opts.limit()).await?;
+    let items = clients.iter().map(ClientInfo::from).collect();
+
+    Ok(Json(list_page(&opts, items, total)))
+}
+
+#[debug_handler]
+async fn get_client(
+    Path(id): Path<String>,
+    State(state): State<HandlerState>,
+    _: AdminExtractor,
+) -> Result<Json<ClientInfo>, ServerError> {
+    let client = Clients::get(state.db(), &id).await?;
+    Ok(Json(ClientInfo::from(&client)))
+}
+
+#[debug_handler]
+async fn update_client_quota(
+    Path(id): Path<String>,
+    State(state): State<HandlerState>,
+    _: AdminExtractor,
+    Json(data): Json<UpdateQuota>,
+) -> Result<Json<ClientInfo>, ServerError> {
+    let db = state.db();
+    let mut client = Clients::get(db, &id).await?;
+    client.update_max_bucket_size(db, data.size).await?;
+
+    Ok(Json(ClientInfo::from(&client)))
+}
+
+/// removes a client with its users, buckets and assets.
+#[debug_handler]
+async fn delete_client(
+    Path(id): Path<String>,
+    State(state): State<HandlerState>,
+    _: AdminExtractor,
+) -> Result<String, ServerError> {
+    let db = state.db();
+    let client = Clients::get(db, &id).await?;
+
+    for user in Users::client_users(db, &client.id()).await? {
+        delete_user_data(&state, &user).await?;
+    }
+
+    for bucket in Buckets::client_buckets(db, &client.id()).await? {
+        remove_bucket(db, &bucket).await?;
+    }
+
+    client.delete(db).await?;
+    Ok("operation successful".to_string())
+}
+
+#[debug_handler]
+async fn list_users(
+    Query(opts): Query<PageOptions>,
+    State(state): State<HandlerState>,
+    _: AdminExtractor,
+) -> Result<Json<ListPage<UserSerializer>>, ServerError> {
+    let (users, total) = Users::get_page(state.db(), opts.page(), opts.limit()).await?;
+    let items = serialize_all(&state, users).await?;
+
+    Ok(Json(list_page(&opts, items, total)))
+}
+
+#[debug_handler]
+async fn get_user(
+    Path(id): Path<String>,
+    State(state): State<HandlerState>,
+    _: AdminExtractor,
+) -> Result<Json<UserSerializer>, ServerError> {
+    let db = state.db();
+    let user = Users::get_by_pid(db, &id).await?;
+
+    Ok(Json(user.into_serializer(db).await?))
+}
+
+#[debug_handler]
+async fn update_user_quota(
+    Path(id): Path<String>,
+    State(state): State<HandlerState>,
+    _: AdminExtractor,
+    Json(data): Json<UpdateQuota>,
+) -> Result<Json<UserSerializer>, ServerError> {
+    let db = state.db();
+    let mut user = Users::get_by_pid(db, &id).await?;
+    user.update_max_bucket(db, data.size).await?;
+
+    Ok(Json(user.into_serializer(db).await?))
+}
+
+/// removes a user with their buckets and assets.
+#[debug_handler]
+async fn delete_user(
+    Path(id): Path<String>,
+    State(state): State<HandlerState>,
+    admin: AdminExtractor,
+) -> Result<String, ServerError> {
+    let user = Users::get_by_pid(state.db(), &id).await?;
+    if user.id() == *admin.id() {
+        return Err(ServerError::PermissionDenied(
+            "admin cannot delete their own account".to_string(),
+        ));
+    }
+
+    delete_user_data(&state, &user).await?;
+    Ok("operation successful".to_string())
+}
+
+async fn delete_user_data(state: &HandlerState, user: &Users) -> Result<(), ServerError> {
+    let db = state.db();
+    for bucket in Buckets::user_buckets(db, &user.id()).await? {
+        remove_bucket(db, &bucket).await?;
+    }
+
+    user.delete(db).await?;
+    Ok(())
+}
+
+#[debug_handler]
+async fn list_buckets(
+    Query(opts): Query<PageOptions>,
+    State(state): State<HandlerState>,
+    _: AdminExtractor,
+) -> Result<Json<ListPage<BucketSerializer>>, ServerError> {
+    let (buckets, total) = Buckets::get_page(state.db(), opts.page(), opts.limit()).await?;
+    let items = serialize_all(&state, buckets).await?;
+
+    Ok(Json(list_page(&opts, items, total)))
+}
+
+#[debug_handler]
+async fn get_bucket(
+    Path(id): Path<String>,
+    State(state): State<HandlerState>,
+    _: AdminExtractor,
+) -> Result<Json<BucketUsage>, ServerError> {
+    let db = state.db();
+    let bucket = Buckets::get_by_pid(db, &id).await?;
+    let size = Storage::for_bucket(&bucket).size().await?;
+
+    Ok(Json(BucketUsage {
+        bucket: bucket.into_serializer(db).await?,
+        size,
+    }))
+}
+
+#[debug_handler]
+async fn update_bucket_quota(
+    Path(id): Path<String>,
+    State(state): State<HandlerState>,
+    _: AdminExtractor,
+    Json(data): Json<UpdateQuota>,
+) -> Result<Json<BucketSerializer>, ServerError> {
+    let db = state.db();
+    let mut bucket = Buckets::get_by_pid(db, &id).await?;
+    bucket.update_partition_size(db, data.size).await?;
+
+    Ok(Json(bucket.into_serializer(db).await?))
+}
+
+#[debug_handler]
+async fn delete_bucket(
+    Path(id): Path<String>,
+    State(state): State<HandlerState>,
+    _: AdminExtractor,
+) -> Result<String, ServerError> {
+    let db = state.db();
+    let bucket = Buckets::get_by_pid(db, &id).await?;
+    remove_bucket(db, &bucket).await?;
+
+    Ok("operation successful".to_string())
+}
+
+#[debug_handler]
+async fn list_bucket_assets(
+    Path(id): Path<String>,
+    Query(opts): Query<PageOptions>,
+    State(state): State<HandlerState>,
+    _: AdminExtractor,
+) -> Result<Json<ListPage<AssetSerializer>>, ServerError> {
+    let db = state.db();
+    let bucket = Buckets::get_by_pid(db, &id).await?;
+
+    let (assets, total) =
+        Assets::get_page_by_bucket(db, &bucket.id(), opts.page(), opts.limit()).await?;
+    let items = serialize_all(&state, assets).await?;
+
+    Ok(Json(list_page(&opts, items, total)))
+}
+
+#[debug_handler]
+async fn get_asset(
+    Path((asset_type, asset_path)): Path<(AssetType, String)>,
+    State(state): State<HandlerState>,
+    _: AdminExtractor,
+) -> Result<Json<AssetSerializer>, ServerError> {
+    let db = state.db();
+    let asset = Assets::get_by_path(db, &asset_path, &asset_type).await?;
+
+    Ok(Json(asset.into_serializer(db).await?))
+}
+
+#[debug_handler]
+async fn delete_asset(
+    Path((asset_type, asset_path)): Path<(AssetType, String)>,
+    State(state): State<HandlerState>,
+    _: AdminExtractor,
+) -> Result<String, ServerError> {
+    remove_asset(state.db(), &asset_path, &asset_type).await?;
+    Ok("operation successful".to_string())
+}
+
+#[debug_handler]
+async fn get_usage(
+    State(state): State<HandlerState>,
+    _: AdminExtractor,
+) -> Result<Json<UsageInfo>, ServerError> {
+    let db = state.db();
+
+    Ok(Json(UsageInfo {
+        clients: Clients::count(db).await?,
+        users: Users::count(db).await?,
+        buckets: Buckets::count(db).await?,
+        assets: Assets::count(db).await?,
+        storage_size: Assets::total_size(db).await?,
+    }))
+}
+
+/// Routes for service administrators.
+fn routes(_: Arc<ServiceConfig>) -> Router<HandlerState> {
+    Router::new()
+        .route("/login", post(login_admin))
+        .route("/usage", get(get_usage))
+        .route("/clients", get(list_clients))
+        .route(
+            "/client/:id",
+            get(get_client)
+                .patch(update_client_quota)
+                .delete(delete_client),
+        )
+        .route("/users", get(list_users))
+        .route(
+            "/user/:id",
+            get(get_user).patch(update_user_quota).delete(delete_user),
+        )
+        .route("/buckets", get(list_buckets))
+        .route(
+            "/bucket/:id",
+            get(get_bucket)
+                .patch(update_bucket_quota)
+                .delete(delete_bucket),
+        )
+        .route("/bucket/:id/assets", get(list_bucket_assets))
+        .route(
+            "/asset/:asset_type/*asset_path",
+            get(get_asset).delete(delete_asset),
+        )
+}
+
+/// # Safety
+/// `config` must be a pointer obtained from `Arc::into_raw`. Ownership of the config is
+/// transferred to the router.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rest_admin(config: *const ServiceConfig) -> *mut Router<HandlerState> {
+    let config = unsafe { Arc::from_raw(config) };
+    let bx = Box::new(routes(config));
+    Box::into_raw(bx)
+}
@@ -0,0 +1,149 @@
+use axum::http::StatusCode;
+use ppd_shared::api::{CreateBucketOptions, ListPage, UpdateQuota, UsageInfo};
+use rest_test_utils::TestApp;
+use serde_json::Value;
+use serial_test::serial;
+
+const BUCKET_PARTITION: &str = "admin-test-bucket";
+
+#[tokio::test]
+#[serial]
+async fn test_rest_admin_login() {
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    let token = app.admin_login().await;
+    server
+        .get("/admin/usage")
+        .authorization(&token)
+        .await
+        .assert_status_ok();
+
+    server
+        .get("/admin/usage")
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rest_admin_rejects_users() {
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    let token = app.direct_login().await;
+
+    server
+        .get("/admin/users")
+        .authorization(&token)
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rest_admin_manage_clients() {
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    app.client_token().await;
+    let token = app.admin_login().await;
+
+    let clients: ListPage<Value> = server.get("/admin/clients").authorization(&token).await.json();
+    assert_eq!(clients.total, 1);
+
+    let client_id = clients.items[0]["id"].as_str().expect("client id missing");
+    let resp = server
+        .patch(&format!("/admin/client/{client_id}"))
+        .authorization(&token)
+        .json(&UpdateQuota { size: Some(20.0) })
+        .await;
+
+    resp.assert_status_ok();
+    let client: Value = resp.json();
+    assert_eq!(client["max_bucket_size"], 20.0);
+
+    server
+        .delete(&format!("/admin/client/{client_id}"))
+        .authorization(&token)
+        .await
+        .assert_status_ok();
+
+    let clients: ListPage<Value> = server.get("/admin/clients").authorization(&token).await.json();
+    assert!(clients.items.is_empty());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rest_admin_manage_users_and_buckets() {
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    let user_token = app.direct_login().await;
+    let token = app.admin_login().await;
+
+    let opts = CreateBucketOptions {
+        label: "Admin Test Bucket".to_string(),
+        partition: Some(BUCKET_PARTITION.to_string()),
+        ..Default::default()
+    };
+
+    let resp = server
+        .post("/direct/user/bucket")
+        .authorization(&user_token)
+        .json(&opts)
+        .await;
+
+    resp.assert_status_ok();
+    let bucket_id = resp.text();
+
+    let user: Value = server.get("/direct/user").authorization(&user_token).await.json();
+    let user_id = user["id"].as_str().expect("user id missing");
+
+    let usage: UsageInfo = server.get("/admin/usage").authorization(&token).await.json();
+    assert_eq!(usage.users, 2);
+    assert_eq!(usage.buckets, 1);
+
+    let users: ListPage<Value> = server
+        .get("/admin/users?page=2&limit=1")
+        .authorization(&token)
+        .await
+        .json();
+
+    assert_eq!(users.total, 2);
+    assert_eq!(users.items.len(), 1);
+
+    let resp = server
+        .patch(&format!("/admin/bucket/{bucket_id}"))
+        .authorization(&token)
+        .json(&UpdateQuota { size: Some(10.0) })
+        .await;
+
+    resp.assert_status_ok();
+    let bucket: Value = resp.json();
+    assert_eq!(bucket["partition_size"], 10.0);
+    assert_eq!(bucket["owner_id"], user_id);
+
+    let resp = server
+        .patch(&format!("/admin/user/{user_id}"))
+        .authorization(&token)
+        .json(&UpdateQuota { size: Some(50.0) })
+        .await;
+
+    resp.assert_status_ok();
+    let user: Value = resp.json();
+    assert_eq!(user["max_bucket"], 50.0);
+
+    server
+        .delete(&format!("/admin/user/{user_id}"))
+        .authorization(&token)
+        .await
+        .assert_status_ok();
+
+    let buckets: ListPage<Value> = server.get("/admin/buckets").authorization(&token).await.json();
+    assert!(buckets.items.is_empty());
+
+    if let Err(err) = std::fs::remove_dir_all(BUCKET_PARTITION) {
+        println!("{err}");
+    }
+}
@@ -1,31 +1,51 @@
 use axum::{
     Json,
-    extract::{Multipart, Path, State},
+    body::Body,
+    extract::{Multipart, Path, Query, State},
 };
 use axum_macros::debug_handler;
-use tokio::{fs::File, io::AsyncWriteExt};
-use uuid::Uuid;
 
 use crate::errors::ServerError;
 use ppd_bk::models::{
     IntoSerializer,
     asset::{AssetType, Assets},
     bucket::Buckets,
+    upload::{UploadSessionSerializer, UploadSessions},
     user::{UserSerializer, Users},
 };
-use ppd_shared::{api::CreateBucketOptions, tools::SECRETS_FILENAME};
+use ppd_shared::{
+    api::{AssetVersion, CreateBucketOptions, DeleteAssetOptions, TrashEntry},
+    opts::ClientScope,
+    tools::{SECRETS_FILENAME, mb_to_bytes},
+};
 use ppdrive::{
     prelude::state::HandlerState,
-    rest::extractors::{BucketSizeValidator, ClientUserExtractor},
+    rest::{
+        AssetUpload, read_asset_upload,
+        extractors::{BucketSizeValidator, ContentLength, ClientUserExtractor, UploadOffset},
+    },
 };
 
-use ppd_fs::{auth::create_or_update_asset, opts::CreateAssetOptions};
+use ppd_fs::{
+    auth::{create_or_update_asset, delete_asset as remove_asset},
+    storage::Storage,
+    opts::{
+        CreateUploadOptions, MoveAssetOptions, PromoteVersionOptions, RenameAssetOptions,
+    },
+    transfer,
+    validate_user_path,
+    trash::{empty_trash, list_trash, purge_trash_entry, restore_asset, trash_asset},
+    versions::{list_versions, promote_version},
+    upload::{append_session_chunk, cancel_session, complete_session, create_session},
+};
 
 #[debug_handler]
 pub async fn get_user(
     State(state): State<HandlerState>,
     user: ClientUserExtractor,
 ) -> Result<Json<UserSerializer>, ServerError> {
+    user.require(ClientScope::UsersRead)?;
+
     let db = state.db();
     let user_model = Users::get(db, user.id()).await?;
     let data = user_model.into_serializer(db).await?;
@@ -39,9 +59,13 @@ pub async fn create_user_bucket(
     user: ClientUserExtractor,
     Json(data): Json<CreateBucketOptions>,
 ) -> Result<String, ServerError> {
+    user.require(ClientScope::BucketsCreate)?;
+
     let db = state.db();
 
     user.validate_bucket_size(db, &data.partition_size).await?;
+    Storage::validate_backend(&data.backend)?;
+
     let id = Buckets::create_by_user(db, data, *user.id()).await?;
 
     Ok(id)
@@ -51,47 +75,18 @@ pub async fn create_user_bucket(
 pub async fn create_asset(
     State(state): State<HandlerState>,
     user: ClientUserExtractor,
-    mut multipart: Multipart,
+    multipart: Multipart,
 ) -> Result<String, ServerError> {
-    let mut opts = CreateAssetOptions::default();
-    let mut tmp_file = None;
-    let mut filesize = None;
-
-    while let Some(field) = multipart.next_field().await? {
-        let name = field.name().unwrap_or("").to_string();
-
-        if name == "options" {
-            let data = field.text().await?;
-            opts = serde_json::from_str(&data)?;
-        } else if name == "file" {
-            let tmp_name = Uuid::new_v4().to_string();
-            let mut tmp_path = std::env::temp_dir();
-            tmp_path.push(tmp_name);
-
-            let mut file = File::create(&tmp_path).await?;
-
-            let data = field.bytes().await?;
-            file.write_all(&data).await?;
-
-            filesize = Some(file.metadata().await?.len());
-            tmp_file = Some(tmp_path);
-        }
-    }
-
-    // options validations
-    if opts.asset_path.is_empty() {
-        return Err(ServerError::InternalError(
-            "asset_path field is required".to_string(),
-        ));
-    }
-
-    if opts.asset_path == SECRETS_FILENAME {
-        return Err(ServerError::AuthorizationError(
-            "asset_path '{SECRET_FILE}' is reserved. please choose another path.".to_string(),
-        ));
-    }
+    user.require(ClientScope::AssetsWrite)?;
 
     let db = state.db();
+    let AssetUpload {
+        opts,
+        tmp_file,
+        filesize,
+    } = read_asset_upload::<ServerError>(&state, multipart).await?;
+
+    validate_asset_path(&opts.asset_path)?;
     create_or_update_asset(db, user.id(), &opts, &tmp_file, &filesize).await?;
     
     Ok("operation successful!".to_string())
@@ -100,14 +95,23 @@ pub async fn create_asset(
 #[debug_handler]
 pub async fn delete_asset(
     Path((asset_type, asset_path)): Path<(AssetType, String)>,
+    Query(opts): Query<DeleteAssetOptions>,
     State(state): State<HandlerState>,
     user: ClientUserExtractor,
 ) -> Result<String, ServerError> {
+    user.require(ClientScope::AssetsWrite)?;
+    validate_asset_path(&asset_path)?;
+
     let db = state.db();
     let asset = Assets::get_by_path(db, &asset_path, &asset_type).await?;
 
     if asset.user_id() == user.id() {
-        asset.delete(db).await?;
+        if opts.permanent || state.config().base.trash_retention == 0 {
+            remove_asset(db, &asset_path, &asset_type).await?;
+        } else {
+            trash_asset(db, &asset_path, &asset_type).await?;
+        }
+
         Ok("operation successful".to_string())
     } else {
         Err(ServerError::AuthorizationError(
@@ -115,3 +119,218 @@ pub async fn delete_asset(
         ))
     }
 }
+
+#[debug_handler]
+pub async fn move_asset(
+    State(state): State<HandlerState>,
+    user: ClientUserExtractor,
+    Json(data): Json<MoveAssetOptions>,
+) -> Result<String, ServerError> {
+    user.require(ClientScope::AssetsWrite)?;
+
+    validate_asset_path(&data.destination)?;
+    transfer::move_asset(state.db(), user.id(), &data).await?;
+
+    Ok("operation successful".to_string())
+}
+
+#[debug_handler]
+pub async fn rename_asset(
+    State(state): State<HandlerState>,
+    user: ClientUserExtractor,
+    Json(data): Json<RenameAssetOptions>,
+) -> Result<String, ServerError> {
+    user.require(ClientScope::AssetsWrite)?;
+
+    validate_asset_path(&data.name)?;
+    transfer::rename_asset(state.db(), user.id(), &data).await?;
+
+    Ok("operation successful".to_string())
+}
+
+#[debug_handler]
+pub async fn copy_asset(
+    State(state): State<HandlerState>,
+    user: ClientUserExtractor,
+    Json(data): Json<MoveAssetOptions>,
+) -> Result<String, ServerError> {
+    user.require(ClientScope::AssetsWrite)?;
+
+    validate_asset_path(&data.destination)?;
+    transfer::copy_asset(state.db(), user.id(), &data).await?;
+
+    Ok("operation successful".to_string())
+}
+
+#[debug_handler]
+pub async fn get_versions(
+    Path(asset_path): Path<String>,
+    State(state): State<HandlerState>,
+    user: ClientUserExtractor,
+) -> Result<Json<Vec<AssetVersion>>, ServerError> {
+    user.require(ClientScope::AssetsRead)?;
+
+    let versions = list_versions(state.db(), user.id(), &asset_path).await?;
+    Ok(Json(versions))
+}
+
+#[debug_handler]
+pub async fn promote_asset_version(
+    State(state): State<HandlerState>,
+    user: ClientUserExtractor,
+    Json(data): Json<PromoteVersionOptions>,
+) -> Result<String, ServerError> {
+    user.require(ClientScope::AssetsWrite)?;
+    promote_version(state.db(), user.id(), &data).await?;
+
+    Ok("operation successful".to_string())
+}
+
+#[debug_handler]
+pub async fn get_trash(
+    Path(bucket): Path<String>,
+    State(state): State<HandlerState>,
+    user: ClientUserExtractor,
+) -> Result<Json<Vec<TrashEntry>>, ServerError> {
+    user.require(ClientScope::AssetsRead)?;
+
+    let retention = state.config().base.trash_retention;
+    let entries = list_trash(state.db(), user.id(), &bucket, retention).await?;
+
+    Ok(Json(entries))
+}
+
+#[debug_handler]
+pub async fn clear_trash(
+    Path(bucket): Path<String>,
+    State(state): State<HandlerState>,
+    user: ClientUserExtractor,
+) -> Result<String, ServerError> {
+    user.require(ClientScope::AssetsWrite)?;
+    empty_trash(state.db(), user.id(), &bucket).await?;
+
+    Ok("operation successful".to_string())
+}
+
+#[debug_handler]
+pub async fn purge_trash(
+    Path((bucket, id)): Path<(String, u64)>,
+    State(state): State<HandlerState>,
+    user: ClientUserExtractor,
+) -> Result<String, ServerError> {
+    user.require(ClientScope::AssetsWrite)?;
+    purge_trash_entry(state.db(), user.id(), &bucket, &id).await?;
+
+    Ok("operation successful".to_string())
+}
+
+#[debug_handler]
+pub async fn restore_trash(
+    Path((bucket, id)): Path<(String, u64)>,
+    State(state): State<HandlerState>,
+    user: ClientUserExtractor,
+) -> Result<String, ServerError> {
+    user.require(ClientScope::AssetsWrite)?;
+    let path = restore_asset(state.db(), user.id(), &bucket, &id).await?;
+
+    Ok(path)
+}
+
+#[debug_handler]
+pub async fn create_upload(
+    State(state): State<HandlerState>,
+    user: ClientUserExtractor,
+    Json(data): Json<CreateUploadOptions>,
+) -> Result<String, ServerError> {
+    user.require(ClientScope::AssetsWrite)?;
+
+    validate_asset_path(&data.asset.asset_path)?;
+
+    let db = state.db();
+    let max_upload = mb_to_bytes(state.config().base.max_upload_size) as u64;
+    let id = create_session(db, user.id(), &data, max_upload).await?;
+
+    Ok(id)
+}
+
+#[debug_handler]
+pub async fn get_upload(
+    Path(id): Path<String>,
+    State(state): State<HandlerState>,
+    user: ClientUserExtractor,
+) -> Result<Json<UploadSessionSerializer>, ServerError> {
+    user.require(ClientScope::AssetsRead)?;
+
+    let db = state.db();
+    let session = UploadSessions::get(db, &id, user.id()).await?;
+    let data = session.into_serializer(db).await?;
+
+    Ok(Json(data))
+}
+
+#[debug_handler]
+pub async fn upload_chunk(
+    Path(id): Path<String>,
+    State(state): State<HandlerState>,
+    user: ClientUserExtractor,
+    UploadOffset(offset): UploadOffset,
+    ContentLength(len): ContentLength,
+    body: Body,
+) -> Result<String, ServerError> {
+    user.require(ClientScope::AssetsWrite)?;
+
+    let db = state.db();
+    let max_upload = mb_to_bytes(state.config().base.max_upload_size) as u64;
+    let chunk = body.into_data_stream();
+    let offset =
+        append_session_chunk(db, user.id(), &id, offset, len, chunk, max_upload).await?;
+
+    Ok(offset.to_string())
+}
+
+#[debug_handler]
+pub async fn complete_upload(
+    Path(id): Path<String>,
+    State(state): State<HandlerState>,
+    user: ClientUserExtractor,
+) -> Result<String, ServerError> {
+    user.require(ClientScope::AssetsWrite)?;
+
+    let db = state.db();
+    complete_session(db, user.id(), &id).await?;
+
+    Ok("operation successful!".to_string())
+}
+
+#[debug_handler]
+pub async fn cancel_upload(
+    Path(id): Path<String>,
+    State(state): State<HandlerState>,
+    user: ClientUserExtractor,
+) -> Result<String, ServerError> {
+    user.require(ClientScope::AssetsWrite)?;
+
+    let db = state.db();
+    cancel_session(db, user.id(), &id).await?;
+
+    Ok("operation successful".to_string())
+}
+
+fn validate_asset_path(asset_path: &str) -> Result<(), ServerError> {
+    if asset_path.is_empty() {
+        return Err(ServerError::InternalError(
+            "asset_path field is required".to_string(),
+        ));
+    }
+
+    if asset_path == SECRETS_FILENAME {
+        return Err(ServerError::AuthorizationError(
+            "asset_path '{SECRET_FILE}' is reserved. please choose another path.".to_string(),
+        ));
+    }
+
+    validate_user_path(asset_path)
+        .map_err(|err| ServerError::AuthorizationError(err.to_string()))?;
+
+    Ok(())
+}
@@ -77,7 +77,11 @@ impl From<DBError> for ServerError {
 
 impl From<HandlerError> for ServerError {
     fn from(value: HandlerError) -> Self {
-        ServerError::InternalError(value.to_string())
+        match value {
+            HandlerError::AuthorizationError(msg) => ServerError::AuthorizationError(msg),
+            HandlerError::PermissionError(msg) => ServerError::PermissionDenied(msg),
+            _ => ServerError::InternalError(value.to_string()),
+        }
     }
 }
 
@@ -10,15 +10,21 @@ use axum_macros::debug_handler;
 
 use crate::errors::ServerError;
 
+use ppd_fs::storage::Storage;
+
 use ppd_shared::{
-    api::{CreateBucketOptions, CreateClientUser, LoginTokens, LoginUserClient},
-    opts::ServiceConfig,
+    api::{
+        CreateBucketOptions, CreateClientUser, LoginTokens, LoginUserClient, RefreshTokenOptions,
+    },
+    opts::{ClientScope, ServiceConfig},
     tools::{SECRETS_FILENAME, mb_to_bytes},
 };
 use ppdrive::{
-    jwt::LoginOpts,
+    jwt::{
+        LoginOpts, TokenType, decode_token, refresh_tokens, revoke_all_logins, revoke_login,
+    },
     prelude::state::HandlerState,
-    rest::extractors::{BucketSizeValidator, ClientExtractor},
+    rest::extractors::{BucketSizeValidator, ClientExtractor, ClientUserExtractor},
 };
 
 use ppd_bk::models::{
@@ -35,6 +41,8 @@ async fn create_user(
     client: ClientExtractor,
     Json(data): Json<CreateClientUser>,
 ) -> Result<String, ServerError> {
+    client.require(ClientScope::UsersWrite)?;
+
     let db = state.db();
     let user_id = Users::create_by_client(db, *client.id(), data.max_bucket).await?;
 
@@ -44,9 +52,11 @@ async fn create_user(
 #[debug_handler]
 async fn login_user(
     State(state): State<HandlerState>,
-    _: ClientExtractor,
+    client: ClientExtractor,
     Json(data): Json<LoginUserClient>,
 ) -> Result<Json<LoginTokens>, ServerError> {
+    client.require(ClientScope::UsersWrite)?;
+
     let LoginUserClient {
         id,
         access_exp,
@@ -61,22 +71,75 @@ async fn login_user(
     let login = LoginOpts {
         user_id: &user.id(),
         config: &config,
-        jwt_secret: secrets.jwt_secret(),
+        secrets: &secrets,
         access_exp,
         refresh_exp,
         user_max_bucket: *user.max_bucket_size()
     };
 
-    let tokens = login.tokens()?;
+    let tokens = login.tokens(db).await?;
     Ok(Json(tokens))
 }
 
+/// exchange a user's refresh token for new tokens. The refresh token can't be used again.
+#[debug_handler]
+async fn refresh_token(
+    State(state): State<HandlerState>,
+    client: ClientExtractor,
+    Json(data): Json<RefreshTokenOptions>,
+) -> Result<Json<LoginTokens>, ServerError> {
+    client.require(ClientScope::UsersWrite)?;
+
+    let db = state.db();
+    let config = state.config();
+    let secrets = state.secrets();
+
+    let claims = decode_token(&data.token, &secrets, TokenType::Refresh)?;
+    let user = Users::get(db, claims.sub()).await?;
+    if user.client_id() != &Some(*client.id()) {
+        return Err(ServerError::PermissionDenied(
+            "refresh token belongs to a user of another client".to_string(),
+        ));
+    }
+
+    let tokens = refresh_tokens(db, &config, &secrets, &data.token).await?;
+    Ok(Json(tokens))
+}
+
+/// revoke the login the refresh token was issued from.
+#[debug_handler]
+async fn logout(
+    State(state): State<HandlerState>,
+    user: ClientUserExtractor,
+    Json(data): Json<RefreshTokenOptions>,
+) -> Result<String, ServerError> {
+    user.require(ClientScope::UsersWrite)?;
+
+    let secrets = state.secrets();
+    revoke_login(state.db(), &secrets, &data.token, user.id()).await?;
+
+    Ok("operation successful".to_string())
+}
+
+/// revoke all logins of the user.
+#[debug_handler]
+async fn logout_all(
+    State(state): State<HandlerState>,
+    user: ClientUserExtractor,
+) -> Result<String, ServerError> {
+    user.require(ClientScope::UsersWrite)?;
+    revoke_all_logins(state.db(), user.id()).await?;
+    Ok("operation successful".to_string())
+}
+
 #[debug_handler]
 async fn delete_user(
     Path(id): Path<String>,
     client: ClientExtractor,
     State(state): State<HandlerState>,
 ) -> Result<String, ServerError> {
+    client.require(ClientScope::UsersWrite)?;
+
     let db = state.db();
     let user = Users::get_by_pid(db, &id).await?;
 
@@ -106,6 +169,8 @@ async fn create_bucket(
     client: ClientExtractor,
     Json(data): Json<CreateBucketOptions>,
 ) -> Result<String, ServerError> {
+    client.require(ClientScope::BucketsCreate)?;
+
     let db = state.db();
 
     client
@@ -119,6 +184,7 @@ async fn create_bucket(
         ));
     }
 
+    Storage::validate_backend(&data.backend)?;
     let bucket_id = Buckets::create_by_client(db, data, *client.id()).await?;
     Ok(bucket_id.to_string())
 }
@@ -131,18 +197,40 @@ fn routes(config: Arc<ServiceConfig>) -> Router<HandlerState> {
         // Routes used by client for administrative tasks. Requests to these routes
         // require ppd-client-token header.
         .route("/user/login", post(login_user))
+        .route("/user/token/refresh", post(refresh_token))
         .route("/user/register", post(create_user))
         .route("/user/:id", delete(delete_user))
         .route("/bucket", post(create_bucket))
         // Routes used by client to operate on behalf of a user. Access to these routes requires
         // both  `ppd-client-token` and `ppd-client-user` headers
         .route("/user", get(get_user))
+        .route("/user/logout", post(logout))
+        .route("/user/logout-all", post(logout_all))
         .route("/user/asset", post(create_asset))
+        .route(
+            "/user/upload/:id",
+            get(get_upload)
+                .patch(upload_chunk)
+                .post(complete_upload)
+                .delete(cancel_upload),
+        )
         .layer(DefaultBodyLimit::max(limit))
+        .route("/user/upload", post(create_upload))
         .route("/user/asset/:asset_type/*asset_path", delete(delete_asset))
+        .route("/user/asset/move", post(move_asset))
+        .route("/user/asset/rename", post(rename_asset))
+        .route("/user/asset/copy", post(copy_asset))
+        .route("/user/asset/versions/*asset_path", get(get_versions))
+        .route("/user/asset/promote", post(promote_asset_version))
         .route("/user/bucket", post(create_user_bucket))
+        .route("/user/bucket/:id/trash", get(get_trash).delete(clear_trash))
+        .route("/user/bucket/:id/trash/:entry_id", delete(purge_trash))
+        .route("/user/bucket/:id/trash/:entry_id/restore", post(restore_trash))
 }
 
+/// # Safety
+/// `config` must be a pointer obtained from `Arc::into_raw`. Ownership of the config is
+/// transferred to the router.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn rest_client(config: *const ServiceConfig) -> *mut Router<HandlerState> {
     let config = unsafe { Arc::from_raw(config) };
@@ -1,3 +1,5 @@
+use axum::http::StatusCode;
+use ppd_shared::api::{LoginTokens, RefreshTokenOptions};
 use serial_test::serial;
 
 use rest_test_utils::{
@@ -30,6 +32,35 @@ async fn test_client_login_user() {
     resp.assert_status_ok();
 }
 
+#[tokio::test]
+#[serial]
+async fn test_client_refresh_user_token() {
+    let app = TestApp::new().await;
+    let token = app.client_token().await;
+
+    let server = app.server();
+    let tokens: LoginTokens = login_user_request(&server, &token).await.json();
+    let opts = RefreshTokenOptions {
+        token: tokens.refresh.expect("refresh token missing").0,
+    };
+
+    server
+        .post("/client/user/token/refresh")
+        .json(&opts)
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+
+    let resp = server
+        .post("/client/user/token/refresh")
+        .add_header(HEADER_TOKEN_KEY, &token)
+        .json(&opts)
+        .await;
+
+    resp.assert_status_ok();
+    let tokens: LoginTokens = resp.json();
+    assert!(tokens.access.is_some());
+}
+
 #[tokio::test]
 #[serial]
 async fn test_client_delete_user() {
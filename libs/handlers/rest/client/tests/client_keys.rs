@@ -0,0 +1,113 @@
+use axum::http::StatusCode;
+use ppd_shared::{
+    opts::{ClientScope, NewClientKey},
+    tools::AppSecrets,
+};
+use ppdrive::tools::{create_client, create_client_key, revoke_client_key};
+use serial_test::serial;
+
+use rest_test_utils::{
+    TestApp,
+    client::{create_client_bucket, create_user_request},
+};
+
+/// create a client with a named key. Returns the client's token, the key's id and token.
+async fn client_with_key(
+    app: &TestApp,
+    scopes: Vec<ClientScope>,
+    expires_in_days: Option<u32>,
+) -> (String, String, String) {
+    let secrets = AppSecrets::read()
+        .await
+        .expect("unable to create app secrets");
+
+    let client = create_client(&app.db, &secrets, "Key Client", None)
+        .await
+        .expect("unable to create client");
+
+    let opts = NewClientKey {
+        name: "test key".to_string(),
+        scopes,
+        expires_in_days,
+    };
+
+    let key = create_client_key(&app.db, &secrets, client.id(), &opts)
+        .await
+        .expect("unable to create client key");
+
+    (
+        client.token().to_string(),
+        key.id().to_string(),
+        key.token().to_string(),
+    )
+}
+
+#[tokio::test]
+#[serial]
+async fn test_client_key_scopes() {
+    let app = TestApp::new().await;
+    let (client_token, _, key_token) =
+        client_with_key(&app, vec![ClientScope::UsersWrite], None).await;
+
+    let server = app.server();
+    create_user_request(&server, &key_token)
+        .await
+        .assert_status_ok();
+
+    create_client_bucket(&server, &key_token)
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+
+    // the client's token keeps all scopes
+    create_client_bucket(&server, &client_token)
+        .await
+        .assert_status_ok();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_client_key_all_scopes() {
+    let app = TestApp::new().await;
+    let (_, _, key_token) = client_with_key(&app, vec![], None).await;
+
+    let server = app.server();
+    create_user_request(&server, &key_token)
+        .await
+        .assert_status_ok();
+
+    create_client_bucket(&server, &key_token)
+        .await
+        .assert_status_ok();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_client_key_expired() {
+    let app = TestApp::new().await;
+    let (_, _, key_token) = client_with_key(&app, vec![], Some(0)).await;
+
+    let server = app.server();
+    create_user_request(&server, &key_token)
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_client_key_revoked() {
+    let app = TestApp::new().await;
+    let (_, key_id, key_token) = client_with_key(&app, vec![], None).await;
+
+    let server = app.server();
+    create_user_request(&server, &key_token)
+        .await
+        .assert_status_ok();
+
+    revoke_client_key(&app.db, &key_id)
+        .await
+        .expect("unable to revoke client key");
+
+    create_user_request(&server, &key_token)
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+}
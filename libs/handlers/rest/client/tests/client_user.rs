@@ -1,8 +1,11 @@
+use axum::{body::Bytes, http::header::CONTENT_LENGTH};
 use axum_test::multipart::{MultipartForm, Part};
 use ppd_bk::models::asset::AssetType;
+use ppd_shared::api::CreateBucketOptions;
 use serial_test::serial;
 
-use ppd_fs::opts::CreateAssetOptions;
+use ppd_fs::opts::{CreateAssetOptions, CreateUploadOptions};
+use ppdrive::rest::extractors::UPLOAD_OFFSET_HEADER;
 
 use rest_test_utils::{
     clean_up_test_assets, client::{
@@ -145,6 +148,117 @@ async fn test_client_user_delete_asset() {
     clean_up_test_assets();
 }
 
+#[tokio::test]
+#[serial]
+async fn test_client_user_upload_exceeds_bucket_size() {
+    clean_up_test_assets();
+
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    let token = app.client_token().await;
+    let bucket_opts = CreateBucketOptions {
+        partition: Some("test-assets/size-partition".to_string()),
+        partition_size: Some(0.5),
+        label: "size limited bucket".to_string(),
+        ..Default::default()
+    };
+
+    let resp = server
+        .post("/client/bucket")
+        .json(&bucket_opts)
+        .add_header(HEADER_TOKEN_KEY, &token)
+        .await;
+
+    resp.assert_status_ok();
+
+    let asset_opts = CreateAssetOptions {
+        asset_path: "large-file".to_string(),
+        asset_type: AssetType::File,
+        bucket: resp.text(),
+        ..Default::default()
+    };
+
+    // file is larger than the bucket's partition size, so upload should be aborted
+    let file_bytes = vec![0u8; 600 * 1024];
+    let file = Part::bytes(file_bytes).file_name("large-file");
+
+    let multipart = MultipartForm::new()
+        .add_text("options", asset_opts_str(&asset_opts))
+        .add_part("file", file);
+
+    let user_id = create_user_request(&server, &token).await.text();
+    let resp = server
+        .post("/client/user/asset")
+        .multipart(multipart)
+        .add_header(HEADER_TOKEN_KEY, &token)
+        .add_header(HEADER_USER_KEY, &user_id)
+        .await;
+
+    resp.assert_status_not_ok();
+    clean_up_test_assets();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_client_user_resumable_upload() {
+    clean_up_test_assets();
+
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    let token = app.client_token().await;
+    let bucket = create_client_bucket(&server, &token).await.text();
+    let user_id = create_user_request(&server, &token).await.text();
+
+    let file_bytes = include_bytes!("README.MD");
+    let (first, second) = file_bytes.split_at(file_bytes.len() / 2);
+
+    let upload_opts = CreateUploadOptions {
+        asset: CreateAssetOptions {
+            asset_path: "test-assets/resumable-file".to_string(),
+            asset_type: AssetType::File,
+            bucket,
+            ..Default::default()
+        },
+        size: Some(file_bytes.len() as u64),
+    };
+
+    let resp = server
+        .post("/client/user/upload")
+        .json(&upload_opts)
+        .add_header(HEADER_TOKEN_KEY, &token)
+        .add_header(HEADER_USER_KEY, &user_id)
+        .await;
+
+    resp.assert_status_ok();
+    let path = format!("/client/user/upload/{}", resp.text());
+
+    let mut offset = 0;
+    for chunk in [first, second] {
+        let resp = server
+            .patch(&path)
+            .add_header(UPLOAD_OFFSET_HEADER, offset.to_string())
+            .add_header(CONTENT_LENGTH, chunk.len().to_string())
+            .bytes(Bytes::copy_from_slice(chunk))
+            .add_header(HEADER_TOKEN_KEY, &token)
+            .add_header(HEADER_USER_KEY, &user_id)
+            .await;
+
+        resp.assert_status_ok();
+        offset = resp.text().parse::<usize>().expect("invalid upload offset");
+    }
+
+    let resp = server
+        .post(&path)
+        .add_header(HEADER_TOKEN_KEY, &token)
+        .add_header(HEADER_USER_KEY, &user_id)
+        .await;
+
+    resp.assert_status_ok();
+    clean_up_test_assets();
+}
+
 fn asset_opts_str(opts: &CreateAssetOptions) -> String {
     serde_json::to_string(opts).expect("unable to create strigify asset options")
 }
@@ -0,0 +1,95 @@
+use ppd_shared::tools::AppSecrets;
+use ppdrive::tools::{create_client, reissue_tokens, verify_client};
+use serial_test::serial;
+
+use rest_test_utils::TestApp;
+
+/// a copy of the app secrets that can be rotated without touching the secrets file.
+async fn secrets() -> AppSecrets {
+    let secrets = AppSecrets::read()
+        .await
+        .expect("unable to create app secrets");
+
+    AppSecrets::parse(&secrets.to_bytes()).expect("unable to parse app secrets")
+}
+
+#[tokio::test]
+#[serial]
+async fn test_client_token_rotation() {
+    let app = TestApp::new().await;
+    let mut secrets = secrets().await;
+
+    let client = create_client(&app.db, &secrets, "Rotated Client", None)
+        .await
+        .expect("unable to create client");
+
+    let key_id = secrets.rotate(1);
+    assert!(verify_client(&app.db, &secrets, client.token()).await.is_ok());
+
+    let reissued = reissue_tokens(&app.db, &secrets, 0)
+        .await
+        .expect("unable to reissue tokens");
+
+    let token = reissued
+        .iter()
+        .find(|t| t.client_id == client.id() && t.key_id.is_none())
+        .map(|t| t.token.clone())
+        .expect("client token was not reissued");
+
+    assert!(token.starts_with(&format!("v2.{key_id}.")));
+    assert!(verify_client(&app.db, &secrets, &token).await.is_ok());
+
+    // dropping the previous key rejects tokens issued with it
+    secrets.rotate(1);
+    secrets.rotate(1);
+    assert!(verify_client(&app.db, &secrets, client.token()).await.is_err());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_client_token_nonce() {
+    let app = TestApp::new().await;
+    let secrets = secrets().await;
+
+    let first = create_client(&app.db, &secrets, "First Client", None)
+        .await
+        .expect("unable to create client");
+
+    let second = create_client(&app.db, &secrets, "Second Client", None)
+        .await
+        .expect("unable to create client");
+
+    let nonce = |token: &str| {
+        let data = token.rsplit('.').next().unwrap_or_default().to_string();
+        data[..48].to_string()
+    };
+
+    assert_ne!(nonce(first.token()), nonce(second.token()));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_legacy_secrets_tokens() {
+    let app = TestApp::new().await;
+
+    // a secrets file written by earlier versions: a secret key, a nonce and a jwt secret
+    let legacy: Vec<u8> = (0..88).map(|i| i as u8).collect();
+    let mut secrets = AppSecrets::parse(&legacy).expect("unable to parse legacy secrets");
+
+    let first = create_client(&app.db, &secrets, "First Legacy Client", None)
+        .await
+        .expect("unable to create client");
+
+    let second = create_client(&app.db, &secrets, "Second Legacy Client", None)
+        .await
+        .expect("unable to create client");
+
+    assert!(first.token().starts_with("v2.0."));
+    assert_ne!(first.token(), second.token());
+    assert!(verify_client(&app.db, &secrets, first.token()).await.is_ok());
+    assert!(verify_client(&app.db, &secrets, second.token()).await.is_ok());
+
+    // tokens of the legacy key keep working after a rotation
+    secrets.rotate(1);
+    assert!(verify_client(&app.db, &secrets, first.token()).await.is_ok());
+}
@@ -0,0 +1,111 @@
+use axum::{Json, extract::State};
+use axum_macros::debug_handler;
+use ppd_bk::models::{bucket::Buckets, user::Users};
+use ppd_fs::auth::delete_bucket;
+use ppd_shared::api::{ChangePassword, DeleteAccount, ForgotPassword, ResetPassword};
+use ppdrive::{
+    prelude::state::HandlerState,
+    rest::{
+        extractors::{BucketSizeValidator, UserExtractor},
+        notifier::{ResetNotice, ResetNotifier},
+    },
+    tools::{self, create_reset_token, set_password, verify_password},
+};
+use validator::Validate;
+
+use crate::errors::ServerError;
+
+/// change the password of the logged in user. All refresh tokens of the user are revoked.
+#[debug_handler]
+pub async fn change_password(
+    State(state): State<HandlerState>,
+    user: UserExtractor,
+    Json(data): Json<ChangePassword>,
+) -> Result<String, ServerError> {
+    data.validate()?;
+
+    let db = state.db();
+    let config = state.config();
+    let mut user = Users::get(db, user.id()).await?;
+
+    if user.password().is_none() {
+        return Err(ServerError::PermissionDenied(
+            "account does not have a password".to_string(),
+        ));
+    }
+
+    verify_password(db, &mut user, &data.current_password, &config.auth.password).await?;
+    set_password(db, &mut user, &data.new_password, &config.auth.password).await?;
+
+    Ok("operation successful".to_string())
+}
+
+/// send a password reset token to the user through the configured notifier. The response
+/// is the same whether the user exists or not.
+#[debug_handler]
+pub async fn forgot_password(
+    State(state): State<HandlerState>,
+    Json(data): Json<ForgotPassword>,
+) -> Result<String, ServerError> {
+    let db = state.db();
+    let config = state.config();
+    let notifier = ResetNotifier::from_config(&config.auth.reset.notifier)?;
+
+    let user = Users::get_by_key(db, "username", &data.username)
+        .await
+        .map_err(|err| ServerError::InternalError(err.to_string()))?;
+
+    if let Some(user) = user
+        && user.password().is_some()
+    {
+        let (token, expires_at) = create_reset_token(db, &user.id(), config.auth.reset.exp).await?;
+        let notice = ResetNotice {
+            user_id: user.pid(),
+            username: &data.username,
+            token: &token,
+            expires_at,
+        };
+
+        notifier.notify(&notice).await?;
+    }
+
+    Ok("if the account exists, a reset token has been sent".to_string())
+}
+
+/// set a new password with a reset token. All refresh tokens of the user are revoked.
+#[debug_handler]
+pub async fn reset_password(
+    State(state): State<HandlerState>,
+    Json(data): Json<ResetPassword>,
+) -> Result<String, ServerError> {
+    data.validate()?;
+
+    let config = state.config();
+    tools::reset_password(state.db(), &data.token, &data.password, &config.auth.password).await?;
+
+    Ok("operation successful".to_string())
+}
+
+/// delete the logged in user with their buckets and assets.
+#[debug_handler]
+pub async fn delete_account(
+    State(state): State<HandlerState>,
+    user: UserExtractor,
+    Json(data): Json<DeleteAccount>,
+) -> Result<String, ServerError> {
+    let db = state.db();
+    let config = state.config();
+    let mut user = Users::get(db, user.id()).await?;
+
+    if user.password().is_some() {
+        let password = data.password.unwrap_or_default();
+        verify_password(db, &mut user, &password, &config.auth.password).await?;
+    }
+
+    for bucket in Buckets::user_buckets(db, &user.id()).await? {
+        delete_bucket(db, &bucket).await?;
+    }
+
+    user.delete(db).await?;
+    Ok("operation successful".to_string())
+}
@@ -5,6 +5,7 @@ use ppd_fs::errors::Error as FsError;
 use ppd_shared::errors::Error as SharedError;
 use ppd_bk::Error as DBError;
 use ppdrive::errors::HandlerError;
+use validator::ValidationErrors;
 
 #[derive(Debug)]
 pub enum ServerError {
@@ -16,6 +17,7 @@ pub enum ServerError {
     AuthorizationError(String),
     IOError(String),
     PermissionDenied(String),
+    BadRequest(String),
 }
 
 impl Display for ServerError {
@@ -29,6 +31,7 @@ impl Display for ServerError {
             ServerError::AuthorizationError(msg) => write!(f, "{msg}"),
             ServerError::IOError(msg) => write!(f, "{msg}"),
             ServerError::PermissionDenied(msg) => write!(f, "{msg}"),
+            ServerError::BadRequest(msg) => write!(f, "{msg}"),
         }
     }
 }
@@ -45,6 +48,12 @@ impl From<serde_json::Error> for ServerError {
     }
 }
 
+impl From<ValidationErrors> for ServerError {
+    fn from(value: ValidationErrors) -> Self {
+        ServerError::BadRequest(value.to_string())
+    }
+}
+
 impl From<std::io::Error> for ServerError {
     fn from(value: std::io::Error) -> Self {
         ServerError::IOError(value.to_string())
@@ -77,7 +86,11 @@ impl From<DBError> for ServerError {
 
 impl From<HandlerError> for ServerError {
     fn from(value: HandlerError) -> Self {
-        ServerError::InternalError(value.to_string())
+        match value {
+            HandlerError::AuthorizationError(msg) => ServerError::AuthorizationError(msg),
+            HandlerError::PermissionError(msg) => ServerError::PermissionDenied(msg),
+            _ => ServerError::InternalError(value.to_string()),
+        }
     }
 }
 
@@ -86,6 +99,7 @@ impl IntoResponse for ServerError {
         let resp = match self {
             ServerError::AuthorizationError(msg) => (StatusCode::UNAUTHORIZED, msg),
             ServerError::PermissionDenied(msg) => (StatusCode::FORBIDDEN, msg),
+            ServerError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
             _ => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
         };
 
@@ -1,35 +1,57 @@
 use std::sync::Arc;
 
-use tokio::{fs::File, io::AsyncWriteExt};
-
 use axum::{
     Json, Router,
-    extract::{DefaultBodyLimit, Multipart, Path, State},
+    body::Body,
+    extract::{DefaultBodyLimit, Multipart, Path, Query, State},
     routing::{delete, get, post},
 };
 use axum_macros::debug_handler;
-use ppd_fs::{auth::create_or_update_asset, opts::CreateAssetOptions};
-use uuid::Uuid;
+use ppd_fs::{
+    auth::{create_or_update_asset, delete_asset as remove_asset},
+    storage::Storage,
+    opts::{
+        CreateUploadOptions, MoveAssetOptions, PromoteVersionOptions, RenameAssetOptions,
+    },
+    transfer,
+    validate_user_path,
+    trash::{empty_trash, list_trash, purge_trash_entry, restore_asset, trash_asset},
+    versions::{list_versions, promote_version},
+    upload::{append_session_chunk, cancel_session, complete_session, create_session},
+};
 
-use crate::errors::ServerError;
+use crate::{
+    account::{change_password, delete_account, forgot_password, reset_password},
+    errors::ServerError,
+};
 
 use ppd_shared::{
-    api::{CreateBucketOptions, LoginTokens, UserCredentials}, opts::ServiceConfig, tools::{SECRETS_FILENAME, mb_to_bytes}
+    api::{
+        AssetVersion, CreateBucketOptions, DeleteAssetOptions, LoginTokens, RefreshTokenOptions,
+        TrashEntry, UserCredentials,
+    },
+    opts::ServiceConfig, tools::{SECRETS_FILENAME, mb_to_bytes}
 };
 use ppdrive::{
-    jwt::LoginOpts,
+    jwt::{LoginOpts, refresh_tokens, revoke_all_logins, revoke_login},
     prelude::state::HandlerState,
-    rest::extractors::{BucketSizeValidator, UserExtractor},
-    tools::{check_password, make_password},
+    rest::{
+        AssetUpload, read_asset_upload,
+        extractors::{BucketSizeValidator, ContentLength, UploadOffset, UserExtractor},
+    },
+    tools::{make_password, verify_password},
 };
 
 use ppd_bk::models::{
     IntoSerializer,
     asset::{AssetType, Assets},
     bucket::Buckets,
+    upload::{UploadSessionSerializer, UploadSessions},
     user::{UserSerializer, Users},
 };
+use validator::Validate;
 
+mod account;
 mod errors;
 
 #[debug_handler]
@@ -37,10 +59,23 @@ async fn register_user(
     State(state): State<HandlerState>,
     Json(data): Json<UserCredentials>,
 ) -> Result<String, ServerError> {
+    data.validate()?;
+
     let db = state.db();
+    let config = state.config();
     let UserCredentials { username, password } = data;
 
-    let password = make_password(&password);
+    let exists = Users::get_by_key(db, "username", &username)
+        .await
+        .map_err(|err| ServerError::InternalError(err.to_string()))?;
+
+    if exists.is_some() {
+        return Err(ServerError::BadRequest(format!(
+            "username '{username}' is already taken"
+        )));
+    }
+
+    let password = make_password(&password, &config.auth.password)?;
     let user_id = Users::create_direct(db, username, password).await?;
 
     Ok(user_id)
@@ -56,29 +91,64 @@ async fn login_user(
     let secrets = state.secrets();
 
     let UserCredentials { username, password } = data;
-    let user = Users::get_by_key(db, "username", &username)
+    let mut user = Users::get_by_key(db, "username", &username)
         .await
         .map_err(|err| ServerError::InternalError(err.to_string()))?
         .ok_or(ServerError::AuthorizationError(format!(
             "user with username '{username}' does not exist"
         )))?;
 
-    let hashed = user.password().clone().unwrap_or(String::new());
-    check_password(&password, &hashed)?;
+    verify_password(db, &mut user, &password, &config.auth.password).await?;
 
     let login = LoginOpts {
         user_id: &user.id(),
         config: &config,
-        jwt_secret: secrets.jwt_secret(),
+        secrets: &secrets,
         access_exp: None,
         refresh_exp: None,
         user_max_bucket: *user.max_bucket_size()
     };
 
-    let tokens = login.tokens()?;
+    let tokens = login.tokens(db).await?;
+    Ok(Json(tokens))
+}
+
+/// exchange a refresh token for new tokens. The refresh token can't be used again.
+#[debug_handler]
+async fn refresh_token(
+    State(state): State<HandlerState>,
+    Json(data): Json<RefreshTokenOptions>,
+) -> Result<Json<LoginTokens>, ServerError> {
+    let config = state.config();
+    let secrets = state.secrets();
+
+    let tokens = refresh_tokens(state.db(), &config, &secrets, &data.token).await?;
     Ok(Json(tokens))
 }
 
+/// revoke the login the refresh token was issued from.
+#[debug_handler]
+async fn logout(
+    State(state): State<HandlerState>,
+    user: UserExtractor,
+    Json(data): Json<RefreshTokenOptions>,
+) -> Result<String, ServerError> {
+    let secrets = state.secrets();
+    revoke_login(state.db(), &secrets, &data.token, user.id()).await?;
+
+    Ok("operation successful".to_string())
+}
+
+/// revoke all logins of the user.
+#[debug_handler]
+async fn logout_all(
+    State(state): State<HandlerState>,
+    user: UserExtractor,
+) -> Result<String, ServerError> {
+    revoke_all_logins(state.db(), user.id()).await?;
+    Ok("operation successful".to_string())
+}
+
 #[debug_handler]
 pub async fn get_user(
     State(state): State<HandlerState>,
@@ -100,6 +170,8 @@ pub async fn create_user_bucket(
     let db = state.db();
 
     user.validate_bucket_size(db, &data.partition_size).await?;
+    Storage::validate_backend(&data.backend)?;
+
     let id = Buckets::create_by_user(db, data, *user.id()).await?;
 
     Ok(id)
@@ -109,47 +181,16 @@ pub async fn create_user_bucket(
 pub async fn create_asset(
     State(state): State<HandlerState>,
     user: UserExtractor,
-    mut multipart: Multipart,
+    multipart: Multipart,
 ) -> Result<String, ServerError> {
-    let mut opts = CreateAssetOptions::default();
-    let mut tmp_file = None;
-    let mut filesize = None;
-
-    while let Some(field) = multipart.next_field().await? {
-        let name = field.name().unwrap_or("").to_string();
-
-        if name == "options" {
-            let data = field.text().await?;
-            opts = serde_json::from_str(&data)?;
-        } else if name == "file" {
-            let tmp_name = Uuid::new_v4().to_string();
-            let mut tmp_path = std::env::temp_dir();
-            tmp_path.push(tmp_name);
-
-            let mut file = File::create(&tmp_path).await?;
-
-            let data = field.bytes().await?;
-            file.write_all(&data).await?;
-
-            filesize = Some(file.metadata().await?.len());
-            tmp_file = Some(tmp_path);
-        }
-    }
-
-    // options validations
-    if opts.asset_path.is_empty() {
-        return Err(ServerError::InternalError(
-            "asset_path field is required".to_string(),
-        ));
-    }
-
-    if opts.asset_path == SECRETS_FILENAME {
-        return Err(ServerError::AuthorizationError(
-            "asset_path '{SECRET_FILE}' is reserved. please choose another path.".to_string(),
-        ));
-    }
-
     let db = state.db();
+    let AssetUpload {
+        opts,
+        tmp_file,
+        filesize,
+    } = read_asset_upload::<ServerError>(&state, multipart).await?;
+
+    validate_asset_path(&opts.asset_path)?;
     create_or_update_asset(db, user.id(), &opts, &tmp_file, &filesize).await?;
     Ok("operation successful!".to_string())
 }
@@ -157,14 +198,22 @@ pub async fn create_asset(
 #[debug_handler]
 pub async fn delete_asset(
     Path((asset_type, asset_path)): Path<(AssetType, String)>,
+    Query(opts): Query<DeleteAssetOptions>,
     State(state): State<HandlerState>,
     user: UserExtractor,
 ) -> Result<String, ServerError> {
+    validate_asset_path(&asset_path)?;
+
     let db = state.db();
     let asset = Assets::get_by_path(db, &asset_path, &asset_type).await?;
 
     if asset.user_id() == user.id() {
-        asset.delete(db).await?;
+        if opts.permanent || state.config().base.trash_retention == 0 {
+            remove_asset(db, &asset_path, &asset_type).await?;
+        } else {
+            trash_asset(db, &asset_path, &asset_type).await?;
+        }
+
         Ok("operation successful".to_string())
     } else {
         Err(ServerError::AuthorizationError(
@@ -173,20 +222,237 @@ pub async fn delete_asset(
     }
 }
 
+#[debug_handler]
+pub async fn move_asset(
+    State(state): State<HandlerState>,
+    user: UserExtractor,
+    Json(data): Json<MoveAssetOptions>,
+) -> Result<String, ServerError> {
+    validate_asset_path(&data.destination)?;
+    transfer::move_asset(state.db(), user.id(), &data).await?;
+
+    Ok("operation successful".to_string())
+}
+
+#[debug_handler]
+pub async fn rename_asset(
+    State(state): State<HandlerState>,
+    user: UserExtractor,
+    Json(data): Json<RenameAssetOptions>,
+) -> Result<String, ServerError> {
+    validate_asset_path(&data.name)?;
+    transfer::rename_asset(state.db(), user.id(), &data).await?;
+
+    Ok("operation successful".to_string())
+}
+
+#[debug_handler]
+pub async fn copy_asset(
+    State(state): State<HandlerState>,
+    user: UserExtractor,
+    Json(data): Json<MoveAssetOptions>,
+) -> Result<String, ServerError> {
+    validate_asset_path(&data.destination)?;
+    transfer::copy_asset(state.db(), user.id(), &data).await?;
+
+    Ok("operation successful".to_string())
+}
+
+#[debug_handler]
+pub async fn get_versions(
+    Path(asset_path): Path<String>,
+    State(state): State<HandlerState>,
+    user: UserExtractor,
+) -> Result<Json<Vec<AssetVersion>>, ServerError> {
+    let versions = list_versions(state.db(), user.id(), &asset_path).await?;
+
+    Ok(Json(versions))
+}
+
+#[debug_handler]
+pub async fn promote_asset_version(
+    State(state): State<HandlerState>,
+    user: UserExtractor,
+    Json(data): Json<PromoteVersionOptions>,
+) -> Result<String, ServerError> {
+    promote_version(state.db(), user.id(), &data).await?;
+
+    Ok("operation successful".to_string())
+}
+
+#[debug_handler]
+pub async fn get_trash(
+    Path(bucket): Path<String>,
+    State(state): State<HandlerState>,
+    user: UserExtractor,
+) -> Result<Json<Vec<TrashEntry>>, ServerError> {
+    let retention = state.config().base.trash_retention;
+    let entries = list_trash(state.db(), user.id(), &bucket, retention).await?;
+
+    Ok(Json(entries))
+}
+
+#[debug_handler]
+pub async fn clear_trash(
+    Path(bucket): Path<String>,
+    State(state): State<HandlerState>,
+    user: UserExtractor,
+) -> Result<String, ServerError> {
+    empty_trash(state.db(), user.id(), &bucket).await?;
+
+    Ok("operation successful".to_string())
+}
+
+#[debug_handler]
+pub async fn purge_trash(
+    Path((bucket, id)): Path<(String, u64)>,
+    State(state): State<HandlerState>,
+    user: UserExtractor,
+) -> Result<String, ServerError> {
+    purge_trash_entry(state.db(), user.id(), &bucket, &id).await?;
+
+    Ok("operation successful".to_string())
+}
+
+#[debug_handler]
+pub async fn restore_trash(
+    Path((bucket, id)): Path<(String, u64)>,
+    State(state): State<HandlerState>,
+    user: UserExtractor,
+) -> Result<String, ServerError> {
+    let path = restore_asset(state.db(), user.id(), &bucket, &id).await?;
+
+    Ok(path)
+}
+
+#[debug_handler]
+pub async fn create_upload(
+    State(state): State<HandlerState>,
+    user: UserExtractor,
+    Json(data): Json<CreateUploadOptions>,
+) -> Result<String, ServerError> {
+    validate_asset_path(&data.asset.asset_path)?;
+
+    let db = state.db();
+    let max_upload = mb_to_bytes(state.config().base.max_upload_size) as u64;
+    let id = create_session(db, user.id(), &data, max_upload).await?;
+
+    Ok(id)
+}
+
+#[debug_handler]
+pub async fn get_upload(
+    Path(id): Path<String>,
+    State(state): State<HandlerState>,
+    user: UserExtractor,
+) -> Result<Json<UploadSessionSerializer>, ServerError> {
+    let db = state.db();
+    let session = UploadSessions::get(db, &id, user.id()).await?;
+    let data = session.into_serializer(db).await?;
+
+    Ok(Json(data))
+}
+
+#[debug_handler]
+pub async fn upload_chunk(
+    Path(id): Path<String>,
+    State(state): State<HandlerState>,
+    user: UserExtractor,
+    UploadOffset(offset): UploadOffset,
+    ContentLength(len): ContentLength,
+    body: Body,
+) -> Result<String, ServerError> {
+    let db = state.db();
+    let max_upload = mb_to_bytes(state.config().base.max_upload_size) as u64;
+    let chunk = body.into_data_stream();
+    let offset =
+        append_session_chunk(db, user.id(), &id, offset, len, chunk, max_upload).await?;
+
+    Ok(offset.to_string())
+}
+
+#[debug_handler]
+pub async fn complete_upload(
+    Path(id): Path<String>,
+    State(state): State<HandlerState>,
+    user: UserExtractor,
+) -> Result<String, ServerError> {
+    let db = state.db();
+    complete_session(db, user.id(), &id).await?;
+
+    Ok("operation successful!".to_string())
+}
+
+#[debug_handler]
+pub async fn cancel_upload(
+    Path(id): Path<String>,
+    State(state): State<HandlerState>,
+    user: UserExtractor,
+) -> Result<String, ServerError> {
+    let db = state.db();
+    cancel_session(db, user.id(), &id).await?;
+
+    Ok("operation successful".to_string())
+}
+
+fn validate_asset_path(asset_path: &str) -> Result<(), ServerError> {
+    if asset_path.is_empty() {
+        return Err(ServerError::InternalError(
+            "asset_path field is required".to_string(),
+        ));
+    }
+
+    if asset_path == SECRETS_FILENAME {
+        return Err(ServerError::AuthorizationError(
+            "asset_path '{SECRET_FILE}' is reserved. please choose another path.".to_string(),
+        ));
+    }
+
+    validate_user_path(asset_path)
+        .map_err(|err| ServerError::AuthorizationError(err.to_string()))?;
+
+    Ok(())
+}
+
 /// Routes for external clients.
 fn routes(config: Arc<ServiceConfig>) -> Router<HandlerState> {
     let limit = mb_to_bytes(config.base.max_upload_size);
 
     Router::new()
-        .route("/user", get(get_user))
+        .route("/user", get(get_user).delete(delete_account))
         .route("/user/register", post(register_user))
         .route("/user/login", post(login_user))
+        .route("/user/token/refresh", post(refresh_token))
+        .route("/user/logout", post(logout))
+        .route("/user/logout-all", post(logout_all))
+        .route("/user/password", post(change_password))
+        .route("/user/password/forgot", post(forgot_password))
+        .route("/user/password/reset", post(reset_password))
         .route("/user/asset", post(create_asset))
+        .route(
+            "/user/upload/:id",
+            get(get_upload)
+                .patch(upload_chunk)
+                .post(complete_upload)
+                .delete(cancel_upload),
+        )
         .layer(DefaultBodyLimit::max(limit))
+        .route("/user/upload", post(create_upload))
         .route("/user/asset/:asset_type/*asset_path", delete(delete_asset))
+        .route("/user/asset/move", post(move_asset))
+        .route("/user/asset/rename", post(rename_asset))
+        .route("/user/asset/copy", post(copy_asset))
+        .route("/user/asset/versions/*asset_path", get(get_versions))
+        .route("/user/asset/promote", post(promote_asset_version))
         .route("/user/bucket", post(create_user_bucket))
+        .route("/user/bucket/:id/trash", get(get_trash).delete(clear_trash))
+        .route("/user/bucket/:id/trash/:entry_id", delete(purge_trash))
+        .route("/user/bucket/:id/trash/:entry_id/restore", post(restore_trash))
 }
 
+/// # Safety
+/// `config` must be a pointer obtained from `Arc::into_raw`. Ownership of the config is
+/// transferred to the router.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn rest_direct(config: *const ServiceConfig) -> *mut Router<HandlerState> {
     let config = unsafe { Arc::from_raw(config) };
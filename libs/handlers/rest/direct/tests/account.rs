@@ -0,0 +1,279 @@
+use std::sync::Arc;
+
+use axum::{Router, http::StatusCode};
+use axum_test::TestServer;
+use ppd_shared::{
+    api::{ChangePassword, DeleteAccount, ForgotPassword, LoginTokens, ResetPassword, UserCredentials},
+    opts::ServiceConfig,
+};
+use ppdrive::prelude::state::HandlerState;
+use rest_direct::rest_direct;
+use rest_test_utils::{TestApp, direct::create_user_bucket};
+use serde_json::Value;
+use serial_test::serial;
+
+const RESET_SINK: &str = "reset-tokens.jsonl";
+
+fn credentials(password: &str) -> UserCredentials {
+    UserCredentials {
+        username: "accountuser".to_string(),
+        password: password.to_string(),
+    }
+}
+
+/// serve the direct router with reset tokens written to [RESET_SINK].
+async fn direct_server(app: &TestApp) -> TestServer {
+    let mut config = ServiceConfig::default();
+    config.auth.reset.notifier = format!("file:{RESET_SINK}");
+
+    let state = HandlerState::new(&config, Arc::new(app.db.clone()))
+        .await
+        .expect("unable to create app state");
+
+    let router = unsafe { Box::from_raw(rest_direct(Arc::into_raw(config.into()))) };
+    let svc = Router::new()
+        .nest("/direct", *router)
+        .with_state(state)
+        .into_make_service();
+
+    TestServer::new(svc).expect("unable to create test server")
+}
+
+async fn login(server: &TestServer, password: &str) -> Option<String> {
+    let resp = server
+        .post("/direct/user/login")
+        .json(&credentials(password))
+        .await;
+
+    if !resp.status_code().is_success() {
+        return None;
+    }
+
+    let tokens: LoginTokens = resp.json();
+    tokens.access.map(|(token, _)| format!("Bearer {token}"))
+}
+
+#[tokio::test]
+#[serial]
+async fn test_direct_register_validation() {
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    server
+        .post("/direct/user/register")
+        .json(&credentials("accountUser@2025"))
+        .await
+        .assert_status_ok();
+
+    // usernames are unique
+    server
+        .post("/direct/user/register")
+        .json(&credentials("accountUser@2025"))
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+
+    let invalid = [
+        UserCredentials {
+            username: "bad user!".to_string(),
+            password: "accountUser@2025".to_string(),
+        },
+        UserCredentials {
+            username: "otheruser".to_string(),
+            password: "weakpassword".to_string(),
+        },
+    ];
+
+    for data in invalid {
+        server
+            .post("/direct/user/register")
+            .json(&data)
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_direct_change_password() {
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    server
+        .post("/direct/user/register")
+        .json(&credentials("accountUser@2025"))
+        .await
+        .assert_status_ok();
+
+    let token = login(&server, "accountUser@2025")
+        .await
+        .expect("unable to log in");
+
+    let mut data = ChangePassword {
+        current_password: "wrongPassword@2025".to_string(),
+        new_password: "changedUser@2025".to_string(),
+    };
+
+    server
+        .post("/direct/user/password")
+        .authorization(&token)
+        .json(&data)
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+
+    data.current_password = "accountUser@2025".to_string();
+    server
+        .post("/direct/user/password")
+        .authorization(&token)
+        .json(&data)
+        .await
+        .assert_status_ok();
+
+    assert!(login(&server, "accountUser@2025").await.is_none());
+    assert!(login(&server, "changedUser@2025").await.is_some());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_direct_reset_password() {
+    let app = TestApp::new().await;
+    let server = direct_server(&app).await;
+    let _ = std::fs::remove_file(RESET_SINK);
+
+    server
+        .post("/direct/user/register")
+        .json(&credentials("accountUser@2025"))
+        .await
+        .assert_status_ok();
+
+    // unknown users get the same response, without a token being sent
+    for username in ["accountuser", "unknownuser"] {
+        server
+            .post("/direct/user/password/forgot")
+            .json(&ForgotPassword {
+                username: username.to_string(),
+            })
+            .await
+            .assert_status_ok();
+    }
+
+    let sink = std::fs::read_to_string(RESET_SINK).expect("reset token was not delivered");
+    let notices: Vec<Value> = sink
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("invalid notice"))
+        .collect();
+
+    assert_eq!(notices.len(), 1);
+    assert_eq!(notices[0]["username"], "accountuser");
+
+    let data = ResetPassword {
+        token: notices[0]["token"].as_str().expect("token missing").to_string(),
+        password: "resetUser@2025".to_string(),
+    };
+
+    server
+        .post("/direct/user/password/reset")
+        .json(&data)
+        .await
+        .assert_status_ok();
+
+    // tokens can be used once
+    server
+        .post("/direct/user/password/reset")
+        .json(&data)
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+
+    assert!(login(&server, "resetUser@2025").await.is_some());
+    let _ = std::fs::remove_file(RESET_SINK);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_direct_reset_password_concurrent() {
+    let app = TestApp::new().await;
+    let server = direct_server(&app).await;
+    let _ = std::fs::remove_file(RESET_SINK);
+
+    server
+        .post("/direct/user/register")
+        .json(&credentials("accountUser@2025"))
+        .await
+        .assert_status_ok();
+
+    server
+        .post("/direct/user/password/forgot")
+        .json(&ForgotPassword {
+            username: "accountuser".to_string(),
+        })
+        .await
+        .assert_status_ok();
+
+    let sink = std::fs::read_to_string(RESET_SINK).expect("reset token was not delivered");
+    let notice: Value = serde_json::from_str(sink.trim()).expect("invalid notice");
+    let token = notice["token"].as_str().expect("token missing");
+
+    let reset = |password: &str| {
+        server
+            .post("/direct/user/password/reset")
+            .json(&ResetPassword {
+                token: token.to_string(),
+                password: password.to_string(),
+            })
+    };
+
+    // the same token redeemed twice at once sets a single password
+    let (first, second) = futures::join!(
+        reset("firstReset@2025").into_future(),
+        reset("secondReset@2025").into_future()
+    );
+
+    let redeemed = [first.status_code(), second.status_code()];
+    assert!(redeemed.contains(&StatusCode::OK));
+    assert!(redeemed.contains(&StatusCode::UNAUTHORIZED));
+
+    let logins = [
+        login(&server, "firstReset@2025").await.is_some(),
+        login(&server, "secondReset@2025").await.is_some(),
+    ];
+    assert_eq!(logins.iter().filter(|ok| **ok).count(), 1);
+    let _ = std::fs::remove_file(RESET_SINK);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_direct_delete_account() {
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    server
+        .post("/direct/user/register")
+        .json(&credentials("accountUser@2025"))
+        .await
+        .assert_status_ok();
+
+    let token = login(&server, "accountUser@2025")
+        .await
+        .expect("unable to log in");
+
+    create_user_bucket(&server, &token).await.assert_status_ok();
+
+    server
+        .delete("/direct/user")
+        .authorization(&token)
+        .json(&DeleteAccount::default())
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+
+    let data = DeleteAccount {
+        password: Some("accountUser@2025".to_string()),
+    };
+
+    server
+        .delete("/direct/user")
+        .authorization(&token)
+        .json(&data)
+        .await
+        .assert_status_ok();
+
+    assert!(login(&server, "accountUser@2025").await.is_none());
+}
@@ -1,11 +1,33 @@
-use axum_test::multipart::{MultipartForm, Part};
-use ppd_bk::models::asset::AssetType;
+use axum::{
+    body::Bytes,
+    http::{
+        StatusCode,
+        header::{
+            ACCEPT, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG,
+            IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, RANGE,
+        },
+    },
+};
+use axum_test::{
+    TestServer,
+    multipart::{MultipartForm, Part},
+};
+use ppd_bk::models::{asset::AssetType, user::Users};
+use ppd_shared::api::{
+    AssetVersion, BucketBackend, CreateBucketOptions, FolderListing, LoginTokens,
+    RefreshTokenOptions, TrashEntry, UserCredentials,
+};
 use serial_test::serial;
 
-use ppd_fs::opts::CreateAssetOptions;
+use ppd_fs::opts::{
+    CreateAssetOptions, CreateUploadOptions, MoveAssetOptions, PromoteVersionOptions,
+    RenameAssetOptions,
+};
+use ppdrive::rest::extractors::UPLOAD_OFFSET_HEADER;
 
 use rest_test_utils::{
-    clean_up_test_assets, direct::{create_user_bucket, login_user_request, register_user}, TestApp
+    clean_up_test_assets, direct::{create_user_bucket, login_user_request, register_user},
+    s3::start_mock_s3, TestApp
 };
 
 #[tokio::test]
@@ -31,6 +53,154 @@ async fn test_rest_direct_login() {
     resp.assert_status_ok();
 }
 
+#[tokio::test]
+#[serial]
+async fn test_direct_user_password_upgrade() {
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    // unsalted SHA3 hash of "legacyUser@2025", as stored by earlier versions
+    let legacy = "0cece4e9cf72a5b60d9a4197a21e3640dfab6ec004e52e68b68f29efb9f3162e";
+    Users::create_direct(&app.db, "legacyuser".to_string(), legacy.to_string())
+        .await
+        .expect("unable to create user");
+
+    let mut credentials = UserCredentials {
+        username: "legacyuser".to_string(),
+        password: "legacyUser@2025".to_string(),
+    };
+
+    for _ in 0..2 {
+        server
+            .post("/direct/user/login")
+            .json(&credentials)
+            .await
+            .assert_status_ok();
+    }
+
+    let user = Users::get_by_key(&app.db, "username", "legacyuser")
+        .await
+        .expect("unable to query user")
+        .expect("user not found");
+
+    let hashed = user.password().clone().unwrap_or_default();
+    assert!(hashed.starts_with("$argon2id$"));
+
+    credentials.password = "wrongPassword@2025".to_string();
+    server
+        .post("/direct/user/login")
+        .json(&credentials)
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+}
+
+/// split login tokens into access and refresh tokens.
+fn login_tokens(tokens: LoginTokens) -> (String, String) {
+    let access = tokens.access.expect("access token missing").0;
+    let refresh = tokens.refresh.expect("refresh token missing").0;
+
+    (access, refresh)
+}
+
+fn refresh_opts(token: &str) -> RefreshTokenOptions {
+    RefreshTokenOptions {
+        token: token.to_string(),
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_direct_user_refresh_token() {
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    let (access, refresh) = login_tokens(login_user_request(&server).await.json());
+
+    // access and refresh tokens can't be used in place of each other
+    server
+        .get("/direct/user")
+        .authorization_bearer(&refresh)
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+
+    server
+        .post("/direct/user/token/refresh")
+        .json(&refresh_opts(&access))
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+
+    let resp = server
+        .post("/direct/user/token/refresh")
+        .json(&refresh_opts(&refresh))
+        .await;
+
+    resp.assert_status_ok();
+    let (new_access, new_refresh) = login_tokens(resp.json());
+
+    server
+        .get("/direct/user")
+        .authorization_bearer(&new_access)
+        .await
+        .assert_status_ok();
+
+    // reusing a rotated token revokes every token of the login
+    server
+        .post("/direct/user/token/refresh")
+        .json(&refresh_opts(&refresh))
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+
+    server
+        .post("/direct/user/token/refresh")
+        .json(&refresh_opts(&new_refresh))
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_direct_user_logout() {
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    let (access, refresh) = login_tokens(login_user_request(&server).await.json());
+    let (_, other_refresh) = login_tokens(login_user_request(&server).await.json());
+
+    server
+        .post("/direct/user/logout")
+        .authorization_bearer(&access)
+        .json(&refresh_opts(&refresh))
+        .await
+        .assert_status_ok();
+
+    server
+        .post("/direct/user/token/refresh")
+        .json(&refresh_opts(&refresh))
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+
+    // other logins are not affected
+    let resp = server
+        .post("/direct/user/token/refresh")
+        .json(&refresh_opts(&other_refresh))
+        .await;
+
+    resp.assert_status_ok();
+    let (access, refresh) = login_tokens(resp.json());
+
+    server
+        .post("/direct/user/logout-all")
+        .authorization_bearer(&access)
+        .await
+        .assert_status_ok();
+
+    server
+        .post("/direct/user/token/refresh")
+        .json(&refresh_opts(&refresh))
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+}
+
 #[tokio::test]
 #[serial]
 /// retrieve an authenticated user using their access token
@@ -151,6 +321,926 @@ async fn test_direct_user_delete_asset() {
     clean_up_test_assets();
 }
 
+#[tokio::test]
+#[serial]
+async fn test_direct_user_delete_folder() {
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    let token = app.direct_login().await;
+    let bucket_opts = CreateBucketOptions {
+        partition: Some("delete-partition".to_string()),
+        label: "delete bucket".to_string(),
+        backend: Some(BucketBackend::Memory),
+        ..Default::default()
+    };
+
+    let resp = server
+        .post("/direct/user/bucket")
+        .json(&bucket_opts)
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_ok();
+    let bucket = resp.text();
+
+    let upload = || async {
+        let asset_opts = CreateAssetOptions {
+            asset_path: "delete-folder/nested/file.md".to_string(),
+            asset_type: AssetType::File,
+            bucket: bucket.clone(),
+            ..Default::default()
+        };
+
+        let file = Part::bytes(include_bytes!("README.MD").as_slice()).file_name("file.md");
+        let multipart = MultipartForm::new()
+            .add_text("options", asset_opts_str(&asset_opts))
+            .add_part("file", file);
+
+        server
+            .post("/direct/user/asset")
+            .multipart(multipart)
+            .authorization_bearer(&token)
+            .await
+    };
+
+    upload().await.assert_status_ok();
+
+    server
+        .delete("/direct/user/asset/Folder/delete-folder?permanent=true")
+        .authorization_bearer(&token)
+        .await
+        .assert_status_ok();
+
+    for path in ["/File/delete-folder/nested/file.md", "/Folder/delete-folder/nested"] {
+        server
+            .get(path)
+            .authorization_bearer(&token)
+            .await
+            .assert_status_not_ok();
+    }
+
+    // no records are left behind, so the paths can be used again
+    upload().await.assert_status_ok();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_direct_user_trash() {
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    let token = app.direct_login().await;
+    let bucket_opts = CreateBucketOptions {
+        partition: Some("trash-partition".to_string()),
+        label: "trash bucket".to_string(),
+        backend: Some(BucketBackend::Memory),
+        ..Default::default()
+    };
+
+    let resp = server
+        .post("/direct/user/bucket")
+        .json(&bucket_opts)
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_ok();
+    let bucket = resp.text();
+
+    let upload = || async {
+        let asset_opts = CreateAssetOptions {
+            asset_path: "trash-folder/file.md".to_string(),
+            asset_type: AssetType::File,
+            bucket: bucket.clone(),
+            ..Default::default()
+        };
+
+        let file = Part::bytes(include_bytes!("README.MD").as_slice()).file_name("file.md");
+        let multipart = MultipartForm::new()
+            .add_text("options", asset_opts_str(&asset_opts))
+            .add_part("file", file);
+
+        server
+            .post("/direct/user/asset")
+            .multipart(multipart)
+            .authorization_bearer(&token)
+            .await
+    };
+
+    upload().await.assert_status_ok();
+
+    server
+        .delete("/direct/user/asset/File/trash-folder/file.md")
+        .authorization_bearer(&token)
+        .await
+        .assert_status_ok();
+
+    server
+        .get("/File/trash-folder/file.md")
+        .authorization_bearer(&token)
+        .await
+        .assert_status_not_ok();
+
+    let trash_url = format!("/direct/user/bucket/{bucket}/trash");
+    let resp = server.get(&trash_url).authorization_bearer(&token).await;
+    resp.assert_status_ok();
+
+    let entries: Vec<TrashEntry> = resp.json();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].path, "trash-folder/file.md");
+
+    // the path is free while the asset is in the trash, so the restored asset is renamed
+    upload().await.assert_status_ok();
+
+    let resp = server
+        .post(&format!("{trash_url}/{}/restore", entries[0].id))
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_ok();
+    assert_eq!(resp.text(), "trash-folder/file (1).md");
+
+    server
+        .get("/File/trash-folder/file%20(1).md")
+        .authorization_bearer(&token)
+        .await
+        .assert_status_ok();
+
+    // purged entries can't be restored
+    server
+        .delete("/direct/user/asset/Folder/trash-folder")
+        .authorization_bearer(&token)
+        .await
+        .assert_status_ok();
+
+    let entries: Vec<TrashEntry> = server
+        .get(&trash_url)
+        .authorization_bearer(&token)
+        .await
+        .json();
+
+    assert_eq!(entries.len(), 1);
+    let entry_url = format!("{trash_url}/{}", entries[0].id);
+
+    server
+        .delete(&entry_url)
+        .authorization_bearer(&token)
+        .await
+        .assert_status_ok();
+
+    server
+        .post(&format!("{entry_url}/restore"))
+        .authorization_bearer(&token)
+        .await
+        .assert_status_not_ok();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_direct_user_versions() {
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    let token = app.direct_login().await;
+    let bucket_opts = CreateBucketOptions {
+        partition: Some("versions-partition".to_string()),
+        label: "versioned bucket".to_string(),
+        backend: Some(BucketBackend::Memory),
+        versioning: Some(true),
+        max_versions: Some(2),
+        ..Default::default()
+    };
+
+    let resp = server
+        .post("/direct/user/bucket")
+        .json(&bucket_opts)
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_ok();
+    let bucket = resp.text();
+
+    let upload = |content: String| {
+        let asset_opts = CreateAssetOptions {
+            asset_path: "versions-folder/notes.txt".to_string(),
+            asset_type: AssetType::File,
+            bucket: bucket.clone(),
+            ..Default::default()
+        };
+
+        let file = Part::bytes(content.into_bytes()).file_name("notes.txt");
+        let multipart = MultipartForm::new()
+            .add_text("options", asset_opts_str(&asset_opts))
+            .add_part("file", file);
+
+        server
+            .post("/direct/user/asset")
+            .multipart(multipart)
+            .authorization_bearer(&token)
+    };
+
+    for idx in 1..=4 {
+        upload(format!("content {idx}")).await.assert_status_ok();
+    }
+
+    // the oldest version is removed beyond max_versions
+    let versions_url = "/direct/user/asset/versions/versions-folder/notes.txt";
+    let versions: Vec<AssetVersion> = server
+        .get(versions_url)
+        .authorization_bearer(&token)
+        .await
+        .json();
+
+    let numbers: Vec<u64> = versions.iter().map(|v| v.version).collect();
+    assert_eq!(numbers, vec![3, 2]);
+
+    let resp = server
+        .get("/File/versions-folder/notes.txt?version=2")
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_ok();
+    assert_eq!(resp.text(), "content 2");
+
+    let promote = PromoteVersionOptions {
+        asset_path: "versions-folder/notes.txt".to_string(),
+        version: 2,
+    };
+
+    server
+        .post("/direct/user/asset/promote")
+        .json(&promote)
+        .authorization_bearer(&token)
+        .await
+        .assert_status_ok();
+
+    let resp = server
+        .get("/File/versions-folder/notes.txt")
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_ok();
+    assert_eq!(resp.text(), "content 2");
+
+    // the replaced content is kept as a new version
+    let versions: Vec<AssetVersion> = server
+        .get(versions_url)
+        .authorization_bearer(&token)
+        .await
+        .json();
+
+    let numbers: Vec<u64> = versions.iter().map(|v| v.version).collect();
+    assert_eq!(numbers, vec![4, 3]);
+
+    let resp = server
+        .get("/File/versions-folder/notes.txt?version=4")
+        .authorization_bearer(&token)
+        .await;
+
+    assert_eq!(resp.text(), "content 4");
+
+    // the path can't be written again from another bucket
+    let other_opts = CreateBucketOptions {
+        partition: Some("versions-other-partition".to_string()),
+        label: "other bucket".to_string(),
+        backend: Some(BucketBackend::Memory),
+        ..Default::default()
+    };
+
+    let other = server
+        .post("/direct/user/bucket")
+        .json(&other_opts)
+        .authorization_bearer(&token)
+        .await
+        .text();
+
+    let asset_opts = CreateAssetOptions {
+        asset_path: "versions-folder/notes.txt".to_string(),
+        asset_type: AssetType::File,
+        bucket: other,
+        ..Default::default()
+    };
+
+    let file = Part::bytes(b"other content".to_vec()).file_name("notes.txt");
+    let multipart = MultipartForm::new()
+        .add_text("options", asset_opts_str(&asset_opts))
+        .add_part("file", file);
+
+    server
+        .post("/direct/user/asset")
+        .multipart(multipart)
+        .authorization_bearer(&token)
+        .await
+        .assert_status_not_ok();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_direct_user_upload_exceeds_bucket_size() {
+    clean_up_test_assets();
+
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    let token = app.direct_login().await;
+    let bucket_opts = CreateBucketOptions {
+        partition: Some("test-assets/size-partition".to_string()),
+        partition_size: Some(0.5),
+        label: "size limited bucket".to_string(),
+        ..Default::default()
+    };
+
+    let resp = server
+        .post("/direct/user/bucket")
+        .json(&bucket_opts)
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_ok();
+
+    let asset_opts = CreateAssetOptions {
+        asset_path: "large-file".to_string(),
+        asset_type: AssetType::File,
+        bucket: resp.text(),
+        ..Default::default()
+    };
+
+    // file is larger than the bucket's partition size, so upload should be aborted
+    let file_bytes = vec![0u8; 600 * 1024];
+    let file = Part::bytes(file_bytes).file_name("large-file");
+
+    let multipart = MultipartForm::new()
+        .add_text("options", asset_opts_str(&asset_opts))
+        .add_part("file", file);
+
+    let resp = server
+        .post("/direct/user/asset")
+        .multipart(multipart)
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_not_ok();
+    clean_up_test_assets();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_direct_user_resumable_upload() {
+    clean_up_test_assets();
+
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    let token = app.direct_login().await;
+    let bucket = create_user_bucket(&server, &token).await.text();
+
+    let file_bytes = include_bytes!("README.MD");
+    let (first, second) = file_bytes.split_at(file_bytes.len() / 2);
+
+    let upload_opts = CreateUploadOptions {
+        asset: CreateAssetOptions {
+            asset_path: "test-assets/resumable-file".to_string(),
+            asset_type: AssetType::File,
+            bucket,
+            ..Default::default()
+        },
+        size: Some(file_bytes.len() as u64),
+    };
+
+    let resp = server
+        .post("/direct/user/upload")
+        .json(&upload_opts)
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_ok();
+    let path = format!("/direct/user/upload/{}", resp.text());
+
+    // chunk sent without an offset should be rejected
+    let resp = server
+        .patch(&path)
+        .bytes(Bytes::copy_from_slice(first))
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_bad_request();
+
+    // chunk sent with a wrong offset should be rejected
+    let resp = server
+        .patch(&path)
+        .add_header(UPLOAD_OFFSET_HEADER, "10")
+        .add_header(CONTENT_LENGTH, first.len().to_string())
+        .bytes(Bytes::copy_from_slice(first))
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_not_ok();
+
+    // chunk sent without its length should be rejected
+    let resp = server
+        .patch(&path)
+        .add_header(UPLOAD_OFFSET_HEADER, "0")
+        .bytes(Bytes::copy_from_slice(first))
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_bad_request();
+
+    // chunk shorter than its declared length is rejected, and the offset is released
+    let resp = server
+        .patch(&path)
+        .add_header(UPLOAD_OFFSET_HEADER, "0")
+        .add_header(CONTENT_LENGTH, (first.len() + 1).to_string())
+        .bytes(Bytes::copy_from_slice(first))
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_not_ok();
+
+    let mut offset = 0;
+    for chunk in [first, second] {
+        let resp = server
+            .patch(&path)
+            .add_header(UPLOAD_OFFSET_HEADER, offset.to_string())
+            .add_header(CONTENT_LENGTH, chunk.len().to_string())
+            .bytes(Bytes::copy_from_slice(chunk))
+            .authorization_bearer(&token)
+            .await;
+
+        resp.assert_status_ok();
+        offset = resp.text().parse::<usize>().expect("invalid upload offset");
+    }
+
+    let resp = server.get(&path).authorization_bearer(&token).await;
+    resp.assert_status_ok();
+
+    let resp = server.post(&path).authorization_bearer(&token).await;
+    resp.assert_status_ok();
+
+    // sessions larger than the maximum upload size should be rejected
+    let upload_opts = CreateUploadOptions {
+        size: Some(u64::MAX),
+        ..upload_opts
+    };
+
+    let resp = server
+        .post("/direct/user/upload")
+        .json(&upload_opts)
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_not_ok();
+
+    clean_up_test_assets();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_direct_user_get_asset_range() {
+    clean_up_test_assets();
+
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    let token = app.direct_login().await;
+    let asset_path = "test-assets/range-file";
+    upload_file(&server, &token, asset_path).await;
+
+    let file_bytes = include_bytes!("README.MD");
+    let path = format!("/File/{asset_path}");
+    let size = file_bytes.len();
+
+    // single range
+    let resp = server
+        .get(&path)
+        .add_header(RANGE, "bytes=0-9")
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status(StatusCode::PARTIAL_CONTENT);
+    resp.assert_header(CONTENT_RANGE, format!("bytes 0-9/{size}"));
+    assert_eq!(resp.as_bytes().as_ref(), &file_bytes[..10]);
+
+    // multiple ranges
+    let resp = server
+        .get(&path)
+        .add_header(RANGE, "bytes=0-4, -5")
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status(StatusCode::PARTIAL_CONTENT);
+    let content_type = resp.header(CONTENT_TYPE);
+    let content_type = content_type.to_str().expect("invalid content type");
+    assert!(content_type.starts_with("multipart/byteranges"));
+
+    // range beyond file size
+    let resp = server
+        .get(&path)
+        .add_header(RANGE, format!("bytes={size}-"))
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status(StatusCode::RANGE_NOT_SATISFIABLE);
+
+    // outdated If-Range validator, full content is returned
+    let resp = server
+        .get(&path)
+        .add_header(RANGE, "bytes=0-9")
+        .add_header(IF_RANGE, "Thu, 01 Jan 1970 00:00:00 GMT")
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_ok();
+    assert_eq!(resp.as_bytes().len(), size);
+
+    clean_up_test_assets();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_direct_user_get_asset_not_modified() {
+    clean_up_test_assets();
+
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    let token = app.direct_login().await;
+    let asset_path = "test-assets/cached-file";
+    upload_file(&server, &token, asset_path).await;
+
+    let path = format!("/File/{asset_path}");
+    let resp = server.get(&path).authorization_bearer(&token).await;
+
+    resp.assert_status_ok();
+    resp.assert_header(CACHE_CONTROL, "private, no-store");
+
+    let etag = resp.header(ETAG);
+    let last_modified = resp.header(LAST_MODIFIED);
+
+    let resp = server
+        .get(&path)
+        .add_header(IF_NONE_MATCH, etag.clone())
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status(StatusCode::NOT_MODIFIED);
+    resp.assert_header(ETAG, etag);
+
+    let resp = server
+        .get(&path)
+        .add_header(IF_MODIFIED_SINCE, last_modified)
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status(StatusCode::NOT_MODIFIED);
+
+    let resp = server
+        .get(&path)
+        .add_header(IF_NONE_MATCH, "\"outdated\"")
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_ok();
+    clean_up_test_assets();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_direct_user_memory_bucket() {
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    let token = app.direct_login().await;
+    let bucket_opts = CreateBucketOptions {
+        partition: Some("memory-partition".to_string()),
+        label: "in-memory bucket".to_string(),
+        backend: Some(BucketBackend::Memory),
+        ..Default::default()
+    };
+
+    let resp = server
+        .post("/direct/user/bucket")
+        .json(&bucket_opts)
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_ok();
+
+    let asset_path = "memory-folder/memory-file";
+    let asset_opts = CreateAssetOptions {
+        asset_path: asset_path.to_string(),
+        asset_type: AssetType::File,
+        bucket: resp.text(),
+        ..Default::default()
+    };
+
+    let file_bytes = include_bytes!("README.MD");
+    let file = Part::bytes(file_bytes.as_slice()).file_name("memory-file");
+    let multipart = MultipartForm::new()
+        .add_text("options", asset_opts_str(&asset_opts))
+        .add_part("file", file);
+
+    let resp = server
+        .post("/direct/user/asset")
+        .multipart(multipart)
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_ok();
+
+    // asset is not written to the filesystem
+    assert!(!std::path::Path::new("memory-partition").exists());
+
+    let path = format!("/File/{asset_path}");
+    let resp = server.get(&path).authorization_bearer(&token).await;
+
+    resp.assert_status_ok();
+    assert_eq!(resp.as_bytes().as_ref(), file_bytes.as_slice());
+
+    let resp = server
+        .get("/Folder/memory-folder")
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_ok();
+    assert!(resp.text().contains("memory-file"));
+
+    let resp = server
+        .get("/Folder/memory-folder")
+        .add_header(ACCEPT, "application/json")
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_ok();
+    let listing: FolderListing = resp.json();
+    assert_eq!(listing.entries.len(), 1);
+    assert_eq!(listing.entries[0].name, "memory-file");
+    assert_eq!(listing.entries[0].size, Some(file_bytes.len() as u64));
+    assert!(listing.next_cursor.is_none());
+
+    let resp = server
+        .delete(&format!("/direct/user/asset/File/{asset_path}"))
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_ok();
+
+    let resp = server.get(&path).authorization_bearer(&token).await;
+    resp.assert_status_not_ok();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_direct_user_move_assets() {
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    let token = app.direct_login().await;
+    let mut buckets = Vec::new();
+
+    for partition in ["move-source", "move-dest"] {
+        let bucket_opts = CreateBucketOptions {
+            partition: Some(partition.to_string()),
+            label: partition.to_string(),
+            backend: Some(BucketBackend::Memory),
+            ..Default::default()
+        };
+
+        let resp = server
+            .post("/direct/user/bucket")
+            .json(&bucket_opts)
+            .authorization_bearer(&token)
+            .await;
+
+        resp.assert_status_ok();
+        buckets.push(resp.text());
+    }
+
+    let asset_opts = CreateAssetOptions {
+        asset_path: "move-folder/docs/readme.md".to_string(),
+        asset_type: AssetType::File,
+        bucket: buckets[0].clone(),
+        ..Default::default()
+    };
+
+    let file_bytes = include_bytes!("README.MD");
+    let file = Part::bytes(file_bytes.as_slice()).file_name("readme.md");
+    let multipart = MultipartForm::new()
+        .add_text("options", asset_opts_str(&asset_opts))
+        .add_part("file", file);
+
+    server
+        .post("/direct/user/asset")
+        .multipart(multipart)
+        .authorization_bearer(&token)
+        .await
+        .assert_status_ok();
+
+    // rename a file within its folder
+    let rename = RenameAssetOptions {
+        asset_path: "move-folder/docs/readme.md".to_string(),
+        asset_type: AssetType::File,
+        name: "guide.md".to_string(),
+    };
+
+    server
+        .post("/direct/user/asset/rename")
+        .json(&rename)
+        .authorization_bearer(&token)
+        .await
+        .assert_status_ok();
+
+    let resp = server
+        .get("/File/move-folder/docs/guide.md")
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_ok();
+    assert_eq!(resp.as_bytes().as_ref(), file_bytes.as_slice());
+
+    // copy a folder with its content
+    let copy = MoveAssetOptions {
+        asset_path: "move-folder/docs".to_string(),
+        asset_type: AssetType::Folder,
+        destination: "move-folder/docs-copy".to_string(),
+        ..Default::default()
+    };
+
+    server
+        .post("/direct/user/asset/copy")
+        .json(&copy)
+        .authorization_bearer(&token)
+        .await
+        .assert_status_ok();
+
+    // the destination is taken now
+    server
+        .post("/direct/user/asset/copy")
+        .json(&copy)
+        .authorization_bearer(&token)
+        .await
+        .assert_status_not_ok();
+
+    // move a folder to another bucket
+    let move_opts = MoveAssetOptions {
+        asset_path: "move-folder/docs".to_string(),
+        asset_type: AssetType::Folder,
+        destination: "moved-folder/docs".to_string(),
+        bucket: Some(buckets[1].clone()),
+        ..Default::default()
+    };
+
+    server
+        .post("/direct/user/asset/move")
+        .json(&move_opts)
+        .authorization_bearer(&token)
+        .await
+        .assert_status_ok();
+
+    for path in ["moved-folder/docs/guide.md", "move-folder/docs-copy/guide.md"] {
+        let resp = server
+            .get(&format!("/File/{path}"))
+            .authorization_bearer(&token)
+            .await;
+
+        resp.assert_status_ok();
+        assert_eq!(resp.as_bytes().as_ref(), file_bytes.as_slice());
+    }
+
+    server
+        .get("/File/move-folder/docs/guide.md")
+        .authorization_bearer(&token)
+        .await
+        .assert_status_not_ok();
+
+    // a folder can't be moved into itself
+    let move_opts = MoveAssetOptions {
+        asset_path: "moved-folder/docs".to_string(),
+        asset_type: AssetType::Folder,
+        destination: "moved-folder/docs/inner".to_string(),
+        ..Default::default()
+    };
+
+    server
+        .post("/direct/user/asset/move")
+        .json(&move_opts)
+        .authorization_bearer(&token)
+        .await
+        .assert_status_not_ok();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_direct_user_s3_bucket() {
+    let objects = start_mock_s3().await;
+
+    let app = TestApp::new().await;
+    let server = app.server();
+
+    let token = app.direct_login().await;
+    let bucket_opts = CreateBucketOptions {
+        partition: Some("s3-partition".to_string()),
+        partition_size: Some(1.0),
+        label: "object store bucket".to_string(),
+        backend: Some(BucketBackend::S3),
+        ..Default::default()
+    };
+
+    let resp = server
+        .post("/direct/user/bucket")
+        .json(&bucket_opts)
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_ok();
+
+    let asset_path = "s3-folder/s3-file";
+    let asset_opts = CreateAssetOptions {
+        asset_path: asset_path.to_string(),
+        asset_type: AssetType::File,
+        bucket: resp.text(),
+        ..Default::default()
+    };
+
+    let file_bytes = include_bytes!("README.MD");
+    let file = Part::bytes(file_bytes.as_slice()).file_name("s3-file");
+    let multipart = MultipartForm::new()
+        .add_text("options", asset_opts_str(&asset_opts))
+        .add_part("file", file);
+
+    let resp = server
+        .post("/direct/user/asset")
+        .multipart(multipart)
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_ok();
+
+    // file is saved under the bucket's partition prefix
+    let stored = objects
+        .lock()
+        .unwrap()
+        .get("s3-partition/s3-folder/s3-file")
+        .cloned();
+    assert_eq!(stored.as_deref(), Some(file_bytes.as_slice()));
+
+    let path = format!("/File/{asset_path}");
+    let resp = server
+        .get(&path)
+        .add_header(RANGE, "bytes=0-9")
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status(StatusCode::PARTIAL_CONTENT);
+    assert_eq!(resp.as_bytes().as_ref(), &file_bytes[..10]);
+
+    let resp = server
+        .get("/Folder/s3-folder")
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_ok();
+    assert!(resp.text().contains("s3-file"));
+
+    let resp = server
+        .delete(&format!("/direct/user/asset/File/{asset_path}"))
+        .authorization_bearer(&token)
+        .await;
+
+    resp.assert_status_ok();
+    assert!(
+        !objects
+            .lock()
+            .unwrap()
+            .contains_key("s3-partition/s3-folder/s3-file")
+    );
+}
+
+/// uploads README.MD to `asset_path` in a new bucket
+async fn upload_file(server: &TestServer, token: &str, asset_path: &str) {
+    let bucket = create_user_bucket(server, token).await.text();
+    let asset_opts = CreateAssetOptions {
+        asset_path: asset_path.to_string(),
+        asset_type: AssetType::File,
+        bucket,
+        ..Default::default()
+    };
+
+    let file_bytes = include_bytes!("README.MD");
+    let file = Part::bytes(file_bytes.as_slice()).file_name("test-file");
+    let multipart = MultipartForm::new()
+        .add_text("options", asset_opts_str(&asset_opts))
+        .add_part("file", file);
+
+    let resp = server
+        .post("/direct/user/asset")
+        .multipart(multipart)
+        .authorization_bearer(token)
+        .await;
+
+    resp.assert_status_ok();
+}
+
 fn asset_opts_str(opts: &CreateAssetOptions) -> String {
     serde_json::to_string(opts).expect("unable to create strigify asset options")
 }
@@ -0,0 +1,101 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use axum::{
+    Json, Router,
+    extract::State,
+    http::{HeaderMap, StatusCode, header::AUTHORIZATION},
+    routing::get,
+};
+use axum_test::TestServer;
+use ppd_bk::models::user::Users;
+use ppd_shared::opts::ServiceConfig;
+use ppdrive::prelude::state::HandlerState;
+use rest_direct::rest_direct;
+use rest_test_utils::TestApp;
+use serde_json::{Value, json};
+use serial_test::serial;
+
+const EXTERNAL_TOKEN: &str = "Bearer external-token";
+const EXTERNAL_ID: &str = "external-user-1";
+
+/// a stand-in for the identity service, counting the tokens it verifies.
+async fn start_auth_service(hits: Arc<AtomicUsize>) -> SocketAddr {
+    async fn verify(
+        State(hits): State<Arc<AtomicUsize>>,
+        headers: HeaderMap,
+    ) -> Result<Json<Value>, StatusCode> {
+        hits.fetch_add(1, Ordering::SeqCst);
+
+        match headers.get(AUTHORIZATION) {
+            Some(token) if token == EXTERNAL_TOKEN => {
+                Ok(Json(json!({ "id": EXTERNAL_ID, "max_bucket": 100.0 })))
+            }
+            _ => Err(StatusCode::UNAUTHORIZED),
+        }
+    }
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("unable to bind listener");
+    let addr = listener.local_addr().expect("unable to get address");
+
+    let app = Router::new().route("/verify", get(verify)).with_state(hits);
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    addr
+}
+
+async fn direct_server(app: &TestApp, auth_url: String) -> TestServer {
+    let mut config = ServiceConfig::default();
+    config.auth.url = Some(auth_url);
+
+    let state = HandlerState::new(&config, Arc::new(app.db.clone()))
+        .await
+        .expect("unable to create app state");
+
+    let router = unsafe { Box::from_raw(rest_direct(Arc::into_raw(config.into()))) };
+    let svc = Router::new()
+        .nest("/direct", *router)
+        .with_state(state)
+        .into_make_service();
+
+    TestServer::new(svc).expect("unable to create test server")
+}
+
+#[tokio::test]
+#[serial]
+async fn test_external_auth_url() {
+    let app = TestApp::new().await;
+    let hits = Arc::new(AtomicUsize::new(0));
+
+    let addr = start_auth_service(hits.clone()).await;
+    let server = direct_server(&app, format!("http://{addr}/verify")).await;
+
+    // the user is created on first sight and the result is cached
+    for _ in 0..2 {
+        let resp = server.get("/direct/user").authorization(EXTERNAL_TOKEN).await;
+        resp.assert_status_ok();
+
+        let user: Value = resp.json();
+        assert_eq!(user["max_bucket"], 100.0);
+    }
+
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    let user = Users::get_by_key(&app.db, "external_id", EXTERNAL_ID)
+        .await
+        .expect("unable to query user");
+    assert!(user.is_some());
+
+    server
+        .get("/direct/user")
+        .authorization("Bearer invalid-token")
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,125 @@
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::{Router, http::StatusCode};
+use axum_test::TestServer;
+use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+use ppd_bk::models::user::Users;
+use ppd_shared::opts::ServiceConfig;
+use ppdrive::prelude::state::HandlerState;
+use rest_direct::rest_direct;
+use rest_test_utils::TestApp;
+use serde_json::{Value, json};
+use serial_test::serial;
+
+const ISSUER: &str = "https://issuer.test";
+const AUDIENCE: &str = "ppdrive";
+const SUBJECT: &str = "oidc-user-1";
+
+/// the JWKS file standing in for the issuer's endpoint.
+const JWKS_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/oidc/jwks.json");
+
+async fn direct_server(app: &TestApp) -> TestServer {
+    let mut config = ServiceConfig::default();
+    config.auth.oidc.issuer = Some(ISSUER.to_string());
+    config.auth.oidc.audience = Some(AUDIENCE.to_string());
+    config.auth.oidc.jwks = Some(JWKS_PATH.to_string());
+
+    let state = HandlerState::new(&config, Arc::new(app.db.clone()))
+        .await
+        .expect("unable to create app state");
+
+    let router = unsafe { Box::from_raw(rest_direct(Arc::into_raw(config.into()))) };
+    let svc = Router::new()
+        .nest("/direct", *router)
+        .with_state(state)
+        .into_make_service();
+
+    TestServer::new(svc).expect("unable to create test server")
+}
+
+/// sign a token for [SUBJECT] with one of the fixture keys.
+fn sign(alg: Algorithm, kid: &str, aud: &str, exp_in: i64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("invalid system time")
+        .as_secs() as i64;
+
+    let claims = json!({
+        "iss": ISSUER,
+        "aud": aud,
+        "sub": SUBJECT,
+        "iat": now,
+        "exp": now + exp_in,
+    });
+
+    let key = match alg {
+        Algorithm::RS256 => EncodingKey::from_rsa_pem(include_bytes!("oidc/rsa_private.pem")),
+        _ => EncodingKey::from_ec_pem(include_bytes!("oidc/ec_private.pem")),
+    }
+    .expect("invalid fixture key");
+
+    let mut header = Header::new(alg);
+    header.kid = Some(kid.to_string());
+
+    let token = encode(&header, &claims, &key).expect("unable to sign token");
+    format!("Bearer {token}")
+}
+
+#[tokio::test]
+#[serial]
+async fn test_oidc_tokens() {
+    let app = TestApp::new().await;
+    let server = direct_server(&app).await;
+
+    // both keys of the JWKS resolve to the same user
+    for token in [
+        sign(Algorithm::RS256, "test-rsa", AUDIENCE, 300),
+        sign(Algorithm::ES256, "test-ec", AUDIENCE, 300),
+    ] {
+        let resp = server.get("/direct/user").authorization(&token).await;
+        resp.assert_status_ok();
+
+        let user: Value = resp.json();
+        assert!(user["id"].is_string());
+    }
+
+    let external_id = format!("{ISSUER}|{SUBJECT}");
+    let user = Users::get_by_key(&app.db, "external_id", &external_id)
+        .await
+        .expect("unable to query user");
+    assert!(user.is_some());
+
+    // local tokens keep working alongside OIDC
+    let token = app.direct_login().await;
+    server
+        .get("/direct/user")
+        .authorization(&token)
+        .await
+        .assert_status_ok();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_oidc_rejects_invalid_tokens() {
+    let app = TestApp::new().await;
+    let server = direct_server(&app).await;
+
+    let invalid = [
+        sign(Algorithm::RS256, "test-rsa", "another-audience", 300),
+        sign(Algorithm::RS256, "test-rsa", AUDIENCE, -300),
+        sign(Algorithm::RS256, "unknown-key", AUDIENCE, 300),
+        // signed with the RSA key but claiming the EC key
+        sign(Algorithm::RS256, "test-ec", AUDIENCE, 300),
+    ];
+
+    for token in invalid {
+        server
+            .get("/direct/user")
+            .authorization(&token)
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+    }
+}
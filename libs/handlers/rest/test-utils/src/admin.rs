@@ -0,0 +1,20 @@
+use std::sync::LazyLock;
+
+use axum_test::{TestResponse, TestServer};
+use ppd_bk::RBatis;
+use ppd_shared::{api::UserCredentials, opts::PasswordHashConfig};
+use ppdrive::tools::create_admin;
+
+pub static ADMIN_CREDENTIALS: LazyLock<UserCredentials> = LazyLock::new(|| UserCredentials {
+    username: "ppdriveadmin".to_string(),
+    password: "ppdriveAdmin@2025".to_string(),
+});
+
+pub async fn login_admin_request(db: &RBatis, server: &TestServer) -> TestResponse {
+    let UserCredentials { username, password } = &*ADMIN_CREDENTIALS;
+    if let Err(err) = create_admin(db, username, password, &PasswordHashConfig::default()).await {
+        println!("{err}");
+    }
+
+    server.post("/admin/login").json(&*ADMIN_CREDENTIALS).await
+}
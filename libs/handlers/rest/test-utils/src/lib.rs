@@ -1,6 +1,9 @@
 use std::sync::Arc;
 
-use axum::{Router, routing::IntoMakeService};
+use axum::{
+    Router,
+    routing::{IntoMakeService, get},
+};
 use axum_test::TestServer;
 use ppd_bk::RBatis;
 use ppd_bk::db::init_db;
@@ -12,21 +15,26 @@ use ppd_shared::{
     tools::{AppSecrets, root_dir},
 };
 use ppdrive::prelude::state::HandlerState;
+use ppdrive::rest::get_asset;
 use ppdrive::tools::create_client;
 
+use rest_admin::rest_admin as admin_router;
 use rest_client::rest_client as client_router;
 use rest_direct::rest_direct as direct_router;
 
-use crate::direct::login_user_request;
+use crate::{admin::login_admin_request, direct::login_user_request};
 
+pub mod admin;
 pub mod client;
 pub mod direct;
+pub mod s3;
 
 pub struct TestApp {
     pub db: RBatis,
     pub svc: IntoMakeService<Router<()>>,
     client_rtr: *mut Router<HandlerState>,
     direct_rtr: *mut Router<HandlerState>,
+    admin_rtr: *mut Router<HandlerState>,
 }
 
 impl TestApp {
@@ -48,9 +56,11 @@ impl TestApp {
 
         let client_router = unsafe { client_router(Arc::into_raw(config.clone().into())) };
         let direct_router = unsafe { direct_router(Arc::into_raw(config.clone().into())) };
+        let admin_router = unsafe { admin_router(Arc::into_raw(config.clone().into())) };
 
         let (client_rtr, client_router) = Self::unwrap_router(client_router);
         let (direct_rtr, direct_router) = Self::unwrap_router(direct_router);
+        let (admin_rtr, admin_router) = Self::unwrap_router(admin_router);
 
         let state = HandlerState::new(&config, db)
             .await
@@ -58,8 +68,10 @@ impl TestApp {
 
         let db = state.db().clone();
         let svc = Router::new()
+            .route("/:asset_type/*asset_path", get(get_asset))
             .nest("/client", client_router)
             .nest("/direct", direct_router)
+            .nest("/admin", admin_router)
             .with_state(state)
             .into_make_service();
 
@@ -68,6 +80,7 @@ impl TestApp {
             svc,
             client_rtr,
             direct_rtr,
+            admin_rtr,
         }
     }
 
@@ -94,6 +107,18 @@ impl TestApp {
         }
     }
 
+    /// create an admin user and return the authorization header value for it.
+    #[allow(dead_code)]
+    pub async fn admin_login(&self) -> String {
+        let resp = login_admin_request(&self.db, &self.server()).await;
+
+        let tokens: LoginTokens = resp.json();
+        match tokens.access {
+            Some(token) => format!("Bearer {}", token.0),
+            None => panic!("unable to create admin access token"),
+        }
+    }
+
     pub fn server(&self) -> TestServer {
         TestServer::new(self.svc.clone()).expect("unable to create test server")
     }
@@ -115,6 +140,10 @@ impl Drop for TestApp {
             if !self.direct_rtr.is_null() {
                 let _ = Box::from_raw(self.direct_rtr);
             }
+
+            if !self.admin_rtr.is_null() {
+                let _ = Box::from_raw(self.admin_rtr);
+            }
         }
     }
 }
@@ -0,0 +1,192 @@
+//! An in-process mock of an S3-compatible object store, for testing S3 bucket backends.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    Router,
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::{
+        HeaderMap, StatusCode,
+        header::{AUTHORIZATION, CONTENT_LENGTH, LAST_MODIFIED, RANGE},
+    },
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use ppd_fs::storage::s3::{
+    COPY_SOURCE, S3_ACCESS_KEY, S3_BUCKET_KEY, S3_ENDPOINT_KEY, S3_REGION_KEY, S3_SECRET_KEY,
+};
+use tokio::net::TcpListener;
+
+type Objects = Arc<Mutex<BTreeMap<String, Vec<u8>>>>;
+
+const LAST_MODIFIED_VALUE: &str = "Wed, 01 Jan 2025 00:00:00 GMT";
+
+/// Starts the mock server and points `PPDRIVE_S3_*` environment variables to it.
+pub async fn start_mock_s3() -> Objects {
+    let objects = Objects::default();
+    let app = Router::new()
+        .route("/:bucket", get(list_objects))
+        .route(
+            "/:bucket/*key",
+            get(get_object).put(put_object).delete(delete_object),
+        )
+        .with_state(objects.clone());
+
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("unable to bind mock s3 server");
+    let addr = listener.local_addr().expect("unable to get mock s3 address");
+
+    tokio::spawn(async move {
+        axum::serve(listener, app)
+            .await
+            .expect("mock s3 server failed");
+    });
+
+    // SAFETY: tests using the mock are run serially
+    unsafe {
+        std::env::set_var(S3_ENDPOINT_KEY, format!("http://{addr}"));
+        std::env::set_var(S3_REGION_KEY, "us-east-1");
+        std::env::set_var(S3_BUCKET_KEY, "ppdrive-test");
+        std::env::set_var(S3_ACCESS_KEY, "test-access-key");
+        std::env::set_var(S3_SECRET_KEY, "test-secret-key");
+    }
+
+    objects
+}
+
+fn authorized(headers: &HeaderMap) -> bool {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("AWS4-HMAC-SHA256 Credential=test-access-key/"))
+}
+
+async fn put_object(
+    Path((_, key)): Path<(String, String)>,
+    State(objects): State<Objects>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    if !authorized(&headers) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    let mut objects = objects.lock().unwrap();
+    let data = match headers.get(COPY_SOURCE).and_then(|v| v.to_str().ok()) {
+        // copy source is `/{bucket}/{key}`
+        Some(source) => {
+            let source = source
+                .trim_start_matches('/')
+                .split_once('/')
+                .map(|(_, key)| key)
+                .unwrap_or_default();
+
+            match objects.get(source) {
+                Some(data) => data.clone(),
+                None => return StatusCode::NOT_FOUND,
+            }
+        }
+        None => body.to_vec(),
+    };
+
+    objects.insert(key, data);
+    StatusCode::OK
+}
+
+async fn delete_object(
+    Path((_, key)): Path<(String, String)>,
+    State(objects): State<Objects>,
+    headers: HeaderMap,
+) -> StatusCode {
+    if !authorized(&headers) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    objects.lock().unwrap().remove(&key);
+    StatusCode::NO_CONTENT
+}
+
+async fn get_object(
+    Path((_, key)): Path<(String, String)>,
+    State(objects): State<Objects>,
+    headers: HeaderMap,
+) -> Response {
+    if !authorized(&headers) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let Some(data) = objects.lock().unwrap().get(&key).cloned() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let range = headers
+        .get(RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("bytes="))
+        .and_then(|v| v.split_once('-'))
+        .and_then(|(start, end)| Some((start.parse::<usize>().ok()?, end.parse::<usize>().ok()?)));
+
+    let (status, data) = match range {
+        Some((start, end)) => {
+            let end = (end + 1).min(data.len());
+            (StatusCode::PARTIAL_CONTENT, data[start.min(end)..end].to_vec())
+        }
+        None => (StatusCode::OK, data),
+    };
+
+    Response::builder()
+        .status(status)
+        .header(CONTENT_LENGTH, data.len())
+        .header(LAST_MODIFIED, LAST_MODIFIED_VALUE)
+        .body(Body::from(data))
+        .unwrap()
+}
+
+async fn list_objects(
+    Path(_): Path<String>,
+    State(objects): State<Objects>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    if !authorized(&headers) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let prefix = query.get("prefix").cloned().unwrap_or_default();
+    let delimiter = query.contains_key("delimiter");
+
+    let mut contents = String::new();
+    let mut prefixes = BTreeSet::new();
+
+    for (key, data) in objects.lock().unwrap().iter() {
+        let Some(rest) = key.strip_prefix(&prefix) else {
+            continue;
+        };
+
+        match rest.split_once('/') {
+            Some((folder, _)) if delimiter => {
+                prefixes.insert(format!("{prefix}{folder}/"));
+            }
+            _ => contents.push_str(&format!(
+                "<Contents><Key>{key}</Key><Size>{}</Size></Contents>",
+                data.len()
+            )),
+        }
+    }
+
+    let prefixes: String = prefixes
+        .iter()
+        .map(|p| format!("<CommonPrefixes><Prefix>{p}</Prefix></CommonPrefixes>"))
+        .collect();
+
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?><ListBucketResult><Prefix>{prefix}</Prefix><IsTruncated>false</IsTruncated>{contents}{prefixes}</ListBucketResult>"
+    );
+
+    xml.into_response()
+}
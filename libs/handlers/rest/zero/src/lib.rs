@@ -0,0 +1,168 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{
+    Router, async_trait,
+    body::Body,
+    extract::{ConnectInfo, DefaultBodyLimit, FromRef, FromRequestParts, Multipart, Path, State},
+    http::{HeaderMap, request::Parts},
+    response::Response,
+    routing::get,
+};
+use axum_macros::debug_handler;
+use ppd_bk::models::asset::AssetType;
+use ppd_fs::{
+    AssetBody,
+    free::{create_or_update, delete_asset as remove_asset, read_asset},
+    upload::TmpFile,
+};
+use ppd_shared::{opts::ServiceConfig, tools::mb_to_bytes};
+use ppdrive::{
+    prelude::state::HandlerState,
+    rest::{file_response, folder_response},
+};
+
+use crate::errors::ServerError;
+
+mod errors;
+
+/// prefix the zero router is mounted at.
+const ZERO_PREFIX: &str = "/zero";
+
+/// An extractor that rejects write requests when the service is read-only or the client's
+/// IP address is not in the write allow-list.
+pub struct WriteAccess;
+
+#[async_trait]
+impl<S> FromRequestParts<S> for WriteAccess
+where
+    HandlerState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ServerError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let state = HandlerState::from_ref(state);
+        let config = state.config();
+        let zero = &config.auth.zero;
+
+        if zero.read_only {
+            return Err(ServerError::PermissionDenied(
+                "this service is read-only".to_string(),
+            ));
+        }
+
+        if !zero.write_ips.is_empty() {
+            let ConnectInfo(addr) = ConnectInfo::<SocketAddr>::from_request_parts(parts, &state)
+                .await
+                .map_err(|err| ServerError::InternalError(err.to_string()))?;
+
+            if !zero.can_write(&addr.ip()) {
+                return Err(ServerError::PermissionDenied(format!(
+                    "{} is not allowed to write to this service",
+                    addr.ip()
+                )));
+            }
+        }
+
+        Ok(WriteAccess)
+    }
+}
+
+#[debug_handler]
+async fn get_asset(
+    Path((asset_type, asset_path)): Path<(AssetType, String)>,
+    State(state): State<HandlerState>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, ServerError> {
+    let config = state.config();
+    let asset_path = asset_path.trim_end_matches('/');
+    let body = read_asset(&config.auth.zero.root, &asset_type, asset_path, ZERO_PREFIX).await?;
+
+    match body {
+        AssetBody::File(file) => {
+            Ok(file_response(&headers, file, &config.base.cache_control).await?)
+        }
+        AssetBody::Folder(listing) => Ok(folder_response(&headers, &listing)?),
+    }
+}
+
+/// create a folder, or upload a file sent in the `file` field of a multipart body.
+#[debug_handler]
+async fn create_asset(
+    Path((asset_type, asset_path)): Path<(AssetType, String)>,
+    State(state): State<HandlerState>,
+    _: WriteAccess,
+    multipart: Option<Multipart>,
+) -> Result<String, ServerError> {
+    let config = state.config();
+    let max_upload = mb_to_bytes(config.base.max_upload_size) as u64;
+    let mut tmp_file = None;
+
+    if let (AssetType::File, Some(mut multipart)) = (&asset_type, multipart) {
+        while let Some(mut field) = multipart.next_field().await? {
+            if field.name() != Some("file") {
+                continue;
+            }
+
+            let mut tmp = TmpFile::create(max_upload).await?;
+            loop {
+                match field.chunk().await {
+                    Ok(Some(chunk)) => tmp.write_chunk(&chunk).await?,
+                    Ok(None) => break,
+                    Err(err) => {
+                        tmp.discard().await;
+                        return Err(err.into());
+                    }
+                }
+            }
+
+            let (path, _) = tmp.finish().await?;
+            tmp_file = Some(path);
+        }
+    }
+
+    if let AssetType::File = asset_type
+        && tmp_file.is_none()
+    {
+        return Err(ServerError::InternalError(
+            "'file' field is required".to_string(),
+        ));
+    }
+
+    create_or_update(&config.auth.zero.root, &asset_type, &asset_path, &tmp_file).await?;
+    Ok("operation successful!".to_string())
+}
+
+#[debug_handler]
+async fn delete_asset(
+    Path((asset_type, asset_path)): Path<(AssetType, String)>,
+    State(state): State<HandlerState>,
+    _: WriteAccess,
+) -> Result<String, ServerError> {
+    let config = state.config();
+    remove_asset(&config.auth.zero.root, &asset_type, &asset_path).await?;
+
+    Ok("operation successful".to_string())
+}
+
+/// Routes for services running without authentication.
+fn routes(config: Arc<ServiceConfig>) -> Router<HandlerState> {
+    let limit = mb_to_bytes(config.base.max_upload_size);
+
+    Router::new()
+        .route(
+            "/:asset_type/*asset_path",
+            get(get_asset).post(create_asset).delete(delete_asset),
+        )
+        .layer(DefaultBodyLimit::max(limit))
+}
+
+/// # Safety
+/// `config` must be a pointer obtained from `Arc::into_raw`. Ownership of the config is
+/// transferred to the router.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rest_zero(config: *const ServiceConfig) -> *mut Router<HandlerState> {
+    let config = unsafe { Arc::from_raw(config) };
+    let bx = Box::new(routes(config));
+    Box::into_raw(bx)
+}
@@ -0,0 +1,145 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{Router, http::StatusCode};
+use axum_test::{
+    TestServer,
+    multipart::{MultipartForm, Part},
+};
+use ppd_shared::opts::{ServiceConfig, ZeroModeConfig};
+use ppdrive::prelude::state::HandlerState;
+use rest_test_utils::TestApp;
+use rest_zero::rest_zero;
+use serial_test::serial;
+
+const ZERO_ROOT: &str = "zero-test-root";
+
+/// serve the zero router with the given write protections.
+async fn zero_server(app: &TestApp, zero: ZeroModeConfig) -> TestServer {
+    let mut config = ServiceConfig::default();
+    config.auth.zero = ZeroModeConfig {
+        root: ZERO_ROOT.to_string(),
+        ..zero
+    };
+
+    let state = HandlerState::new(&config, Arc::new(app.db.clone()))
+        .await
+        .expect("unable to create app state");
+
+    let router = unsafe { Box::from_raw(rest_zero(Arc::into_raw(config.into()))) };
+    let svc = Router::new()
+        .nest("/zero", *router)
+        .with_state(state)
+        .into_make_service_with_connect_info::<SocketAddr>();
+
+    TestServer::new(svc).expect("unable to create test server")
+}
+
+fn file_form() -> MultipartForm {
+    let file = include_bytes!("../../direct/tests/README.MD");
+    let part = Part::bytes(file.as_slice()).file_name("README.MD");
+
+    MultipartForm::new().add_part("file", part)
+}
+
+fn clean_up() {
+    if let Err(err) = std::fs::remove_dir_all(ZERO_ROOT) {
+        println!("{err}");
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rest_zero_assets() {
+    let app = TestApp::new().await;
+    let server = zero_server(&app, ZeroModeConfig::default()).await;
+
+    server
+        .post("/zero/File/docs/readme.md")
+        .multipart(file_form())
+        .await
+        .assert_status_ok();
+
+    let resp = server.get("/zero/File/docs/readme.md").await;
+    resp.assert_status_ok();
+    assert_eq!(
+        resp.as_bytes().as_ref(),
+        include_bytes!("../../direct/tests/README.MD")
+    );
+
+    let resp = server.get("/zero/Folder/docs").await;
+    resp.assert_status_ok();
+    assert!(resp.text().contains("/zero/File/docs/readme.md"));
+
+    server
+        .delete("/zero/Folder/docs")
+        .await
+        .assert_status_ok();
+
+    server
+        .get("/zero/File/docs/readme.md")
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+
+    clean_up();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rest_zero_rejects_paths_outside_root() {
+    let app = TestApp::new().await;
+    let server = zero_server(&app, ZeroModeConfig::default()).await;
+
+    server
+        .get("/zero/File/..%2FCargo.toml")
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+
+    clean_up();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rest_zero_read_only() {
+    let app = TestApp::new().await;
+    let zero = ZeroModeConfig {
+        read_only: true,
+        ..Default::default()
+    };
+
+    let server = zero_server(&app, zero).await;
+    server
+        .post("/zero/Folder/docs")
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+
+    clean_up();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rest_zero_write_ips() {
+    let app = TestApp::new().await;
+    let zero = ZeroModeConfig {
+        write_ips: vec!["10.0.0.1".parse().expect("invalid ip")],
+        ..Default::default()
+    };
+
+    let server = zero_server(&app, zero).await;
+    server
+        .post("/zero/Folder/docs")
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+
+    let zero = ZeroModeConfig {
+        write_ips: vec!["127.0.0.1".parse().expect("invalid ip")],
+        ..Default::default()
+    };
+
+    let server = zero_server(&app, zero).await;
+    server
+        .post("/zero/Folder/docs")
+        .await
+        .assert_status_ok();
+
+    clean_up();
+}
@@ -0,0 +1,12 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // use the vendored protoc so that protobuf doesn't need to be installed on the host
+    if std::env::var_os("PROTOC").is_none() {
+        let protoc = protoc_bin_vendored::protoc_bin_path()?;
+
+        // SAFETY: build scripts are single-threaded
+        unsafe { std::env::set_var("PROTOC", protoc) };
+    }
+
+    tonic_build::compile_protos("proto/ppdrive.proto")?;
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use ppd_shared::opts::ServiceConfig;
+use ppdrive::prelude::state::HandlerState;
+use tokio_stream::wrappers::TcpListenerStream;
+use tokio_util::sync::CancellationToken;
+use tonic::{
+    service::{Routes, RoutesBuilder},
+    transport::Server,
+};
+use tracing::info_span;
+
+use crate::{
+    assets::AssetHandler,
+    buckets::BucketHandler,
+    proto::{
+        asset_service_server::AssetServiceServer, bucket_service_server::BucketServiceServer,
+        user_service_server::UserServiceServer,
+    },
+    users::UserHandler,
+};
+
+/// User, bucket and asset services.
+pub fn routes(state: HandlerState) -> Routes {
+    let mut builder = RoutesBuilder::default();
+    builder
+        .add_service(UserServiceServer::new(UserHandler::new(state.clone())))
+        .add_service(BucketServiceServer::new(BucketHandler::new(state.clone())))
+        .add_service(AssetServiceServer::new(AssetHandler::new(state)));
+
+    builder.routes()
+}
+
+pub async fn serve_app(config: Arc<ServiceConfig>, state: HandlerState, token: CancellationToken) {
+    match tokio::net::TcpListener::bind(format!("0.0.0.0:{}", &config.base.port)).await {
+        Ok(listener) => {
+            if let Ok(addr) = listener.local_addr() {
+                tracing::info!("new grpc service listening on {addr}");
+            }
+
+            let serve = Server::builder()
+                .trace_fn(|request| info_span!("grpc_request", path = request.uri().path()))
+                .add_routes(routes(state))
+                .serve_with_incoming_shutdown(TcpListenerStream::new(listener), token.cancelled());
+
+            if let Err(err) = serve.await {
+                tracing::error!("grpc service error: {err}");
+            }
+        }
+        Err(err) => {
+            tracing::error!("Error starting listener: {err}");
+        }
+    }
+}
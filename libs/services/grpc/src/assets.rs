@@ -0,0 +1,313 @@
+use std::{pin::Pin, time::UNIX_EPOCH};
+
+use futures_util::{Stream, StreamExt, stream};
+use ppd_bk::models::{
+    asset::{AssetSharing, AssetType, Assets},
+    permission::Permission,
+};
+use ppd_fs::{
+    AssetBody,
+    auth::{create_or_update_asset, delete_asset as remove_asset},
+    opts::CreateAssetOptions,
+    read_asset,
+    upload::{TmpFile, upload_limit},
+};
+use ppd_shared::{
+    opts::ClientScope,
+    tools::{SECRETS_FILENAME, mb_to_bytes},
+};
+use ppdrive::{prelude::state::HandlerState, rest::extractors::BucketSizeValidator};
+use tokio_util::io::ReaderStream;
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::{
+    GrpcResult,
+    auth::ClientUser,
+    errors::GrpcError,
+    proto::{
+        self, AssetOptions, DeleteAssetRequest, DownloadRequest, DownloadResponse, Empty,
+        FileInfo, UploadRequest, UploadResponse, asset_service_server::AssetService,
+        download_response, upload_request,
+    },
+};
+
+/// size of chunks sent by [download].
+const CHUNK_SIZE: usize = 64 * 1024;
+
+pub type DownloadStream = Pin<Box<dyn Stream<Item = Result<DownloadResponse, Status>> + Send>>;
+
+impl From<proto::AssetType> for AssetType {
+    fn from(value: proto::AssetType) -> Self {
+        match value {
+            proto::AssetType::File => AssetType::File,
+            proto::AssetType::Folder => AssetType::Folder,
+        }
+    }
+}
+
+impl From<proto::Permission> for Permission {
+    fn from(value: proto::Permission) -> Self {
+        match value {
+            proto::Permission::Create => Permission::Create,
+            proto::Permission::Read => Permission::Read,
+            proto::Permission::Update => Permission::Update,
+            proto::Permission::Delete => Permission::Delete,
+        }
+    }
+}
+
+fn asset_type(value: i32) -> GrpcResult<AssetType> {
+    proto::AssetType::try_from(value)
+        .map(AssetType::from)
+        .map_err(|err| GrpcError::InvalidArgument(err.to_string()))
+}
+
+impl TryFrom<AssetOptions> for CreateAssetOptions {
+    type Error = GrpcError;
+
+    fn try_from(value: AssetOptions) -> Result<Self, Self::Error> {
+        let sharing = value
+            .sharing
+            .into_iter()
+            .map(|share| {
+                let permissions = share
+                    .permissions
+                    .into_iter()
+                    .map(|p| proto::Permission::try_from(p).map(Permission::from))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|err| GrpcError::InvalidArgument(err.to_string()))?;
+
+                Ok(AssetSharing {
+                    user_id: share.user_id,
+                    permissions,
+                })
+            })
+            .collect::<GrpcResult<Vec<_>>>()?;
+
+        Ok(CreateAssetOptions {
+            asset_path: value.asset_path,
+            asset_type: asset_type(value.asset_type)?,
+            bucket: value.bucket,
+            public: value.public,
+            custom_path: value.custom_path,
+            update_asset_path: value.update_asset_path,
+            create_parents: value.create_parents,
+            sharing: (!sharing.is_empty()).then_some(sharing),
+        })
+    }
+}
+
+fn validate_asset_path(asset_path: &str) -> GrpcResult<()> {
+    if asset_path.is_empty() {
+        return Err(GrpcError::InvalidArgument(
+            "asset_path field is required".to_string(),
+        ));
+    }
+
+    if asset_path == SECRETS_FILENAME {
+        return Err(GrpcError::PermissionDenied(format!(
+            "asset_path '{SECRETS_FILENAME}' is not allowed"
+        )));
+    }
+
+    Ok(())
+}
+
+/// receive file chunks into a temporary file, aborting as soon as bucket or upload limit
+/// is exceeded.
+async fn receive_file(
+    state: &HandlerState,
+    bucket: &str,
+    stream: &mut Streaming<UploadRequest>,
+) -> GrpcResult<TmpFile> {
+    let max_upload = mb_to_bytes(state.config().base.max_upload_size) as u64;
+    let limit = upload_limit(state.db(), bucket, max_upload).await?;
+    let mut tmp = TmpFile::create(limit).await?;
+
+    loop {
+        let data = match stream.message().await {
+            Ok(Some(message)) => message.data,
+            Ok(None) => break,
+            Err(err) => {
+                tmp.discard().await;
+                return Err(err.into());
+            }
+        };
+
+        match data {
+            Some(upload_request::Data::Chunk(chunk)) => tmp.write_chunk(&chunk).await?,
+            _ => {
+                tmp.discard().await;
+                return Err(GrpcError::InvalidArgument(
+                    "only the first upload message can contain asset options".to_string(),
+                ));
+            }
+        }
+    }
+
+    Ok(tmp)
+}
+
+pub async fn upload(
+    state: &HandlerState,
+    request: Request<Streaming<UploadRequest>>,
+) -> GrpcResult<UploadResponse> {
+    let db = state.db();
+    let user = ClientUser::from_metadata(state, request.metadata()).await?;
+    user.require(ClientScope::AssetsWrite)?;
+    let mut stream = request.into_inner();
+
+    let opts = match stream.message().await? {
+        Some(UploadRequest {
+            data: Some(upload_request::Data::Options(opts)),
+        }) => CreateAssetOptions::try_from(opts)?,
+        _ => {
+            return Err(GrpcError::InvalidArgument(
+                "the first upload message must contain asset options".to_string(),
+            ));
+        }
+    };
+
+    validate_asset_path(&opts.asset_path)?;
+
+    let mut tmp_file = None;
+    let mut filesize = None;
+
+    if let AssetType::File = opts.asset_type {
+        let tmp = receive_file(state, &opts.bucket, &mut stream).await?;
+        let (path, size) = tmp.finish().await?;
+
+        filesize = Some(size);
+        tmp_file = Some(path);
+    }
+
+    create_or_update_asset(db, user.id(), &opts, &tmp_file, &filesize).await?;
+
+    Ok(UploadResponse {
+        size: filesize.unwrap_or_default(),
+    })
+}
+
+// tonic streams yield `Status` errors
+#[allow(clippy::result_large_err)]
+pub async fn download(
+    state: &HandlerState,
+    request: Request<DownloadRequest>,
+) -> GrpcResult<DownloadStream> {
+    let db = state.db();
+    let user = ClientUser::optional(state, request.metadata(), ClientScope::AssetsRead).await?;
+
+    let DownloadRequest {
+        asset_path,
+        offset,
+        length,
+    } = request.into_inner();
+
+    if asset_path == SECRETS_FILENAME {
+        return Err(GrpcError::PermissionDenied("access denied".to_string()));
+    }
+
+    let user_id = user.map(|u| *u.id());
+    let file = match read_asset(db, &asset_path, &AssetType::File, &user_id, &Default::default()).await? {
+        AssetBody::File(file) => file,
+        AssetBody::Folder(_) => {
+            return Err(GrpcError::InvalidArgument(
+                "only files can be downloaded".to_string(),
+            ));
+        }
+    };
+
+    let size = *file.size();
+    let start = offset.unwrap_or_default();
+    if start > size {
+        return Err(GrpcError::OutOfRange(format!(
+            "offset {start} is beyond the file size {size}"
+        )));
+    }
+
+    let len = length.map_or(size - start, |len| len.min(size - start));
+    let reader = file.range_reader(start, len).await?;
+
+    let info = FileInfo {
+        mime: file.mime().to_string(),
+        size,
+        etag: file.etag(),
+        modified: file
+            .modified()
+            .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64),
+    };
+
+    let info = DownloadResponse {
+        data: Some(download_response::Data::Info(info)),
+    };
+
+    let chunks = ReaderStream::with_capacity(reader, CHUNK_SIZE).map(|chunk| match chunk {
+        Ok(chunk) => Ok(DownloadResponse {
+            data: Some(download_response::Data::Chunk(chunk.to_vec())),
+        }),
+        Err(err) => Err(Status::internal(err.to_string())),
+    });
+
+    Ok(Box::pin(stream::once(async { Ok(info) }).chain(chunks)))
+}
+
+pub async fn delete_asset(
+    state: &HandlerState,
+    request: Request<DeleteAssetRequest>,
+) -> GrpcResult<Empty> {
+    let db = state.db();
+    let user = ClientUser::from_metadata(state, request.metadata()).await?;
+    user.require(ClientScope::AssetsWrite)?;
+
+    let DeleteAssetRequest {
+        asset_path,
+        asset_type,
+    } = request.into_inner();
+
+    let asset_type = self::asset_type(asset_type)?;
+    let asset = Assets::get_by_path(db, &asset_path, &asset_type).await?;
+
+    if asset.user_id() != user.id() {
+        return Err(GrpcError::PermissionDenied("permission denied".to_string()));
+    }
+
+    remove_asset(db, &asset_path, &asset_type).await?;
+    Ok(Empty {})
+}
+
+pub struct AssetHandler {
+    state: HandlerState,
+}
+
+impl AssetHandler {
+    pub fn new(state: HandlerState) -> Self {
+        Self { state }
+    }
+}
+
+#[tonic::async_trait]
+impl AssetService for AssetHandler {
+    async fn upload(
+        &self,
+        request: Request<Streaming<UploadRequest>>,
+    ) -> Result<Response<UploadResponse>, Status> {
+        Ok(Response::new(upload(&self.state, request).await?))
+    }
+
+    type DownloadStream = DownloadStream;
+
+    async fn download(
+        &self,
+        request: Request<DownloadRequest>,
+    ) -> Result<Response<Self::DownloadStream>, Status> {
+        Ok(Response::new(download(&self.state, request).await?))
+    }
+
+    async fn delete_asset(
+        &self,
+        request: Request<DeleteAssetRequest>,
+    ) -> Result<Response<Empty>, Status> {
+        Ok(Response::new(delete_asset(&self.state, request).await?))
+    }
+}
@@ -0,0 +1,150 @@
+use std::ops::Deref;
+
+use ppd_bk::{
+    RBatis,
+    models::{bucket::Buckets, user::Users},
+};
+use ppd_shared::opts::ClientScope;
+use ppdrive::{
+    HandlerResult,
+    prelude::state::HandlerState,
+    rest::extractors::BucketSizeValidator,
+    tools::{check_scope, verify_client},
+};
+use tonic::metadata::MetadataMap;
+
+use crate::{GrpcResult, errors::GrpcError};
+
+pub const CLIENT_TOKEN_KEY: &str = "ppd-client-token";
+pub const CLIENT_USER_KEY: &str = "ppd-client-user";
+
+/// A client authenticated with the `ppd-client-token` metadata. This is the gRPC
+/// equivalent of `ClientExtractor`.
+pub struct Client {
+    id: u64,
+    max_bucket_size: Option<f64>,
+    scopes: Vec<ClientScope>,
+}
+
+impl Client {
+    pub async fn from_metadata(state: &HandlerState, metadata: &MetadataMap) -> GrpcResult<Self> {
+        let token = metadata_value(metadata, CLIENT_TOKEN_KEY)?;
+        let secrets = state.secrets();
+
+        let client = verify_client(state.db(), secrets.deref(), token)
+            .await
+            .map_err(|err| GrpcError::Unauthenticated(err.to_string()))?;
+
+        Ok(Client {
+            id: client.id,
+            max_bucket_size: client.max_bucket_size,
+            scopes: client.scopes,
+        })
+    }
+
+    /// reject the request if the client key used doesn't grant `scope`.
+    pub fn require(&self, scope: ClientScope) -> GrpcResult<()> {
+        check_scope(&self.scopes, scope)?;
+        Ok(())
+    }
+}
+
+impl BucketSizeValidator for Client {
+    fn id(&self) -> &u64 {
+        &self.id
+    }
+
+    fn max_bucket_size(&self) -> &Option<f64> {
+        &self.max_bucket_size
+    }
+
+    async fn current_size(&self, db: &RBatis) -> HandlerResult<f64> {
+        let size = Buckets::client_total_bucket_size(db, self.id()).await?;
+        Ok(size)
+    }
+}
+
+/// A user created by the client, specified with the `ppd-client-user` metadata. This is the
+/// gRPC equivalent of `ClientUserExtractor`.
+pub struct ClientUser {
+    id: u64,
+    max_bucket_size: Option<f64>,
+    scopes: Vec<ClientScope>,
+}
+
+impl ClientUser {
+    pub async fn from_metadata(state: &HandlerState, metadata: &MetadataMap) -> GrpcResult<Self> {
+        let user_id = metadata_value(metadata, CLIENT_USER_KEY)?;
+        let client = Client::from_metadata(state, metadata).await?;
+
+        Self::get(state.db(), user_id, &client).await
+    }
+
+    /// like [ClientUser::from_metadata], but returns `None` if `ppd-client-user` is not
+    /// provided. The client is always authenticated and its key must grant `scope`.
+    pub async fn optional(
+        state: &HandlerState,
+        metadata: &MetadataMap,
+        scope: ClientScope,
+    ) -> GrpcResult<Option<Self>> {
+        let client = Client::from_metadata(state, metadata).await?;
+        client.require(scope)?;
+
+        match metadata.get(CLIENT_USER_KEY) {
+            Some(_) => {
+                let user_id = metadata_value(metadata, CLIENT_USER_KEY)?;
+                let user = Self::get(state.db(), user_id, &client).await?;
+
+                Ok(Some(user))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn get(db: &RBatis, user_id: &str, client: &Client) -> GrpcResult<Self> {
+        let user = Users::get_for_client(db, user_id, client.id())
+            .await
+            .map_err(|err| GrpcError::Unauthenticated(err.to_string()))?
+            .ok_or(GrpcError::Unauthenticated(
+                "user with provided id does not exist or may not be accessible by client"
+                    .to_string(),
+            ))?;
+
+        Ok(ClientUser {
+            id: user.id(),
+            max_bucket_size: *user.max_bucket_size(),
+            scopes: client.scopes.clone(),
+        })
+    }
+
+    /// reject the request if the client key used doesn't grant `scope`.
+    pub fn require(&self, scope: ClientScope) -> GrpcResult<()> {
+        check_scope(&self.scopes, scope)?;
+        Ok(())
+    }
+}
+
+impl BucketSizeValidator for ClientUser {
+    fn id(&self) -> &u64 {
+        &self.id
+    }
+
+    fn max_bucket_size(&self) -> &Option<f64> {
+        &self.max_bucket_size
+    }
+
+    async fn current_size(&self, db: &RBatis) -> HandlerResult<f64> {
+        let size = Buckets::user_total_bucket_size(db, self.id()).await?;
+        Ok(size)
+    }
+}
+
+fn metadata_value<'a>(metadata: &'a MetadataMap, key: &str) -> GrpcResult<&'a str> {
+    metadata
+        .get(key)
+        .ok_or(GrpcError::Unauthenticated(format!(
+            "missing '{key}' in metadata"
+        )))?
+        .to_str()
+        .map_err(|err| GrpcError::Unauthenticated(err.to_string()))
+}
@@ -0,0 +1,111 @@
+use ppd_bk::models::bucket::Buckets;
+use ppd_fs::storage::Storage;
+use ppd_shared::{
+    api::{BucketBackend, CreateBucketOptions},
+    opts::ClientScope,
+    tools::SECRETS_FILENAME,
+};
+use ppdrive::{prelude::state::HandlerState, rest::extractors::BucketSizeValidator};
+use tonic::{Request, Response, Status};
+
+use crate::{
+    GrpcResult,
+    auth::{Client, ClientUser},
+    errors::GrpcError,
+    proto::{self, CreateBucketRequest, CreateBucketResponse, bucket_service_server::BucketService},
+};
+
+impl TryFrom<CreateBucketRequest> for CreateBucketOptions {
+    type Error = GrpcError;
+
+    fn try_from(value: CreateBucketRequest) -> Result<Self, Self::Error> {
+        let backend = match value.backend.map(proto::BucketBackend::try_from) {
+            Some(Ok(backend)) => Some(match backend {
+                proto::BucketBackend::Local => BucketBackend::Local,
+                proto::BucketBackend::Memory => BucketBackend::Memory,
+                proto::BucketBackend::S3 => BucketBackend::S3,
+            }),
+            Some(Err(err)) => return Err(GrpcError::InvalidArgument(err.to_string())),
+            None => None,
+        };
+
+        Ok(CreateBucketOptions {
+            partition: value.partition,
+            partition_size: value.partition_size,
+            accepts: value.accepts,
+            label: value.label,
+            public: value.public,
+            backend,
+            versioning: None,
+            max_versions: None,
+        })
+    }
+}
+
+pub async fn create_bucket(
+    state: &HandlerState,
+    request: Request<CreateBucketRequest>,
+) -> GrpcResult<CreateBucketResponse> {
+    let db = state.db();
+    let client = Client::from_metadata(state, request.metadata()).await?;
+    client.require(ClientScope::BucketsCreate)?;
+    let data = CreateBucketOptions::try_from(request.into_inner())?;
+
+    client.validate_bucket_size(db, &data.partition_size).await?;
+    if let Some(partition) = &data.partition
+        && partition == SECRETS_FILENAME
+    {
+        return Err(GrpcError::PermissionDenied(format!(
+            "partition name {SECRETS_FILENAME} is not allowed"
+        )));
+    }
+
+    Storage::validate_backend(&data.backend)?;
+    let id = Buckets::create_by_client(db, data, *client.id()).await?;
+
+    Ok(CreateBucketResponse { id })
+}
+
+pub async fn create_user_bucket(
+    state: &HandlerState,
+    request: Request<CreateBucketRequest>,
+) -> GrpcResult<CreateBucketResponse> {
+    let db = state.db();
+    let user = ClientUser::from_metadata(state, request.metadata()).await?;
+    user.require(ClientScope::BucketsCreate)?;
+    let data = CreateBucketOptions::try_from(request.into_inner())?;
+
+    user.validate_bucket_size(db, &data.partition_size).await?;
+    Storage::validate_backend(&data.backend)?;
+
+    let id = Buckets::create_by_user(db, data, *user.id()).await?;
+
+    Ok(CreateBucketResponse { id })
+}
+
+pub struct BucketHandler {
+    state: HandlerState,
+}
+
+impl BucketHandler {
+    pub fn new(state: HandlerState) -> Self {
+        Self { state }
+    }
+}
+
+#[tonic::async_trait]
+impl BucketService for BucketHandler {
+    async fn create_bucket(
+        &self,
+        request: Request<CreateBucketRequest>,
+    ) -> Result<Response<CreateBucketResponse>, Status> {
+        Ok(Response::new(create_bucket(&self.state, request).await?))
+    }
+
+    async fn create_user_bucket(
+        &self,
+        request: Request<CreateBucketRequest>,
+    ) -> Result<Response<CreateBucketResponse>, Status> {
+        Ok(Response::new(create_user_bucket(&self.state, request).await?))
+    }
+}
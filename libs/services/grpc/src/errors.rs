@@ -0,0 +1,86 @@
+use std::fmt::Display;
+
+use ppd_bk::Error as DBError;
+use ppd_fs::errors::Error as FsError;
+use ppdrive::errors::HandlerError;
+use tonic::Status;
+
+#[derive(Debug)]
+pub enum GrpcError {
+    InvalidArgument(String),
+    Unauthenticated(String),
+    PermissionDenied(String),
+    NotFound(String),
+    OutOfRange(String),
+    InternalError(String),
+}
+
+impl Display for GrpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GrpcError::InvalidArgument(msg) => write!(f, "{msg}"),
+            GrpcError::Unauthenticated(msg) => write!(f, "{msg}"),
+            GrpcError::PermissionDenied(msg) => write!(f, "{msg}"),
+            GrpcError::NotFound(msg) => write!(f, "{msg}"),
+            GrpcError::OutOfRange(msg) => write!(f, "{msg}"),
+            GrpcError::InternalError(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+impl From<FsError> for GrpcError {
+    fn from(value: FsError) -> Self {
+        match value {
+            FsError::NotFound(msg) => GrpcError::NotFound(msg),
+            FsError::PermissionError(msg) => GrpcError::PermissionDenied(msg),
+            _ => GrpcError::InternalError(value.to_string()),
+        }
+    }
+}
+
+impl From<DBError> for GrpcError {
+    fn from(value: DBError) -> Self {
+        match value {
+            DBError::NotFound(msg) => GrpcError::NotFound(msg),
+            DBError::PermissionError(msg) => GrpcError::PermissionDenied(msg),
+            _ => GrpcError::InternalError(value.to_string()),
+        }
+    }
+}
+
+impl From<HandlerError> for GrpcError {
+    fn from(value: HandlerError) -> Self {
+        match value {
+            HandlerError::AuthorizationError(msg) => GrpcError::Unauthenticated(msg),
+            HandlerError::PermissionError(msg) => GrpcError::PermissionDenied(msg),
+            HandlerError::NotFound(msg) => GrpcError::NotFound(msg),
+            HandlerError::BadRequest(msg) => GrpcError::InvalidArgument(msg),
+            _ => GrpcError::InternalError(value.to_string()),
+        }
+    }
+}
+
+impl From<std::io::Error> for GrpcError {
+    fn from(value: std::io::Error) -> Self {
+        GrpcError::InternalError(value.to_string())
+    }
+}
+
+impl From<Status> for GrpcError {
+    fn from(value: Status) -> Self {
+        GrpcError::InternalError(value.message().to_string())
+    }
+}
+
+impl From<GrpcError> for Status {
+    fn from(value: GrpcError) -> Self {
+        match value {
+            GrpcError::InvalidArgument(msg) => Status::invalid_argument(msg),
+            GrpcError::Unauthenticated(msg) => Status::unauthenticated(msg),
+            GrpcError::PermissionDenied(msg) => Status::permission_denied(msg),
+            GrpcError::NotFound(msg) => Status::not_found(msg),
+            GrpcError::OutOfRange(msg) => Status::out_of_range(msg),
+            GrpcError::InternalError(msg) => Status::internal(msg),
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use std::sync::Arc;
+
+use crate::app::serve_app;
+use errors::GrpcError;
+use ppd_bk::RBatis;
+use ppd_shared::{opts::ServiceConfig, start_logger, tools::init_secrets};
+use ppdrive::prelude::state::HandlerState;
+use tokio::runtime::Runtime;
+use tokio_util::sync::CancellationToken;
+
+pub use app::routes;
+pub use auth::{CLIENT_TOKEN_KEY, CLIENT_USER_KEY};
+
+mod app;
+mod assets;
+mod auth;
+mod buckets;
+mod errors;
+mod users;
+
+/// Types and clients generated from `proto/ppdrive.proto`.
+pub mod proto {
+    tonic::include_proto!("ppdrive");
+}
+
+pub type GrpcResult<T> = Result<T, GrpcError>;
+
+#[unsafe(no_mangle)]
+pub fn ppd_grpc(config: Arc<ServiceConfig>, db: Arc<RBatis>, token: CancellationToken) {
+    if let Ok(rt) = Runtime::new() {
+        rt.block_on(async {
+            let _guard =
+                start_logger("ppd_grpc=debug,tonic=debug").expect("unable to start logger");
+
+            if let Err(err) = init_secrets().await {
+                tracing::error!("unable to initialize secrets: {err}");
+            }
+
+            match HandlerState::new(&config, db).await {
+                Ok(state) => serve_app(config, state, token).await,
+                Err(err) => tracing::error!("unable to create app state: {err}"),
+            }
+        })
+    }
+}
@@ -0,0 +1,161 @@
+use ppd_bk::models::{
+    IntoSerializer,
+    user::{UserRole, Users},
+};
+use ppd_shared::opts::ClientScope;
+use ppdrive::{
+    jwt::LoginOpts, prelude::state::HandlerState, rest::extractors::BucketSizeValidator,
+};
+use tonic::{Request, Response, Status};
+
+use crate::{
+    GrpcResult,
+    auth::{Client, ClientUser},
+    errors::GrpcError,
+    proto::{
+        self, CreateUserRequest, CreateUserResponse, DeleteUserRequest, Empty, LoginTokens,
+        LoginUserRequest, Token, User, user_service_server::UserService,
+    },
+};
+
+pub async fn create_user(
+    state: &HandlerState,
+    request: Request<CreateUserRequest>,
+) -> GrpcResult<CreateUserResponse> {
+    let db = state.db();
+    let client = Client::from_metadata(state, request.metadata()).await?;
+    client.require(ClientScope::UsersWrite)?;
+
+    let data = request.into_inner();
+    let id = Users::create_by_client(db, *client.id(), data.max_bucket).await?;
+
+    Ok(CreateUserResponse { id })
+}
+
+pub async fn login_user(
+    state: &HandlerState,
+    request: Request<LoginUserRequest>,
+) -> GrpcResult<LoginTokens> {
+    Client::from_metadata(state, request.metadata())
+        .await?
+        .require(ClientScope::UsersWrite)?;
+
+    let LoginUserRequest {
+        id,
+        access_exp,
+        refresh_exp,
+    } = request.into_inner();
+
+    let db = state.db();
+    let config = state.config();
+    let secrets = state.secrets();
+
+    let user = Users::get_by_pid(db, &id).await?;
+    let login = LoginOpts {
+        user_id: &user.id(),
+        config: &config,
+        secrets: &secrets,
+        access_exp,
+        refresh_exp,
+        user_max_bucket: *user.max_bucket_size(),
+    };
+
+    let tokens = login.tokens(db).await?;
+    let token = |(token, expires)| Token { token, expires };
+
+    Ok(LoginTokens {
+        access: tokens.access.map(token),
+        refresh: tokens.refresh.map(token),
+    })
+}
+
+pub async fn delete_user(
+    state: &HandlerState,
+    request: Request<DeleteUserRequest>,
+) -> GrpcResult<Empty> {
+    let db = state.db();
+    let client = Client::from_metadata(state, request.metadata()).await?;
+    client.require(ClientScope::UsersWrite)?;
+
+    let id = request.into_inner().id;
+    let user = Users::get_by_pid(db, &id).await?;
+
+    if let Some(client_id) = user.client_id()
+        && client_id != client.id()
+    {
+        return Err(GrpcError::PermissionDenied(
+            "client cannot delete this user".to_string(),
+        ));
+    }
+
+    match user.role()? {
+        UserRole::Admin => Err(GrpcError::PermissionDenied(
+            "client cannot delete admin".to_string(),
+        )),
+        _ => {
+            user.delete(db).await?;
+            Ok(Empty {})
+        }
+    }
+}
+
+pub async fn get_user(state: &HandlerState, request: Request<Empty>) -> GrpcResult<User> {
+    let db = state.db();
+    let user = ClientUser::from_metadata(state, request.metadata()).await?;
+    user.require(ClientScope::UsersRead)?;
+
+    let user = Users::get(db, user.id()).await?;
+    let data = user.into_serializer(db).await?;
+
+    let role = match data.role() {
+        UserRole::General => proto::UserRole::General,
+        UserRole::Admin => proto::UserRole::Admin,
+        UserRole::Service => proto::UserRole::Service,
+    };
+
+    Ok(User {
+        id: data.id().to_string(),
+        email: data.email().clone(),
+        role: role.into(),
+        created_at: data.created_at().to_string(),
+        max_bucket: *data.max_bucket(),
+    })
+}
+
+pub struct UserHandler {
+    state: HandlerState,
+}
+
+impl UserHandler {
+    pub fn new(state: HandlerState) -> Self {
+        Self { state }
+    }
+}
+
+#[tonic::async_trait]
+impl UserService for UserHandler {
+    async fn create_user(
+        &self,
+        request: Request<CreateUserRequest>,
+    ) -> Result<Response<CreateUserResponse>, Status> {
+        Ok(Response::new(create_user(&self.state, request).await?))
+    }
+
+    async fn login_user(
+        &self,
+        request: Request<LoginUserRequest>,
+    ) -> Result<Response<LoginTokens>, Status> {
+        Ok(Response::new(login_user(&self.state, request).await?))
+    }
+
+    async fn delete_user(
+        &self,
+        request: Request<DeleteUserRequest>,
+    ) -> Result<Response<Empty>, Status> {
+        Ok(Response::new(delete_user(&self.state, request).await?))
+    }
+
+    async fn get_user(&self, request: Request<Empty>) -> Result<Response<User>, Status> {
+        Ok(Response::new(get_user(&self.state, request).await?))
+    }
+}
@@ -0,0 +1,223 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use futures_util::stream;
+use ppd_grpc::{
+    CLIENT_TOKEN_KEY, CLIENT_USER_KEY,
+    proto::{
+        AssetOptions, AssetType, CreateBucketRequest, CreateUserRequest, DeleteAssetRequest,
+        DownloadRequest, Empty, UploadRequest, asset_service_client::AssetServiceClient,
+        bucket_service_client::BucketServiceClient, download_response,
+        upload_request::Data, user_service_client::UserServiceClient,
+    },
+    routes,
+};
+use ppd_shared::opts::ServiceConfig;
+use ppdrive::prelude::state::HandlerState;
+use rest_test_utils::TestApp;
+use serial_test::serial;
+use tokio_stream::{StreamExt, wrappers::TcpListenerStream};
+use tonic::{Code, Request, transport::{Channel, Server}};
+
+const BUCKET_PARTITION: &str = "grpc-test-bucket";
+
+/// serve the grpc services on a random port.
+async fn start_server(app: &TestApp) -> SocketAddr {
+    let state = HandlerState::new(&ServiceConfig::default(), Arc::new(app.db.clone()))
+        .await
+        .expect("unable to create app state");
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("unable to bind listener");
+    let addr = listener.local_addr().expect("unable to get address");
+
+    tokio::spawn(
+        Server::builder()
+            .add_routes(routes(state))
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+
+    addr
+}
+
+async fn channel(addr: SocketAddr) -> Channel {
+    Channel::from_shared(format!("http://{addr}"))
+        .expect("invalid address")
+        .connect()
+        .await
+        .expect("unable to connect")
+}
+
+fn with_auth<T>(message: T, token: &str, user: Option<&str>) -> Request<T> {
+    let mut request = Request::new(message);
+    let metadata = request.metadata_mut();
+
+    metadata.insert(CLIENT_TOKEN_KEY, token.parse().expect("invalid token"));
+    if let Some(user) = user {
+        metadata.insert(CLIENT_USER_KEY, user.parse().expect("invalid user id"));
+    }
+
+    request
+}
+
+#[tokio::test]
+#[serial]
+async fn test_grpc_assets() {
+    let app = TestApp::new().await;
+    let token = app.client_token().await;
+    let channel = channel(start_server(&app).await).await;
+
+    let mut users = UserServiceClient::new(channel.clone());
+    let mut buckets = BucketServiceClient::new(channel.clone());
+    let mut assets = AssetServiceClient::new(channel);
+
+    let user_id = users
+        .create_user(with_auth(CreateUserRequest { max_bucket: None }, &token, None))
+        .await
+        .expect("unable to create user")
+        .into_inner()
+        .id;
+
+    let user = users
+        .get_user(with_auth(Empty {}, &token, Some(&user_id)))
+        .await
+        .expect("unable to get user")
+        .into_inner();
+    assert_eq!(user.id, user_id);
+
+    let bucket = CreateBucketRequest {
+        label: "gRPC Test Bucket".to_string(),
+        partition: Some(BUCKET_PARTITION.to_string()),
+        ..Default::default()
+    };
+
+    let bucket_id = buckets
+        .create_user_bucket(with_auth(bucket, &token, Some(&user_id)))
+        .await
+        .expect("unable to create bucket")
+        .into_inner()
+        .id;
+
+    // upload a file in chunks
+    let file_bytes = include_bytes!("../../../handlers/rest/direct/tests/README.MD");
+    let asset_path = "grpc-docs/readme.md".to_string();
+
+    let options = AssetOptions {
+        asset_path: asset_path.clone(),
+        asset_type: AssetType::File.into(),
+        bucket: bucket_id.clone(),
+        create_parents: Some(true),
+        ..Default::default()
+    };
+
+    let mut messages = vec![UploadRequest {
+        data: Some(Data::Options(options)),
+    }];
+
+    messages.extend(file_bytes.chunks(100).map(|chunk| UploadRequest {
+        data: Some(Data::Chunk(chunk.to_vec())),
+    }));
+
+    let resp = assets
+        .upload(with_auth(stream::iter(messages), &token, Some(&user_id)))
+        .await
+        .expect("unable to upload file")
+        .into_inner();
+    assert_eq!(resp.size, file_bytes.len() as u64);
+
+    // download a range of the file
+    let request = DownloadRequest {
+        asset_path: asset_path.clone(),
+        offset: Some(10),
+        length: Some(20),
+    };
+
+    let mut download = assets
+        .download(with_auth(request, &token, Some(&user_id)))
+        .await
+        .expect("unable to download file")
+        .into_inner();
+
+    let mut content = Vec::new();
+    while let Some(message) = download.next().await {
+        match message.expect("download failed").data {
+            Some(download_response::Data::Info(info)) => {
+                assert_eq!(info.size, file_bytes.len() as u64)
+            }
+            Some(download_response::Data::Chunk(chunk)) => content.extend(chunk),
+            None => {}
+        }
+    }
+    assert_eq!(content.as_slice(), &file_bytes[10..30]);
+
+    // private assets can't be read without user
+    let request = DownloadRequest {
+        asset_path: asset_path.clone(),
+        ..Default::default()
+    };
+
+    let status = assets
+        .download(with_auth(request, &token, None))
+        .await
+        .expect_err("private asset should not be readable");
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    // reserved folders can't be written to, even through `.` segments
+    for reserved in [".ppdrive_trash/readme.md", "./.ppdrive_versions/1/readme.md"] {
+        let options = AssetOptions {
+            asset_path: reserved.to_string(),
+            asset_type: AssetType::File.into(),
+            bucket: bucket_id.clone(),
+            ..Default::default()
+        };
+
+        let messages = vec![
+            UploadRequest {
+                data: Some(Data::Options(options)),
+            },
+            UploadRequest {
+                data: Some(Data::Chunk(file_bytes.to_vec())),
+            },
+        ];
+
+        let status = assets
+            .upload(with_auth(stream::iter(messages), &token, Some(&user_id)))
+            .await
+            .expect_err("reserved folder should not be writable");
+        assert_eq!(status.code(), Code::PermissionDenied);
+    }
+
+    let request = DeleteAssetRequest {
+        asset_path: asset_path.clone(),
+        asset_type: AssetType::File.into(),
+    };
+
+    assets
+        .delete_asset(with_auth(request, &token, Some(&user_id)))
+        .await
+        .expect("unable to delete asset");
+
+    if let Err(err) = std::fs::remove_dir_all(BUCKET_PARTITION) {
+        println!("{err}");
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_grpc_unauthenticated() {
+    let app = TestApp::new().await;
+    let channel = channel(start_server(&app).await).await;
+    let mut users = UserServiceClient::new(channel);
+
+    let status = users
+        .create_user(CreateUserRequest { max_bucket: None })
+        .await
+        .expect_err("request without client token should fail");
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let status = users
+        .create_user(with_auth(CreateUserRequest { max_bucket: None }, "invalid-token", None))
+        .await
+        .expect_err("request with invalid client token should fail");
+    assert_eq!(status.code(), Code::Unauthenticated);
+}
@@ -6,6 +6,7 @@ use axum::{extract::MatchedPath, http::Request, routing::get, Router};
 use ppdrive::plugin::router::Routers;
 use ppd_shared::opts::ServiceConfig;
 use std::env::set_var;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio_util::sync::CancellationToken;
 use tower_http::cors::{AllowOrigin, Any};
@@ -16,7 +17,7 @@ use crate::ServerResult;
 use ppdrive::{
     jwt::{BEARER_KEY, BEARER_VALUE},
     prelude::state::HandlerState,
-    rest::get_asset,
+    rest::{extractors::UPLOAD_OFFSET_HEADER, get_asset},
 };
 
 fn to_origins(origins: &Option<Vec<String>>) -> AllowOrigin {
@@ -54,6 +55,7 @@ pub async fn serve_app(
             CONTENT_TYPE,
             AUTHORIZATION,
             HeaderName::from_static("ppd-client-token"),
+            HeaderName::from_static(UPLOAD_OFFSET_HEADER),
         ])
         .allow_methods(Any);
 
@@ -64,6 +66,8 @@ pub async fn serve_app(
         .route("/:asset_type/*asset_path", get(get_asset))
         .nest("/client", routers.client())
         .nest("/direct", routers.direct())
+        .nest("/admin", routers.admin())
+        .nest("/zero", routers.zero())
         .layer(
             TraceLayer::new_for_http().make_span_with(|request: &Request<_>| {
                 let matched_path = request
@@ -81,7 +85,7 @@ pub async fn serve_app(
         )
         .layer(cors)
         .with_state(state)
-        .into_make_service();
+        .into_make_service_with_connect_info::<SocketAddr>();
 
     match tokio::net::TcpListener::bind(format!("0.0.0.0:{}", &config.base.port)).await {
         Ok(listener) => {
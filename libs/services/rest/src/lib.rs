@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use crate::app::serve_app;
+use crate::{app::serve_app, trash::purge_trash};
 use errors::ServerError;
 use ppd_bk::RBatis;
 use ppdrive::prelude::state::HandlerState;
@@ -10,6 +10,7 @@ use tokio_util::sync::CancellationToken;
 
 mod app;
 mod errors;
+mod trash;
 pub type ServerResult<T> = Result<T, ServerError>;
 
 #[no_mangle]
@@ -25,6 +26,8 @@ pub fn ppd_rest(config: Arc<ServiceConfig>, db: Arc<RBatis>, token: Cancellation
 
             match HandlerState::new(&config, db).await {
                 Ok(state) => {
+                    tokio::spawn(purge_trash(state.clone(), token.clone()));
+
                     if let Err(err) = serve_app(config, state, token).await {
                         tracing::error!("unable to serve app: {err}")
                     }
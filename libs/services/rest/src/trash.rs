@@ -0,0 +1,39 @@
+use std::time::Duration;
+
+use ppd_fs::trash::purge_expired;
+use ppd_shared::opts::ServiceAuthMode;
+use ppdrive::prelude::state::HandlerState;
+use tokio_util::sync::CancellationToken;
+
+/// how often expired trash is purged.
+const PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// permanently remove trashed assets once the retention period of the service has passed,
+/// until the service is stopped.
+pub async fn purge_trash(state: HandlerState, token: CancellationToken) {
+    let config = state.config();
+    let retention = config.base.trash_retention;
+
+    // assets are deleted right away without retention, and zero mode has no bookkeeping
+    let modes = &config.auth.modes;
+    if retention == 0
+        || modes
+            .iter()
+            .all(|mode| matches!(mode, ServiceAuthMode::Zero))
+    {
+        return;
+    }
+
+    loop {
+        match purge_expired(state.db(), retention).await {
+            Ok(0) => {}
+            Ok(purged) => tracing::info!("purged {purged} expired assets from the trash"),
+            Err(err) => tracing::error!("unable to purge expired trash: {err}"),
+        }
+
+        tokio::select! {
+            _ = token.cancelled() => break,
+            _ = tokio::time::sleep(PURGE_INTERVAL) => {}
+        }
+    }
+}
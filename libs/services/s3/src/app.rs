@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    extract::MatchedPath,
+    http::Request,
+    routing::get,
+};
+use ppd_shared::opts::ServiceConfig;
+use ppdrive::prelude::state::HandlerState;
+use tokio_util::sync::CancellationToken;
+use tower_http::trace::TraceLayer;
+use tracing::info_span;
+
+use crate::{
+    bucket::{create_bucket, list_buckets, list_objects},
+    object::{delete_object, get_object, post_object, put_object},
+};
+
+/// Routes of the S3 API. Only path-style requests (`/{bucket}/{key}`) are supported.
+pub fn routes() -> Router<HandlerState> {
+    Router::new()
+        .route("/", get(list_buckets))
+        .route("/:bucket", get(list_objects).put(create_bucket))
+        .route(
+            "/:bucket/*key",
+            get(get_object)
+                .put(put_object)
+                .post(post_object)
+                .delete(delete_object),
+        )
+}
+
+pub async fn serve_app(config: Arc<ServiceConfig>, state: HandlerState, token: CancellationToken) {
+    let svc = routes()
+        .layer(
+            TraceLayer::new_for_http().make_span_with(|request: &Request<_>| {
+                let matched_path = request
+                    .extensions()
+                    .get::<MatchedPath>()
+                    .map(MatchedPath::as_str);
+
+                info_span!(
+                    "http_request",
+                    method = ?request.method(),
+                    matched_path,
+                )
+            }),
+        )
+        .with_state(state)
+        .into_make_service();
+
+    match tokio::net::TcpListener::bind(format!("0.0.0.0:{}", &config.base.port)).await {
+        Ok(listener) => {
+            if let Ok(addr) = listener.local_addr() {
+                tracing::info!("new s3 service listening on {addr}");
+            }
+
+            tokio::select! {
+                _ = token.cancelled() => {},
+                _ = axum::serve(listener, svc) => {}
+            }
+        }
+        Err(err) => {
+            tracing::error!("Error starting listener: {err}");
+        }
+    }
+}
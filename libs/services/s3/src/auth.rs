@@ -0,0 +1,186 @@
+use std::ops::Deref;
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{HeaderMap, Method, header::AUTHORIZATION, request::Parts},
+};
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use ppd_bk::{RBatis, models::bucket::Buckets};
+use ppd_shared::{
+    opts::ClientScope,
+    sigv4::{Authorization, CanonicalRequest, SigningScope, UNSIGNED_PAYLOAD, uri_decode, verify},
+};
+use ppdrive::{
+    HandlerResult, prelude::state::HandlerState, rest::extractors::BucketSizeValidator,
+    tools::{check_scope, get_client_with_s3_secrets},
+};
+
+use crate::errors::S3Error;
+
+pub const AMZ_DATE_HEADER: &str = "x-amz-date";
+pub const CONTENT_SHA256_HEADER: &str = "x-amz-content-sha256";
+
+/// payload hash of aws-chunked request bodies whose chunks are not signed.
+pub const STREAMING_UNSIGNED_PAYLOAD: &str = "STREAMING-UNSIGNED-PAYLOAD-TRAILER";
+
+/// maximum difference between request time and server time.
+const MAX_CLOCK_SKEW: TimeDelta = TimeDelta::minutes(15);
+
+/// An extractor that verifies the SigV4 signature of a request and returns the client
+/// that signed it. The access key id is the id of a client key, or the client's id, and the
+/// secret key is the matching S3 secret.
+pub struct S3Client {
+    id: u64,
+    pid: String,
+    max_bucket_size: Option<f64>,
+}
+
+impl S3Client {
+    pub fn pid(&self) -> &str {
+        &self.pid
+    }
+}
+
+impl BucketSizeValidator for S3Client {
+    fn id(&self) -> &u64 {
+        &self.id
+    }
+
+    fn max_bucket_size(&self) -> &Option<f64> {
+        &self.max_bucket_size
+    }
+
+    async fn current_size(&self, db: &RBatis) -> HandlerResult<f64> {
+        let size = Buckets::client_total_bucket_size(db, self.id()).await?;
+        Ok(size)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for S3Client
+where
+    HandlerState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = S3Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let headers = &parts.headers;
+        let value = header(headers, AUTHORIZATION.as_str()).ok_or(S3Error::AccessDenied(
+            "anonymous requests are not allowed".to_string(),
+        ))?;
+
+        let auth = Authorization::parse(value).ok_or(S3Error::AuthorizationHeaderMalformed(
+            "only AWS4-HMAC-SHA256 authorization is supported".to_string(),
+        ))?;
+
+        let amz_date = header(headers, AMZ_DATE_HEADER).ok_or(S3Error::AccessDenied(
+            format!("missing '{AMZ_DATE_HEADER}' header"),
+        ))?;
+
+        if !amz_date.starts_with(auth.date) || auth.service != "s3" {
+            return Err(S3Error::AuthorizationHeaderMalformed(
+                "invalid credential scope".to_string(),
+            ));
+        }
+
+        let time = NaiveDateTime::parse_from_str(amz_date, "%Y%m%dT%H%M%SZ")
+            .map_err(|err| S3Error::AccessDenied(err.to_string()))?
+            .and_utc();
+
+        if (Utc::now() - time).abs() > MAX_CLOCK_SKEW {
+            return Err(S3Error::RequestTimeTooSkewed);
+        }
+
+        let payload_hash = payload_hash(headers)?;
+
+        let signed_headers: Vec<(String, String)> = auth
+            .signed_headers
+            .iter()
+            .map(|name| {
+                let values: Vec<&str> = headers
+                    .get_all(*name)
+                    .iter()
+                    .filter_map(|v| v.to_str().ok())
+                    .map(str::trim)
+                    .collect();
+
+                (name.to_string(), values.join(","))
+            })
+            .collect();
+
+        let query: Vec<(String, String)> = parts
+            .uri
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|p| !p.is_empty())
+            .map(|p| {
+                let (k, v) = p.split_once('=').unwrap_or((p, ""));
+                (uri_decode(k), uri_decode(v))
+            })
+            .collect();
+
+        let request = CanonicalRequest {
+            method: parts.method.as_str(),
+            path: parts.uri.path(),
+            query: &query,
+            headers: &signed_headers,
+            payload_hash,
+        };
+
+        let scope = SigningScope {
+            amz_date,
+            region: auth.region,
+            service: auth.service,
+        };
+
+        let state = HandlerState::from_ref(state);
+        let secrets = state.secrets();
+        let (client, secrets, scopes) =
+            get_client_with_s3_secrets(state.db(), secrets.deref(), auth.access_key)
+                .await
+                .map_err(|_| S3Error::InvalidAccessKeyId)?;
+
+        if !secrets
+            .iter()
+            .any(|secret| verify(secret, &scope, &request, auth.signature))
+        {
+            return Err(S3Error::SignatureDoesNotMatch);
+        }
+
+        let required = match parts.method {
+            Method::GET | Method::HEAD => ClientScope::AssetsRead,
+            _ => ClientScope::AssetsWrite,
+        };
+
+        check_scope(&scopes, required).map_err(|err| S3Error::AccessDenied(err.to_string()))?;
+
+        Ok(S3Client {
+            id: client.id(),
+            pid: client.pid().to_string(),
+            max_bucket_size: *client.max_bucket_size(),
+        })
+    }
+}
+
+/// payload hash declared by the client. this is either the hex encoded sha256 of the body,
+/// [UNSIGNED_PAYLOAD] or [STREAMING_UNSIGNED_PAYLOAD].
+pub fn payload_hash(headers: &HeaderMap) -> Result<&str, S3Error> {
+    let hash = header(headers, CONTENT_SHA256_HEADER).ok_or(S3Error::InvalidRequest(format!(
+        "missing '{CONTENT_SHA256_HEADER}' header"
+    )))?;
+
+    if hash.starts_with("STREAMING-") && hash != STREAMING_UNSIGNED_PAYLOAD {
+        return Err(S3Error::NotImplemented(format!(
+            "payload '{hash}' is not supported. use '{UNSIGNED_PAYLOAD}' or '{STREAMING_UNSIGNED_PAYLOAD}'."
+        )));
+    }
+
+    Ok(hash)
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
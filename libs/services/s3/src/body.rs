@@ -0,0 +1,178 @@
+//! Receiving object data from request bodies.
+
+use std::path::PathBuf;
+
+use axum::{body::Body, http::HeaderMap};
+use futures_util::StreamExt;
+use ppd_fs::upload::TmpFile;
+use ppd_shared::sigv4::UNSIGNED_PAYLOAD;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    S3Result,
+    auth::{STREAMING_UNSIGNED_PAYLOAD, payload_hash},
+    errors::S3Error,
+};
+
+/// maximum length of an aws-chunked chunk header.
+const MAX_CHUNK_HEADER: usize = 4096;
+
+/// Object data saved to a temporary file.
+pub struct Upload {
+    pub tmp: PathBuf,
+    pub size: u64,
+
+    /// hex encoded sha256 of the data.
+    pub sha256: String,
+}
+
+/// Write a request body to a temporary file, decoding aws-chunked bodies and checking the
+/// payload hash declared by the client. Fails if the data is larger than `limit`.
+pub async fn receive(body: Body, headers: &HeaderMap, limit: u64) -> S3Result<Upload> {
+    let declared = payload_hash(headers)?.to_string();
+    let mut decoder = (declared == STREAMING_UNSIGNED_PAYLOAD).then(AwsChunked::default);
+
+    let mut tmp = TmpFile::create(limit).await?;
+    let mut hasher = Sha256::new();
+    let mut stream = body.into_data_stream();
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                tmp.discard().await;
+                return Err(err.into());
+            }
+        };
+
+        let data = match &mut decoder {
+            Some(decoder) => match decoder.push(&chunk) {
+                Ok(data) => data,
+                Err(err) => {
+                    tmp.discard().await;
+                    return Err(err);
+                }
+            },
+            None => chunk.to_vec(),
+        };
+
+        hasher.update(&data);
+        tmp.write_chunk(&data).await.map_err(|err| {
+            S3Error::InvalidRequest(format!("unable to receive object: {err}"))
+        })?;
+    }
+
+    let sha256 = hex::encode(hasher.finalize());
+    let complete = decoder.as_ref().is_none_or(AwsChunked::is_done);
+    let verified = declared == UNSIGNED_PAYLOAD
+        || declared == STREAMING_UNSIGNED_PAYLOAD
+        || declared.eq_ignore_ascii_case(&sha256);
+
+    if !complete || !verified {
+        tmp.discard().await;
+
+        return Err(if complete {
+            S3Error::XAmzContentSHA256Mismatch
+        } else {
+            S3Error::InvalidRequest("incomplete aws-chunked body".to_string())
+        });
+    }
+
+    let (tmp, size) = tmp.finish().await?;
+    Ok(Upload { tmp, size, sha256 })
+}
+
+#[derive(Default)]
+enum ChunkState {
+    /// reading the size line of the next chunk.
+    #[default]
+    Header,
+
+    /// number of data bytes left in the current chunk.
+    Data(usize),
+
+    /// reading the line break after chunk data.
+    DataEnd,
+
+    /// last chunk received. trailers (i.e checksums) are ignored.
+    Done,
+}
+
+/// Incremental decoder for `aws-chunked` content encoding.
+#[derive(Default)]
+struct AwsChunked {
+    buf: Vec<u8>,
+    state: ChunkState,
+}
+
+impl AwsChunked {
+    /// add received bytes to the decoder and return decoded data available so far.
+    fn push(&mut self, bytes: &[u8]) -> S3Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.buf.extend_from_slice(bytes);
+
+        loop {
+            match self.state {
+                ChunkState::Header => {
+                    let Some(end) = self.buf.windows(2).position(|w| w == b"\r\n") else {
+                        if self.buf.len() > MAX_CHUNK_HEADER {
+                            return Err(invalid_chunk());
+                        }
+                        break;
+                    };
+
+                    let line = String::from_utf8_lossy(&self.buf[..end]).to_string();
+                    let size = line.split(';').next().unwrap_or_default().trim();
+                    let size = usize::from_str_radix(size, 16).map_err(|_| invalid_chunk())?;
+
+                    self.buf.drain(..end + 2);
+                    self.state = if size == 0 {
+                        ChunkState::Done
+                    } else {
+                        ChunkState::Data(size)
+                    };
+                }
+                ChunkState::Data(left) => {
+                    if self.buf.is_empty() {
+                        break;
+                    }
+
+                    let n = left.min(self.buf.len());
+                    data.extend(self.buf.drain(..n));
+
+                    self.state = if n == left {
+                        ChunkState::DataEnd
+                    } else {
+                        ChunkState::Data(left - n)
+                    };
+                }
+                ChunkState::DataEnd => {
+                    if self.buf.len() < 2 {
+                        break;
+                    }
+
+                    if &self.buf[..2] != b"\r\n" {
+                        return Err(invalid_chunk());
+                    }
+
+                    self.buf.drain(..2);
+                    self.state = ChunkState::Header;
+                }
+                ChunkState::Done => {
+                    self.buf.clear();
+                    break;
+                }
+            }
+        }
+
+        Ok(data)
+    }
+
+    fn is_done(&self) -> bool {
+        matches!(self.state, ChunkState::Done)
+    }
+}
+
+fn invalid_chunk() -> S3Error {
+    S3Error::InvalidRequest("malformed aws-chunked body".to_string())
+}
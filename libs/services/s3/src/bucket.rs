@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::header::{CONTENT_TYPE, LOCATION},
+    response::{IntoResponse, Response},
+};
+use axum_macros::debug_handler;
+use ppd_bk::{
+    RBatis,
+    models::{
+        asset::{AssetType, Assets},
+        bucket::{BucketOwnerType, Buckets},
+    },
+};
+use ppd_fs::{FileBody, storage::Storage};
+use ppd_shared::{api::CreateBucketOptions, tools::SECRETS_FILENAME};
+use ppdrive::{prelude::state::HandlerState, rest::extractors::BucketSizeValidator};
+
+use crate::{S3Result, auth::S3Client, errors::S3Error, xml};
+
+const DEFAULT_MAX_KEYS: usize = 1000;
+
+/// name of a bucket in the S3 API. this is the bucket's partition, or its id if the bucket
+/// has no partition.
+fn bucket_name(bucket: &Buckets) -> &str {
+    bucket.partition().as_deref().unwrap_or(bucket.pid())
+}
+
+/// retrieve a bucket by its S3 name and check that it belongs to the client.
+pub(crate) async fn client_bucket(db: &RBatis, client: &S3Client, name: &str) -> S3Result<Buckets> {
+    let bucket = match Buckets::get_by_partition(db, name).await {
+        Ok(bucket) => bucket,
+        Err(_) => Buckets::get_by_pid(db, name)
+            .await
+            .map_err(|_| S3Error::NoSuchBucket(format!("bucket '{name}' does not exist.")))?,
+    };
+
+    if !matches!(bucket.owner_type(), BucketOwnerType::Client) || bucket.owner_id() != client.id() {
+        return Err(S3Error::AccessDenied(format!(
+            "bucket '{name}' does not belong to this client."
+        )));
+    }
+
+    Ok(bucket)
+}
+
+/// S3 bucket naming rules, plus the partition length required by ppdrive.
+fn validate_bucket_name(name: &str) -> S3Result<()> {
+    let valid_chars = name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '.');
+
+    let valid_ends = name
+        .chars()
+        .next()
+        .zip(name.chars().last())
+        .is_some_and(|(first, last)| first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric());
+
+    if !(6..=63).contains(&name.len()) || !valid_chars || !valid_ends || name.contains("..") {
+        return Err(S3Error::InvalidBucketName(format!(
+            "'{name}' is not a valid bucket name. bucket names must be 6 to 63 lowercase letters, numbers, hyphens or dots."
+        )));
+    }
+
+    if name == SECRETS_FILENAME {
+        return Err(S3Error::InvalidBucketName(format!(
+            "bucket name '{name}' is not allowed."
+        )));
+    }
+
+    Ok(())
+}
+
+#[debug_handler]
+pub async fn list_buckets(
+    State(state): State<HandlerState>,
+    client: S3Client,
+) -> S3Result<Response> {
+    let buckets = Buckets::client_buckets(state.db(), client.id()).await?;
+
+    let buckets: String = buckets
+        .iter()
+        .map(|b| format!("<Bucket>{}</Bucket>", xml::element("Name", bucket_name(b))))
+        .collect();
+
+    let body = format!(
+        r#"{}<ListAllMyBucketsResult xmlns="{}"><Owner>{}</Owner><Buckets>{buckets}</Buckets></ListAllMyBucketsResult>"#,
+        xml::DECLARATION,
+        xml::NAMESPACE,
+        xml::element("ID", client.pid()),
+    );
+
+    Ok(([(CONTENT_TYPE, xml::CONTENT_TYPE)], body).into_response())
+}
+
+/// Create a bucket. The bucket name is used as the bucket's partition.
+#[debug_handler]
+pub async fn create_bucket(
+    Path(name): Path<String>,
+    State(state): State<HandlerState>,
+    client: S3Client,
+) -> S3Result<Response> {
+    let db = state.db();
+    validate_bucket_name(&name)?;
+
+    if let Ok(bucket) = Buckets::get_by_partition(db, &name).await {
+        let owned = matches!(bucket.owner_type(), BucketOwnerType::Client)
+            && bucket.owner_id() == client.id();
+
+        return Err(if owned {
+            S3Error::BucketAlreadyOwnedByYou(format!("you already own bucket '{name}'."))
+        } else {
+            S3Error::BucketAlreadyExists(format!("bucket name '{name}' is not available."))
+        });
+    }
+
+    client.validate_bucket_size(db, &None).await?;
+
+    let opts = CreateBucketOptions {
+        label: name.clone(),
+        partition: Some(name.clone()),
+        ..Default::default()
+    };
+
+    Storage::validate_backend(&opts.backend)?;
+    Buckets::create_by_client(db, opts, *client.id()).await?;
+
+    Ok([(LOCATION, format!("/{name}"))].into_response())
+}
+
+/// ListObjectsV2. Objects are listed in key order, and keys after the continuation
+/// token (or `start-after`) are returned.
+#[debug_handler]
+pub async fn list_objects(
+    Path(name): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+    State(state): State<HandlerState>,
+    client: S3Client,
+) -> S3Result<Response> {
+    let db = state.db();
+    let bucket = client_bucket(db, &client, &name).await?;
+
+    let prefix = query.get("prefix").map(String::as_str).unwrap_or_default();
+    let delimiter = query
+        .get("delimiter")
+        .map(String::as_str)
+        .filter(|d| !d.is_empty());
+
+    let max_keys = match query.get("max-keys") {
+        Some(max) => max
+            .parse::<usize>()
+            .map_err(|_| S3Error::InvalidArgument("invalid 'max-keys'".to_string()))?
+            .min(DEFAULT_MAX_KEYS),
+        None => DEFAULT_MAX_KEYS,
+    };
+
+    let token = query.get("continuation-token");
+    let start_after = token.or(query.get("start-after")).map(String::as_str);
+
+    let mut assets = Assets::get_by_bucket(db, &bucket.id(), &AssetType::File).await?;
+    assets.retain(|asset| asset.deleted_at().is_none());
+    assets.sort_by(|a, b| a.path().cmp(b.path()));
+
+    let mut contents = Vec::new();
+    let mut prefixes: Vec<String> = Vec::new();
+    let mut last_key = None;
+    let mut truncated = false;
+
+    for asset in &assets {
+        let key = asset.path();
+        if !key.starts_with(prefix) {
+            continue;
+        }
+
+        // continuation tokens that end with a delimiter are common prefixes returned by
+        // the previous page, so we skip all keys under them.
+        if let Some(start) = start_after
+            && (key <= start || delimiter.is_some_and(|d| start.ends_with(d) && key.starts_with(start)))
+        {
+            continue;
+        }
+
+        let common_prefix = delimiter.and_then(|d| {
+            key[prefix.len()..]
+                .find(d)
+                .map(|i| key[..prefix.len() + i + d.len()].to_string())
+        });
+
+        if let Some(cp) = &common_prefix
+            && prefixes.last() == Some(cp)
+        {
+            continue;
+        }
+
+        if contents.len() + prefixes.len() == max_keys {
+            truncated = true;
+            break;
+        }
+
+        match common_prefix {
+            Some(cp) => {
+                last_key = Some(cp.clone());
+                prefixes.push(cp);
+            }
+            None => {
+                last_key = Some(key.to_string());
+                contents.push(asset);
+            }
+        }
+    }
+
+    let key_count = contents.len() + prefixes.len();
+    let mut objects = String::new();
+    for asset in contents {
+        let file = FileBody::open(&bucket, asset).await?;
+        let modified = file.modified().map(xml::timestamp).unwrap_or_default();
+
+        objects.push_str(&format!(
+            "<Contents>{}{}{}{}<StorageClass>STANDARD</StorageClass></Contents>",
+            xml::element("Key", asset.path()),
+            xml::element("LastModified", &modified),
+            xml::element("ETag", &file.etag()),
+            xml::element("Size", &file.size().to_string()),
+        ));
+    }
+
+    let common_prefixes: String = prefixes
+        .iter()
+        .map(|p| format!("<CommonPrefixes>{}</CommonPrefixes>", xml::element("Prefix", p)))
+        .collect();
+
+    let mut meta = format!(
+        "{}{}{}{}{}",
+        xml::element("Name", &name),
+        xml::element("Prefix", prefix),
+        xml::element("KeyCount", &key_count.to_string()),
+        xml::element("MaxKeys", &max_keys.to_string()),
+        xml::element("IsTruncated", &truncated.to_string()),
+    );
+
+    if let Some(delimiter) = delimiter {
+        meta.push_str(&xml::element("Delimiter", delimiter));
+    }
+
+    if let Some(token) = token {
+        meta.push_str(&xml::element("ContinuationToken", token));
+    }
+
+    if truncated && let Some(last_key) = last_key {
+        meta.push_str(&xml::element("NextContinuationToken", &last_key));
+    }
+
+    let body = format!(
+        r#"{}<ListBucketResult xmlns="{}">{meta}{objects}{common_prefixes}</ListBucketResult>"#,
+        xml::DECLARATION,
+        xml::NAMESPACE,
+    );
+
+    Ok(([(CONTENT_TYPE, xml::CONTENT_TYPE)], body).into_response())
+}
@@ -0,0 +1,165 @@
+use std::{error::Error, fmt::Display};
+
+use axum::{
+    http::{StatusCode, header::CONTENT_TYPE},
+    response::IntoResponse,
+};
+use ppd_bk::Error as DBError;
+use ppd_fs::errors::Error as FsError;
+use ppdrive::errors::HandlerError;
+
+use crate::xml;
+
+/// Errors returned by the S3 API. Each variant maps to an S3 error code, so that
+/// S3 clients can handle them as they would with any S3-compatible server.
+#[derive(Debug)]
+pub enum S3Error {
+    AccessDenied(String),
+    AuthorizationHeaderMalformed(String),
+    BucketAlreadyExists(String),
+    BucketAlreadyOwnedByYou(String),
+    InternalError(String),
+    InvalidAccessKeyId,
+    InvalidArgument(String),
+    InvalidBucketName(String),
+    InvalidPart(String),
+    InvalidRequest(String),
+    MalformedXML,
+    NoSuchBucket(String),
+    NoSuchKey(String),
+    NoSuchUpload(String),
+    NotImplemented(String),
+    RequestTimeTooSkewed,
+    SignatureDoesNotMatch,
+    XAmzContentSHA256Mismatch,
+}
+
+impl S3Error {
+    pub fn code(&self) -> &'static str {
+        use S3Error::*;
+
+        match self {
+            AccessDenied(_) => "AccessDenied",
+            AuthorizationHeaderMalformed(_) => "AuthorizationHeaderMalformed",
+            BucketAlreadyExists(_) => "BucketAlreadyExists",
+            BucketAlreadyOwnedByYou(_) => "BucketAlreadyOwnedByYou",
+            InternalError(_) => "InternalError",
+            InvalidAccessKeyId => "InvalidAccessKeyId",
+            InvalidArgument(_) => "InvalidArgument",
+            InvalidBucketName(_) => "InvalidBucketName",
+            InvalidPart(_) => "InvalidPart",
+            InvalidRequest(_) => "InvalidRequest",
+            MalformedXML => "MalformedXML",
+            NoSuchBucket(_) => "NoSuchBucket",
+            NoSuchKey(_) => "NoSuchKey",
+            NoSuchUpload(_) => "NoSuchUpload",
+            NotImplemented(_) => "NotImplemented",
+            RequestTimeTooSkewed => "RequestTimeTooSkewed",
+            SignatureDoesNotMatch => "SignatureDoesNotMatch",
+            XAmzContentSHA256Mismatch => "XAmzContentSHA256Mismatch",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        use S3Error::*;
+
+        match self {
+            AccessDenied(_) | InvalidAccessKeyId | RequestTimeTooSkewed
+            | SignatureDoesNotMatch => StatusCode::FORBIDDEN,
+            BucketAlreadyExists(_) | BucketAlreadyOwnedByYou(_) => StatusCode::CONFLICT,
+            NoSuchBucket(_) | NoSuchKey(_) | NoSuchUpload(_) => StatusCode::NOT_FOUND,
+            NotImplemented(_) => StatusCode::NOT_IMPLEMENTED,
+            InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl Display for S3Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use S3Error::*;
+
+        match self {
+            AccessDenied(msg)
+            | AuthorizationHeaderMalformed(msg)
+            | BucketAlreadyExists(msg)
+            | BucketAlreadyOwnedByYou(msg)
+            | InternalError(msg)
+            | InvalidArgument(msg)
+            | InvalidBucketName(msg)
+            | InvalidPart(msg)
+            | InvalidRequest(msg)
+            | NoSuchBucket(msg)
+            | NoSuchKey(msg)
+            | NoSuchUpload(msg)
+            | NotImplemented(msg) => write!(f, "{msg}"),
+            InvalidAccessKeyId => write!(f, "the access key id does not exist."),
+            MalformedXML => write!(f, "the xml provided is not well-formed."),
+            RequestTimeTooSkewed => {
+                write!(f, "the difference between request time and server time is too large.")
+            }
+            SignatureDoesNotMatch => write!(f, "the request signature does not match."),
+            XAmzContentSHA256Mismatch => {
+                write!(f, "the provided 'x-amz-content-sha256' does not match the request body.")
+            }
+        }
+    }
+}
+
+impl From<FsError> for S3Error {
+    fn from(value: FsError) -> Self {
+        match value {
+            FsError::NotFound(msg) => S3Error::NoSuchKey(msg),
+            FsError::PermissionError(msg) => S3Error::AccessDenied(msg),
+            _ => S3Error::InternalError(value.to_string()),
+        }
+    }
+}
+
+impl From<DBError> for S3Error {
+    fn from(value: DBError) -> Self {
+        match value {
+            DBError::PermissionError(msg) => S3Error::AccessDenied(msg),
+            _ => S3Error::InternalError(value.to_string()),
+        }
+    }
+}
+
+impl From<HandlerError> for S3Error {
+    fn from(value: HandlerError) -> Self {
+        match value {
+            HandlerError::AuthorizationError(msg) | HandlerError::PermissionError(msg) => {
+                S3Error::AccessDenied(msg)
+            }
+            HandlerError::NotFound(msg) => S3Error::NoSuchKey(msg),
+            _ => S3Error::InternalError(value.to_string()),
+        }
+    }
+}
+
+impl From<std::io::Error> for S3Error {
+    fn from(value: std::io::Error) -> Self {
+        S3Error::InternalError(value.to_string())
+    }
+}
+
+impl From<axum::Error> for S3Error {
+    fn from(value: axum::Error) -> Self {
+        S3Error::InternalError(value.to_string())
+    }
+}
+
+impl IntoResponse for S3Error {
+    fn into_response(self) -> axum::response::Response {
+        let body = format!(
+            "{}<Error><Code>{}</Code><Message>{}</Message></Error>",
+            xml::DECLARATION,
+            self.code(),
+            xml::escape(&self.to_string())
+        );
+
+        (self.status(), [(CONTENT_TYPE, xml::CONTENT_TYPE)], body).into_response()
+    }
+}
+
+impl Error for S3Error {}
@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use crate::app::serve_app;
+use errors::S3Error;
+use ppd_bk::RBatis;
+use ppd_shared::{opts::ServiceConfig, start_logger, tools::init_secrets};
+use ppdrive::prelude::state::HandlerState;
+use tokio::runtime::Runtime;
+use tokio_util::sync::CancellationToken;
+
+pub use app::routes;
+
+mod app;
+mod auth;
+mod body;
+mod bucket;
+mod errors;
+mod object;
+mod xml;
+
+pub type S3Result<T> = Result<T, S3Error>;
+
+#[unsafe(no_mangle)]
+pub fn ppd_s3(config: Arc<ServiceConfig>, db: Arc<RBatis>, token: CancellationToken) {
+    if let Ok(rt) = Runtime::new() {
+        rt.block_on(async {
+            let _guard =
+                start_logger("ppd_s3=debug,tower_http=debug").expect("unable to start logger");
+
+            if let Err(err) = init_secrets().await {
+                tracing::error!("unable to initialize secrets: {err}");
+            }
+
+            match HandlerState::new(&config, db).await {
+                Ok(state) => serve_app(config, state, token).await,
+                Err(err) => tracing::error!("unable to create app state: {err}"),
+            }
+        })
+    }
+}
@@ -0,0 +1,385 @@
+use std::{collections::HashMap, path::Path as FilePath};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::{
+        HeaderMap, StatusCode,
+        header::{CONTENT_TYPE, ETAG},
+    },
+    response::{IntoResponse, Response},
+};
+use axum_macros::debug_handler;
+use ppd_bk::{
+    RBatis,
+    models::{
+        asset::{AssetType, Assets},
+        bucket::Buckets,
+        upload::UploadSessions,
+        user::Users,
+    },
+};
+use ppd_fs::{
+    FileBody,
+    auth::{create_or_update_asset, delete_asset},
+    opts::{CreateAssetOptions, CreateUploadOptions},
+    reserved_folder,
+    storage::{Storage, StorageBackend},
+    upload::{
+        cancel_session, complete_multipart_session, create_session, put_session_part,
+        upload_limit,
+    },
+};
+use ppd_shared::{
+    sigv4::{UNSIGNED_PAYLOAD, sha256_hex},
+    tools::{SECRETS_FILENAME, mb_to_bytes},
+};
+use ppdrive::{
+    prelude::state::HandlerState,
+    rest::{extractors::BucketSizeValidator, file_response},
+};
+
+use crate::{
+    S3Result,
+    auth::{S3Client, payload_hash},
+    body::{Upload, receive},
+    bucket::client_bucket,
+    errors::S3Error,
+    xml,
+};
+
+const MAX_PART_NUMBER: u32 = 10000;
+
+/// header used to make new objects public.
+const ACL_HEADER: &str = "x-amz-acl";
+const COPY_SOURCE_HEADER: &str = "x-amz-copy-source";
+
+type S3Query = Query<HashMap<String, String>>;
+
+fn validate_key(key: &str) -> S3Result<()> {
+    if key.is_empty()
+        || key.starts_with('/')
+        || key.split('/').any(|part| part == "." || part == "..")
+        || key == SECRETS_FILENAME
+        || reserved_folder(key).is_some()
+    {
+        return Err(S3Error::InvalidArgument(format!(
+            "'{key}' is not a valid object key."
+        )));
+    }
+
+    Ok(())
+}
+
+/// retrieve a file asset, making sure it belongs to the bucket.
+async fn bucket_file(db: &RBatis, bucket: &Buckets, key: &str) -> S3Result<Assets> {
+    match Assets::get_by_path(db, key, &AssetType::File).await {
+        Ok(asset) if asset.bucket_id() == &bucket.id() => Ok(asset),
+        _ => Err(S3Error::NoSuchKey(format!("object '{key}' does not exist."))),
+    }
+}
+
+/// reject a key used by an object in another bucket, since asset paths are unique across
+/// buckets. Objects of `bucket` are overwritten in place, so their versions are kept.
+async fn check_key_bucket(db: &RBatis, bucket: &Buckets, key: &str) -> S3Result<()> {
+    if let Ok(asset) = Assets::get_by_path(db, key, &AssetType::File).await
+        && asset.bucket_id() != &bucket.id()
+    {
+        return Err(S3Error::AccessDenied(format!(
+            "key '{key}' is used by an object in another bucket."
+        )));
+    }
+
+    Ok(())
+}
+
+fn asset_options(bucket: &Buckets, key: &str, headers: &HeaderMap) -> CreateAssetOptions {
+    let public = headers
+        .get(ACL_HEADER)
+        .is_some_and(|acl| acl == "public-read");
+
+    CreateAssetOptions {
+        asset_path: key.to_string(),
+        asset_type: AssetType::File,
+        bucket: bucket.pid().to_string(),
+        public: Some(public),
+        ..Default::default()
+    }
+}
+
+/// receive an object's data, limited by the service's maximum upload size and the space
+/// left in the bucket.
+async fn receive_object(
+    state: &HandlerState,
+    bucket: &Buckets,
+    headers: &HeaderMap,
+    body: Body,
+) -> S3Result<Upload> {
+    let max_upload = mb_to_bytes(state.config().base.max_upload_size) as u64;
+    let limit = upload_limit(state.db(), bucket.pid(), max_upload).await?;
+
+    receive(body, headers, limit).await
+}
+
+async fn remove_tmp(tmp: &FilePath) {
+    if tmp.is_file()
+        && let Err(err) = tokio::fs::remove_file(tmp).await
+    {
+        tracing::warn!("unable to remove temporary upload file: {err}");
+    }
+}
+
+/// retrieve a multipart upload of the bucket.
+async fn client_upload(
+    db: &RBatis,
+    user: &Users,
+    bucket: &Buckets,
+    upload_id: &str,
+) -> S3Result<UploadSessions> {
+    match UploadSessions::get(db, upload_id, &user.id()).await {
+        Ok(session) if session.bucket_id() == &bucket.id() => Ok(session),
+        _ => Err(S3Error::NoSuchUpload(format!(
+            "upload '{upload_id}' does not exist."
+        ))),
+    }
+}
+
+fn xml_response(body: String) -> Response {
+    let body = format!("{}{body}", xml::DECLARATION);
+    ([(CONTENT_TYPE, xml::CONTENT_TYPE)], body).into_response()
+}
+
+/// GetObject and HeadObject.
+#[debug_handler]
+pub async fn get_object(
+    Path((name, key)): Path<(String, String)>,
+    State(state): State<HandlerState>,
+    client: S3Client,
+    headers: HeaderMap,
+) -> S3Result<Response<Body>> {
+    let db = state.db();
+    let bucket = client_bucket(db, &client, &name).await?;
+    let asset = bucket_file(db, &bucket, &key).await?;
+
+    let file = FileBody::open(&bucket, &asset).await?;
+    let config = state.config();
+    let resp = file_response(&headers, file, &config.base.cache_control).await?;
+
+    Ok(resp)
+}
+
+/// PutObject, or UploadPart if the request has `uploadId` and `partNumber` parameters.
+#[debug_handler]
+pub async fn put_object(
+    Path((name, key)): Path<(String, String)>,
+    Query(query): S3Query,
+    State(state): State<HandlerState>,
+    client: S3Client,
+    headers: HeaderMap,
+    body: Body,
+) -> S3Result<Response> {
+    let db = state.db();
+    validate_key(&key)?;
+
+    if headers.contains_key(COPY_SOURCE_HEADER) {
+        return Err(S3Error::NotImplemented(
+            "copying objects is not supported.".to_string(),
+        ));
+    }
+
+    let bucket = client_bucket(db, &client, &name).await?;
+    let user = Users::client_service_user(db, client.id()).await?;
+
+    if let (Some(upload_id), Some(part_number)) = (query.get("uploadId"), query.get("partNumber"))
+    {
+        let part_number = part_number
+            .parse::<u32>()
+            .ok()
+            .filter(|n| (1..=MAX_PART_NUMBER).contains(n))
+            .ok_or(S3Error::InvalidArgument(format!(
+                "part number must be between 1 and {MAX_PART_NUMBER}."
+            )))?;
+
+        client_upload(db, &user, &bucket, upload_id).await?;
+        let upload = receive_object(&state, &bucket, &headers, body).await?;
+
+        let max_upload = mb_to_bytes(state.config().base.max_upload_size) as u64;
+        let put = put_session_part(
+            db,
+            &user.id(),
+            upload_id,
+            part_number,
+            &upload.tmp,
+            max_upload,
+        )
+        .await;
+
+        if let Err(err) = put {
+            remove_tmp(&upload.tmp).await;
+            return Err(err.into());
+        }
+
+        let etag = format!("\"{}\"", upload.sha256);
+        return Ok([(ETAG, etag)].into_response());
+    }
+
+    // keys ending with "/" are folder markers
+    if let Some(folder) = key.strip_suffix('/') {
+        if Assets::get_by_path(db, folder, &AssetType::Folder).await.is_err() {
+            let mut opts = asset_options(&bucket, folder, &headers);
+            opts.asset_type = AssetType::Folder;
+
+            create_or_update_asset(db, &user.id(), &opts, &None, &None).await?;
+        }
+
+        return Ok(StatusCode::OK.into_response());
+    }
+
+    let upload = receive_object(&state, &bucket, &headers, body).await?;
+    let opts = asset_options(&bucket, &key, &headers);
+
+    let save = async {
+        check_key_bucket(db, &bucket, &key).await?;
+        create_or_update_asset(db, &user.id(), &opts, &Some(upload.tmp.clone()), &Some(upload.size))
+            .await?;
+
+        S3Result::Ok(())
+    };
+
+    if let Err(err) = save.await {
+        remove_tmp(&upload.tmp).await;
+        return Err(err);
+    }
+
+    let asset = bucket_file(db, &bucket, &key).await?;
+    let file = FileBody::open(&bucket, &asset).await?;
+
+    Ok([(ETAG, file.etag())].into_response())
+}
+
+/// CreateMultipartUpload (`uploads` parameter) or CompleteMultipartUpload (`uploadId` parameter).
+#[debug_handler]
+pub async fn post_object(
+    Path((name, key)): Path<(String, String)>,
+    Query(query): S3Query,
+    State(state): State<HandlerState>,
+    client: S3Client,
+    headers: HeaderMap,
+    body: Bytes,
+) -> S3Result<Response> {
+    let db = state.db();
+    validate_key(&key)?;
+
+    let bucket = client_bucket(db, &client, &name).await?;
+    let user = Users::client_service_user(db, client.id()).await?;
+
+    if query.contains_key("uploads") {
+        let opts = CreateUploadOptions {
+            asset: asset_options(&bucket, &key, &headers),
+            size: None,
+        };
+
+        let max_upload = mb_to_bytes(state.config().base.max_upload_size) as u64;
+        let upload_id = create_session(db, &user.id(), &opts, max_upload).await?;
+        let body = format!(
+            r#"<InitiateMultipartUploadResult xmlns="{}">{}{}{}</InitiateMultipartUploadResult>"#,
+            xml::NAMESPACE,
+            xml::element("Bucket", &name),
+            xml::element("Key", &key),
+            xml::element("UploadId", &upload_id),
+        );
+
+        return Ok(xml_response(body));
+    }
+
+    let Some(upload_id) = query.get("uploadId") else {
+        return Err(S3Error::InvalidRequest(
+            "expected 'uploads' or 'uploadId' parameter.".to_string(),
+        ));
+    };
+
+    let declared = payload_hash(&headers)?;
+    if declared != UNSIGNED_PAYLOAD && !declared.eq_ignore_ascii_case(&sha256_hex(&body)) {
+        return Err(S3Error::XAmzContentSHA256Mismatch);
+    }
+
+    let body = String::from_utf8_lossy(&body);
+    if !body.contains("<CompleteMultipartUpload") {
+        return Err(S3Error::MalformedXML);
+    }
+
+    let parts = xml::values(&body, "PartNumber")
+        .iter()
+        .map(|n| n.trim().parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| S3Error::MalformedXML)?;
+
+    if parts.is_empty() || parts.windows(2).any(|w| w[0] >= w[1]) {
+        return Err(S3Error::InvalidPart(
+            "parts must be listed in ascending order.".to_string(),
+        ));
+    }
+
+    client_upload(db, &user, &bucket, upload_id).await?;
+    check_key_bucket(db, &bucket, &key).await?;
+    complete_multipart_session(db, &user.id(), upload_id, &parts)
+        .await
+        .map_err(|err| match err {
+            ppd_fs::errors::Error::NotFound(msg) => S3Error::InvalidPart(msg),
+            err => err.into(),
+        })?;
+
+    let asset = bucket_file(db, &bucket, &key).await?;
+    let file = FileBody::open(&bucket, &asset).await?;
+
+    let body = format!(
+        r#"<CompleteMultipartUploadResult xmlns="{}">{}{}{}{}</CompleteMultipartUploadResult>"#,
+        xml::NAMESPACE,
+        xml::element("Location", &format!("/{name}/{key}")),
+        xml::element("Bucket", &name),
+        xml::element("Key", &key),
+        xml::element("ETag", &file.etag()),
+    );
+
+    Ok(xml_response(body))
+}
+
+/// DeleteObject, or AbortMultipartUpload if the request has an `uploadId` parameter.
+/// Deleting an object that doesn't exist is not an error.
+#[debug_handler]
+pub async fn delete_object(
+    Path((name, key)): Path<(String, String)>,
+    Query(query): S3Query,
+    State(state): State<HandlerState>,
+    client: S3Client,
+) -> S3Result<StatusCode> {
+    let db = state.db();
+    let bucket = client_bucket(db, &client, &name).await?;
+
+    if let Some(upload_id) = query.get("uploadId") {
+        let user = Users::client_service_user(db, client.id()).await?;
+        client_upload(db, &user, &bucket, upload_id).await?;
+        cancel_session(db, &user.id(), upload_id).await?;
+
+        return Ok(StatusCode::NO_CONTENT);
+    }
+
+    match key.strip_suffix('/') {
+        // folder markers are only removed if the folder is empty
+        Some(folder) => {
+            if let Ok(asset) = Assets::get_by_path(db, folder, &AssetType::Folder).await
+                && asset.bucket_id() == &bucket.id()
+                && Storage::for_bucket(&bucket).list(folder).await?.is_empty()
+            {
+                delete_asset(db, folder, &AssetType::Folder).await?;
+            }
+        }
+        None => {
+            if bucket_file(db, &bucket, &key).await.is_ok() {
+                delete_asset(db, &key, &AssetType::File).await?;
+            }
+        }
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,23 @@
+//! Helpers for reading and writing the (small) XML documents used by the S3 API.
+
+use std::time::SystemTime;
+
+use chrono::{DateTime, Utc};
+
+pub use ppd_shared::xml::{escape, values};
+
+pub const DECLARATION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>"#;
+pub const NAMESPACE: &str = "http://s3.amazonaws.com/doc/2006-03-01/";
+pub const CONTENT_TYPE: &str = "application/xml";
+
+/// `<tag>value</tag>`, with value escaped.
+pub fn element(tag: &str, value: &str) -> String {
+    format!("<{tag}>{}</{tag}>", escape(value))
+}
+
+/// ISO 8601 timestamp, as used in S3 listings.
+pub fn timestamp(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%Y-%m-%dT%H:%M:%S%.3fZ")
+        .to_string()
+}
@@ -0,0 +1,213 @@
+use std::sync::Arc;
+
+use axum::http::{
+    Method, StatusCode,
+    header::{AUTHORIZATION, ETAG, RANGE},
+};
+use axum_test::{TestRequest, TestServer};
+use ppd_s3::routes;
+use ppd_shared::{
+    opts::ServiceConfig,
+    sigv4::{CanonicalRequest, SigningScope, amz_date, authorization, sha256_hex, uri_decode},
+    tools::AppSecrets,
+};
+use ppdrive::{prelude::state::HandlerState, tools::create_client};
+use rest_test_utils::TestApp;
+use serial_test::serial;
+
+const BUCKET: &str = "s3-test-bucket";
+
+/// signs requests the way S3 SDKs do.
+struct S3TestClient {
+    server: TestServer,
+    access_key: String,
+    secret_key: String,
+}
+
+impl S3TestClient {
+    async fn new(app: &TestApp) -> Self {
+        let secrets = AppSecrets::read()
+            .await
+            .expect("unable to create app secrets");
+
+        let client = create_client(&app.db, &secrets, "S3 Client", None)
+            .await
+            .expect("unable to create client");
+
+        let state = HandlerState::new(&ServiceConfig::default(), Arc::new(app.db.clone()))
+            .await
+            .expect("unable to create app state");
+
+        let svc = routes().with_state(state).into_make_service();
+        let server = TestServer::new(svc).expect("unable to create test server");
+
+        Self {
+            server,
+            access_key: client.id().to_string(),
+            secret_key: client.s3_secret().to_string(),
+        }
+    }
+
+    fn request(&self, method: Method, path: &str, query: &str, body: &[u8]) -> TestRequest {
+        self.signed(method, path, query, body, &self.secret_key)
+    }
+
+    fn signed(
+        &self,
+        method: Method,
+        path: &str,
+        query: &str,
+        body: &[u8],
+        secret_key: &str,
+    ) -> TestRequest {
+        let amz_date = amz_date();
+        let payload_hash = sha256_hex(body);
+
+        let query_params: Vec<(String, String)> = query
+            .split('&')
+            .filter(|p| !p.is_empty())
+            .map(|p| {
+                let (k, v) = p.split_once('=').unwrap_or((p, ""));
+                (uri_decode(k), uri_decode(v))
+            })
+            .collect();
+
+        let headers = vec![
+            ("x-amz-content-sha256".to_string(), payload_hash.clone()),
+            ("x-amz-date".to_string(), amz_date.clone()),
+        ];
+
+        let request = CanonicalRequest {
+            method: method.as_str(),
+            path,
+            query: &query_params,
+            headers: &headers,
+            payload_hash: &payload_hash,
+        };
+
+        let scope = SigningScope {
+            amz_date: &amz_date,
+            region: "us-east-1",
+            service: "s3",
+        };
+
+        let auth = authorization(&self.access_key, secret_key, &scope, &request);
+        let url = if query.is_empty() {
+            path.to_string()
+        } else {
+            format!("{path}?{query}")
+        };
+
+        self.server
+            .method(method, &url)
+            .add_header(AUTHORIZATION, auth)
+            .add_header("x-amz-content-sha256", payload_hash)
+            .add_header("x-amz-date", amz_date)
+            .bytes(body.to_vec().into())
+    }
+}
+
+fn xml_value(xml: &str, tag: &str) -> String {
+    let open = format!("<{tag}>");
+    let start = xml.find(&open).expect("tag not found") + open.len();
+    let end = xml[start..].find(&format!("</{tag}>")).expect("tag not closed");
+
+    xml[start..start + end].to_string()
+}
+
+#[tokio::test]
+#[serial]
+async fn test_s3_objects() {
+    let app = TestApp::new().await;
+    let s3 = S3TestClient::new(&app).await;
+    let bucket_path = format!("/{BUCKET}");
+
+    let resp = s3.request(Method::PUT, &bucket_path, "", b"").await;
+    resp.assert_status_ok();
+
+    let resp = s3.request(Method::GET, "/", "", b"").await;
+    resp.assert_status_ok();
+    assert!(resp.text().contains(&format!("<Name>{BUCKET}</Name>")));
+
+    // PutObject and GetObject
+    let file_bytes = include_bytes!("../../../handlers/rest/direct/tests/README.MD");
+    let object_path = format!("/{BUCKET}/docs/readme.md");
+
+    let resp = s3.request(Method::PUT, &object_path, "", file_bytes).await;
+    resp.assert_status_ok();
+    assert!(resp.headers().contains_key(ETAG));
+
+    let resp = s3
+        .request(Method::GET, &object_path, "", b"")
+        .add_header(RANGE, "bytes=0-9")
+        .await;
+
+    resp.assert_status(StatusCode::PARTIAL_CONTENT);
+    assert_eq!(resp.as_bytes().as_ref(), &file_bytes[..10]);
+
+    // ListObjectsV2
+    let resp = s3
+        .request(Method::GET, &bucket_path, "delimiter=%2F&list-type=2", b"")
+        .await;
+
+    resp.assert_status_ok();
+    assert!(resp.text().contains("<CommonPrefixes><Prefix>docs/</Prefix></CommonPrefixes>"));
+
+    let resp = s3
+        .request(Method::GET, &bucket_path, "list-type=2&prefix=docs%2F", b"")
+        .await;
+
+    resp.assert_status_ok();
+    assert!(resp.text().contains("<Key>docs/readme.md</Key>"));
+
+    // multipart upload, with parts sent out of order
+    let object_path = format!("/{BUCKET}/parts.bin");
+    let resp = s3.request(Method::POST, &object_path, "uploads", b"").await;
+    resp.assert_status_ok();
+
+    let upload_id = xml_value(&resp.text(), "UploadId");
+    for (part_number, data) in [(2, b"world".as_slice()), (1, b"hello ".as_slice())] {
+        let query = format!("partNumber={part_number}&uploadId={upload_id}");
+        let resp = s3.request(Method::PUT, &object_path, &query, data).await;
+        resp.assert_status_ok();
+    }
+
+    let complete = b"<CompleteMultipartUpload><Part><PartNumber>1</PartNumber></Part><Part><PartNumber>2</PartNumber></Part></CompleteMultipartUpload>";
+    let query = format!("uploadId={upload_id}");
+    let resp = s3.request(Method::POST, &object_path, &query, complete).await;
+    resp.assert_status_ok();
+
+    let resp = s3.request(Method::GET, &object_path, "", b"").await;
+    resp.assert_status_ok();
+    assert_eq!(resp.text(), "hello world");
+
+    // DeleteObject
+    let resp = s3.request(Method::DELETE, &object_path, "", b"").await;
+    resp.assert_status(StatusCode::NO_CONTENT);
+
+    let resp = s3.request(Method::GET, &object_path, "", b"").await;
+    resp.assert_status_not_found();
+    assert!(resp.text().contains("<Code>NoSuchKey</Code>"));
+
+    if let Err(err) = std::fs::remove_dir_all(BUCKET) {
+        println!("{err}");
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_s3_invalid_signature() {
+    let app = TestApp::new().await;
+    let s3 = S3TestClient::new(&app).await;
+
+    let resp = s3
+        .signed(Method::GET, "/", "", b"", "wrong-secret-key")
+        .await;
+
+    resp.assert_status_forbidden();
+    assert!(resp.text().contains("<Code>SignatureDoesNotMatch</Code>"));
+
+    let resp = s3.server.get("/").await;
+    resp.assert_status_forbidden();
+    assert!(resp.text().contains("<Code>AccessDenied</Code>"));
+}
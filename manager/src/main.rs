@@ -5,9 +5,13 @@ use rbatis::RBatis;
 use tokio::{net::TcpListener, sync::Mutex};
 use tokio_util::sync::CancellationToken;
 
-use crate::ops::process_request;
+use crate::{
+    ops::{process_request, restore_services},
+    state::{ServicesState, services_file},
+};
 
 mod ops;
+mod state;
 
 #[cfg(test)]
 mod tests;
@@ -25,8 +29,8 @@ struct Manager {
 }
 
 impl Manager {
-    fn new(port: Option<u16>) -> Self {
-        let inner = ServiceManager::new(port);
+    fn new(port: Option<u16>, state: ServicesState) -> Self {
+        let inner = ServiceManager::new(port, state);
         Self {
             inner: Arc::new(inner),
         }
@@ -35,6 +39,8 @@ impl Manager {
     /// start the service manager at the provided port. this is a tcp listener opened at the
     /// connected port.
     async fn start(&self) -> AppResult<()> {
+        restore_services(self.inner.clone()).await;
+
         let token = self.token();
         tokio::select! {
            run = self.run() => {
@@ -112,7 +118,7 @@ impl Manager {
 
 impl Default for Manager {
     fn default() -> Self {
-        Self::new(None)
+        Self::new(None, ServicesState::default())
     }
 }
 
@@ -128,6 +134,9 @@ impl Clone for Manager {
 struct ServiceManager {
     tasks: Mutex<Vec<ServiceTask>>,
 
+    /// services recorded in the state file
+    state: Mutex<ServicesState>,
+
     /// cancellation token used for stopping this manager
     token: CancellationToken,
 
@@ -135,8 +144,12 @@ struct ServiceManager {
 }
 
 impl ServiceManager {
-    fn new(port: Option<u16>) -> Self {
-        let mut manager = Self::default();
+    fn new(port: Option<u16>, state: ServicesState) -> Self {
+        let mut manager = Self {
+            state: Mutex::new(state),
+            ..Default::default()
+        };
+
         if let Some(port) = port {
             manager.port = port;
         }
@@ -144,6 +157,24 @@ impl ServiceManager {
         manager
     }
 
+    /// a random id which is not used by running or recorded services.
+    async fn new_id(&self) -> AppResult<u8> {
+        let tasks = self.tasks.lock().await;
+        let state = self.state.lock().await;
+
+        let used = |id: u8| tasks.iter().any(|t| t.id == id) || state.get(id).is_some();
+        if (0..=u8::MAX).all(used) {
+            return Err(anyhow::Error::msg("no service id is available."));
+        }
+
+        loop {
+            let id = rand::random();
+            if !used(id) {
+                break Ok(id);
+            }
+        }
+    }
+
     pub async fn get_task(&self, svc_id: u8) -> AppResult<ServiceTask> {
         let tasks = self.tasks.lock().await;
 
@@ -177,6 +208,7 @@ impl Default for ServiceManager {
     fn default() -> Self {
         Self {
             tasks: Mutex::new(vec![]),
+            state: Mutex::new(ServicesState::default()),
             token: CancellationToken::new(),
             port: DEFAULT_PORT,
         }
@@ -192,9 +224,9 @@ pub struct ServiceTask {
 }
 
 impl ServiceTask {
-    pub fn new(config: &ServiceConfig) -> Self {
+    pub fn new(id: u8, config: &ServiceConfig) -> Self {
         Self {
-            id: rand::random(),
+            id,
             config: config.clone(),
             token: None,
             db: Arc::new(RBatis::new()),
@@ -209,7 +241,8 @@ async fn main() -> AppResult<()> {
     let args: Vec<String> = std::env::args().collect();
     let port = args.get(1).map(|p| p.parse().unwrap_or(DEFAULT_PORT));
 
-    let manager = Manager::new(port);
+    let state = ServicesState::load(&services_file()?).await?;
+    let manager = Manager::new(port, state);
     manager.start().await?;
 
     Ok(())
@@ -1,48 +1,45 @@
 use std::sync::Arc;
 
 use anyhow::anyhow;
-use bincode::config;
 use ppd_shared::{
-    opts::{ClientDetails, ClientInfo, Response, ServiceConfig, ServiceInfo, ServiceRequest},
+    opts::{
+        ClientDetails, ClientInfo, ClientKeyInfo, NewClientKey, ReissuedToken, Response,
+        ServiceConfig, ServiceInfo, ServiceRequest,
+    },
     tools::AppSecrets,
 };
 use ppdrive::{
     db::init_db,
     plugin::service::Service,
-    tools::{create_client, get_clients, regenerate_token},
+    tools::{
+        create_admin, create_client, create_client_key, get_client_keys, get_clients,
+        regenerate_token, reissue_tokens, revoke_client_key,
+    },
 };
-use tokio::{io::AsyncReadExt, net::TcpStream};
+use tokio::net::TcpStream;
 use tokio_util::sync::CancellationToken;
 use tracing::Instrument;
 
-use crate::{AppResult, ServiceManager, ServiceTask, SharedManager};
+use crate::{
+    AppResult, ServiceManager, ServiceTask, SharedManager,
+    state::SavedService,
+};
 
-/// adds a new service to the task pool
-pub async fn start_service(
-    manager: SharedManager,
-    config: ServiceConfig,
-    socket: &mut TcpStream,
-) -> AppResult<u8> {
+/// start a service in the background and add it to the task pool
+async fn launch_service(manager: &SharedManager, id: u8, config: ServiceConfig) -> AppResult<()> {
     let db_url = &config.base.db_url;
     let db = init_db(db_url).await.map_err(|err| anyhow!(err))?;
 
     let token = CancellationToken::new();
     let db = Arc::new(db);
 
-    let mut task = ServiceTask::new(&config);
+    let mut task = ServiceTask::new(id, &config);
     task.token = Some(token.clone());
     task.db = db.clone();
 
     let mut tasks = manager.tasks.lock().await;
-    let id = task.id;
     tasks.push(task);
-
     std::mem::drop(tasks); // drop tasks MutexGuard to prevent deadlock
-    let resp = Response::success(id).message(format!("service added to manager with id {id}."));
-
-    resp.write(socket)
-        .await
-        .map_err(|err| anyhow!(err.to_string()))?;
 
     tokio::spawn(
         async move {
@@ -54,27 +51,136 @@ pub async fn start_service(
         .instrument(tracing::info_span!("ppd_start_service")),
     );
 
+    Ok(())
+}
+
+/// record a service in the state file
+async fn save_service(
+    manager: &SharedManager,
+    id: u8,
+    config: &ServiceConfig,
+    autostart: bool,
+) -> AppResult<()> {
+    let svc = SavedService {
+        id,
+        ty: config.ty,
+        autostart,
+        config: config.clone(),
+    };
+
+    manager.state.lock().await.insert(svc).await
+}
+
+/// adds a new service to the task pool. the service is recorded, and launched again when
+/// the manager starts if `autostart` is set.
+pub async fn start_service(
+    manager: SharedManager,
+    config: ServiceConfig,
+    autostart: bool,
+    socket: &mut TcpStream,
+) -> AppResult<u8> {
+    let id = manager.new_id().await?;
+    launch_service(&manager, id, config.clone()).await?;
+
+    if let Err(err) = save_service(&manager, id, &config, autostart).await {
+        tracing::error!("unable to record service {id}: {err}")
+    }
+
+    let resp = Response::success(id).message(format!("service added to manager with id {id}."));
+
+    resp.write(socket)
+        .await
+        .map_err(|err| anyhow!(err.to_string()))?;
+
+    Ok(id)
+}
+
+/// record a service without starting it. it's launched when the manager starts.
+async fn record_service(manager: SharedManager, config: ServiceConfig) -> AppResult<u8> {
+    let id = manager.new_id().await?;
+    save_service(&manager, id, &config, true).await?;
+
     Ok(id)
 }
 
-/// stop a running service with the given id
-pub async fn stop_service(manager: SharedManager, id: u8, socket: &mut TcpStream) -> AppResult<()> {
+/// start a recorded service which is not running
+async fn start_saved_service(manager: SharedManager, id: u8) -> AppResult<()> {
+    if manager.get_task(id).await.is_ok() {
+        return Err(anyhow!("service {id} is already running."));
+    }
+
+    let config = manager
+        .state
+        .lock()
+        .await
+        .get(id)
+        .map(|svc| svc.config())
+        .ok_or(anyhow!("service with id {id} is not recorded."))?;
+
+    launch_service(&manager, id, config).await
+}
+
+/// launch recorded services with autostart set, with their original ids
+pub async fn restore_services(manager: SharedManager) {
+    let services: Vec<_> = {
+        let state = manager.state.lock().await;
+        state
+            .services()
+            .iter()
+            .filter(|svc| svc.autostart)
+            .map(|svc| (svc.id, svc.config()))
+            .collect()
+    };
+
+    for (id, config) in services {
+        match launch_service(&manager, id, config).await {
+            Ok(_) => tracing::info!("service {id} restored."),
+            Err(err) => tracing::error!("unable to restore service {id}: {err}"),
+        }
+    }
+}
+
+/// stop a running service with the given id. The service stays recorded, without autostart, so
+/// it can be started again, unless `remove` is set.
+pub async fn stop_service(
+    manager: SharedManager,
+    id: u8,
+    remove: bool,
+    socket: &mut TcpStream,
+) -> AppResult<()> {
     let mut tasks = manager.tasks.lock().await;
     let item = tasks.iter().enumerate().find(|(_, item)| item.id == id);
 
-    let resp = match item {
+    let running = match item {
         Some((idx, item)) => {
             if let Some(token) = &item.token {
                 token.cancel();
             }
 
             tasks.remove(idx);
-            Response::success(())
-                .message(format!("service {id} removed from manager successfully."))
+            true
         }
-        None => Response::error(()).message(format!(
+        None => false,
+    };
+
+    std::mem::drop(tasks);
+    let mut state = manager.state.lock().await;
+    let saved = match remove {
+        true => state.remove(id).await?,
+        false => state.disable(id).await?,
+    };
+
+    let resp = if running || saved {
+        let msg = match remove {
+            true => format!("service {id} removed from manager successfully."),
+            false => format!("service {id} stopped. start it again with `ppdrive start --svc-id {id}`."),
+        };
+
+        Response::success(()).message(msg)
+    } else {
+        Response::error(()).message(format!(
             "unable to cancel service with id {id}. it's propably not running."
-        )),
+        ))
     };
 
     resp.write(socket)
@@ -84,13 +190,29 @@ pub async fn stop_service(manager: SharedManager, id: u8, socket: &mut TcpStream
     Ok(())
 }
 
-/// list running services
+/// list running and recorded services
 pub async fn list_services(manager: SharedManager, socket: &mut TcpStream) -> AppResult<()> {
     let tasks = manager.tasks.lock().await;
-    let items: Vec<ServiceInfo> = tasks.iter().map(|s| s.into()).collect();
+    let state = manager.state.lock().await;
+
+    let autostart = |id: u8| state.get(id).is_some_and(|svc| svc.autostart);
+    let mut items: Vec<ServiceInfo> = tasks
+        .iter()
+        .map(|t| service_info(t.id, &t.config, true, autostart(t.id)))
+        .collect();
+
+    let stopped = state
+        .services()
+        .iter()
+        .filter(|svc| !tasks.iter().any(|t| t.id == svc.id))
+        .map(|svc| service_info(svc.id, &svc.config(), false, svc.autostart));
 
-    let resp =
-        Response::success(items).message(format!("list generated for {} service(s)", tasks.len()));
+    items.extend(stopped);
+
+    let resp = Response::success(items).message(format!(
+        "list generated for {} running service(s)",
+        tasks.len()
+    ));
 
     resp.write(socket)
         .await
@@ -121,15 +243,15 @@ async fn refresh_client_token(
     manager: SharedManager,
     svc_id: u8,
     client_id: String,
-) -> AppResult<String> {
+) -> AppResult<ClientDetails> {
     let task = manager.get_task(svc_id).await?;
     let secrets = AppSecrets::read().await.map_err(|err| anyhow!(err))?;
 
-    let token = regenerate_token(&task.db, &secrets, &client_id)
+    let client = regenerate_token(&task.db, &secrets, &client_id)
         .await
         .map_err(|err| anyhow!(err))?;
 
-    Ok(token)
+    Ok(client)
 }
 
 /// create new client for a specified
@@ -140,25 +262,166 @@ async fn get_client_list(manager: SharedManager, svc_id: u8) -> AppResult<Vec<Cl
     Ok(clients)
 }
 
+/// create a named key for a client of a specified service
+async fn add_client_key(
+    manager: SharedManager,
+    svc_id: u8,
+    client_id: String,
+    opts: NewClientKey,
+) -> AppResult<ClientDetails> {
+    let task = manager.get_task(svc_id).await?;
+    let secrets = AppSecrets::read().await.map_err(|err| anyhow!(err))?;
+
+    let key = create_client_key(&task.db, &secrets, &client_id, &opts)
+        .await
+        .map_err(|err| anyhow!(err))?;
+
+    Ok(key)
+}
+
+/// list keys of a client of a specified service
+async fn list_client_keys(
+    manager: SharedManager,
+    svc_id: u8,
+    client_id: String,
+) -> AppResult<Vec<ClientKeyInfo>> {
+    let task = manager.get_task(svc_id).await?;
+    let keys = get_client_keys(&task.db, &client_id)
+        .await
+        .map_err(|err| anyhow!(err))?;
+
+    Ok(keys)
+}
+
+/// revoke a client key of a specified service
+async fn revoke_key(manager: SharedManager, svc_id: u8, key_id: String) -> AppResult<()> {
+    let task = manager.get_task(svc_id).await?;
+    revoke_client_key(&task.db, &key_id)
+        .await
+        .map_err(|err| anyhow!(err))?;
+
+    Ok(())
+}
+
+/// tokens re-issued by [rotate_secrets].
+struct RotatedSecrets {
+    key_id: u32,
+    tokens: Vec<ReissuedToken>,
+
+    /// recorded services whose database couldn't be reached, with the reason.
+    skipped: Vec<(u8, String)>,
+}
+
+/// make a new secret key current, keeping `keep` previous keys, and re-issue client tokens
+/// of running and recorded services with it.
+async fn rotate_secrets(manager: SharedManager, keep: u8) -> AppResult<RotatedSecrets> {
+    let mut secrets = AppSecrets::read().await.map_err(|err| anyhow!(err))?;
+    let key_id = secrets.rotate(keep);
+    secrets.write().await.map_err(|err| anyhow!(err))?;
+
+    let tasks = manager.tasks.lock().await;
+    let mut tokens = Vec::new();
+
+    for task in tasks.iter() {
+        let reissued = reissue_tokens(&task.db, &secrets, task.id)
+            .await
+            .map_err(|err| anyhow!(err))?;
+
+        tokens.extend(reissued);
+    }
+
+    let running: Vec<u8> = tasks.iter().map(|task| task.id).collect();
+    std::mem::drop(tasks);
+
+    // stopped services keep their clients, whose tokens would stop working once the
+    // previous key is dropped
+    let stopped: Vec<SavedService> = manager
+        .state
+        .lock()
+        .await
+        .services()
+        .iter()
+        .filter(|svc| !running.contains(&svc.id))
+        .cloned()
+        .collect();
+
+    let mut skipped = Vec::new();
+    for svc in stopped {
+        let reissued = match init_db(&svc.config.base.db_url).await {
+            Ok(db) => reissue_tokens(&db, &secrets, svc.id)
+                .await
+                .map_err(|err| err.to_string()),
+            Err(err) => Err(err.to_string()),
+        };
+
+        match reissued {
+            Ok(reissued) => tokens.extend(reissued),
+            Err(err) => skipped.push((svc.id, err)),
+        }
+    }
+
+    Ok(RotatedSecrets {
+        key_id,
+        tokens,
+        skipped,
+    })
+}
+
+/// create an admin user for a specified service
+async fn create_service_admin(
+    manager: SharedManager,
+    svc_id: u8,
+    username: String,
+    password: String,
+) -> AppResult<String> {
+    let task = manager.get_task(svc_id).await?;
+    let id = create_admin(&task.db, &username, &password, &task.config.auth.password)
+        .await
+        .map_err(|err| anyhow!(err))?;
+
+    Ok(id)
+}
+
 pub async fn process_request(
     socket: &mut TcpStream,
     manager: Arc<ServiceManager>,
 ) -> AppResult<()> {
-    let mut buf = [0u8; 1024];
-    let n = socket.read(&mut buf).await?;
-
-    if n == 0 {
+    let Some(req) = ServiceRequest::read(socket)
+        .await
+        .map_err(|err| anyhow!(err))?
+    else {
         return Ok(());
-    }
+    };
 
-    let (req, _) = bincode::decode_from_slice::<ServiceRequest, _>(&buf, config::standard())?;
     match req {
-        ServiceRequest::Add(config) => {
-            start_service(manager, config, socket).await?;
+        ServiceRequest::Add(config, autostart) => {
+            start_service(manager, *config, autostart, socket).await?;
+            Ok(())
+        }
+
+        ServiceRequest::Save(config) => {
+            let resp = match record_service(manager, *config).await {
+                Ok(id) => Response::success(id).message(format!(
+                    "service {id} saved. it will be launched when the manager starts."
+                )),
+                Err(err) => Response::error(0).message(err.to_string()),
+            };
+
+            resp.write(socket).await.map_err(|err| anyhow!(err))?;
+            Ok(())
+        }
+
+        ServiceRequest::Launch(id) => {
+            let resp = match start_saved_service(manager, id).await {
+                Ok(_) => Response::success(()).message(format!("service {id} launched.")),
+                Err(err) => Response::error(()).message(err.to_string()),
+            };
+
+            resp.write(socket).await.map_err(|err| anyhow!(err))?;
             Ok(())
         }
 
-        ServiceRequest::Cancel(id) => stop_service(manager, id, socket).await,
+        ServiceRequest::Cancel(id, remove) => stop_service(manager, id, remove, socket).await,
         ServiceRequest::List => list_services(manager, socket).await,
 
         ServiceRequest::Stop => {
@@ -182,7 +445,7 @@ pub async fn process_request(
 
         ServiceRequest::RefreshClientToken(svc_id, client_id) => {
             let resp = match refresh_client_token(manager, svc_id, client_id).await {
-                Ok(token) => Response::success(Some(token))
+                Ok(client) => Response::success(Some(client))
                     .message("client token regenerated successfully."),
                 Err(err) => Response::error(None).message(err.to_string()),
             };
@@ -204,6 +467,77 @@ pub async fn process_request(
             Ok(())
         }
 
+        ServiceRequest::AddClientKey(svc_id, client_id, opts) => {
+            let resp = match add_client_key(manager, svc_id, client_id, opts).await {
+                Ok(key) => Response::success(Some(key)).message("client key created successfully."),
+                Err(err) => Response::error(None).message(err.to_string()),
+            };
+
+            resp.write(socket).await.map_err(|err| anyhow!(err))?;
+            Ok(())
+        }
+
+        ServiceRequest::ListClientKeys(svc_id, client_id) => {
+            let resp = match list_client_keys(manager, svc_id, client_id).await {
+                Ok(keys) => {
+                    let len = keys.len();
+                    Response::success(keys).message(format!("total {len} keys available."))
+                }
+                Err(err) => Response::error(vec![]).message(err.to_string()),
+            };
+
+            resp.write(socket).await.map_err(|err| anyhow!(err))?;
+            Ok(())
+        }
+
+        ServiceRequest::RevokeClientKey(svc_id, key_id) => {
+            let resp = match revoke_key(manager, svc_id, key_id).await {
+                Ok(_) => Response::success(()).message("client key revoked successfully."),
+                Err(err) => Response::error(()).message(err.to_string()),
+            };
+
+            resp.write(socket).await.map_err(|err| anyhow!(err))?;
+            Ok(())
+        }
+
+        ServiceRequest::RotateSecrets(keep) => {
+            let resp = match rotate_secrets(manager, keep).await {
+                Ok(rotated) => {
+                    let RotatedSecrets {
+                        key_id,
+                        tokens,
+                        skipped,
+                    } = rotated;
+
+                    let mut msg = format!(
+                        "secrets rotated to key {key_id}. tokens issued with previous keys remain valid until the keys are dropped."
+                    );
+
+                    for (id, err) in skipped {
+                        msg.push_str(&format!(
+                            "\ntokens of service {id} were not re-issued: {err}"
+                        ));
+                    }
+
+                    Response::success(tokens).message(msg)
+                }
+                Err(err) => Response::error(vec![]).message(err.to_string()),
+            };
+
+            resp.write(socket).await.map_err(|err| anyhow!(err))?;
+            Ok(())
+        }
+
+        ServiceRequest::CreateAdmin(svc_id, username, password) => {
+            let resp = match create_service_admin(manager, svc_id, username, password).await {
+                Ok(id) => Response::success(Some(id)).message("admin created successfully."),
+                Err(err) => Response::error(None).message(err.to_string()),
+            };
+
+            resp.write(socket).await.map_err(|err| anyhow!(err))?;
+            Ok(())
+        }
+
         ServiceRequest::CheckStatus => {
             let resp = Response::success(());
             resp.write(socket).await.map_err(|err| anyhow!(err))?;
@@ -213,13 +547,13 @@ pub async fn process_request(
     }
 }
 
-impl From<&ServiceTask> for ServiceInfo {
-    fn from(value: &ServiceTask) -> Self {
-        Self {
-            id: value.id,
-            port: value.config.base.port,
-            ty: value.config.ty,
-            auth_modes: value.config.auth.modes.clone(),
-        }
+fn service_info(id: u8, config: &ServiceConfig, running: bool, autostart: bool) -> ServiceInfo {
+    ServiceInfo {
+        id,
+        port: config.base.port,
+        ty: config.ty,
+        auth_modes: config.auth.modes.clone(),
+        running,
+        autostart,
     }
 }
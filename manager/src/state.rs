@@ -0,0 +1,146 @@
+//! services recorded by the manager, so they can be launched again when the manager
+//! restarts.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use ppd_shared::{
+    opts::{ServiceConfig, ServiceType},
+    tools::root_dir,
+};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use crate::AppResult;
+
+/// name of the state file in [root_dir].
+pub const SERVICES_FILENAME: &str = "ppd_services.toml";
+
+/// path of the state file, instead of [SERVICES_FILENAME] in [root_dir].
+pub const SERVICES_FILE_ENV: &str = "PPDRIVE_SERVICES_FILE";
+
+/// path of the state file.
+pub fn services_file() -> AppResult<PathBuf> {
+    match std::env::var_os(SERVICES_FILE_ENV) {
+        Some(path) => Ok(PathBuf::from(path)),
+        None => Ok(root_dir()
+            .map_err(|err| anyhow!(err.to_string()))?
+            .join(SERVICES_FILENAME)),
+    }
+}
+
+/// a service recorded in the state file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedService {
+    pub id: u8,
+    pub ty: ServiceType,
+
+    /// whether the service is launched when the manager starts.
+    pub autostart: bool,
+    pub config: ServiceConfig,
+}
+
+impl SavedService {
+    /// config of the service, with its type.
+    pub fn config(&self) -> ServiceConfig {
+        ServiceConfig {
+            ty: self.ty,
+            ..self.config.clone()
+        }
+    }
+}
+
+/// services recorded by the manager. Changes are written to the state file right away, so
+/// services survive crashes too.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ServicesState {
+    #[serde(default)]
+    services: Vec<SavedService>,
+
+    /// path of the state file. Nothing is written when it's not set.
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl ServicesState {
+    /// load the state file at `path`. A missing file is an empty state.
+    pub async fn load(path: &Path) -> AppResult<Self> {
+        let mut state = match tokio::fs::read_to_string(path).await {
+            Ok(content) => toml::from_str(&content).map_err(|err| {
+                anyhow!("invalid services file '{}': {err}", path.display())
+            })?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(err) => return Err(err.into()),
+        };
+
+        state.path = Some(path.to_path_buf());
+        Ok(state)
+    }
+
+    pub fn services(&self) -> &[SavedService] {
+        &self.services
+    }
+
+    pub fn get(&self, id: u8) -> Option<&SavedService> {
+        self.services.iter().find(|svc| svc.id == id)
+    }
+
+    /// record a service, replacing a service with the same id.
+    pub async fn insert(&mut self, svc: SavedService) -> AppResult<()> {
+        self.services.retain(|item| item.id != svc.id);
+        self.services.push(svc);
+        self.save().await
+    }
+
+    /// keep a service from being launched when the manager starts. Returns whether the
+    /// service was recorded.
+    pub async fn disable(&mut self, id: u8) -> AppResult<bool> {
+        let Some(svc) = self.services.iter_mut().find(|svc| svc.id == id) else {
+            return Ok(false);
+        };
+
+        svc.autostart = false;
+        self.save().await?;
+        Ok(true)
+    }
+
+    /// remove a service. Returns whether the service was recorded.
+    pub async fn remove(&mut self, id: u8) -> AppResult<bool> {
+        let len = self.services.len();
+        self.services.retain(|svc| svc.id != id);
+
+        if self.services.len() == len {
+            return Ok(false);
+        }
+
+        self.save().await?;
+        Ok(true)
+    }
+
+    /// write the state file. The file is replaced at once, so a crash while writing
+    /// doesn't corrupt it. Configs hold credentials (i.e database urls), so the file is only
+    /// readable by its owner.
+    async fn save(&self) -> AppResult<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let content = toml::to_string_pretty(self)?;
+        let tmp = path.with_extension("toml.tmp");
+        let _ = tokio::fs::remove_file(&tmp).await;
+
+        let mut opts = tokio::fs::OpenOptions::new();
+        opts.create_new(true).write(true);
+
+        #[cfg(unix)]
+        opts.mode(0o600);
+
+        let mut file = opts.open(&tmp).await?;
+        file.write_all(content.as_bytes()).await?;
+        file.flush().await?;
+
+        tokio::fs::rename(&tmp, path).await?;
+
+        Ok(())
+    }
+}
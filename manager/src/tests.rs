@@ -1,11 +1,13 @@
 use crate::{
     AppResult, Manager,
     ops::{list_services, start_service, stop_service},
+    state::{SavedService, ServicesState},
 };
 use anyhow::anyhow;
 use ppdrive::plugin::service::Service;
-use ppd_shared::opts::ServiceConfig;
+use ppd_shared::opts::{Response, ServiceConfig, ServiceRequest, ServiceType};
 use serial_test::serial;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
 #[tokio::test]
 #[serial]
@@ -31,8 +33,10 @@ async fn test_start_and_stop_manager() -> AppResult<()> {
 #[serial]
 async fn test_start_service() -> AppResult<()> {
     let manager = Manager::default();
-    let mut config = ServiceConfig::default();
-    config.auto_install = true;
+    let config = ServiceConfig {
+        auto_install: true,
+        ..Default::default()
+    };
 
     // client is responsible for initializing the service before before sending a
     // request to start the service
@@ -44,7 +48,7 @@ async fn test_start_service() -> AppResult<()> {
     let handle = manager.start_background().await;
     let mut socket = manager.connect().await?;
 
-    let start = start_service(shared, config, &mut socket).await;
+    let start = start_service(shared, config, false, &mut socket).await;
     assert!(start.is_ok());
     manager.close().await;
 
@@ -58,8 +62,10 @@ async fn test_start_service() -> AppResult<()> {
 #[serial]
 async fn test_stop_service() -> AppResult<()> {
     let manager = Manager::default();
-    let mut config = ServiceConfig::default();
-    config.auto_install = true;
+    let config = ServiceConfig {
+        auto_install: true,
+        ..Default::default()
+    };
 
     // client is responsible for initializing the service before before sending a
     // request to start the service
@@ -71,8 +77,8 @@ async fn test_stop_service() -> AppResult<()> {
     let handle = manager.start_background().await;
     let mut socket = manager.connect().await?;
 
-    let id = start_service(shared.clone(), config, &mut socket).await?;
-    let stop = stop_service(shared, id, &mut socket).await;
+    let id = start_service(shared.clone(), config, false, &mut socket).await?;
+    let stop = stop_service(shared, id, false, &mut socket).await;
     assert!(stop.is_ok());
 
     manager.close().await;
@@ -80,3 +86,85 @@ async fn test_stop_service() -> AppResult<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+#[serial]
+async fn test_split_request() -> AppResult<()> {
+    let manager = Manager::default();
+    let handle = manager.start_background().await;
+
+    // requests are read whole, even when they arrive in several segments
+    let frame = ServiceRequest::CheckStatus
+        .to_frame()
+        .map_err(|err| anyhow!(err))?;
+    let (head, tail) = frame.split_at(2);
+
+    let mut socket = manager.connect().await?;
+    socket.write_all(head).await?;
+    socket.flush().await?;
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    socket.write_all(tail).await?;
+
+    let mut buf = Vec::new();
+    socket.read_to_end(&mut buf).await?;
+    let decoded = bincode::decode_from_slice::<Response<()>, _>(&buf, bincode::config::standard());
+    assert!(decoded.is_ok());
+
+    manager.close().await;
+    let _ = handle.await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_services_state() -> AppResult<()> {
+    let path = std::env::temp_dir().join("ppd_services_test.toml");
+    let _ = tokio::fs::remove_file(&path).await;
+
+    let mut config = ServiceConfig::default();
+    config.base.port = 5050;
+
+    let mut state = ServicesState::load(&path).await?;
+    assert!(state.services().is_empty());
+
+    for id in [7, 9] {
+        let svc = SavedService {
+            id,
+            ty: ServiceType::Grpc,
+            autostart: id == 7,
+            config: config.clone(),
+        };
+
+        state.insert(svc).await?;
+    }
+
+    assert!(state.remove(9).await?);
+    assert!(!state.remove(9).await?);
+
+    // configs hold credentials
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = tokio::fs::metadata(&path).await?.permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    // services are restored with their ids and configs
+    let state = ServicesState::load(&path).await?;
+    assert_eq!(state.services().len(), 1);
+
+    let svc = state.get(7).expect("service not recorded");
+    assert!(svc.autostart);
+    assert_eq!(svc.config().ty, ServiceType::Grpc);
+    assert_eq!(svc.config().base.port, 5050);
+
+    // stopped services stay recorded, without autostart
+    let mut state = state;
+    assert!(state.disable(7).await?);
+    let state = ServicesState::load(&path).await?;
+    assert!(state.get(7).is_some_and(|svc| !svc.autostart));
+
+    tokio::fs::remove_file(&path).await?;
+    Ok(())
+}
@@ -2,16 +2,24 @@ use std::path::PathBuf;
 
 use modeller::prelude::*;
 use ppd_shared::tools::root_dir;
+use rbatis::RBatis;
+use rbs::value;
+use serde::Deserialize;
 
 use crate::{
     DBResult,
+    db::DatabaseType,
+    errors::Error as DBError,
     models::{
         asset::Assets,
         bucket::Buckets,
-        client::Clients,
+        client::{ClientKeys, Clients},
         mime::{BucketMimes, Mimes},
         permission::AssetPermissions,
+        token::{PasswordResets, RefreshTokens},
+        upload::UploadSessions,
         user::Users,
+        version::AssetVersions,
     },
 };
 
@@ -20,24 +28,81 @@ fn modeller_path() -> DBResult<PathBuf> {
     Ok(path)
 }
 
-pub async fn run_migrations(url: &str) -> DBResult<()> {
+pub async fn run_migrations(db: &RBatis, url: &str) -> DBResult<()> {
+    check_usernames(db, url).await?;
+
     let mut config = ConfigBuilder::new()
         .db_url(url)
         .metadata_path(modeller_path()?)
         .build();
 
     Clients::write_stream(&mut config);
+    ClientKeys::write_stream(&mut config);
     Buckets::write_stream(&mut config);
     Mimes::write_stream(&mut config);
     BucketMimes::write_stream(&mut config);
     Users::write_stream(&mut config);
     Assets::write_stream(&mut config);
     AssetPermissions::write_stream(&mut config);
+    AssetVersions::write_stream(&mut config);
+    UploadSessions::write_stream(&mut config);
+    RefreshTokens::write_stream(&mut config);
+    PasswordResets::write_stream(&mut config);
 
     run_modeller(&config).await?;
     Ok(())
 }
 
+/// usernames are unique. Refuse to migrate databases with duplicate usernames rather than
+/// failing to create the unique index.
+async fn check_usernames(db: &RBatis, url: &str) -> DBResult<()> {
+    // new databases have no users yet
+    if !table_exists(db, url, "users").await? {
+        return Ok(());
+    }
+
+    let duplicates = Users::duplicate_usernames(db).await?;
+    if duplicates.is_empty() {
+        return Ok(());
+    }
+
+    Err(DBError::ServerError(format!(
+        "unable to migrate database: usernames must be unique, but these are used by more \
+        than one user: {}. rename the users and start again.",
+        duplicates.join(", ")
+    )))
+}
+
+/// whether the database at `url` has a table named `name`.
+async fn table_exists(db: &RBatis, url: &str, name: &str) -> DBResult<bool> {
+    use DatabaseType::*;
+
+    #[derive(Deserialize)]
+    struct Table {
+        #[allow(dead_code)]
+        name: String,
+    }
+
+    let sql = match url.parse()? {
+        Sqlite => "SELECT name FROM sqlite_master WHERE type = 'table' AND name = ?",
+        MySql => {
+            "SELECT table_name AS name FROM information_schema.tables \
+            WHERE table_schema = DATABASE() AND table_name = ?"
+        }
+        Postgres => {
+            "SELECT table_name AS name FROM information_schema.tables \
+            WHERE table_schema = current_schema() AND table_name = ?"
+        }
+        MsSql => {
+            "SELECT table_name AS name FROM information_schema.tables \
+            WHERE table_schema = SCHEMA_NAME() AND table_name = ?"
+        }
+    };
+
+    let tables: Vec<Table> = db.query_decode(sql, vec![value!(name)]).await?;
+    Ok(!tables.is_empty())
+}
+
 pub async fn clean_db() -> DBResult<()> {
     let path = modeller_path()?;
     tokio::fs::remove_file(path)
@@ -31,7 +31,7 @@ pub async fn init_db(url: &str) -> DBResult<RBatis> {
         }
     });
     
-    run_migrations(url).await?;
+    run_migrations(&rb, url).await?;
     Ok(rb)
 }
 
@@ -2,16 +2,21 @@ use crate::{
     DBResult,
     errors::Error as AppError,
     models::{
-        check_model, de_sqlite_bool,
+        IntoSerializer, aggregate, check_model, de_sqlite_bool,
         permission::{AssetPermissions, Permission},
         user::Users,
+        version::AssetVersions,
     },
 };
 use modeller::prelude::*;
-use rbatis::{RBatis, crud, impl_select, impl_select_page};
+use ppd_shared::api::{CursorKey, EntryType, ListCursor, ListFolderOptions, ListSort, SortOrder};
+use rbatis::{PageRequest, RBatis, crud, impl_select, impl_select_page};
 use rbs::value;
 use serde::{Deserialize, Serialize};
-use std::fmt::Display;
+use std::{
+    fmt::Display,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 #[derive(Default, Deserialize, Serialize)]
 pub enum AssetType {
@@ -79,14 +84,47 @@ pub struct Assets {
     #[serde(deserialize_with = "de_sqlite_bool")]
     public: bool,
     asset_type: u8,
+
+    /// size of files (bytes).
+    size: Option<u64>,
+
+    #[modeller(length = 255)]
+    mime: Option<String>,
+
+    /// unix timestamps. Not recorded for assets created by older versions.
+    created_at: Option<i64>,
+    updated_at: Option<i64>,
+
+    /// unix timestamp of when the asset was moved to the trash.
+    deleted_at: Option<i64>,
+
+    /// path of a trashed asset before it was deleted. Only set for the asset that was deleted,
+    /// not for the content of a deleted folder.
+    #[modeller(length = 3000)]
+    trashed_from: Option<String>,
 }
 
 crud!(Assets {});
 
 impl_select!(Assets{ select_by_path(path: &str, asset_type: u8) -> Option => "`WHERE (asset_path = #{path} OR custom_path = #{path}) AND asset_type = #{asset_type} LIMIT 1`" });
+impl_select!(Assets{ select_by_bucket(bucket_id: &u64, asset_type: u8) => "`WHERE bucket_id = #{bucket_id} AND asset_type = #{asset_type} ORDER BY asset_path`" });
 impl_select_page!(Assets { select_by_user(user_id: &u64) => "`WHERE user_id = #{user_id}`" });
+impl_select_page!(Assets { select_page_by_bucket(bucket_id: &u64) => "`WHERE bucket_id = #{bucket_id}`
+    if do_count == false:
+      ` ORDER BY asset_path`" });
+impl_select!(Assets{ select_trash(bucket_id: &u64, user_id: &u64) => "`WHERE bucket_id = #{bucket_id} AND user_id = #{user_id} AND trashed_from IS NOT NULL ORDER BY deleted_at DESC`" });
+impl_select!(Assets{ select_expired_trash(before: i64) => "`WHERE trashed_from IS NOT NULL AND deleted_at < #{before}`" });
 
 impl Assets {
+    pub async fn get(db: &RBatis, id: &u64) -> DBResult<Self> {
+        let asset = Assets::select_by_map(db, value! { "id": id })
+            .await?
+            .into_iter()
+            .next();
+
+        check_model(asset, "asset not found")
+    }
+
     pub async fn get_by_path(db: &RBatis, path: &str, asset_type: &AssetType) -> DBResult<Self> {
         let asset_type: u8 = asset_type.into();
         let asset = Assets::select_by_path(db, path, asset_type).await?;
@@ -94,6 +132,197 @@ impl Assets {
         check_model(asset, "asset not found")
     }
 
+    /// assets of the given type in a bucket, ordered by path.
+    pub async fn get_by_bucket(
+        db: &RBatis,
+        bucket_id: &u64,
+        asset_type: &AssetType,
+    ) -> DBResult<Vec<Self>> {
+        let assets = Assets::select_by_bucket(db, bucket_id, asset_type.into()).await?;
+        Ok(assets)
+    }
+
+    /// page `page_no` (starting at 1) of the assets of a bucket ordered by path, and the
+    /// total number of assets in the bucket.
+    pub async fn get_page_by_bucket(
+        db: &RBatis,
+        bucket_id: &u64,
+        page_no: u64,
+        page_size: u64,
+    ) -> DBResult<(Vec<Self>, u64)> {
+        let request = PageRequest::new(page_no, page_size);
+        let page = Assets::select_page_by_bucket(db, &request, bucket_id).await?;
+
+        Ok((page.records, page.total))
+    }
+
+    pub async fn count(db: &RBatis) -> DBResult<u64> {
+        aggregate(db, "SELECT COUNT(*) AS total FROM assets").await
+    }
+
+    /// total size (bytes) of files and their versions.
+    pub async fn total_size(db: &RBatis) -> DBResult<u64> {
+        let files = aggregate(db, "SELECT SUM(size) AS total FROM assets").await?;
+        let versions = aggregate(db, "SELECT SUM(size) AS total FROM asset_versions").await?;
+
+        Ok(files + versions)
+    }
+
+    /// assets inside `folder` at any depth, ordered by path (parents first).
+    pub async fn get_descendants(db: &RBatis, folder: &str) -> DBResult<Vec<Self>> {
+        let prefix = format!("{}/%", escape_like(folder));
+        let assets: Vec<Assets> = db
+            .query_decode(
+                "SELECT * FROM assets WHERE asset_path LIKE ? ESCAPE '!' ORDER BY asset_path",
+                vec![value!(prefix)],
+            )
+            .await?;
+
+        Ok(assets)
+    }
+
+    /// assets deleted by `user_id` from a bucket, most recent first. The content of deleted
+    /// folders is not included.
+    pub async fn get_trash(db: &RBatis, bucket_id: &u64, user_id: &u64) -> DBResult<Vec<Self>> {
+        let assets = Assets::select_trash(db, bucket_id, user_id).await?;
+        Ok(assets)
+    }
+
+    /// assets deleted before the unix timestamp `before`. The content of deleted folders is
+    /// not included.
+    pub async fn get_expired_trash(db: &RBatis, before: i64) -> DBResult<Vec<Self>> {
+        let assets = Assets::select_expired_trash(db, before).await?;
+        Ok(assets)
+    }
+
+    /// checks whether an asset of any type uses `path` as its path or custom path.
+    pub async fn path_exists(db: &RBatis, path: &str) -> DBResult<bool> {
+        for asset_type in [AssetType::File, AssetType::Folder] {
+            if Assets::select_by_path(db, path, u8::from(&asset_type))
+                .await?
+                .is_some()
+            {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// direct children of `folder` that `viewer` can read, as selected by `opts`. Entries
+    /// are returned after `after`, in pages of `limit`.
+    pub async fn list_children(
+        db: &RBatis,
+        folder: &str,
+        viewer: &Option<u64>,
+        opts: &ListFolderOptions,
+        after: Option<&ListCursor>,
+        limit: u64,
+    ) -> DBResult<Vec<Self>> {
+        let prefix = format!("{}/", escape_like(folder));
+        let mut sql = "SELECT * FROM assets WHERE asset_path LIKE ? ESCAPE '!' AND asset_path NOT LIKE ? ESCAPE '!'".to_string();
+        let mut args = vec![value!(format!("{prefix}%")), value!(format!("{prefix}%/%"))];
+
+        match viewer {
+            Some(user_id) => {
+                sql.push_str(" AND (public = ? OR user_id = ? OR id IN (SELECT asset_id FROM asset_permissions WHERE user_id = ? AND permission = ?))");
+                args.extend([
+                    value!(true),
+                    value!(user_id),
+                    value!(user_id),
+                    value!(u8::from(Permission::Read)),
+                ]);
+            }
+            None => {
+                sql.push_str(" AND public = ?");
+                args.push(value!(true));
+            }
+        }
+
+        if let Some(entry_type) = &opts.entry_type {
+            let asset_type = match entry_type {
+                EntryType::File => AssetType::File,
+                EntryType::Folder => AssetType::Folder,
+            };
+
+            sql.push_str(" AND asset_type = ?");
+            args.push(value!(u8::from(&asset_type)));
+        }
+
+        if let Some(mime) = &opts.mime {
+            let mime = mime.trim_end_matches("/*");
+            if mime.contains('/') {
+                sql.push_str(" AND mime = ?");
+                args.push(value!(mime));
+            } else {
+                sql.push_str(" AND mime LIKE ? ESCAPE '!'");
+                args.push(value!(format!("{}/%", escape_like(mime))));
+            }
+        }
+
+        let keys = sort_keys(opts.sort);
+        let (op, dir) = match opts.order {
+            SortOrder::Asc => (">", "ASC"),
+            SortOrder::Desc => ("<", "DESC"),
+        };
+
+        if let Some(cursor) = after {
+            if cursor.keys.len() != keys.len() {
+                return Err(AppError::ParseError("invalid cursor".to_string()));
+            }
+
+            // rows after the cursor in (keys..., id) order
+            let mut clauses = Vec::with_capacity(keys.len() + 1);
+            for idx in 0..=keys.len() {
+                let mut clause: Vec<String> = keys[..idx].iter().map(|k| format!("{k} = ?")).collect();
+                for key in &cursor.keys[..idx] {
+                    args.push(cursor_value(key));
+                }
+
+                match keys.get(idx) {
+                    Some(key) => {
+                        clause.push(format!("{key} {op} ?"));
+                        args.push(cursor_value(&cursor.keys[idx]));
+                    }
+                    None => {
+                        clause.push(format!("id {op} ?"));
+                        args.push(value!(cursor.id));
+                    }
+                }
+
+                clauses.push(format!("({})", clause.join(" AND ")));
+            }
+
+            sql.push_str(&format!(" AND ({})", clauses.join(" OR ")));
+        }
+
+        let order: Vec<String> = keys.iter().map(|k| format!("{k} {dir}")).collect();
+        sql.push_str(&format!(" ORDER BY {}, id {dir} LIMIT ?", order.join(", ")));
+        args.push(value!(limit));
+
+        let assets: Vec<Assets> = db.query_decode(&sql, args).await?;
+        Ok(assets)
+    }
+
+    /// cursor of a listing page ending with this asset.
+    pub fn cursor(&self, sort: ListSort, order: SortOrder) -> ListCursor {
+        let path = || CursorKey::Text(self.asset_path.clone());
+        let keys = match sort {
+            ListSort::Name => vec![path()],
+            ListSort::Size => vec![CursorKey::Int(self.size.unwrap_or_default() as i64)],
+            ListSort::Created => vec![CursorKey::Int(self.created_at.unwrap_or_default())],
+            ListSort::Updated => vec![CursorKey::Int(self.updated_at.unwrap_or_default())],
+            ListSort::Type => vec![CursorKey::Int(1 - i64::from(self.asset_type)), path()],
+        };
+
+        ListCursor {
+            sort,
+            order,
+            keys,
+            id: self.id(),
+        }
+    }
+
     pub async fn insert_group(db: &RBatis, values: Vec<NewAsset>) -> DBResult<()> {
         let mut tables = Vec::with_capacity(values.len());
 
@@ -110,11 +339,79 @@ impl Assets {
             public,
             custom_path,
             asset_path,
+            size,
+            mime,
         } = values;
 
         self.public = public;
         self.custom_path = custom_path;
         self.asset_path = asset_path;
+        self.updated_at = Some(unix_now());
+
+        if size.is_some() {
+            self.size = size;
+            self.mime = mime;
+        }
+
+        Assets::update_by_map(db, self, value! { "id": &self.id() }).await?;
+        Ok(())
+    }
+
+    /// replace the size and mime type of a file whose content was replaced.
+    pub async fn set_content(
+        &mut self,
+        db: &RBatis,
+        size: Option<u64>,
+        mime: Option<String>,
+    ) -> DBResult<()> {
+        self.size = size;
+        self.mime = mime;
+        self.updated_at = Some(unix_now());
+
+        Assets::update_by_map(db, self, value! { "id": &self.id() }).await?;
+        Ok(())
+    }
+
+    /// move the asset record to `asset_path` in bucket `bucket_id`. `mime` is the mime type
+    /// of the asset at its new path.
+    pub async fn relocate(
+        &mut self,
+        db: &RBatis,
+        asset_path: &str,
+        bucket_id: u64,
+        mime: Option<String>,
+    ) -> DBResult<()> {
+        self.asset_path = asset_path.to_string();
+        self.bucket_id = bucket_id;
+        self.mime = mime;
+        self.updated_at = Some(unix_now());
+
+        Assets::update_by_map(db, self, value! { "id": &self.id() }).await?;
+        Ok(())
+    }
+
+    /// move the record to `trash_path` in the trash. `trashed_from` is the path the asset was
+    /// deleted from, for the deleted asset itself.
+    pub async fn trash(
+        &mut self,
+        db: &RBatis,
+        trash_path: &str,
+        trashed_from: Option<String>,
+        deleted_at: i64,
+    ) -> DBResult<()> {
+        self.asset_path = trash_path.to_string();
+        self.trashed_from = trashed_from;
+        self.deleted_at = Some(deleted_at);
+
+        Assets::update_by_map(db, self, value! { "id": &self.id() }).await?;
+        Ok(())
+    }
+
+    /// move the record out of the trash, to `asset_path`.
+    pub async fn untrash(&mut self, db: &RBatis, asset_path: &str) -> DBResult<()> {
+        self.asset_path = asset_path.to_string();
+        self.trashed_from = None;
+        self.deleted_at = None;
 
         Assets::update_by_map(db, self, value! { "id": &self.id() }).await?;
         Ok(())
@@ -135,8 +432,9 @@ impl Assets {
     }
 
     pub async fn delete(&self, db: &RBatis) -> DBResult<()> {
-        // delete asset permissions
+        // delete asset permissions and versions
         AssetPermissions::delete_for_asset(db, &self.id()).await?;
+        AssetVersions::delete_for_asset(db, &self.id()).await?;
 
         // delete asset record
         Assets::delete_by_map(
@@ -150,6 +448,47 @@ impl Assets {
         Ok(())
     }
 
+    /// delete the asset at `path` and, if it's a folder, the assets inside it, with their
+    /// permissions and versions. Assets whose id is in `keep` are not deleted. Records are
+    /// deleted in a single transaction.
+    pub async fn delete_tree(db: &RBatis, path: &str, keep: &[u64]) -> DBResult<()> {
+        let mut filter = "(asset_path = ? OR asset_path LIKE ? ESCAPE '!')".to_string();
+        let mut args = vec![value!(path), value!(format!("{}/%", escape_like(path)))];
+
+        if !keep.is_empty() {
+            let marks = vec!["?"; keep.len()].join(", ");
+            filter.push_str(&format!(" AND id NOT IN ({marks})"));
+            args.extend(keep.iter().map(|id| value!(id)));
+        }
+
+        let tx = db.acquire_begin().await?;
+        let deleted = async {
+            for table in ["asset_permissions", "asset_versions"] {
+                let sql = format!(
+                    "DELETE FROM {table} WHERE asset_id IN (SELECT id FROM assets WHERE {filter})"
+                );
+                tx.exec(&sql, args.clone()).await?;
+            }
+
+            let sql = format!("DELETE FROM assets WHERE {filter}");
+            tx.exec(&sql, args).await?;
+
+            Ok::<_, rbatis::Error>(())
+        }
+        .await;
+
+        if let Err(err) = deleted {
+            if let Err(err) = tx.rollback().await {
+                tracing::error!("unable to roll back deletion of '{path}': {err}");
+            }
+
+            return Err(err.into());
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
     /// update asset sharing and ownership record
     pub async fn share(&self, db: &RBatis, sharing: &Vec<AssetSharing>) -> DBResult<()> {
         for opt in sharing {
@@ -205,6 +544,38 @@ impl Assets {
         &self.user_id
     }
 
+    pub fn bucket_id(&self) -> &u64 {
+        &self.bucket_id
+    }
+
+    pub fn asset_type(&self) -> AssetType {
+        AssetType::try_from(self.asset_type).unwrap_or_default()
+    }
+
+    pub fn size(&self) -> &Option<u64> {
+        &self.size
+    }
+
+    pub fn mime(&self) -> &Option<String> {
+        &self.mime
+    }
+
+    pub fn created_at(&self) -> &Option<i64> {
+        &self.created_at
+    }
+
+    pub fn updated_at(&self) -> &Option<i64> {
+        &self.updated_at
+    }
+
+    pub fn deleted_at(&self) -> &Option<i64> {
+        &self.deleted_at
+    }
+
+    pub fn trashed_from(&self) -> &Option<String> {
+        &self.trashed_from
+    }
+
     pub fn url_path(&self) -> String {
         let t = &self.asset_type;
         let asset_type = AssetType::try_from(*t).ok().unwrap_or_default();
@@ -215,6 +586,53 @@ impl Assets {
     }
 }
 
+#[derive(Serialize)]
+pub struct AssetSerializer {
+    path: String,
+    custom_path: Option<String>,
+    asset_type: AssetType,
+    public: bool,
+    url: String,
+    size: Option<u64>,
+    mime: Option<String>,
+    created_at: Option<i64>,
+    updated_at: Option<i64>,
+    deleted_at: Option<i64>,
+}
+
+impl IntoSerializer for Assets {
+    type Serializer = AssetSerializer;
+
+    async fn into_serializer(self, _: &RBatis) -> DBResult<Self::Serializer> {
+        let url = self.url_path();
+        let asset_type = self.asset_type.try_into()?;
+        let Assets {
+            asset_path: path,
+            custom_path,
+            public,
+            size,
+            mime,
+            created_at,
+            updated_at,
+            deleted_at,
+            ..
+        } = self;
+
+        Ok(AssetSerializer {
+            path,
+            custom_path,
+            asset_type,
+            public,
+            url,
+            size,
+            mime,
+            created_at,
+            updated_at,
+            deleted_at,
+        })
+    }
+}
+
 pub struct NewAsset {
     pub asset_path: String,
     pub custom_path: Option<String>,
@@ -222,6 +640,10 @@ pub struct NewAsset {
     pub bucket_id: u64,
     pub public: bool,
     pub asset_type: u8,
+
+    /// size (bytes) and mime type of files.
+    pub size: Option<u64>,
+    pub mime: Option<String>,
 }
 
 impl From<NewAsset> for Assets {
@@ -233,8 +655,11 @@ impl From<NewAsset> for Assets {
             bucket_id,
             public,
             asset_type,
+            size,
+            mime,
         } = value;
 
+        let now = unix_now();
         Assets {
             id: None,
             asset_path,
@@ -243,6 +668,12 @@ impl From<NewAsset> for Assets {
             bucket_id,
             public,
             asset_type,
+            size,
+            mime,
+            created_at: Some(now),
+            updated_at: Some(now),
+            deleted_at: None,
+            trashed_from: None,
         }
     }
 }
@@ -251,6 +682,10 @@ pub struct UpdateAssetValues {
     pub public: bool,
     pub custom_path: Option<String>,
     pub asset_path: String,
+
+    /// new size and mime type of a file whose content changed. Kept when `size` is `None`.
+    pub size: Option<u64>,
+    pub mime: Option<String>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -258,3 +693,37 @@ pub struct AssetSharing {
     pub user_id: String,
     pub permissions: Vec<Permission>,
 }
+
+/// sort expressions of a listing, before the `id` tie-breaker. Values must match
+/// [Assets::cursor].
+fn sort_keys(sort: ListSort) -> &'static [&'static str] {
+    match sort {
+        ListSort::Name => &["asset_path"],
+        ListSort::Size => &["COALESCE(size, 0)"],
+        ListSort::Created => &["COALESCE(created_at, 0)"],
+        ListSort::Updated => &["COALESCE(updated_at, 0)"],
+        ListSort::Type => &["1 - asset_type", "asset_path"],
+    }
+}
+
+fn cursor_value(key: &CursorKey) -> rbs::Value {
+    match key {
+        CursorKey::Int(value) => value!(value),
+        CursorKey::Text(value) => value!(value),
+    }
+}
+
+/// escape `LIKE` wildcards, with `!` as the escape character.
+fn escape_like(value: &str) -> String {
+    value
+        .replace('!', "!!")
+        .replace('%', "!%")
+        .replace('_', "!_")
+}
+
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
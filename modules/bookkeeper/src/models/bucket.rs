@@ -1,13 +1,15 @@
 use crate::{
     DBResult, Error as AppError,
     models::{
-        de_sqlite_bool,
+        IntoSerializer, aggregate, de_sqlite_bool,
+        client::Clients,
         mime::{BucketMimes, Mimes},
+        user::Users,
     },
 };
 use modeller::prelude::*;
-use ppd_shared::api::CreateBucketOptions;
-use rbatis::{RBatis, crud, impl_select};
+use ppd_shared::api::{BucketBackend, CreateBucketOptions};
+use rbatis::{PageRequest, RBatis, crud, impl_select, impl_select_page};
 use rbs::value;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -35,13 +37,48 @@ pub struct Buckets {
 
     #[serde(deserialize_with = "de_sqlite_bool")]
     public: bool,
+
+    /// storage backend of the bucket's assets. see [BucketBackend].
+    backend: u8,
+
+    /// whether overwritten files keep their previous content as versions.
+    #[serde(deserialize_with = "de_sqlite_bool")]
+    versioning: bool,
+
+    /// number of versions kept for each file. The oldest versions are removed first.
+    max_versions: Option<u64>,
 }
 
 crud!(Buckets {});
 
 impl_select!(Buckets { get_by_key<V: Serialize>(key: &str, value: V) -> Option => "`WHERE ${key} = #{value} LIMIT 1`" });
+impl_select_page!(Buckets { select_page() => "
+    if do_count == false:
+      `ORDER BY id`" });
 
 impl Buckets {
+    /// page `page_no` (starting at 1) of buckets, and the total number of buckets.
+    pub async fn get_page(
+        db: &RBatis,
+        page_no: u64,
+        page_size: u64,
+    ) -> DBResult<(Vec<Self>, u64)> {
+        let page = Self::select_page(db, &PageRequest::new(page_no, page_size)).await?;
+        Ok((page.records, page.total))
+    }
+
+    pub async fn count(db: &RBatis) -> DBResult<u64> {
+        aggregate(db, "SELECT COUNT(*) AS total FROM buckets").await
+    }
+
+    pub async fn get(db: &RBatis, id: &u64) -> DBResult<Self> {
+        let s = Self::get_by_key(db, "id", id)
+            .await?
+            .ok_or(AppError::NotFound("bucket not found".to_string()))?;
+
+        Ok(s)
+    }
+
     pub async fn get_by_pid(db: &RBatis, pid: &str) -> DBResult<Self> {
         let s = Self::get_by_key(db, "pid", pid)
             .await?
@@ -64,7 +101,15 @@ impl Buckets {
         Ok(id)
     }
 
-    async fn user_buckets(db: &RBatis, user_id: &u64) -> DBResult<Vec<Buckets>> {
+    pub async fn get_by_partition(db: &RBatis, partition: &str) -> DBResult<Self> {
+        let s = Self::get_by_key(db, "partition", partition)
+            .await?
+            .ok_or(AppError::NotFound("bucket not found".to_string()))?;
+
+        Ok(s)
+    }
+
+    pub async fn user_buckets(db: &RBatis, user_id: &u64) -> DBResult<Vec<Buckets>> {
         let owner_type = u8::from(BucketOwnerType::User);
         let buckets = Buckets::select_by_map(
             db,
@@ -78,7 +123,7 @@ impl Buckets {
         Ok(buckets)
     }
 
-    async fn client_buckets(db: &RBatis, client_id: &u64) -> DBResult<Vec<Buckets>> {
+    pub async fn client_buckets(db: &RBatis, client_id: &u64) -> DBResult<Vec<Buckets>> {
         let owner_type = u8::from(BucketOwnerType::Client);
         let buckets = Buckets::select_by_map(
             db,
@@ -124,6 +169,32 @@ impl Buckets {
         Ok(id)
     }
 
+    /// update the size limit of the bucket's partition.
+    pub async fn update_partition_size(
+        &mut self,
+        db: &RBatis,
+        partition_size: Option<f64>,
+    ) -> DBResult<()> {
+        if partition_size.is_some() && self.partition.is_none() {
+            return Err(AppError::PermissionError(
+                "You can not set \"partition_size\" without setting \"partition\".".to_string(),
+            ));
+        }
+
+        if let Some(size) = partition_size
+            && size < 0.0
+        {
+            return Err(AppError::PermissionError(
+                "partition_size must be minimum of 1".to_string(),
+            ));
+        }
+
+        self.partition_size = partition_size;
+        Buckets::update_by_map(db, self, value! { "id": &self.id }).await?;
+
+        Ok(())
+    }
+
     pub async fn delete(db: &RBatis, pid: &str) -> DBResult<()> {
         Self::delete_by_map(db, value! { "pid": pid }).await?;
         Ok(())
@@ -239,6 +310,9 @@ impl Buckets {
             accepts,
             label,
             public,
+            backend,
+            versioning,
+            max_versions,
         } = opts;
 
         if let Some(size) = partition_size
@@ -256,7 +330,7 @@ impl Buckets {
                 ));
             }
 
-            let b = Buckets::get_by_key(db, "root_folder", folder).await?;
+            let b = Buckets::get_by_key(db, "partition", folder).await?;
             if b.is_some() {
                 return Err(AppError::PermissionError(format!(
                     "folder name '{folder}' is not available. Try a different folder name."
@@ -270,6 +344,12 @@ impl Buckets {
             ));
         }
 
+        if max_versions == Some(0) {
+            return Err(AppError::PermissionError(
+                "max_versions must be minimum of 1".to_string(),
+            ));
+        }
+
         let accepts = accepts.unwrap_or(String::from("*"));
         let pid = Uuid::new_v4().to_string();
 
@@ -283,6 +363,9 @@ impl Buckets {
             partition,
             accepts: accepts.clone(),
             public: public.unwrap_or_default(),
+            backend: backend.unwrap_or_default().into(),
+            versioning: versioning.unwrap_or_default(),
+            max_versions,
         };
 
         Buckets::insert(db, &data).await?;
@@ -302,6 +385,14 @@ impl Buckets {
         self.id.unwrap_or_default()
     }
 
+    pub fn pid(&self) -> &str {
+        &self.pid
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
     pub fn owner_id(&self) -> &u64 {
         &self.owner_id
     }
@@ -321,8 +412,22 @@ impl Buckets {
     pub fn partition_size(&self) -> &Option<f64> {
         &self.partition_size
     }
+
+    pub fn backend(&self) -> BucketBackend {
+        self.backend.into()
+    }
+
+    pub fn versioning(&self) -> bool {
+        self.versioning
+    }
+
+    pub fn max_versions(&self) -> &Option<u64> {
+        &self.max_versions
+    }
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum BucketOwnerType {
     Client,
     User,
@@ -347,6 +452,62 @@ impl From<u8> for BucketOwnerType {
     }
 }
 
+#[derive(Serialize)]
+pub struct BucketSerializer {
+    id: String,
+    label: String,
+    owner_type: BucketOwnerType,
+
+    /// id of the client or user that owns the bucket.
+    owner_id: String,
+    partition: Option<String>,
+    partition_size: Option<f64>,
+    accepts: String,
+    public: bool,
+    backend: BucketBackend,
+    versioning: bool,
+    max_versions: Option<u64>,
+}
+
+impl IntoSerializer for Buckets {
+    type Serializer = BucketSerializer;
+
+    async fn into_serializer(self, db: &RBatis) -> DBResult<Self::Serializer> {
+        let owner_id = match self.owner_type() {
+            BucketOwnerType::Client => Clients::get_by_id(db, &self.owner_id).await?.pid().to_string(),
+            BucketOwnerType::User => Users::get(db, &self.owner_id).await?.pid().to_string(),
+        };
+
+        let backend = self.backend();
+        let owner_type = self.owner_type();
+        let Buckets {
+            pid,
+            label,
+            partition,
+            partition_size,
+            accepts,
+            public,
+            versioning,
+            max_versions,
+            ..
+        } = self;
+
+        Ok(BucketSerializer {
+            id: pid,
+            label,
+            owner_type,
+            owner_id,
+            partition,
+            partition_size,
+            accepts,
+            public,
+            backend,
+            versioning,
+            max_versions,
+        })
+    }
+}
+
 struct OwnerInfo {
     id: u64,
     ty: BucketOwnerType,
@@ -1,13 +1,13 @@
 use modeller::prelude::*;
-use ppd_shared::opts::ClientInfo;
-use rbatis::{RBatis, crud, impl_select, rbdc::DateTime};
+use ppd_shared::opts::{ClientInfo, ClientKeyInfo, ClientScope};
+use rbatis::{PageRequest, RBatis, crud, impl_select, impl_select_page, rbdc::DateTime};
 use rbs::value;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::DBResult;
 
-use super::check_model;
+use super::{aggregate, check_model};
 
 #[derive(Serialize, Deserialize, Modeller)]
 pub struct Clients {
@@ -30,13 +30,35 @@ pub struct Clients {
 
 crud!(Clients {});
 impl_select!(Clients { get_by_key<V: Serialize>(key: &str, value: V) -> Option => "`WHERE ${key} = #{value} LIMIT 1`" });
+impl_select_page!(Clients { select_page() => "
+    if do_count == false:
+      `ORDER BY id`" });
 
 impl Clients {
+    /// page `page_no` (starting at 1) of clients, and the total number of clients.
+    pub async fn get_page(
+        db: &RBatis,
+        page_no: u64,
+        page_size: u64,
+    ) -> DBResult<(Vec<Self>, u64)> {
+        let page = Self::select_page(db, &PageRequest::new(page_no, page_size)).await?;
+        Ok((page.records, page.total))
+    }
+
+    pub async fn count(db: &RBatis) -> DBResult<u64> {
+        aggregate(db, "SELECT COUNT(*) AS total FROM clients").await
+    }
+
     pub async fn get(rb: &RBatis, pid: &str) -> DBResult<Self> {
         let client = Clients::get_by_key(rb, "pid", pid).await?;
         check_model(client, "client not found")
     }
 
+    pub async fn get_by_id(rb: &RBatis, id: &u64) -> DBResult<Self> {
+        let client = Clients::get_by_key(rb, "id", id).await?;
+        check_model(client, "client not found")
+    }
+
     /// retrieve client using key column
     pub async fn get_with_key(rb: &RBatis, key: &str) -> DBResult<Self> {
         let client = Clients::get_by_key(rb, "key", key).await?;
@@ -70,6 +92,24 @@ impl Clients {
         Ok(())
     }
 
+    /// update the total maximum size of buckets the client can create.
+    pub async fn update_max_bucket_size(
+        &mut self,
+        db: &RBatis,
+        max_bucket_size: Option<f64>,
+    ) -> DBResult<()> {
+        self.max_bucket_size = max_bucket_size;
+        Clients::update_by_map(db, self, value! { "id": &self.id }).await?;
+
+        Ok(())
+    }
+
+    pub async fn delete(&self, db: &RBatis) -> DBResult<()> {
+        ClientKeys::delete_by_map(db, value! { "client_id": &self.id }).await?;
+        Clients::delete_by_map(db, value! { "id": &self.id }).await?;
+        Ok(())
+    }
+
     pub fn new_key() -> String {
         Uuid::new_v4().to_string()
     }
@@ -109,3 +149,137 @@ impl From<&Clients> for ClientInfo {
         }
     }
 }
+
+/// A named key of a client. Keys can be limited to a set of scopes and can expire, so a
+/// client can rotate keys without downtime.
+#[derive(Serialize, Deserialize, Modeller)]
+pub struct ClientKeys {
+    id: Option<u64>,
+
+    #[modeller(unique)]
+    pid: String,
+
+    #[modeller(foreign_key(rf = "clients(id)", on_delete = "cascade"))]
+    client_id: u64,
+
+    /// keep away from public API
+    #[modeller(unique)]
+    key: String,
+
+    #[modeller(length = 120)]
+    name: String,
+
+    /// comma separated scopes. The key has all scopes when empty.
+    scopes: String,
+
+    /// unix timestamp after which the key can't be used.
+    expires_at: Option<i64>,
+
+    /// unix timestamp of the last request made with the key.
+    last_used: Option<i64>,
+
+    created_at: DateTime,
+}
+
+crud!(ClientKeys {});
+impl_select!(ClientKeys { get_by_key<V: Serialize>(key: &str, value: V) -> Option => "`WHERE ${key} = #{value} LIMIT 1`" });
+impl_select!(ClientKeys { client_keys(client_id: &u64) -> Vec => "`WHERE client_id = #{client_id} ORDER BY id`" });
+
+impl ClientKeys {
+    pub async fn get(rb: &RBatis, pid: &str) -> DBResult<Self> {
+        let key = ClientKeys::get_by_key(rb, "pid", pid).await?;
+        check_model(key, "client key not found")
+    }
+
+    /// retrieve client key using key column
+    pub async fn find_with_key(rb: &RBatis, key: &str) -> DBResult<Option<Self>> {
+        let key = ClientKeys::get_by_key(rb, "key", key).await?;
+        Ok(key)
+    }
+
+    pub async fn list(rb: &RBatis, client_id: &u64) -> DBResult<Vec<Self>> {
+        let keys = ClientKeys::client_keys(rb, client_id).await?;
+        Ok(keys)
+    }
+
+    pub async fn create(
+        rb: &RBatis,
+        client_id: u64,
+        key: String,
+        name: String,
+        scopes: &[ClientScope],
+        expires_at: Option<i64>,
+    ) -> DBResult<String> {
+        let pid = Uuid::new_v4().to_string();
+        let scopes: Vec<String> = scopes.iter().map(|s| s.to_string()).collect();
+
+        let value = ClientKeys {
+            id: None,
+            pid: pid.clone(),
+            client_id,
+            key,
+            name,
+            scopes: scopes.join(","),
+            expires_at,
+            last_used: None,
+            created_at: DateTime::now(),
+        };
+
+        ClientKeys::insert(rb, &value).await?;
+        Ok(pid)
+    }
+
+    /// record a request made with the key at `now` (unix timestamp).
+    pub async fn touch(&mut self, db: &RBatis, now: i64) -> DBResult<()> {
+        self.last_used = Some(now);
+        ClientKeys::update_by_map(db, self, value! { "id": &self.id }).await?;
+
+        Ok(())
+    }
+
+    pub async fn delete(&self, db: &RBatis) -> DBResult<()> {
+        ClientKeys::delete_by_map(db, value! { "id": &self.id }).await?;
+        Ok(())
+    }
+
+    pub fn pid(&self) -> &str {
+        &self.pid
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn client_id(&self) -> &u64 {
+        &self.client_id
+    }
+
+    /// scopes granted to the key. Empty if the key has all scopes.
+    pub fn scopes(&self) -> Vec<ClientScope> {
+        self.scopes
+            .split(',')
+            .filter_map(|s| s.trim().parse().ok())
+            .collect()
+    }
+
+    pub fn expires_at(&self) -> &Option<i64> {
+        &self.expires_at
+    }
+
+    pub fn last_used(&self) -> &Option<i64> {
+        &self.last_used
+    }
+}
+
+impl From<&ClientKeys> for ClientKeyInfo {
+    fn from(value: &ClientKeys) -> Self {
+        ClientKeyInfo {
+            id: value.pid.clone(),
+            name: value.name.clone(),
+            scopes: value.scopes(),
+            expires_at: value.expires_at,
+            last_used: value.last_used,
+            created_at: value.created_at.to_string(),
+        }
+    }
+}
@@ -7,7 +7,10 @@ pub mod bucket;
 pub mod client;
 pub mod mime;
 pub mod permission;
+pub mod token;
+pub mod upload;
 pub mod user;
+pub mod version;
 
 pub trait IntoSerializer {
     type Serializer;
@@ -28,3 +31,41 @@ where
     let v = i64::deserialize(deserializer)?;
     Ok(v != 0)
 }
+
+/// a single aggregate value selected `AS total`, i.e `SELECT COUNT(*) AS total FROM users`.
+/// Aggregates of empty tables (NULL) are 0.
+async fn aggregate(db: &RBatis, sql: &str) -> DBResult<u64> {
+    #[derive(Deserialize)]
+    struct Aggregate {
+        #[serde(default, deserialize_with = "de_aggregate")]
+        total: u64,
+    }
+
+    let rows: Vec<Aggregate> = db.query_decode(sql, vec![]).await?;
+    Ok(rows.first().map(|row| row.total).unwrap_or_default())
+}
+
+/// Databases return aggregates as integers, floats or (PostgreSQL's numeric) strings.
+fn de_aggregate<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Number {
+        Int(u64),
+        Float(f64),
+        Text(String),
+    }
+
+    let total = match Option::<Number>::deserialize(deserializer)? {
+        Some(Number::Int(v)) => v,
+        Some(Number::Float(v)) => v as u64,
+        Some(Number::Text(v)) => v
+            .parse::<f64>()
+            .map_err(serde::de::Error::custom)? as u64,
+        None => 0,
+    };
+
+    Ok(total)
+}
@@ -0,0 +1,229 @@
+use modeller::prelude::*;
+use rbatis::{RBatis, crud, impl_select, rbdc::DateTime};
+use rbs::value;
+use serde::{Deserialize, Serialize};
+
+use crate::DBResult;
+
+use super::de_sqlite_bool;
+
+/// A refresh token issued to a user. Only the hash of the token is stored. Tokens issued
+/// from the same login share a `family`, which lets us revoke the whole login when a rotated
+/// token is used again.
+#[derive(Serialize, Deserialize, Modeller)]
+pub struct RefreshTokens {
+    id: Option<u64>,
+
+    #[modeller(unique)]
+    token_hash: String,
+
+    #[modeller(foreign_key(rf = "users(id)", on_delete = "cascade"))]
+    user_id: u64,
+
+    /// id shared by tokens rotated from the same login.
+    family: String,
+
+    /// whether the token has been exchanged for a new one.
+    #[serde(deserialize_with = "de_sqlite_bool")]
+    used: bool,
+
+    /// lifetime (seconds) of access tokens issued by this login.
+    access_exp: i64,
+
+    /// lifetime (seconds) of refresh tokens issued by this login.
+    refresh_exp: i64,
+
+    created_at: DateTime,
+}
+
+crud!(RefreshTokens {});
+impl_select!(RefreshTokens { get_by_hash(token_hash: &str) -> Option => "`WHERE token_hash = #{token_hash} LIMIT 1`" });
+impl_select!(RefreshTokens { select_for_user(user_id: &u64) => "`WHERE user_id = #{user_id}`" });
+
+impl RefreshTokens {
+    pub async fn find(db: &RBatis, token_hash: &str) -> DBResult<Option<Self>> {
+        let token = RefreshTokens::get_by_hash(db, token_hash).await?;
+        Ok(token)
+    }
+
+    pub async fn create(db: &RBatis, value: NewRefreshToken) -> DBResult<()> {
+        let NewRefreshToken {
+            token_hash,
+            user_id,
+            family,
+            access_exp,
+            refresh_exp,
+        } = value;
+
+        let token = RefreshTokens {
+            id: None,
+            token_hash,
+            user_id,
+            family,
+            used: false,
+            access_exp,
+            refresh_exp,
+            created_at: DateTime::now(),
+        };
+
+        RefreshTokens::insert(db, &token).await?;
+        Ok(())
+    }
+
+    /// mark the token as exchanged. Returns `false` if it had already been exchanged (i.e by
+    /// a concurrent request).
+    pub async fn mark_used(&mut self, db: &RBatis) -> DBResult<bool> {
+        let res = db
+            .exec(
+                "UPDATE refresh_tokens SET used = ? WHERE id = ? AND used = ?",
+                vec![value!(true), value!(&self.id), value!(false)],
+            )
+            .await?;
+
+        self.used = true;
+        Ok(res.rows_affected > 0)
+    }
+
+    /// remove the tokens of the user that have expired. Used tokens are kept until they
+    /// expire, so that using them again is still detected.
+    pub async fn delete_expired(db: &RBatis, user_id: &u64) -> DBResult<()> {
+        let now = DateTime::now().unix_timestamp();
+        let expired: Vec<u64> = RefreshTokens::select_for_user(db, user_id)
+            .await?
+            .iter()
+            .filter(|token| token.created_at.unix_timestamp() + token.refresh_exp < now)
+            .filter_map(|token| token.id)
+            .collect();
+
+        if expired.is_empty() {
+            return Ok(());
+        }
+
+        let marks = vec!["?"; expired.len()].join(", ");
+        let sql = format!("DELETE FROM refresh_tokens WHERE id IN ({marks})");
+        db.exec(&sql, expired.iter().map(|id| value!(id)).collect())
+            .await?;
+
+        Ok(())
+    }
+
+    /// revoke all tokens issued from the same login.
+    pub async fn delete_family(db: &RBatis, family: &str) -> DBResult<()> {
+        RefreshTokens::delete_by_map(db, value! { "family": family }).await?;
+        Ok(())
+    }
+
+    /// revoke all tokens of the user.
+    pub async fn delete_for_user(db: &RBatis, user_id: &u64) -> DBResult<()> {
+        RefreshTokens::delete_by_map(db, value! { "user_id": user_id }).await?;
+        Ok(())
+    }
+
+    pub fn user_id(&self) -> &u64 {
+        &self.user_id
+    }
+
+    pub fn family(&self) -> &str {
+        &self.family
+    }
+
+    pub fn used(&self) -> bool {
+        self.used
+    }
+
+    pub fn access_exp(&self) -> &i64 {
+        &self.access_exp
+    }
+
+    pub fn refresh_exp(&self) -> &i64 {
+        &self.refresh_exp
+    }
+}
+
+pub struct NewRefreshToken {
+    pub token_hash: String,
+    pub user_id: u64,
+    pub family: String,
+    pub access_exp: i64,
+    pub refresh_exp: i64,
+}
+
+/// A password reset token. Only the hash of the token is stored.
+#[derive(Serialize, Deserialize, Modeller)]
+pub struct PasswordResets {
+    id: Option<u64>,
+
+    #[modeller(unique)]
+    token_hash: String,
+
+    #[modeller(foreign_key(rf = "users(id)", on_delete = "cascade"))]
+    user_id: u64,
+
+    /// unix timestamp after which the token can't be used.
+    expires_at: i64,
+
+    created_at: DateTime,
+}
+
+crud!(PasswordResets {});
+impl_select!(PasswordResets { get_by_hash(token_hash: &str) -> Option => "`WHERE token_hash = #{token_hash} LIMIT 1`" });
+
+impl PasswordResets {
+    pub async fn find(db: &RBatis, token_hash: &str) -> DBResult<Option<Self>> {
+        let reset = PasswordResets::get_by_hash(db, token_hash).await?;
+        Ok(reset)
+    }
+
+    /// save a reset token, replacing earlier tokens of the user.
+    pub async fn create(
+        db: &RBatis,
+        token_hash: String,
+        user_id: u64,
+        expires_at: i64,
+    ) -> DBResult<()> {
+        Self::delete_for_user(db, &user_id).await?;
+
+        let reset = PasswordResets {
+            id: None,
+            token_hash,
+            user_id,
+            expires_at,
+            created_at: DateTime::now(),
+        };
+
+        PasswordResets::insert(db, &reset).await?;
+        Ok(())
+    }
+
+    /// consume a token that hasn't expired. Returns `false` if the token has expired or was
+    /// already consumed (i.e by a concurrent request).
+    pub async fn consume(db: &RBatis, token_hash: &str) -> DBResult<bool> {
+        let now = DateTime::now().unix_timestamp();
+        let res = db
+            .exec(
+                "DELETE FROM password_resets WHERE token_hash = ? AND expires_at >= ?",
+                vec![value!(token_hash), value!(now)],
+            )
+            .await?;
+
+        Ok(res.rows_affected > 0)
+    }
+
+    pub async fn delete(&self, db: &RBatis) -> DBResult<()> {
+        PasswordResets::delete_by_map(db, value! { "id": &self.id }).await?;
+        Ok(())
+    }
+
+    pub async fn delete_for_user(db: &RBatis, user_id: &u64) -> DBResult<()> {
+        PasswordResets::delete_by_map(db, value! { "user_id": user_id }).await?;
+        Ok(())
+    }
+
+    pub fn user_id(&self) -> &u64 {
+        &self.user_id
+    }
+
+    pub fn expires_at(&self) -> &i64 {
+        &self.expires_at
+    }
+}
@@ -0,0 +1,175 @@
+use modeller::prelude::*;
+use rbatis::{RBatis, crud, impl_select, rbdc::DateTime};
+use rbs::value;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::DBResult;
+
+use super::{IntoSerializer, check_model};
+
+/// A resumable upload. Chunks are appended to the session until it's completed, after
+/// which the upload is saved as a regular asset.
+#[derive(Serialize, Deserialize, Modeller)]
+pub struct UploadSessions {
+    id: Option<u64>,
+
+    #[modeller(unique)]
+    pid: String,
+
+    #[modeller(foreign_key(rf = "users(id)", on_delete = "cascade"))]
+    user_id: u64,
+
+    #[modeller(foreign_key(rf = "buckets(id)", on_delete = "cascade"))]
+    bucket_id: u64,
+
+    /// asset options (serialized) to be applied when the upload is completed
+    #[modeller(length = 5000)]
+    options: String,
+
+    /// total size of the upload (bytes), if known when the session was created
+    size: Option<u64>,
+
+    /// number of bytes received so far
+    offset: u64,
+
+    created_at: DateTime,
+}
+
+crud!(UploadSessions {});
+impl_select!(UploadSessions { get_for_user(pid: &str, user_id: &u64) -> Option => "`WHERE pid = #{pid} AND user_id = #{user_id} LIMIT 1`" });
+
+impl UploadSessions {
+    /// retrieve an upload session that belongs to the user
+    pub async fn get(db: &RBatis, pid: &str, user_id: &u64) -> DBResult<Self> {
+        let session = UploadSessions::get_for_user(db, pid, user_id).await?;
+        check_model(session, "upload session not found")
+    }
+
+    pub async fn create(db: &RBatis, value: NewUploadSession) -> DBResult<String> {
+        let NewUploadSession {
+            user_id,
+            bucket_id,
+            options,
+            size,
+        } = value;
+
+        let pid = Uuid::new_v4().to_string();
+        let session = UploadSessions {
+            id: None,
+            pid,
+            user_id,
+            bucket_id,
+            options,
+            size,
+            offset: 0,
+            created_at: DateTime::now(),
+        };
+
+        UploadSessions::insert(db, &session).await?;
+        Ok(session.pid)
+    }
+
+    pub async fn update_offset(&mut self, db: &RBatis, offset: u64) -> DBResult<()> {
+        self.offset = offset;
+        UploadSessions::update_by_map(db, self, value! { "id": &self.id() }).await?;
+
+        Ok(())
+    }
+
+    /// move the session's offset from `offset` to `new_offset`. Returns `false` without
+    /// updating the session if it's no longer at `offset` (i.e another request moved it first).
+    pub async fn advance_offset(
+        &mut self,
+        db: &RBatis,
+        offset: u64,
+        new_offset: u64,
+    ) -> DBResult<bool> {
+        let current = self.offset;
+        self.offset = new_offset;
+
+        let res = UploadSessions::update_by_map(
+            db,
+            self,
+            value! { "id": &self.id(), "offset": &offset },
+        )
+        .await;
+
+        match res {
+            Ok(res) if res.rows_affected > 0 => Ok(true),
+            Ok(_) => {
+                self.offset = current;
+                Ok(false)
+            }
+            Err(err) => {
+                self.offset = current;
+                Err(err.into())
+            }
+        }
+    }
+
+    pub async fn delete(&self, db: &RBatis) -> DBResult<()> {
+        UploadSessions::delete_by_map(db, value! { "id": &self.id }).await?;
+        Ok(())
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id.unwrap_or_default()
+    }
+
+    pub fn pid(&self) -> &str {
+        &self.pid
+    }
+
+    pub fn bucket_id(&self) -> &u64 {
+        &self.bucket_id
+    }
+
+    pub fn options(&self) -> &str {
+        &self.options
+    }
+
+    pub fn size(&self) -> &Option<u64> {
+        &self.size
+    }
+
+    pub fn offset(&self) -> &u64 {
+        &self.offset
+    }
+}
+
+pub struct NewUploadSession {
+    pub user_id: u64,
+    pub bucket_id: u64,
+    pub options: String,
+    pub size: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct UploadSessionSerializer {
+    id: String,
+    size: Option<u64>,
+    offset: u64,
+    created_at: String,
+}
+
+impl IntoSerializer for UploadSessions {
+    type Serializer = UploadSessionSerializer;
+
+    async fn into_serializer(self, _: &RBatis) -> DBResult<Self::Serializer> {
+        let UploadSessions {
+            pid: id,
+            size,
+            offset,
+            created_at,
+            ..
+        } = self;
+
+        Ok(UploadSessionSerializer {
+            id,
+            size,
+            offset,
+            created_at: created_at.to_string(),
+        })
+    }
+}
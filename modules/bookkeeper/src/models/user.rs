@@ -1,12 +1,15 @@
 use modeller::prelude::*;
-use rbatis::{RBatis, crud, impl_select, rbdc::DateTime};
+use rbatis::{PageRequest, RBatis, crud, impl_select, impl_select_page, rbdc::DateTime};
 use rbs::value;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{errors::Error as DBError, DBResult};
 
-use super::{IntoSerializer, asset::Assets, check_model, permission::AssetPermissions};
+use super::{
+    IntoSerializer, aggregate, asset::Assets, check_model, permission::AssetPermissions,
+    token::{PasswordResets, RefreshTokens},
+};
 
 #[derive(Serialize, Deserialize, Modeller)]
 pub struct Users {
@@ -16,19 +19,59 @@ pub struct Users {
     pid: String,
     role: u8,
     client_id: Option<u64>,
+
+    #[modeller(unique)]
     username: Option<String>,
     password: Option<String>,
 
     /// maximum accumulated size of buckets user can create
     max_bucket: Option<f64>,
+
+    /// id of the user in the external authentication service (see `--auth-url`).
+    #[modeller(unique)]
+    external_id: Option<String>,
     created_at: DateTime,
 }
 
 crud!(Users {});
 impl_select!(Users { get_by_key<V: Serialize>(key: &str, value: V) -> Option => "`WHERE ${key} = #{value} LIMIT 1`" });
 impl_select!(Users { get_for_client(id: &str, client_id: &u64) -> Option => "`WHERE pid = #{id} AND client_id = #{client_id} LIMIT 1`" });
+impl_select!(Users { get_client_role(client_id: &u64, role: u8) -> Option => "`WHERE client_id = #{client_id} AND role = #{role} LIMIT 1`" });
+impl_select_page!(Users { select_page() => "
+    if do_count == false:
+      `ORDER BY id`" });
 
 impl Users {
+    /// page `page_no` (starting at 1) of users, and the total number of users.
+    pub async fn get_page(
+        db: &RBatis,
+        page_no: u64,
+        page_size: u64,
+    ) -> DBResult<(Vec<Self>, u64)> {
+        let page = Self::select_page(db, &PageRequest::new(page_no, page_size)).await?;
+        Ok((page.records, page.total))
+    }
+
+    pub async fn count(db: &RBatis) -> DBResult<u64> {
+        aggregate(db, "SELECT COUNT(*) AS total FROM users").await
+    }
+
+    /// usernames shared by more than one user. Databases created before usernames were unique
+    /// may hold duplicates, which must be renamed before the unique index is created. The users
+    /// table must exist.
+    pub async fn duplicate_usernames(db: &RBatis) -> DBResult<Vec<String>> {
+        #[derive(Deserialize)]
+        struct Duplicate {
+            username: String,
+        }
+
+        let sql = "SELECT username FROM users WHERE username IS NOT NULL \
+            GROUP BY username HAVING COUNT(*) > 1";
+
+        let rows: Vec<Duplicate> = db.query_decode(sql, vec![]).await?;
+        Ok(rows.into_iter().map(|row| row.username).collect())
+    }
+
     pub async fn get(rb: &RBatis, user_id: &u64) -> DBResult<Users> {
         let user = Users::get_by_key(rb, "id", user_id).await?;
         check_model(user, "user not found")
@@ -61,6 +104,7 @@ impl Users {
             password: None,
             client_id: Some(client_id),
             max_bucket: bucket_size,
+            external_id: None,
             created_at,
         };
 
@@ -68,9 +112,47 @@ impl Users {
         Ok(user.pid)
     }
 
+    /// The user that owns assets created by a client through the S3 API. The user is
+    /// created on first use.
+    pub async fn client_service_user(db: &RBatis, client_id: &u64) -> DBResult<Users> {
+        let role: u8 = UserRole::Service.into();
+        if let Some(user) = Users::get_client_role(db, client_id, role).await? {
+            return Ok(user);
+        }
+
+        let user = Users {
+            id: None,
+            pid: Uuid::new_v4().to_string(),
+            role,
+            username: None,
+            password: None,
+            client_id: Some(*client_id),
+            max_bucket: None,
+            external_id: None,
+            created_at: DateTime::now(),
+        };
+
+        Users::insert(db, &user).await?;
+        Users::get_by_pid(db, &user.pid).await
+    }
+
     pub async fn create_direct(db: &RBatis, username: String, password: String) -> DBResult<String> {
+        Self::create_local(db, username, password, UserRole::General).await
+    }
+
+    /// create an admin user. admins log in with username and password like direct users.
+    pub async fn create_admin(db: &RBatis, username: String, password: String) -> DBResult<String> {
+        Self::create_local(db, username, password, UserRole::Admin).await
+    }
+
+    async fn create_local(
+        db: &RBatis,
+        username: String,
+        password: String,
+        role: UserRole,
+    ) -> DBResult<String> {
         let pid = Uuid::new_v4().to_string();
-        let role: u8 = UserRole::General.into();
+        let role: u8 = role.into();
         let created_at = DateTime::now();
         
         let user = Users {
@@ -81,6 +163,7 @@ impl Users {
             role,
             client_id: None,
             max_bucket: None,
+            external_id: None,
             created_at
         };
 
@@ -88,6 +171,55 @@ impl Users {
         Ok(user.pid)
     }
 
+    /// The user linked to an account of the external authentication service. The user is
+    /// created on first sight.
+    pub async fn get_or_create_external(
+        db: &RBatis,
+        external_id: &str,
+        max_bucket: Option<f64>,
+    ) -> DBResult<Users> {
+        if let Some(user) = Users::get_by_key(db, "external_id", external_id).await? {
+            return Ok(user);
+        }
+
+        let user = Users {
+            id: None,
+            pid: Uuid::new_v4().to_string(),
+            role: UserRole::General.into(),
+            username: None,
+            password: None,
+            client_id: None,
+            max_bucket,
+            external_id: Some(external_id.to_string()),
+            created_at: DateTime::now(),
+        };
+
+        Users::insert(db, &user).await?;
+        Users::get_by_pid(db, &user.pid).await
+    }
+
+    /// users created by a client.
+    pub async fn client_users(db: &RBatis, client_id: &u64) -> DBResult<Vec<Users>> {
+        let users = Users::select_by_map(db, value! { "client_id": client_id }).await?;
+        Ok(users)
+    }
+
+    /// update the maximum accumulated size of buckets the user can create.
+    pub async fn update_max_bucket(&mut self, db: &RBatis, max_bucket: Option<f64>) -> DBResult<()> {
+        self.max_bucket = max_bucket;
+        Users::update_by_map(db, self, value! { "id": &self.id }).await?;
+
+        Ok(())
+    }
+
+    /// replace the user's password hash.
+    pub async fn update_password(&mut self, db: &RBatis, password: String) -> DBResult<()> {
+        self.password = Some(password);
+        Users::update_by_map(db, self, value! { "id": &self.id }).await?;
+
+        Ok(())
+    }
+
     pub async fn delete(&self, rb: &RBatis) -> DBResult<()> {
         Users::delete_by_map(
             rb,
@@ -102,8 +234,11 @@ impl Users {
         Ok(())
     }
 
-    /// Removes user permissions and assets. To be called inside or after [User::delete].
+    /// Removes user permissions, assets and tokens. To be called inside or after
+    /// [User::delete].
     async fn clean_up(&self, rb: &RBatis) -> DBResult<()> {
+        RefreshTokens::delete_for_user(rb, &self.id()).await?;
+        PasswordResets::delete_for_user(rb, &self.id()).await?;
         AssetPermissions::delete_for_user(rb, &self.id()).await?;
         Assets::delete_for_user(rb, &self.id()).await?;
         Ok(())
@@ -113,6 +248,10 @@ impl Users {
         self.id.unwrap_or_default()
     }
 
+    pub fn pid(&self) -> &str {
+        &self.pid
+    }
+
     pub fn role(&self) -> DBResult<UserRole> {
         UserRole::try_from(self.role)
     }
@@ -139,6 +278,28 @@ pub struct UserSerializer {
     max_bucket: Option<f64>,
 }
 
+impl UserSerializer {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn email(&self) -> &Option<String> {
+        &self.email
+    }
+
+    pub fn role(&self) -> &UserRole {
+        &self.role
+    }
+
+    pub fn created_at(&self) -> &str {
+        &self.created_at
+    }
+
+    pub fn max_bucket(&self) -> &Option<f64> {
+        &self.max_bucket
+    }
+}
+
 impl IntoSerializer for Users {
     type Serializer = UserSerializer;
 
@@ -168,6 +329,9 @@ impl IntoSerializer for Users {
 pub enum UserRole {
     General,
     Admin,
+
+    /// owns assets a client creates through the S3 API.
+    Service,
 }
 
 impl TryFrom<u8> for UserRole {
@@ -180,6 +344,8 @@ impl TryFrom<u8> for UserRole {
             Ok(General)
         } else if value == 1 {
             Ok(Admin)
+        } else if value == 2 {
+            Ok(Service)
         } else {
             Err(DBError::ParseError(format!("invalid user_role '{value}' ")))
         }
@@ -193,6 +359,7 @@ impl From<UserRole> for u8 {
         match value {
             General => 0,
             Admin => 1,
+            Service => 2,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,110 @@
+use modeller::prelude::*;
+use rbatis::{RBatis, crud, impl_select};
+use rbs::value;
+use serde::{Deserialize, Serialize};
+
+use crate::DBResult;
+
+use super::{asset::Assets, check_model};
+
+/// Previous content of a file asset, kept when the file is overwritten in a bucket with
+/// versioning enabled.
+#[derive(Serialize, Deserialize, Modeller)]
+#[modeller(index(name = "idx_asset_x_version", fields(asset_id, version), unique))]
+pub struct AssetVersions {
+    id: Option<u64>,
+
+    #[modeller(foreign_key(rf = "assets(id)", on_delete = "cascade"))]
+    asset_id: u64,
+
+    /// number of the version. The first content of an asset is version 1.
+    version: u64,
+
+    /// bucket whose storage holds the content. Assets can be moved to other buckets, while
+    /// their versions stay where they were written.
+    #[modeller(foreign_key(rf = "buckets(id)", on_delete = "cascade"))]
+    bucket_id: u64,
+
+    size: Option<u64>,
+    mime: Option<String>,
+
+    /// unix timestamp of when the content was uploaded.
+    created_at: Option<i64>,
+}
+
+crud!(AssetVersions {});
+impl_select!(AssetVersions{ select_for_asset(asset_id: &u64) => "`WHERE asset_id = #{asset_id} ORDER BY version DESC`" });
+impl_select!(AssetVersions{ select_version(asset_id: &u64, version: &u64) -> Option => "`WHERE asset_id = #{asset_id} AND version = #{version} LIMIT 1`" });
+
+impl AssetVersions {
+    /// versions of an asset, most recent first.
+    pub async fn get_for_asset(db: &RBatis, asset_id: &u64) -> DBResult<Vec<Self>> {
+        let versions = AssetVersions::select_for_asset(db, asset_id).await?;
+        Ok(versions)
+    }
+
+    pub async fn get(db: &RBatis, asset_id: &u64, version: &u64) -> DBResult<Self> {
+        let version = AssetVersions::select_version(db, asset_id, version).await?;
+        check_model(version, "asset version not found")
+    }
+
+    /// record the current content of `asset`, stored in `bucket_id`, as its next version.
+    pub async fn create(db: &RBatis, asset: &Assets, bucket_id: u64) -> DBResult<Self> {
+        let version = AssetVersions::get_for_asset(db, &asset.id())
+            .await?
+            .first()
+            .map(|latest| latest.version + 1)
+            .unwrap_or(1);
+
+        let value = AssetVersions {
+            id: None,
+            asset_id: asset.id(),
+            version,
+            bucket_id,
+            size: *asset.size(),
+            mime: asset.mime().clone(),
+            created_at: asset.updated_at().or(*asset.created_at()),
+        };
+
+        AssetVersions::insert(db, &value).await?;
+        Ok(value)
+    }
+
+    pub async fn delete(&self, db: &RBatis) -> DBResult<()> {
+        AssetVersions::delete_by_map(
+            db,
+            value! { "asset_id": &self.asset_id, "version": &self.version },
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete_for_asset(db: &RBatis, asset_id: &u64) -> DBResult<()> {
+        AssetVersions::delete_by_map(db, value! { "asset_id": asset_id }).await?;
+        Ok(())
+    }
+
+    pub fn asset_id(&self) -> &u64 {
+        &self.asset_id
+    }
+
+    pub fn version(&self) -> &u64 {
+        &self.version
+    }
+
+    pub fn bucket_id(&self) -> &u64 {
+        &self.bucket_id
+    }
+
+    pub fn size(&self) -> &Option<u64> {
+        &self.size
+    }
+
+    pub fn mime(&self) -> &Option<String> {
+        &self.mime
+    }
+
+    pub fn created_at(&self) -> &Option<i64> {
+        &self.created_at
+    }
+}
@@ -8,21 +8,28 @@ pub struct ValidatePathDetails<'a> {
     pub path: &'a str,
     pub custom_path: &'a Option<String>,
     pub ty: &'a AssetType,
+
+    /// user writing the asset, and the bucket it's written to. Paths of the user's own assets
+    /// can be written again in the same bucket.
+    pub user_id: &'a u64,
+    pub bucket_id: &'a u64,
 }
 
-/// Validate whether `asset_path` or `custom_path` is already used by another asset
+/// Validate whether `asset_path` or `custom_path` is already used by an asset of another user
+/// or bucket
 pub async fn validate_asset_paths(db: &RBatis, asset: ValidatePathDetails<'_>) -> DBResult<()> {
     let ValidatePathDetails {
         path,
         ty,
         custom_path,
-        ..
+        user_id,
+        bucket_id,
     } = asset;
 
     let path = custom_path.clone().unwrap_or(path.to_string());
     let asset = Assets::get_by_path(db, &path, ty).await.ok();
 
-    if asset.is_some() {
+    if asset.is_some_and(|asset| asset.user_id() != user_id || asset.bucket_id() != bucket_id) {
         let field = if custom_path.is_some() {
             "custom_path"
         } else {
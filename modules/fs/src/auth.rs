@@ -1,4 +1,7 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    path::{Path, PathBuf},
+};
 
 use ppd_bk::RBatis;
 use ppd_bk::models::asset::{AssetType, Assets, NewAsset, UpdateAssetValues};
@@ -7,8 +10,10 @@ use ppd_bk::validators::{ValidatePathDetails, validate_asset_paths};
 use ppd_shared::tools::mb_to_bytes;
 
 use crate::errors::Error;
-use crate::utils::{create_asset_parents, get_bucket_size};
-use crate::{FsResult, opts::CreateAssetOptions};
+use crate::storage::{Storage, StorageBackend};
+use crate::utils::{create_asset_parents, get_bucket_size, remove_content};
+use crate::versions::{keep_version, prune_versions, remove_versions, restore_kept};
+use crate::{FsResult, opts::CreateAssetOptions, validate_user_path};
 
 /// create or update an asset
 pub async fn create_or_update_asset(
@@ -29,6 +34,12 @@ pub async fn create_or_update_asset(
         bucket,
     } = opts;
 
+    // reserved folders are only written by the service itself (i.e trash and versions)
+    let paths = [Some(asset_path), custom_path.as_ref(), update_asset_path.as_ref()];
+    for path in paths.into_iter().flatten() {
+        validate_user_path(path)?;
+    }
+
     // retrieve bucket and validate bucket ownership
     let bucket = Buckets::get_by_pid(db, bucket).await?;
     if !bucket.validate_write(user_id) {
@@ -37,10 +48,7 @@ pub async fn create_or_update_asset(
         ));
     }
 
-    // extract destination path
-    let partition = bucket.partition().as_deref();
-    let dest = partition.map_or(asset_path.to_string(), |rf| format!("{rf}/{asset_path}"));
-    let dest = Path::new(&dest);
+    let storage = Storage::for_bucket(&bucket);
 
     // validate file mimetype and check bucket size limit
     if let Some(tmp_file) = tmp {
@@ -64,29 +72,57 @@ pub async fn create_or_update_asset(
         path: asset_path,
         ty: asset_type,
         custom_path,
+        user_id,
+        bucket_id: &bucket.id(),
     };
 
     validate_asset_paths(db, vd).await?;
 
     // create parents if required
     if create_parents.unwrap_or(true) {
-        create_asset_parents(db, dest, user_id, &bucket.id(), public).await?;
+        let path = Path::new(asset_path);
+        create_asset_parents(db, &storage, path, user_id, &bucket.id(), public).await?;
+    }
+
+    // size and mime type of new file content
+    let (size, mime) = match (asset_type, tmp) {
+        (AssetType::File, Some(tmp)) => {
+            let size = match filesize {
+                Some(size) => *size,
+                None => tokio::fs::metadata(tmp).await?.len(),
+            };
+
+            let mime = mime_guess::from_path(asset_path).first_or_octet_stream();
+            (Some(size), Some(mime.to_string()))
+        }
+        _ => (None, None),
+    };
+
+    // keep the content being replaced as a version
+    let mut kept = None;
+    if let (AssetType::File, Some(_)) = (asset_type, tmp)
+        && bucket.versioning()
+        && let Ok(current) = Assets::get_by_path(db, asset_path, asset_type).await
+        && current.path() == asset_path
+        && current.user_id() == user_id
+        && current.bucket_id() == &bucket.id()
+    {
+        kept = keep_version(db, &bucket, &current).await?;
     }
 
     match asset_type {
         AssetType::File => {
-            if let Some(tmp) = tmp {
-                #[cfg(target_os = "linux")]
-                {
-                    tokio::fs::copy(tmp, &dest).await?;
-                    tokio::fs::remove_file(tmp).await?;
+            if let Some(tmp) = tmp
+                && let Err(err) = storage.put_file(asset_path, tmp).await
+            {
+                if let Some(kept) = kept {
+                    restore_kept(db, &bucket, asset_path, kept).await;
                 }
 
-                #[cfg(not(target_os = "linux"))]
-                tokio::fs::rename(tmp, &dest).await?;
+                return Err(err);
             }
         }
-        AssetType::Folder => tokio::fs::create_dir(&dest).await?,
+        AssetType::Folder => storage.create_folder(asset_path).await?,
     }
 
     // if path already exists, update it. Else, create.
@@ -95,7 +131,7 @@ pub async fn create_or_update_asset(
 
     let asset: Result<Assets, Error> = match Assets::get_by_path(db, &path, asset_type).await {
         Ok(mut exists) => {
-            if exists.user_id() != user_id {
+            if exists.user_id() != user_id || exists.bucket_id() != &bucket.id() {
                 return Err(Error::PermissionError(
                     "you do not have permission to update this resource.".to_string(),
                 ));
@@ -106,6 +142,8 @@ pub async fn create_or_update_asset(
                 asset_path,
                 custom_path: custom_path.clone(),
                 public,
+                size,
+                mime,
             };
 
             exists.update(db, values).await?;
@@ -119,6 +157,8 @@ pub async fn create_or_update_asset(
                 custom_path: custom_path.clone(),
                 asset_type: u8::from(asset_type),
                 bucket_id: bucket.id(),
+                size,
+                mime,
             };
 
             Assets::create(db, value).await?;
@@ -128,8 +168,12 @@ pub async fn create_or_update_asset(
         }
     };
 
-    // share asset with collaborators
     let asset = asset?;
+    if kept.is_some() {
+        prune_versions(db, &bucket, &asset.id()).await;
+    }
+
+    // share asset with collaborators
     if public
         && let Some(sharing) = sharing
         && !sharing.is_empty()
@@ -140,36 +184,105 @@ pub async fn create_or_update_asset(
     Ok(())
 }
 
-/// removes an asset and associated records. if asset is a folder, this will remove all its content as well
+/// removes an asset and its records. A folder is removed with everything inside it.
+///
+/// Content is removed before records. An asset whose content can't be removed keeps its record,
+/// and so do its parent folders. Such failures are reported with [Error::PartialDelete]. The
+/// other records are removed together, but if that fails they are left pointing at content
+/// that was already removed.
 pub async fn delete_asset(db: &RBatis, path: &str, asset_type: &AssetType) -> FsResult<()> {
-    // delete asset records
     let asset = Assets::get_by_path(db, path, asset_type).await?;
-    asset.delete(db).await?;
+    let path = asset.path().to_string();
 
-    // delete asset's children records
+    let mut assets = vec![asset];
     if let AssetType::Folder = asset_type {
-        let mut entries = tokio::fs::read_dir(asset.path()).await?;
-        while let Ok(Some(entry)) = entries.next_entry().await {
-            let path = entry.path();
-            let child_type = if path.is_file() {
-                AssetType::File
-            } else {
-                AssetType::Folder
-            };
+        assets.extend(Assets::get_descendants(db, &path).await?);
+    }
 
-            if let Some(path) = path.to_str()
-                && let Ok(child) = Assets::get_by_path(db, path, &child_type).await
-            {
-                child.delete(db).await?;
+    let mut storages: HashMap<u64, Storage> = HashMap::new();
+    let mut kept: Vec<&Assets> = Vec::new();
+    let mut failed = Vec::new();
+
+    // deepest paths first, so folders are emptied before they are removed
+    for asset in assets.iter().rev() {
+        let prefix = format!("{}/", asset.path());
+        if kept.iter().any(|k| k.path().starts_with(&prefix)) {
+            kept.push(asset);
+            continue;
+        }
+
+        let storage = match storages.entry(*asset.bucket_id()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let bucket = Buckets::get(db, asset.bucket_id()).await?;
+                entry.insert(Storage::for_bucket(&bucket))
             }
+        };
+
+        // versions go first, so a file is only removed once it has none left
+        let removed = async {
+            if let AssetType::File = asset.asset_type() {
+                remove_versions(db, &asset.id()).await?;
+            }
+
+            remove_content(storage, asset.path(), asset.asset_type()).await
+        }
+        .await;
+
+        if let Err(err) = removed {
+            tracing::error!("unable to remove '{}' from storage: {err}", asset.path());
+            failed.push((asset.path().to_string(), err.to_string()));
+            kept.push(asset);
         }
     }
 
-    // delete asset
-    match asset_type {
-        AssetType::File => tokio::fs::remove_file(path).await?,
-        AssetType::Folder => tokio::fs::remove_dir_all(path).await?,
+    let keep: Vec<u64> = kept.iter().map(|asset| asset.id()).collect();
+    Assets::delete_tree(db, &path, &keep).await?;
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::PartialDelete {
+            deleted: assets.len() - keep.len(),
+            failed,
+        })
+    }
+}
+
+/// removes a bucket, its assets and their records. a partitioned bucket is removed with its
+/// partition. otherwise the storage root is shared, so only the bucket's assets are removed.
+pub async fn delete_bucket(db: &RBatis, bucket: &Buckets) -> FsResult<()> {
+    let storage = Storage::for_bucket(bucket);
+    let partitioned = bucket.partition().is_some();
+
+    for asset_type in [AssetType::File, AssetType::Folder] {
+        // deepest paths first, so folders are emptied before they are removed
+        let mut assets = Assets::get_by_bucket(db, &bucket.id(), &asset_type).await?;
+        assets.reverse();
+
+        for asset in assets {
+            if let AssetType::File = asset_type {
+                remove_versions(db, &asset.id()).await?;
+            }
+
+            asset.delete(db).await?;
+
+            if partitioned || storage.metadata(asset.path()).await?.is_none() {
+                continue;
+            }
+
+            match asset_type {
+                AssetType::File => storage.remove_file(asset.path()).await?,
+                AssetType::Folder => storage.remove_folder(asset.path()).await?,
+            }
+        }
+    }
+
+    // the partition is only created on first write, so it may not exist
+    if partitioned && let Err(err) = storage.remove_folder("").await {
+        tracing::debug!("unable to remove partition of bucket {}: {err}", bucket.pid());
     }
 
+    Buckets::delete(db, bucket.pid()).await?;
     Ok(())
 }
@@ -10,6 +10,14 @@ pub enum Error {
     ServerError(String),
     PermissionError(String),
     NotFound(String),
+
+    /// some assets of a deleted folder could not be removed. They are kept with their parent
+    /// folders. Holds the number of deleted assets, and the path of every asset that could
+    /// not be removed with the reason.
+    PartialDelete {
+        deleted: usize,
+        failed: Vec<(String, String)>,
+    },
 }
 
 impl Display for Error {
@@ -22,6 +30,19 @@ impl Display for Error {
             ServerError(msg) => write!(f, "{msg}"),
             PermissionError(msg) => write!(f, "{msg}"),
             NotFound(msg) => write!(f, "{msg}"),
+            PartialDelete { deleted, failed } => {
+                let failed: Vec<String> = failed
+                    .iter()
+                    .map(|(path, err)| format!("'{path}' ({err})"))
+                    .collect();
+
+                write!(
+                    f,
+                    "deleted {deleted} assets, but {} could not be deleted: {}",
+                    failed.len(),
+                    failed.join(", ")
+                )
+            }
         }
     }
 }
@@ -37,3 +58,9 @@ impl From<IOError> for Error {
         Error::IOError(value)
     }
 }
+
+impl From<reqwest::Error> for Error {
+    fn from(value: reqwest::Error) -> Self {
+        Error::ServerError(value.to_string())
+    }
+}
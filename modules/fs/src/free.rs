@@ -1,31 +1,140 @@
-use std::path::PathBuf;
+//! Asset operations for services running without authentication (zero mode). Assets are
+//! saved on the local filesystem under a root folder, without database records.
+
+use std::{
+    path::{Component, Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use ppd_bk::models::asset::AssetType;
+use ppd_shared::api::{EntryType, FolderEntry, FolderListing};
+
+use crate::{
+    AssetBody, FileBody, FsResult,
+    errors::Error,
+    storage::{LocalStorage, Storage, StorageBackend},
+};
 
-use crate::FsResult;
+/// validate that `asset_path` is a relative path that stays inside the storage root.
+pub fn validate_path(asset_path: &str) -> FsResult<()> {
+    let valid = !asset_path.is_empty()
+        && Path::new(asset_path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+
+    if !valid {
+        return Err(Error::PermissionError(format!(
+            "invalid asset path '{asset_path}'"
+        )));
+    }
+
+    Ok(())
+}
 
 pub async fn create_or_update(
+    root: &str,
     asset_type: &AssetType,
     asset_path: &str,
     tmp: &Option<PathBuf>,
 ) -> FsResult<()> {
+    validate_path(asset_path)?;
+    let storage = LocalStorage::new(Some(root));
+
     match asset_type {
         AssetType::File => {
             if let Some(tmp) = tmp {
-                tokio::fs::rename(tmp, asset_path).await?;
+                if let Some(parent) = Path::new(asset_path).parent()
+                    && let Some(parent) = parent.to_str()
+                    && !parent.is_empty()
+                {
+                    storage.create_folder(parent).await?;
+                }
+
+                storage.put_file(asset_path, tmp).await?;
             }
         }
-        AssetType::Folder => tokio::fs::create_dir(asset_path).await?,
+        AssetType::Folder => storage.create_folder(asset_path).await?,
     }
 
     Ok(())
 }
 
-pub async fn delete_asset(asset_type: &AssetType, asset_path: &str) -> FsResult<()> {
+pub async fn delete_asset(root: &str, asset_type: &AssetType, asset_path: &str) -> FsResult<()> {
+    validate_path(asset_path)?;
+    let storage = LocalStorage::new(Some(root));
+
     match asset_type {
-        AssetType::File => tokio::fs::remove_file(asset_path).await?,
-        AssetType::Folder => tokio::fs::remove_dir_all(asset_path).await?,
+        AssetType::File => storage.remove_file(asset_path).await?,
+        AssetType::Folder => storage.remove_folder(asset_path).await?,
     }
 
     Ok(())
 }
+
+/// read an asset saved under `root`. Links in folder listings are prefixed with `url_prefix`.
+/// Folders are listed in full, sorted by name.
+pub async fn read_asset(
+    root: &str,
+    asset_type: &AssetType,
+    asset_path: &str,
+    url_prefix: &str,
+) -> FsResult<AssetBody> {
+    validate_path(asset_path)?;
+
+    let storage = LocalStorage::new(Some(root));
+    let meta = storage.metadata(asset_path).await?;
+
+    match (asset_type, meta) {
+        (AssetType::File, Some(meta)) if meta.is_file => {
+            let file = FileBody::from_meta(Storage::Local(storage), asset_path, true, meta);
+            Ok(AssetBody::File(file))
+        }
+        (AssetType::Folder, Some(meta)) if !meta.is_file => {
+            let mut entries = storage.list(asset_path).await?;
+            entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+            let mut listing = FolderListing {
+                path: asset_path.to_string(),
+                ..Default::default()
+            };
+
+            for entry in entries {
+                let name = entry.name;
+                let path = format!("{asset_path}/{name}");
+                let (ty, entry_type) = if entry.is_file {
+                    (AssetType::File, EntryType::File)
+                } else {
+                    (AssetType::Folder, EntryType::Folder)
+                };
+
+                let meta = storage.metadata(&path).await?;
+                let mime = entry
+                    .is_file
+                    .then(|| mime_guess::from_path(&name).first_or_octet_stream().to_string());
+
+                listing.entries.push(FolderEntry {
+                    url: format!("{url_prefix}/{ty}/{path}"),
+                    size: meta.as_ref().filter(|m| m.is_file).map(|m| m.size),
+                    updated_at: meta.and_then(|m| m.modified).and_then(unix_time),
+                    created_at: None,
+                    public: true,
+                    name,
+                    path,
+                    entry_type,
+                    mime,
+                });
+            }
+
+            Ok(AssetBody::Folder(listing))
+        }
+        _ => Err(Error::NotFound(format!(
+            "path '{asset_path}' does not exist for '{asset_type}'."
+        ))),
+    }
+}
+
+fn unix_time(time: SystemTime) -> Option<i64> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs() as i64)
+}
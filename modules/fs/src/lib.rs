@@ -1,38 +1,225 @@
-use std::path::Path;
+use std::{
+    pin::Pin,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use mime_guess::Mime;
 use ppd_bk::{
     RBatis,
-    models::asset::{AssetType, Assets},
+    models::{
+        asset::{AssetType, Assets},
+        bucket::Buckets,
+    },
 };
 
-use crate::errors::Error;
+use ppd_shared::api::{EntryType, FolderEntry, FolderListing, ListFolderOptions};
+use tokio::io::AsyncRead;
+
+use crate::{
+    errors::Error,
+    storage::{Storage, StorageBackend, StorageMeta},
+};
 
 #[cfg(feature = "auth")]
 pub mod auth;
 
-#[cfg(not(feature = "auth"))]
 pub mod free;
 
 pub mod errors;
 pub mod opts;
+pub mod storage;
+
+#[cfg(feature = "auth")]
+pub mod transfer;
+
+#[cfg(feature = "auth")]
+pub mod trash;
+
+#[cfg(feature = "auth")]
+pub mod upload;
+
+#[cfg(feature = "auth")]
+pub mod versions;
+
 mod utils;
 
 pub type FsResult<T> = Result<T, Error>;
 
+/// folders of storages that hold content managed by PPDRIVE. Assets can't be created in them.
+#[cfg(feature = "auth")]
+pub const RESERVED_FOLDERS: [&str; 2] = [trash::TRASH_FOLDER, versions::VERSIONS_FOLDER];
+
+/// the reserved folder `path` is in, if any. See [RESERVED_FOLDERS].
+#[cfg(feature = "auth")]
+pub fn reserved_folder(path: &str) -> Option<&'static str> {
+    RESERVED_FOLDERS.into_iter().find(|folder| {
+        path.strip_prefix(folder)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    })
+}
+
+/// check that `path` can be written by users. Empty, `.` and `..` segments are rejected, so a
+/// path can't reach a reserved folder (or another user's content) without naming it first.
+#[cfg(feature = "auth")]
+pub fn validate_user_path(path: &str) -> FsResult<()> {
+    if path
+        .split('/')
+        .any(|seg| seg.is_empty() || seg == "." || seg == "..")
+    {
+        return Err(Error::PermissionError(format!("invalid path '{path}'.")));
+    }
+
+    if let Some(folder) = reserved_folder(path) {
+        return Err(Error::PermissionError(format!(
+            "'{folder}' is reserved. please choose another path."
+        )));
+    }
+
+    Ok(())
+}
+
+/// Readable stream of an asset's content.
+pub type AssetReader = Pin<Box<dyn AsyncRead + Send>>;
+
 pub enum AssetBody {
-    File(Mime, Vec<u8>),
-    Folder(String),
+    File(FileBody),
+    Folder(FolderListing),
+}
+
+/// A file asset. Content is not loaded into memory, instead it's read (in full or by
+/// byte range) when the response is being streamed.
+pub struct FileBody {
+    storage: Storage,
+    key: String,
+    mime: Mime,
+    size: u64,
+    modified: Option<SystemTime>,
+    public: bool,
 }
 
+impl FileBody {
+    fn new(storage: Storage, asset: &Assets, meta: StorageMeta) -> Self {
+        Self::from_meta(storage, asset.path(), *asset.public(), meta)
+    }
+
+    fn from_meta(storage: Storage, key: &str, public: bool, meta: StorageMeta) -> Self {
+        let mime = mime_guess::from_path(key).first_or_octet_stream();
+
+        FileBody {
+            storage,
+            key: key.to_string(),
+            mime,
+            size: meta.size,
+            modified: meta.modified,
+            public,
+        }
+    }
+
+    /// a version of a file asset, stored at `key`. The mime type is taken from the asset's path.
+    #[cfg(feature = "auth")]
+    fn version(storage: Storage, key: &str, asset: &Assets, meta: StorageMeta) -> Self {
+        FileBody {
+            mime: mime_guess::from_path(asset.path()).first_or_octet_stream(),
+            ..Self::from_meta(storage, key, *asset.public(), meta)
+        }
+    }
+
+    /// open a file asset of `bucket` without checking read permissions.
+    pub async fn open(bucket: &Buckets, asset: &Assets) -> FsResult<Self> {
+        let storage = Storage::for_bucket(bucket);
+
+        match storage.metadata(asset.path()).await? {
+            Some(meta) if meta.is_file => Ok(FileBody::new(storage, asset, meta)),
+            _ => Err(Error::NotFound(format!(
+                "file '{}' does not exist in storage.",
+                asset.path()
+            ))),
+        }
+    }
+
+    pub fn mime(&self) -> &Mime {
+        &self.mime
+    }
+
+    pub fn size(&self) -> &u64 {
+        &self.size
+    }
+
+    pub fn modified(&self) -> &Option<SystemTime> {
+        &self.modified
+    }
+
+    pub fn public(&self) -> &bool {
+        &self.public
+    }
+
+    /// strong entity tag derived from file size and modification time
+    pub fn etag(&self) -> String {
+        let modified = self
+            .modified
+            .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+
+        format!("\"{:x}-{:x}\"", self.size, modified.as_nanos())
+    }
+
+    /// reader for the entire file
+    pub async fn reader(&self) -> FsResult<AssetReader> {
+        self.range_reader(0, self.size).await
+    }
+
+    /// reader for `len` bytes of the file, starting at `start`
+    pub async fn range_reader(&self, start: u64, len: u64) -> FsResult<AssetReader> {
+        self.storage.read(&self.key, start, len).await
+    }
+}
+
+/// read an asset that `user_id` has access to. Folders are listed as selected by `list`.
 pub async fn read_asset(
     db: &RBatis,
     asset_path: &str,
     asset_type: &AssetType,
     user_id: &Option<u64>,
+    list: &ListFolderOptions,
 ) -> FsResult<AssetBody> {
+    let asset = readable_asset(db, asset_path, asset_type, user_id).await?;
+    let bucket = Buckets::get(db, asset.bucket_id()).await?;
+    let storage = Storage::for_bucket(&bucket);
+    let meta = storage.metadata(asset.path()).await?;
+
+    match asset_type {
+        AssetType::File => match meta {
+            Some(meta) if meta.is_file => Ok(AssetBody::File(FileBody::new(storage, &asset, meta))),
+            _ => Err(Error::NotFound(format!(
+                "asset record found but path '{asset_path}' does not exist if filesystem for '{asset_type}'."
+            ))),
+        },
+        AssetType::Folder => match meta {
+            Some(meta) if !meta.is_file => {
+                let listing = list_folder(db, &asset, user_id, list).await?;
+                Ok(AssetBody::Folder(listing))
+            }
+            _ => Err(Error::NotFound(format!(
+                "asset record found but path '{asset_path}' does not exist for '{asset_type}'."
+            ))),
+        },
+    }
+}
+
+/// retrieve an asset that `user_id` can read.
+async fn readable_asset(
+    db: &RBatis,
+    asset_path: &str,
+    asset_type: &AssetType,
+    user_id: &Option<u64>,
+) -> FsResult<Assets> {
     let asset = Assets::get_by_path(db, asset_path, asset_type).await?;
 
+    // trashed assets can only be restored
+    if asset.deleted_at().is_some() {
+        return Err(Error::NotFound("asset not found".to_string()));
+    }
+
     // if asset has custom path and custom path is not provided in url,
     // we return an error. The purpose of custom path is to conceal the
     // original path
@@ -57,78 +244,59 @@ pub async fn read_asset(
         }
     }
 
-    let path = Path::new(asset.path());
-    match asset_type {
-        AssetType::File => {
-            if path.exists() && path.is_file() {
-                let content = tokio::fs::read(path).await?;
-                let mime_type = mime_guess::from_path(path).first_or_octet_stream();
-
-                let resp = AssetBody::File(mime_type, content);
-                Ok(resp)
-            } else {
-                Err(Error::NotFound(format!(
-                    "asset record found but path '{asset_path}' does not exist if filesystem for '{asset_type}'."
-                )))
-            }
-        }
-        AssetType::Folder => {
-            if path.exists() && path.is_dir() {
-                let mut contents = tokio::fs::read_dir(path).await?;
-                let mut filenames = Vec::new();
-
-                // let's attempt to read folder contents, checking for
-                // asset ownership all along
-                while let Ok(Some(entry)) = contents.next_entry().await {
-                    let path = entry.path();
-                    let filename = entry.file_name();
-
-                    if let (Some(path_str), Some(filename)) = (path.to_str(), filename.to_str()) {
-                        let asset_type = if path.is_file() {
-                            AssetType::File
-                        } else {
-                            AssetType::Folder
-                        };
-
-                        let asset = Assets::get_by_path(db, path_str, &asset_type).await;
-                        if let Ok(asset) = asset {
-                            let html =
-                                format!("<li><a href='/{}'>{filename}</a></li>", asset.url_path());
-
-                            if *asset.public() {
-                                filenames.push(html);
-                            } else if let Some(user_id) = user_id {
-                                let can_read = asset.can_read(db, user_id).await;
-                                if (user_id == asset.user_id()) || can_read.is_ok() {
-                                    filenames.push(html);
-                                }
-                            }
-                        }
-                    }
-                }
+    Ok(asset)
+}
 
-                let content = if filenames.is_empty() {
-                    "<p>No content found.</p>".to_string()
-                } else {
-                    format!(r#"<ul>{}</ul>"#, filenames.join("\n"))
-                };
-
-                let body = format!(
-                    r#"
-                    <DOCTYPE! html>
-                    <html>
-                        {content}
-                    </html>
-                "#
-                );
-
-                let resp = AssetBody::Folder(body);
-                Ok(resp)
-            } else {
-                Err(Error::NotFound(format!(
-                    "asset record found but path '{asset_path}' does not exist for '{asset_type}'."
-                )))
-            }
-        }
+/// a page of the entries of `folder` that `user_id` can read. Entries are read from the
+/// bookkeeper, not from the storage.
+pub async fn list_folder(
+    db: &RBatis,
+    folder: &Assets,
+    user_id: &Option<u64>,
+    opts: &ListFolderOptions,
+) -> FsResult<FolderListing> {
+    let after = opts
+        .after()
+        .map_err(|err| Error::ServerError(err.to_string()))?;
+
+    // fetch an extra entry to know whether there's a next page
+    let limit = opts.limit();
+    let mut assets =
+        Assets::list_children(db, folder.path(), user_id, opts, after.as_ref(), limit + 1).await?;
+
+    let next_cursor = if assets.len() as u64 > limit {
+        assets.truncate(limit as usize);
+        assets
+            .last()
+            .map(|asset| asset.cursor(opts.sort, opts.order).encode())
+    } else {
+        None
+    };
+
+    Ok(FolderListing {
+        path: folder.path().to_string(),
+        entries: assets.iter().map(folder_entry).collect(),
+        next_cursor,
+    })
+}
+
+fn folder_entry(asset: &Assets) -> FolderEntry {
+    let path = asset.path();
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let entry_type = match asset.asset_type() {
+        AssetType::File => EntryType::File,
+        AssetType::Folder => EntryType::Folder,
+    };
+
+    FolderEntry {
+        name: name.to_string(),
+        path: path.to_string(),
+        entry_type,
+        size: *asset.size(),
+        mime: asset.mime().clone(),
+        created_at: *asset.created_at(),
+        updated_at: *asset.updated_at(),
+        public: *asset.public(),
+        url: format!("/{}", asset.url_path()),
     }
 }
@@ -33,4 +33,56 @@ pub struct CreateAssetOptions {
 
     /// Users to share this asset with. This can only be set if `public` option is false
     pub sharing: Option<Vec<AssetSharing>>,
-}
\ No newline at end of file
+}
+#[derive(Default, Deserialize, Serialize)]
+pub struct CreateUploadOptions {
+    /// Options of the asset to be created when the upload is completed.
+    #[serde(flatten)]
+    pub asset: CreateAssetOptions,
+
+    /// Total size of the upload (bytes). If provided, the upload can only be completed once
+    /// exactly this number of bytes have been received.
+    pub size: Option<u64>,
+}
+
+/// Options for moving or copying an asset. Folders are moved or copied with their content.
+#[derive(Default, Deserialize, Serialize)]
+pub struct MoveAssetOptions {
+    /// Path of the asset to be moved or copied
+    pub asset_path: String,
+
+    /// The type of asset - whether it's a file or folder
+    pub asset_type: AssetType,
+
+    /// Path where the asset should be moved or copied to. Nothing must exist at this path.
+    pub destination: String,
+
+    /// The UID of the bucket to move or copy the asset to. Defaults to the asset's bucket.
+    pub bucket: Option<String>,
+
+    /// Whether missing parent folders of `destination` should be created. Defaults to `true`.
+    pub create_parents: Option<bool>,
+}
+
+/// Options for renaming an asset, keeping it in the same folder.
+#[derive(Default, Deserialize, Serialize)]
+pub struct RenameAssetOptions {
+    /// Path of the asset to be renamed
+    pub asset_path: String,
+
+    /// The type of asset - whether it's a file or folder
+    pub asset_type: AssetType,
+
+    /// New name of the asset
+    pub name: String,
+}
+
+/// Options for making a previous version of a file its current content.
+#[derive(Default, Deserialize, Serialize)]
+pub struct PromoteVersionOptions {
+    /// Path of the file
+    pub asset_path: String,
+
+    /// The version to be promoted
+    pub version: u64,
+}
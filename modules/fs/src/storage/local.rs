@@ -0,0 +1,158 @@
+use std::{
+    io::SeekFrom,
+    path::{Path, PathBuf},
+};
+
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use super::{StorageBackend, StorageEntry, StorageMeta};
+use crate::{AssetReader, FsResult, errors::Error};
+
+/// Saves assets on the local filesystem. Paths are relative to the working directory
+/// unless the storage has a root (i.e the bucket's partition).
+#[derive(Clone)]
+pub struct LocalStorage {
+    root: Option<PathBuf>,
+}
+
+impl LocalStorage {
+    pub fn new(root: Option<&str>) -> Self {
+        Self {
+            root: root.map(PathBuf::from),
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        match &self.root {
+            Some(root) => root.join(key),
+            None => PathBuf::from(key),
+        }
+    }
+}
+
+impl StorageBackend for LocalStorage {
+    async fn put_file(&self, key: &str, tmp: &Path) -> FsResult<()> {
+        if let Some(root) = &self.root {
+            tokio::fs::create_dir_all(root).await?;
+        }
+
+        let dest = self.path(key);
+
+        #[cfg(target_os = "linux")]
+        {
+            tokio::fs::copy(tmp, &dest).await?;
+            tokio::fs::remove_file(tmp).await?;
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        tokio::fs::rename(tmp, &dest).await?;
+
+        Ok(())
+    }
+
+    async fn create_folder(&self, key: &str) -> FsResult<()> {
+        tokio::fs::create_dir_all(self.path(key)).await?;
+        Ok(())
+    }
+
+    async fn remove_file(&self, key: &str) -> FsResult<()> {
+        tokio::fs::remove_file(self.path(key)).await?;
+        Ok(())
+    }
+
+    async fn remove_folder(&self, key: &str) -> FsResult<()> {
+        tokio::fs::remove_dir_all(self.path(key)).await?;
+        Ok(())
+    }
+
+    async fn rename(&self, key: &str, dest: &str) -> FsResult<()> {
+        let dest = self.path(dest);
+        if let Some(parent) = dest.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        tokio::fs::rename(self.path(key), dest).await?;
+        Ok(())
+    }
+
+    async fn metadata(&self, key: &str) -> FsResult<Option<StorageMeta>> {
+        let path = self.path(key);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let meta = tokio::fs::metadata(path).await?;
+        Ok(Some(StorageMeta {
+            is_file: meta.is_file(),
+            size: meta.len(),
+            modified: meta.modified().ok(),
+        }))
+    }
+
+    async fn read(&self, key: &str, start: u64, len: u64) -> FsResult<AssetReader> {
+        let mut file = tokio::fs::File::open(self.path(key)).await?;
+        if start > 0 {
+            file.seek(SeekFrom::Start(start)).await?;
+        }
+
+        Ok(Box::pin(file.take(len)))
+    }
+
+    async fn list(&self, key: &str) -> FsResult<Vec<StorageEntry>> {
+        let mut contents = tokio::fs::read_dir(self.path(key)).await?;
+        let mut entries = Vec::new();
+
+        while let Ok(Some(entry)) = contents.next_entry().await {
+            if let Some(name) = entry.file_name().to_str() {
+                entries.push(StorageEntry {
+                    name: name.to_string(),
+                    is_file: entry.path().is_file(),
+                });
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// size of the storage root. storage without a root is not accounted for and
+    /// always has a size of 0.
+    async fn size(&self) -> FsResult<u64> {
+        let mut size = 0;
+        if let Some(root) = &self.root {
+            if !root.exists() {
+                tokio::fs::create_dir_all(root).await?;
+                return Ok(size);
+            }
+
+            get_folder_size(root, &mut size).await?;
+        }
+
+        Ok(size)
+    }
+}
+
+/// compute total size (in bytes) of a folder.
+async fn get_folder_size(path: &Path, size: &mut u64) -> FsResult<()> {
+    if path.is_file() {
+        return Err(Error::ServerError(
+            "provided path is not a folder path".to_string(),
+        ));
+    }
+
+    let mut rd = tokio::fs::read_dir(path).await?;
+
+    while let Ok(Some(entry)) = rd.next_entry().await {
+        let path = entry.path();
+
+        if path.is_file() {
+            let m = path.metadata()?;
+            *size += m.len()
+        } else {
+            Box::pin(get_folder_size(&path, size)).await?;
+        }
+    }
+
+    Ok(())
+}
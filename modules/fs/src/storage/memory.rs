@@ -0,0 +1,238 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Cursor,
+    path::Path,
+    sync::{Arc, LazyLock, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time::SystemTime,
+};
+
+use tokio::io::AsyncReadExt;
+
+use super::{StorageBackend, StorageEntry, StorageMeta};
+use crate::{AssetReader, FsResult, errors::Error};
+
+enum MemoryObject {
+    File { data: Arc<[u8]>, modified: SystemTime },
+    Folder,
+}
+
+/// Objects of all memory storages, keyed by their full path.
+static STORE: LazyLock<RwLock<BTreeMap<String, MemoryObject>>> =
+    LazyLock::new(|| RwLock::new(BTreeMap::new()));
+
+fn store() -> RwLockReadGuard<'static, BTreeMap<String, MemoryObject>> {
+    STORE.read().unwrap_or_else(|err| err.into_inner())
+}
+
+fn store_mut() -> RwLockWriteGuard<'static, BTreeMap<String, MemoryObject>> {
+    STORE.write().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Keeps assets in memory. Content is shared by every storage in the process and is lost
+/// when the service stops.
+#[derive(Clone)]
+pub struct MemoryStorage {
+    root: Option<String>,
+}
+
+impl MemoryStorage {
+    pub fn new(root: Option<&str>) -> Self {
+        Self {
+            root: root.map(|r| r.trim_matches('/').to_string()),
+        }
+    }
+
+    fn path(&self, key: &str) -> String {
+        let key = key.trim_matches('/');
+        match &self.root {
+            Some(root) if key.is_empty() => root.clone(),
+            Some(root) => format!("{root}/{key}"),
+            None => key.to_string(),
+        }
+    }
+}
+
+/// prefix of objects inside the folder at `path`.
+fn folder_prefix(path: &str) -> String {
+    if path.is_empty() {
+        path.to_string()
+    } else {
+        format!("{path}/")
+    }
+}
+
+fn not_found(key: &str) -> Error {
+    Error::NotFound(format!("'{key}' does not exist in storage"))
+}
+
+impl StorageBackend for MemoryStorage {
+    async fn put_file(&self, key: &str, tmp: &Path) -> FsResult<()> {
+        let data = tokio::fs::read(tmp).await?;
+        tokio::fs::remove_file(tmp).await?;
+
+        let file = MemoryObject::File {
+            data: data.into(),
+            modified: SystemTime::now(),
+        };
+
+        store_mut().insert(self.path(key), file);
+        Ok(())
+    }
+
+    async fn create_folder(&self, key: &str) -> FsResult<()> {
+        store_mut().insert(self.path(key), MemoryObject::Folder);
+        Ok(())
+    }
+
+    async fn remove_file(&self, key: &str) -> FsResult<()> {
+        match store_mut().remove(&self.path(key)) {
+            Some(_) => Ok(()),
+            None => Err(not_found(key)),
+        }
+    }
+
+    async fn remove_folder(&self, key: &str) -> FsResult<()> {
+        let path = self.path(key);
+        let prefix = folder_prefix(&path);
+
+        let mut store = store_mut();
+        let existed = store.remove(&path).is_some();
+        let len = store.len();
+        store.retain(|k, _| !k.starts_with(&prefix));
+
+        if existed || store.len() != len {
+            Ok(())
+        } else {
+            Err(not_found(key))
+        }
+    }
+
+    async fn rename(&self, key: &str, dest: &str) -> FsResult<()> {
+        let (path, dest) = (self.path(key), self.path(dest));
+        let prefix = folder_prefix(&path);
+
+        let mut store = store_mut();
+        let keys: Vec<String> = store
+            .keys()
+            .filter(|k| **k == path || k.starts_with(&prefix))
+            .cloned()
+            .collect();
+
+        if keys.is_empty() {
+            return Err(not_found(key));
+        }
+
+        for k in keys {
+            if let Some(object) = store.remove(&k) {
+                store.insert(format!("{dest}{}", &k[path.len()..]), object);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn metadata(&self, key: &str) -> FsResult<Option<StorageMeta>> {
+        let path = self.path(key);
+        let store = store();
+
+        let meta = match store.get(&path) {
+            Some(MemoryObject::File { data, modified }) => Some(StorageMeta {
+                is_file: true,
+                size: data.len() as u64,
+                modified: Some(*modified),
+            }),
+            Some(MemoryObject::Folder) => Some(StorageMeta {
+                is_file: false,
+                size: 0,
+                modified: None,
+            }),
+            // a folder may exist implicitly through its content
+            None => {
+                let prefix = folder_prefix(&path);
+                store
+                    .range(prefix.clone()..)
+                    .next()
+                    .filter(|(k, _)| k.starts_with(&prefix))
+                    .map(|_| StorageMeta {
+                        is_file: false,
+                        size: 0,
+                        modified: None,
+                    })
+            }
+        };
+
+        Ok(meta)
+    }
+
+    async fn read(&self, key: &str, start: u64, len: u64) -> FsResult<AssetReader> {
+        let data = match store().get(&self.path(key)) {
+            Some(MemoryObject::File { data, .. }) => data.clone(),
+            _ => return Err(not_found(key)),
+        };
+
+        let mut cursor = Cursor::new(data);
+        cursor.set_position(start);
+
+        Ok(Box::pin(cursor.take(len)))
+    }
+
+    async fn list(&self, key: &str) -> FsResult<Vec<StorageEntry>> {
+        let prefix = folder_prefix(&self.path(key));
+        let store = store();
+
+        let mut files = BTreeSet::new();
+        let mut folders = BTreeSet::new();
+
+        for (k, object) in store.range(prefix.clone()..) {
+            let Some(rest) = k.strip_prefix(&prefix) else {
+                break;
+            };
+
+            match rest.split_once('/') {
+                Some((folder, _)) => {
+                    folders.insert(folder.to_string());
+                }
+                None => match object {
+                    MemoryObject::File { .. } => {
+                        files.insert(rest.to_string());
+                    }
+                    MemoryObject::Folder => {
+                        folders.insert(rest.to_string());
+                    }
+                },
+            }
+        }
+
+        let folders = folders.into_iter().map(|name| StorageEntry {
+            name,
+            is_file: false,
+        });
+
+        let files = files.into_iter().map(|name| StorageEntry {
+            name,
+            is_file: true,
+        });
+
+        Ok(folders.chain(files).collect())
+    }
+
+    /// size of the storage root. storage without a root is not accounted for and
+    /// always has a size of 0.
+    async fn size(&self) -> FsResult<u64> {
+        let Some(root) = &self.root else {
+            return Ok(0);
+        };
+
+        let prefix = folder_prefix(root);
+        let size = store()
+            .range(prefix.clone()..)
+            .take_while(|(k, _)| k.starts_with(&prefix))
+            .map(|(_, object)| match object {
+                MemoryObject::File { data, .. } => data.len() as u64,
+                MemoryObject::Folder => 0,
+            })
+            .sum();
+
+        Ok(size)
+    }
+}
@@ -0,0 +1,167 @@
+use std::{future::Future, path::Path, time::SystemTime};
+
+use ppd_bk::models::bucket::Buckets;
+use ppd_shared::api::BucketBackend;
+
+use crate::{AssetReader, FsResult};
+
+pub use local::LocalStorage;
+pub use memory::MemoryStorage;
+pub use s3::{S3Config, S3Storage};
+
+mod local;
+mod memory;
+pub mod s3;
+
+/// Details about an object in storage.
+pub struct StorageMeta {
+    pub is_file: bool,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+/// An entry in a storage folder.
+pub struct StorageEntry {
+    pub name: String,
+    pub is_file: bool,
+}
+
+/// Where bucket assets are saved. Objects are addressed by their asset path (`key`),
+/// which is relative to the root of the backend.
+pub trait StorageBackend {
+    /// move a (local) temporary file into storage at `key`.
+    fn put_file(&self, key: &str, tmp: &Path) -> impl Future<Output = FsResult<()>> + Send;
+
+    /// create a folder and all its missing parents.
+    fn create_folder(&self, key: &str) -> impl Future<Output = FsResult<()>> + Send;
+
+    fn remove_file(&self, key: &str) -> impl Future<Output = FsResult<()>> + Send;
+
+    /// remove a folder and all its content.
+    fn remove_folder(&self, key: &str) -> impl Future<Output = FsResult<()>> + Send;
+
+    /// move the file or folder (with its content) at `key` to `dest`. Missing parents of
+    /// `dest` are created.
+    fn rename(&self, key: &str, dest: &str) -> impl Future<Output = FsResult<()>> + Send;
+
+    /// returns `None` if nothing exists at `key`.
+    fn metadata(&self, key: &str) -> impl Future<Output = FsResult<Option<StorageMeta>>> + Send;
+
+    /// reader for `len` bytes of the file at `key`, starting at `start`.
+    fn read(
+        &self,
+        key: &str,
+        start: u64,
+        len: u64,
+    ) -> impl Future<Output = FsResult<AssetReader>> + Send;
+
+    /// list the direct children of the folder at `key`.
+    fn list(&self, key: &str) -> impl Future<Output = FsResult<Vec<StorageEntry>>> + Send;
+
+    /// total size (in bytes) of files in the backend.
+    fn size(&self) -> impl Future<Output = FsResult<u64>> + Send;
+}
+
+/// Storage backend of a bucket.
+#[derive(Clone)]
+pub enum Storage {
+    Local(LocalStorage),
+    Memory(MemoryStorage),
+    S3(S3Storage),
+}
+
+impl Storage {
+    /// storage for a bucket's assets, rooted at the bucket's partition (if any).
+    pub fn for_bucket(bucket: &Buckets) -> Self {
+        let root = bucket.partition().as_deref();
+
+        match bucket.backend() {
+            BucketBackend::Local => Storage::Local(LocalStorage::new(root)),
+            BucketBackend::Memory => Storage::Memory(MemoryStorage::new(root)),
+            BucketBackend::S3 => Storage::S3(S3Storage::new(root)),
+        }
+    }
+
+    /// check that a backend can be used before creating a bucket with it.
+    pub fn validate_backend(backend: &Option<BucketBackend>) -> FsResult<()> {
+        if let Some(BucketBackend::S3) = backend {
+            S3Config::from_env()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl StorageBackend for Storage {
+    async fn put_file(&self, key: &str, tmp: &Path) -> FsResult<()> {
+        match self {
+            Storage::Local(s) => s.put_file(key, tmp).await,
+            Storage::Memory(s) => s.put_file(key, tmp).await,
+            Storage::S3(s) => s.put_file(key, tmp).await,
+        }
+    }
+
+    async fn create_folder(&self, key: &str) -> FsResult<()> {
+        match self {
+            Storage::Local(s) => s.create_folder(key).await,
+            Storage::Memory(s) => s.create_folder(key).await,
+            Storage::S3(s) => s.create_folder(key).await,
+        }
+    }
+
+    async fn remove_file(&self, key: &str) -> FsResult<()> {
+        match self {
+            Storage::Local(s) => s.remove_file(key).await,
+            Storage::Memory(s) => s.remove_file(key).await,
+            Storage::S3(s) => s.remove_file(key).await,
+        }
+    }
+
+    async fn remove_folder(&self, key: &str) -> FsResult<()> {
+        match self {
+            Storage::Local(s) => s.remove_folder(key).await,
+            Storage::Memory(s) => s.remove_folder(key).await,
+            Storage::S3(s) => s.remove_folder(key).await,
+        }
+    }
+
+    async fn rename(&self, key: &str, dest: &str) -> FsResult<()> {
+        match self {
+            Storage::Local(s) => s.rename(key, dest).await,
+            Storage::Memory(s) => s.rename(key, dest).await,
+            Storage::S3(s) => s.rename(key, dest).await,
+        }
+    }
+
+    async fn metadata(&self, key: &str) -> FsResult<Option<StorageMeta>> {
+        match self {
+            Storage::Local(s) => s.metadata(key).await,
+            Storage::Memory(s) => s.metadata(key).await,
+            Storage::S3(s) => s.metadata(key).await,
+        }
+    }
+
+    async fn read(&self, key: &str, start: u64, len: u64) -> FsResult<AssetReader> {
+        match self {
+            Storage::Local(s) => s.read(key, start, len).await,
+            Storage::Memory(s) => s.read(key, start, len).await,
+            Storage::S3(s) => s.read(key, start, len).await,
+        }
+    }
+
+    async fn list(&self, key: &str) -> FsResult<Vec<StorageEntry>> {
+        match self {
+            Storage::Local(s) => s.list(key).await,
+            Storage::Memory(s) => s.list(key).await,
+            Storage::S3(s) => s.list(key).await,
+        }
+    }
+
+    async fn size(&self) -> FsResult<u64> {
+        match self {
+            Storage::Local(s) => s.size().await,
+            Storage::Memory(s) => s.size().await,
+            Storage::S3(s) => s.size().await,
+        }
+    }
+}
@@ -0,0 +1,456 @@
+use std::{
+    path::Path,
+    sync::{Arc, LazyLock},
+};
+
+use futures_util::TryStreamExt;
+use ppd_shared::{
+    sigv4::{self, CanonicalRequest, SigningScope, UNSIGNED_PAYLOAD},
+    xml,
+};
+use reqwest::{
+    Body, Client, Method, Response, StatusCode, Url,
+    header::{CONTENT_LENGTH, LAST_MODIFIED, RANGE},
+};
+use tokio_util::io::{ReaderStream, StreamReader};
+
+use super::{StorageBackend, StorageEntry, StorageMeta};
+use crate::{AssetReader, FsResult, errors::Error};
+
+pub const S3_ENDPOINT_KEY: &str = "PPDRIVE_S3_ENDPOINT";
+pub const S3_REGION_KEY: &str = "PPDRIVE_S3_REGION";
+pub const S3_BUCKET_KEY: &str = "PPDRIVE_S3_BUCKET";
+pub const S3_ACCESS_KEY: &str = "PPDRIVE_S3_ACCESS_KEY_ID";
+pub const S3_SECRET_KEY: &str = "PPDRIVE_S3_SECRET_ACCESS_KEY";
+
+const DEFAULT_REGION: &str = "us-east-1";
+
+/// header of a copy request, holding the object to be copied.
+pub const COPY_SOURCE: &str = "x-amz-copy-source";
+
+static CLIENT: LazyLock<Client> = LazyLock::new(Client::new);
+
+/// Connection details of an S3-compatible object store.
+pub struct S3Config {
+    /// base url of the object store, e.g `http://localhost:9000`.
+    endpoint: Url,
+    region: String,
+
+    /// object store bucket where assets are saved.
+    bucket: String,
+    access_key: String,
+    secret_key: String,
+}
+
+impl S3Config {
+    /// read config from `PPDRIVE_S3_*` environment variables.
+    pub fn from_env() -> FsResult<Self> {
+        let var = |key: &str| {
+            std::env::var(key)
+                .map_err(|_| Error::ServerError(format!("s3 storage requires '{key}' to be set.")))
+        };
+
+        let endpoint = var(S3_ENDPOINT_KEY)?;
+        let endpoint = Url::parse(&endpoint)
+            .map_err(|err| Error::ServerError(format!("invalid s3 endpoint: {err}")))?;
+
+        Ok(Self {
+            endpoint,
+            region: std::env::var(S3_REGION_KEY).unwrap_or(DEFAULT_REGION.to_string()),
+            bucket: var(S3_BUCKET_KEY)?,
+            access_key: var(S3_ACCESS_KEY)?,
+            secret_key: var(S3_SECRET_KEY)?,
+        })
+    }
+
+    fn host(&self) -> String {
+        let host = self.endpoint.host_str().unwrap_or_default();
+        match self.endpoint.port() {
+            Some(port) => format!("{host}:{port}"),
+            None => host.to_string(),
+        }
+    }
+}
+
+/// Saves assets in an S3-compatible object store (path-style addressing). The storage root
+/// (i.e the bucket's partition) is used as a key prefix, and folders are kept as empty
+/// objects whose key ends with `/`.
+#[derive(Clone)]
+pub struct S3Storage {
+    root: Option<String>,
+
+    /// connection details, or the reason they're not available.
+    config: Result<Arc<S3Config>, String>,
+}
+
+impl S3Storage {
+    pub fn new(root: Option<&str>) -> Self {
+        Self {
+            root: root.map(|r| r.trim_matches('/').to_string()),
+            config: S3Config::from_env()
+                .map(Arc::new)
+                .map_err(|err| err.to_string()),
+        }
+    }
+
+    fn config(&self) -> FsResult<&S3Config> {
+        self.config
+            .as_deref()
+            .map_err(|err| Error::ServerError(err.clone()))
+    }
+
+    /// object key of an asset
+    fn object_key(&self, key: &str) -> String {
+        let key = key.trim_matches('/');
+        match &self.root {
+            Some(root) if key.is_empty() => root.clone(),
+            Some(root) => format!("{root}/{key}"),
+            None => key.to_string(),
+        }
+    }
+
+    /// send a signed request to the object store. `key` is the object key, or `None` for
+    /// bucket level requests.
+    async fn send(
+        &self,
+        method: Method,
+        key: Option<&str>,
+        query: Vec<(String, String)>,
+        headers: Vec<(&str, String)>,
+        body: Option<Body>,
+    ) -> FsResult<Response> {
+        let config = self.config()?;
+
+        let mut path = format!("/{}", sigv4::uri_encode(&config.bucket, true));
+        if let Some(key) = key {
+            path = format!("{path}/{}", sigv4::uri_encode(key, false));
+        }
+
+        let amz_date = sigv4::amz_date();
+        let signed_headers = vec![
+            ("host".to_string(), config.host()),
+            (
+                "x-amz-content-sha256".to_string(),
+                UNSIGNED_PAYLOAD.to_string(),
+            ),
+            ("x-amz-date".to_string(), amz_date.clone()),
+        ];
+
+        let request = CanonicalRequest {
+            method: method.as_str(),
+            path: &path,
+            query: &query,
+            headers: &signed_headers,
+            payload_hash: UNSIGNED_PAYLOAD,
+        };
+
+        let scope = SigningScope {
+            amz_date: &amz_date,
+            region: &config.region,
+            service: "s3",
+        };
+
+        let authorization =
+            sigv4::authorization(&config.access_key, &config.secret_key, &scope, &request);
+
+        let mut url = config.endpoint.clone();
+        url.set_path(&path);
+        if !query.is_empty() {
+            let query = query
+                .iter()
+                .map(|(k, v)| format!("{}={}", sigv4::uri_encode(k, true), sigv4::uri_encode(v, true)))
+                .collect::<Vec<_>>()
+                .join("&");
+            url.set_query(Some(&query));
+        }
+
+        let mut req = CLIENT
+            .request(method, url)
+            .header("authorization", authorization)
+            .header("x-amz-content-sha256", UNSIGNED_PAYLOAD)
+            .header("x-amz-date", amz_date);
+
+        for (name, value) in headers {
+            req = req.header(name, value);
+        }
+
+        if let Some(body) = body {
+            req = req.body(body);
+        }
+
+        let resp = req.send().await?;
+        let status = resp.status();
+
+        if status == StatusCode::NOT_FOUND {
+            Err(Error::NotFound(format!(
+                "'{}' does not exist in storage",
+                key.unwrap_or_default()
+            )))
+        } else if !status.is_success() {
+            let msg = resp.text().await.unwrap_or_default();
+            Err(Error::ServerError(format!(
+                "s3 request failed with status {status}: {msg}"
+            )))
+        } else {
+            Ok(resp)
+        }
+    }
+
+    /// copy the object at `key` to `dest`, within the object store.
+    async fn copy_object(&self, key: &str, dest: &str) -> FsResult<()> {
+        let config = self.config()?;
+        let source = format!(
+            "/{}/{}",
+            sigv4::uri_encode(&config.bucket, true),
+            sigv4::uri_encode(key, false)
+        );
+
+        let headers = vec![
+            (COPY_SOURCE, source),
+            (CONTENT_LENGTH.as_str(), "0".to_string()),
+        ];
+
+        self.send(Method::PUT, Some(dest), vec![], headers, Some(Body::from("")))
+            .await?;
+
+        Ok(())
+    }
+
+    /// list objects (and folders, if `delimiter` is set) whose key starts with `prefix`.
+    async fn list_objects(&self, prefix: &str, delimiter: bool) -> FsResult<ObjectList> {
+        let mut list = ObjectList::default();
+        let mut token = None;
+
+        loop {
+            let mut query = vec![
+                ("list-type".to_string(), "2".to_string()),
+                ("prefix".to_string(), prefix.to_string()),
+            ];
+
+            if delimiter {
+                query.push(("delimiter".to_string(), "/".to_string()));
+            }
+
+            if let Some(token) = token.take() {
+                query.push(("continuation-token".to_string(), token));
+            }
+
+            let xml = self
+                .send(Method::GET, None, query, vec![], None)
+                .await?
+                .text()
+                .await?;
+
+            for block in xml::values(&xml, "Contents") {
+                let key = xml::values(&block, "Key").pop().unwrap_or_default();
+                let size = xml::values(&block, "Size")
+                    .pop()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or_default();
+
+                list.objects.push((key, size));
+            }
+
+            for block in xml::values(&xml, "CommonPrefixes") {
+                list.prefixes.extend(xml::values(&block, "Prefix"));
+            }
+
+            let truncated = xml::values(&xml, "IsTruncated").pop();
+            token = xml::values(&xml, "NextContinuationToken").pop();
+
+            if truncated.as_deref() != Some("true") || token.is_none() {
+                break;
+            }
+        }
+
+        Ok(list)
+    }
+}
+
+/// Result of a ListObjectsV2 request.
+#[derive(Default)]
+struct ObjectList {
+    /// object keys and sizes.
+    objects: Vec<(String, u64)>,
+
+    /// common prefixes (folders), when listed with a delimiter.
+    prefixes: Vec<String>,
+}
+
+/// prefix of objects inside the folder at `key`.
+fn folder_prefix(key: &str) -> String {
+    if key.is_empty() {
+        key.to_string()
+    } else {
+        format!("{key}/")
+    }
+}
+
+impl StorageBackend for S3Storage {
+    async fn put_file(&self, key: &str, tmp: &Path) -> FsResult<()> {
+        let file = tokio::fs::File::open(tmp).await?;
+        let len = file.metadata().await?.len();
+        let body = Body::wrap_stream(ReaderStream::new(file));
+
+        let key = self.object_key(key);
+        let headers = vec![(CONTENT_LENGTH.as_str(), len.to_string())];
+        self.send(Method::PUT, Some(&key), vec![], headers, Some(body))
+            .await?;
+
+        tokio::fs::remove_file(tmp).await?;
+        Ok(())
+    }
+
+    async fn create_folder(&self, key: &str) -> FsResult<()> {
+        let key = folder_prefix(&self.object_key(key));
+        let headers = vec![(CONTENT_LENGTH.as_str(), "0".to_string())];
+        self.send(Method::PUT, Some(&key), vec![], headers, Some(Body::from("")))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn remove_file(&self, key: &str) -> FsResult<()> {
+        let key = self.object_key(key);
+        self.send(Method::DELETE, Some(&key), vec![], vec![], None)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn remove_folder(&self, key: &str) -> FsResult<()> {
+        // an empty object key would remove every object of the object store bucket
+        let key = self.object_key(key);
+        if key.is_empty() {
+            return Err(Error::PermissionError(
+                "the root of an object store can not be removed.".to_string(),
+            ));
+        }
+
+        let prefix = folder_prefix(&key);
+        let list = self.list_objects(&prefix, false).await?;
+
+        for (key, _) in list.objects {
+            self.send(Method::DELETE, Some(&key), vec![], vec![], None)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// objects have no rename operation, so they are copied to `dest` then removed.
+    async fn rename(&self, key: &str, dest: &str) -> FsResult<()> {
+        let (source, target) = (self.object_key(key), self.object_key(dest));
+        let list = self.list_objects(&folder_prefix(&source), false).await?;
+
+        let mut keys: Vec<String> = list.objects.into_iter().map(|(k, _)| k).collect();
+        if keys.is_empty() {
+            keys.push(source.clone());
+        }
+
+        // copy everything before removing anything, so a failure leaves the source intact
+        for key in &keys {
+            let dest = format!("{target}{}", &key[source.len()..]);
+            self.copy_object(key, &dest).await?;
+        }
+
+        for key in &keys {
+            self.send(Method::DELETE, Some(key), vec![], vec![], None)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn metadata(&self, key: &str) -> FsResult<Option<StorageMeta>> {
+        let object_key = self.object_key(key);
+
+        match self
+            .send(Method::HEAD, Some(&object_key), vec![], vec![], None)
+            .await
+        {
+            Ok(resp) => {
+                let headers = resp.headers();
+                let size = headers
+                    .get(CONTENT_LENGTH)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or_default();
+
+                let modified = headers
+                    .get(LAST_MODIFIED)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| httpdate::parse_http_date(v).ok());
+
+                Ok(Some(StorageMeta {
+                    is_file: true,
+                    size,
+                    modified,
+                }))
+            }
+            // not a file. check whether it's a folder
+            Err(Error::NotFound(_)) => {
+                let prefix = folder_prefix(&object_key);
+                let list = self.list_objects(&prefix, true).await?;
+                let exists = !list.objects.is_empty() || !list.prefixes.is_empty();
+
+                Ok(exists.then_some(StorageMeta {
+                    is_file: false,
+                    size: 0,
+                    modified: None,
+                }))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn read(&self, key: &str, start: u64, len: u64) -> FsResult<AssetReader> {
+        if len == 0 {
+            return Ok(Box::pin(tokio::io::empty()));
+        }
+
+        let key = self.object_key(key);
+        let range = format!("bytes={start}-{}", start + len - 1);
+        let resp = self
+            .send(Method::GET, Some(&key), vec![], vec![(RANGE.as_str(), range)], None)
+            .await?;
+
+        let stream = resp.bytes_stream().map_err(std::io::Error::other);
+        Ok(Box::pin(StreamReader::new(stream)))
+    }
+
+    async fn list(&self, key: &str) -> FsResult<Vec<StorageEntry>> {
+        let prefix = folder_prefix(&self.object_key(key));
+        let list = self.list_objects(&prefix, true).await?;
+
+        let folders = list.prefixes.into_iter().filter_map(|p| {
+            let name = p.strip_prefix(&prefix)?.trim_end_matches('/');
+            Some(StorageEntry {
+                name: name.to_string(),
+                is_file: false,
+            })
+        });
+
+        // skip the folder's own marker object
+        let files = list.objects.into_iter().filter_map(|(k, _)| {
+            let name = k.strip_prefix(&prefix).filter(|n| !n.is_empty())?;
+            Some(StorageEntry {
+                name: name.to_string(),
+                is_file: true,
+            })
+        });
+
+        Ok(folders.chain(files).collect())
+    }
+
+    /// size of the storage root. storage without a root is not accounted for and
+    /// always has a size of 0.
+    async fn size(&self) -> FsResult<u64> {
+        let Some(root) = &self.root else {
+            return Ok(0);
+        };
+
+        let list = self.list_objects(&folder_prefix(root), false).await?;
+        Ok(list.objects.iter().map(|(_, size)| size).sum())
+    }
+}
@@ -0,0 +1,425 @@
+//! moving, renaming and copying assets. Content is written to the destination before records
+//! are updated, and completed steps are undone when a later one fails, so records and storage
+//! keep pointing at the same content.
+
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    path::Path,
+};
+
+use ppd_bk::{
+    RBatis,
+    models::{
+        asset::{AssetType, Assets, NewAsset},
+        bucket::Buckets,
+    },
+};
+use ppd_shared::tools::mb_to_bytes;
+
+use crate::{
+    FsResult,
+    errors::Error,
+    opts::{MoveAssetOptions, RenameAssetOptions},
+    storage::{Storage, StorageBackend},
+    validate_user_path,
+    utils::{copy_file, create_asset_parents, get_bucket_size, remove_content},
+};
+
+/// move an asset to another path and/or bucket. Folders are moved with their content.
+pub async fn move_asset(db: &RBatis, user_id: &u64, opts: &MoveAssetOptions) -> FsResult<()> {
+    let mut transfer = Transfer::plan(db, user_id, opts).await?;
+    transfer.validate(db, false).await?;
+    transfer.create_parents(db, user_id, opts.create_parents).await?;
+
+    // content that stays in its bucket is renamed, instead of copied and removed
+    let in_place = transfer
+        .items
+        .iter()
+        .all(|item| item.bucket == transfer.dest_bucket);
+
+    let storage = transfer.storage(&transfer.dest_bucket);
+    let (source, dest) = (transfer.root().source.clone(), transfer.root().dest.clone());
+
+    if in_place {
+        storage.rename(&source, &dest).await?;
+    } else {
+        transfer.copy_content().await?;
+    }
+
+    if let Err(err) = transfer.relocate(db).await {
+        let undo = if in_place {
+            storage.rename(&dest, &source).await
+        } else {
+            transfer.remove_copies().await
+        };
+
+        if let Err(err) = undo {
+            tracing::error!("unable to restore content of '{source}' after a failed move: {err}");
+        }
+
+        return Err(err);
+    }
+
+    if !in_place {
+        transfer.remove_sources().await;
+    }
+
+    Ok(())
+}
+
+/// rename an asset, keeping it in the same folder and bucket.
+pub async fn rename_asset(db: &RBatis, user_id: &u64, opts: &RenameAssetOptions) -> FsResult<()> {
+    let RenameAssetOptions {
+        asset_path,
+        asset_type,
+        name,
+    } = opts;
+
+    if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+        return Err(Error::ServerError(format!("invalid asset name '{name}'.")));
+    }
+
+    let asset = Assets::get_by_path(db, asset_path, asset_type).await?;
+    let destination = match asset.path().rsplit_once('/') {
+        Some((parent, _)) => format!("{parent}/{name}"),
+        None => name.to_string(),
+    };
+
+    let opts = MoveAssetOptions {
+        asset_path: asset.path().to_string(),
+        asset_type: asset.asset_type(),
+        destination,
+        bucket: None,
+        create_parents: Some(false),
+    };
+
+    move_asset(db, user_id, &opts).await
+}
+
+/// copy an asset to another path and/or bucket. Folders are copied with their content. Copies
+/// belong to `user_id` and are not shared with anyone.
+pub async fn copy_asset(db: &RBatis, user_id: &u64, opts: &MoveAssetOptions) -> FsResult<()> {
+    let transfer = Transfer::plan(db, user_id, opts).await?;
+    transfer.validate(db, true).await?;
+    transfer.create_parents(db, user_id, opts.create_parents).await?;
+    transfer.copy_content().await?;
+
+    if let Err(err) = transfer.create_records(db, user_id).await {
+        if let Err(err) = transfer.remove_copies().await {
+            tracing::error!(
+                "unable to remove copies at '{}' after a failed copy: {err}",
+                transfer.root().dest
+            );
+        }
+
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+/// an asset to be moved or copied.
+struct TransferItem {
+    asset: Assets,
+
+    /// path and bucket of the asset before the transfer.
+    source: String,
+    bucket: u64,
+
+    /// destination path.
+    dest: String,
+}
+
+impl TransferItem {
+    /// mime type of a file at its destination.
+    fn mime(&self) -> Option<String> {
+        match self.asset.asset_type() {
+            AssetType::File => {
+                let mime = mime_guess::from_path(&self.dest).first_or_octet_stream();
+                Some(mime.to_string())
+            }
+            AssetType::Folder => None,
+        }
+    }
+}
+
+/// a checked move or copy of an asset and its descendants.
+struct Transfer {
+    /// the asset, followed by its descendants (parents first).
+    items: Vec<TransferItem>,
+
+    /// buckets of the items and the destination bucket, by id.
+    buckets: HashMap<u64, Buckets>,
+    dest_bucket: u64,
+}
+
+impl Transfer {
+    /// collect the asset and its descendants, checking that they belong to `user_id`, that
+    /// `user_id` can write to the destination bucket and that destination paths are free.
+    async fn plan(db: &RBatis, user_id: &u64, opts: &MoveAssetOptions) -> FsResult<Self> {
+        let MoveAssetOptions {
+            asset_path,
+            asset_type,
+            destination,
+            bucket,
+            ..
+        } = opts;
+
+        validate_user_path(destination)?;
+
+        let asset = Assets::get_by_path(db, asset_path, asset_type).await?;
+        if asset.deleted_at().is_some() {
+            return Err(Error::NotFound("asset not found".to_string()));
+        }
+
+        let source = asset.path().to_string();
+
+        if destination == &source || destination.starts_with(&format!("{source}/")) {
+            return Err(Error::ServerError(
+                "an asset cannot be moved or copied into itself.".to_string(),
+            ));
+        }
+
+        let dest_bucket = match bucket {
+            Some(pid) => Buckets::get_by_pid(db, pid).await?,
+            None => Buckets::get(db, asset.bucket_id()).await?,
+        };
+
+        if !dest_bucket.validate_write(user_id) {
+            return Err(Error::PermissionError(
+                "you have not permission to write to this bucket".to_string(),
+            ));
+        }
+
+        let mut assets = vec![asset];
+        if let AssetType::Folder = asset_type {
+            assets.extend(Assets::get_descendants(db, &source).await?);
+        }
+
+        let dest_id = dest_bucket.id();
+        let mut buckets = HashMap::from([(dest_id, dest_bucket)]);
+        let mut items = Vec::with_capacity(assets.len());
+
+        for asset in assets {
+            if asset.user_id() != user_id {
+                return Err(Error::PermissionError(format!(
+                    "you do not have permission to move '{}'.",
+                    asset.path()
+                )));
+            }
+
+            let dest = format!("{destination}{}", &asset.path()[source.len()..]);
+            if Assets::path_exists(db, &dest).await? {
+                return Err(Error::PermissionError(format!(
+                    "destination '{dest}' is already used by another asset."
+                )));
+            }
+
+            let bucket = *asset.bucket_id();
+            if let Entry::Vacant(entry) = buckets.entry(bucket) {
+                entry.insert(Buckets::get(db, &bucket).await?);
+            }
+
+            items.push(TransferItem {
+                source: asset.path().to_string(),
+                asset,
+                bucket,
+                dest,
+            });
+        }
+
+        let transfer = Self {
+            items,
+            buckets,
+            dest_bucket: dest_id,
+        };
+
+        // content without a record must not be overwritten either
+        let root = transfer.root();
+        if transfer
+            .storage(&dest_id)
+            .metadata(&root.dest)
+            .await?
+            .is_some()
+        {
+            return Err(Error::PermissionError(format!(
+                "destination '{}' already exists in storage.",
+                root.dest
+            )));
+        }
+
+        Ok(transfer)
+    }
+
+    fn root(&self) -> &TransferItem {
+        &self.items[0]
+    }
+
+    fn storage(&self, bucket: &u64) -> Storage {
+        Storage::for_bucket(&self.buckets[bucket])
+    }
+
+    /// check file mime types and the size limit of the destination bucket. Moved content that
+    /// stays in its bucket is not counted again.
+    async fn validate(&self, db: &RBatis, copy: bool) -> FsResult<()> {
+        let dest = &self.buckets[&self.dest_bucket];
+        let mut added = 0;
+
+        for item in &self.items {
+            let Some(mime) = item.mime() else {
+                continue;
+            };
+
+            dest.validate_mime(db, &mime).await?;
+
+            let meta = self.storage(&item.bucket).metadata(&item.source).await?;
+            match meta {
+                Some(meta) if meta.is_file => {
+                    if copy || item.bucket != self.dest_bucket {
+                        added += meta.size;
+                    }
+                }
+                _ => {
+                    return Err(Error::NotFound(format!(
+                        "file '{}' does not exist in storage.",
+                        item.source
+                    )));
+                }
+            }
+        }
+
+        if added > 0
+            && let Some(max_size) = dest.partition_size()
+        {
+            let used = get_bucket_size(dest).await?;
+            if used + added > mb_to_bytes(*max_size) as u64 {
+                return Err(Error::ServerError("bucket size exceeded.".to_string()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// create the missing parents of the destination, or check that its parent exists.
+    async fn create_parents(
+        &self,
+        db: &RBatis,
+        user_id: &u64,
+        create: Option<bool>,
+    ) -> FsResult<()> {
+        let dest = Path::new(&self.root().dest);
+
+        if create.unwrap_or(true) {
+            let storage = self.storage(&self.dest_bucket);
+            create_asset_parents(db, &storage, dest, user_id, &self.dest_bucket, &None).await?;
+        } else if let Some(parent) = dest.parent().and_then(|p| p.to_str())
+            && !parent.is_empty()
+            && Assets::get_by_path(db, parent, &AssetType::Folder)
+                .await
+                .is_err()
+        {
+            return Err(Error::NotFound(format!(
+                "destination folder '{parent}' does not exist."
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// copy the content of every item to its destination. Copies are removed if any fails.
+    async fn copy_content(&self) -> FsResult<()> {
+        let dest = self.storage(&self.dest_bucket);
+
+        for item in &self.items {
+            let copied = match item.asset.asset_type() {
+                AssetType::Folder => dest.create_folder(&item.dest).await,
+                AssetType::File => {
+                    let source = self.storage(&item.bucket);
+                    copy_file(&source, &item.source, &dest, &item.dest).await
+                }
+            };
+
+            if let Err(err) = copied {
+                if let Err(err) = self.remove_copies().await {
+                    tracing::error!("unable to remove copies at '{}': {err}", self.root().dest);
+                }
+
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// remove the content copied to the destination.
+    async fn remove_copies(&self) -> FsResult<()> {
+        let storage = self.storage(&self.dest_bucket);
+        remove_content(&storage, &self.root().dest, self.root().asset.asset_type()).await
+    }
+
+    /// remove the content of a moved asset from its former storages. Records already point at
+    /// the copies, so failures are only logged.
+    async fn remove_sources(&self) {
+        let root = self.root();
+        let mut buckets: Vec<u64> = self.items.iter().map(|item| item.bucket).collect();
+        buckets.sort();
+        buckets.dedup();
+
+        for bucket in buckets {
+            let storage = self.storage(&bucket);
+            if let Err(err) = remove_content(&storage, &root.source, root.asset.asset_type()).await
+            {
+                tracing::warn!("unable to remove moved content at '{}': {err}", root.source);
+            }
+        }
+    }
+
+    /// point the records at their destination. Updated records are restored if any fails.
+    async fn relocate(&mut self, db: &RBatis) -> FsResult<()> {
+        let mimes: Vec<Option<String>> = self
+            .items
+            .iter()
+            .map(|item| item.asset.mime().clone())
+            .collect();
+
+        for idx in 0..self.items.len() {
+            let item = &mut self.items[idx];
+            let mime = item.mime();
+            let dest = item.dest.clone();
+
+            if let Err(err) = item.asset.relocate(db, &dest, self.dest_bucket, mime).await {
+                for (item, mime) in self.items[..=idx].iter_mut().zip(mimes).rev() {
+                    let source = item.source.clone();
+                    if let Err(err) = item.asset.relocate(db, &source, item.bucket, mime).await {
+                        tracing::error!("unable to restore record of '{}': {err}", item.source);
+                    }
+                }
+
+                return Err(err.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// create records for the copies.
+    async fn create_records(&self, db: &RBatis, user_id: &u64) -> FsResult<()> {
+        let values = self
+            .items
+            .iter()
+            .map(|item| NewAsset {
+                asset_path: item.dest.clone(),
+                custom_path: None,
+                user_id: *user_id,
+                bucket_id: self.dest_bucket,
+                public: *item.asset.public(),
+                asset_type: u8::from(&item.asset.asset_type()),
+                size: *item.asset.size(),
+                mime: item.mime(),
+            })
+            .collect();
+
+        Assets::insert_group(db, values).await?;
+        Ok(())
+    }
+}
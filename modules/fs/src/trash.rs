@@ -0,0 +1,296 @@
+//! the trash of buckets. Deleted assets are moved to [TRASH_FOLDER] of their storage, where
+//! they are kept until they are restored, purged, or expire. Trashed assets keep their
+//! records, so they still count toward the size of their bucket.
+
+use std::path::Path;
+
+use ppd_bk::{
+    RBatis,
+    models::{
+        asset::{AssetType, Assets, unix_now},
+        bucket::Buckets,
+    },
+};
+use ppd_shared::api::{EntryType, TrashEntry};
+
+use crate::{
+    FsResult,
+    auth::delete_asset,
+    errors::Error,
+    storage::{Storage, StorageBackend},
+    utils::create_asset_parents,
+};
+
+/// folder of the trash in storages, and the path prefix of trashed records.
+pub const TRASH_FOLDER: &str = ".ppdrive_trash";
+
+const SECONDS_PER_DAY: i64 = 86400;
+
+/// move an asset to the trash. Folders are moved with their content. The asset's path is free
+/// to be used again until it's restored.
+pub async fn trash_asset(db: &RBatis, path: &str, asset_type: &AssetType) -> FsResult<()> {
+    let asset = Assets::get_by_path(db, path, asset_type).await?;
+    if asset.deleted_at().is_some() {
+        return Err(Error::NotFound("asset not found".to_string()));
+    }
+
+    let source = asset.path().to_string();
+    let dest = format!("{TRASH_FOLDER}/{}", asset.id());
+
+    let mut assets = vec![asset];
+    if let AssetType::Folder = asset_type {
+        assets.extend(Assets::get_descendants(db, &source).await?);
+    }
+
+    let storages = move_content(db, &assets, &source, &dest).await?;
+    let paths: Vec<String> = assets
+        .iter()
+        .map(|asset| asset.path().to_string())
+        .collect();
+    let now = unix_now();
+
+    for idx in 0..assets.len() {
+        let trash_path = format!("{dest}{}", &paths[idx][source.len()..]);
+        let trashed_from = (idx == 0).then(|| source.clone());
+
+        if let Err(err) = assets[idx].trash(db, &trash_path, trashed_from, now).await {
+            for (asset, path) in assets[..=idx].iter_mut().zip(&paths).rev() {
+                if let Err(err) = asset.untrash(db, path).await {
+                    tracing::error!("unable to restore record of '{path}': {err}");
+                }
+            }
+
+            undo_move(&storages, &source, &dest).await;
+            return Err(err.into());
+        }
+    }
+
+    Ok(())
+}
+
+/// restore an entry of the trash of `bucket`. The asset is restored where it was deleted from,
+/// or next to it (i.e `report (1).pdf`) when the path is used again. Returns the path of the
+/// restored asset.
+pub async fn restore_asset(db: &RBatis, user_id: &u64, bucket: &str, id: &u64) -> FsResult<String> {
+    let bucket = trash_bucket(db, user_id, bucket).await?;
+    let entry = trash_entry(db, user_id, &bucket, id).await?;
+
+    let source = entry.path().to_string();
+    let deleted_at = entry.deleted_at().unwrap_or_default();
+    let trashed_from = entry.trashed_from().clone().unwrap_or_default();
+
+    let storage = Storage::for_bucket(&bucket);
+    let dest = free_path(db, &storage, &trashed_from).await?;
+    create_asset_parents(db, &storage, Path::new(&dest), user_id, &bucket.id(), &None).await?;
+
+    let mut assets = vec![entry];
+    if let AssetType::Folder = assets[0].asset_type() {
+        assets.extend(Assets::get_descendants(db, &source).await?);
+    }
+
+    let storages = move_content(db, &assets, &source, &dest).await?;
+    let paths: Vec<String> = assets
+        .iter()
+        .map(|asset| asset.path().to_string())
+        .collect();
+
+    for idx in 0..assets.len() {
+        let asset_path = format!("{dest}{}", &paths[idx][source.len()..]);
+
+        if let Err(err) = assets[idx].untrash(db, &asset_path).await {
+            for (idx, (asset, path)) in assets[..=idx].iter_mut().zip(&paths).enumerate().rev() {
+                let trashed_from = (idx == 0).then(|| trashed_from.clone());
+                if let Err(err) = asset.trash(db, path, trashed_from, deleted_at).await {
+                    tracing::error!("unable to restore record of '{path}': {err}");
+                }
+            }
+
+            undo_move(&storages, &source, &dest).await;
+            return Err(err.into());
+        }
+    }
+
+    Ok(dest)
+}
+
+/// entries of the trash of `bucket`, most recently deleted first. Entries expire
+/// `retention` days after they are deleted.
+pub async fn list_trash(
+    db: &RBatis,
+    user_id: &u64,
+    bucket: &str,
+    retention: u64,
+) -> FsResult<Vec<TrashEntry>> {
+    let bucket = trash_bucket(db, user_id, bucket).await?;
+    let assets = Assets::get_trash(db, &bucket.id(), user_id).await?;
+
+    let entries = assets
+        .iter()
+        .map(|asset| {
+            let deleted_at = asset.deleted_at().unwrap_or_default();
+            let entry_type = match asset.asset_type() {
+                AssetType::File => EntryType::File,
+                AssetType::Folder => EntryType::Folder,
+            };
+
+            TrashEntry {
+                id: asset.id(),
+                path: asset.trashed_from().clone().unwrap_or_default(),
+                entry_type,
+                size: *asset.size(),
+                deleted_at,
+                expires_at: deleted_at + retention as i64 * SECONDS_PER_DAY,
+            }
+        })
+        .collect();
+
+    Ok(entries)
+}
+
+/// permanently delete an entry of the trash of `bucket`.
+pub async fn purge_trash_entry(db: &RBatis, user_id: &u64, bucket: &str, id: &u64) -> FsResult<()> {
+    let bucket = trash_bucket(db, user_id, bucket).await?;
+    let entry = trash_entry(db, user_id, &bucket, id).await?;
+
+    delete_asset(db, entry.path(), &entry.asset_type()).await
+}
+
+/// permanently delete every entry of the trash of `bucket`. Returns the number of purged
+/// entries.
+pub async fn empty_trash(db: &RBatis, user_id: &u64, bucket: &str) -> FsResult<usize> {
+    let bucket = trash_bucket(db, user_id, bucket).await?;
+    let entries = Assets::get_trash(db, &bucket.id(), user_id).await?;
+
+    let mut failed = Vec::new();
+    for entry in &entries {
+        if let Err(err) = delete_asset(db, entry.path(), &entry.asset_type()).await {
+            let path = entry.trashed_from().clone().unwrap_or_default();
+            failed.push((path, err.to_string()));
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(entries.len())
+    } else {
+        Err(Error::PartialDelete {
+            deleted: entries.len() - failed.len(),
+            failed,
+        })
+    }
+}
+
+/// permanently delete the entries of every trash that were deleted more than `retention`
+/// days ago. Returns the number of purged entries.
+pub async fn purge_expired(db: &RBatis, retention: u64) -> FsResult<usize> {
+    let before = unix_now() - retention as i64 * SECONDS_PER_DAY;
+    let entries = Assets::get_expired_trash(db, before).await?;
+
+    let mut purged = 0;
+    for entry in &entries {
+        match delete_asset(db, entry.path(), &entry.asset_type()).await {
+            Ok(_) => purged += 1,
+            Err(err) => tracing::error!("unable to purge '{}' from the trash: {err}", entry.path()),
+        }
+    }
+
+    Ok(purged)
+}
+
+/// retrieve a bucket whose trash `user_id` manages.
+async fn trash_bucket(db: &RBatis, user_id: &u64, bucket: &str) -> FsResult<Buckets> {
+    let bucket = Buckets::get_by_pid(db, bucket).await?;
+    if !bucket.validate_write(user_id) {
+        return Err(Error::PermissionError(
+            "you have not permission to write to this bucket".to_string(),
+        ));
+    }
+
+    Ok(bucket)
+}
+
+/// retrieve an entry of the trash of `bucket` that was deleted by `user_id`.
+async fn trash_entry(db: &RBatis, user_id: &u64, bucket: &Buckets, id: &u64) -> FsResult<Assets> {
+    match Assets::get(db, id).await {
+        Ok(asset)
+            if asset.trashed_from().is_some()
+                && asset.bucket_id() == &bucket.id()
+                && asset.user_id() == user_id =>
+        {
+            Ok(asset)
+        }
+        _ => Err(Error::NotFound(format!("trash entry {id} not found."))),
+    }
+}
+
+/// `path`, or the first of `name (1).ext`, `name (2).ext`... that is not used by an asset
+/// or content of `storage`.
+async fn free_path(db: &RBatis, storage: &Storage, path: &str) -> FsResult<String> {
+    let (parent, name) = match path.rsplit_once('/') {
+        Some((parent, name)) => (format!("{parent}/"), name),
+        None => (String::new(), path),
+    };
+
+    // dots leading the name belong to the stem, i.e `.env`
+    let (stem, ext) = match name.rfind('.') {
+        Some(idx) if idx > 0 => name.split_at(idx),
+        _ => (name, ""),
+    };
+
+    let mut candidate = path.to_string();
+    let mut count = 0;
+
+    while Assets::path_exists(db, &candidate).await?
+        || storage.metadata(&candidate).await?.is_some()
+    {
+        count += 1;
+        candidate = format!("{parent}{stem} ({count}){ext}");
+    }
+
+    Ok(candidate)
+}
+
+/// move the content of `assets` (an asset followed by its descendants) from `source` to `dest`
+/// in every storage that holds some of it. Content is moved back if any move fails. Returns
+/// the storages content was moved in.
+async fn move_content(
+    db: &RBatis,
+    assets: &[Assets],
+    source: &str,
+    dest: &str,
+) -> FsResult<Vec<Storage>> {
+    let mut buckets: Vec<u64> = assets.iter().map(|asset| *asset.bucket_id()).collect();
+    buckets.sort();
+    buckets.dedup();
+
+    let mut moved = Vec::with_capacity(buckets.len());
+    for bucket in buckets {
+        let bucket = Buckets::get(db, &bucket).await?;
+        let storage = Storage::for_bucket(&bucket);
+
+        let renamed = match storage.metadata(source).await {
+            Ok(Some(_)) => storage.rename(source, dest).await.map(|_| true),
+            Ok(None) => Ok(false),
+            Err(err) => Err(err),
+        };
+
+        match renamed {
+            Ok(true) => moved.push(storage),
+            Ok(false) => {}
+            Err(err) => {
+                undo_move(&moved, source, dest).await;
+                return Err(err);
+            }
+        }
+    }
+
+    Ok(moved)
+}
+
+/// move content moved by [move_content] back to `source`. Failures are only logged.
+async fn undo_move(storages: &[Storage], source: &str, dest: &str) {
+    for storage in storages {
+        if let Err(err) = storage.rename(dest, source).await {
+            tracing::error!("unable to move content of '{dest}' back to '{source}': {err}");
+        }
+    }
+}
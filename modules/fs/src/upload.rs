@@ -0,0 +1,449 @@
+use std::{
+    fmt::Display,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+};
+
+use futures_util::{Stream, StreamExt};
+use ppd_bk::{
+    RBatis,
+    models::{
+        bucket::Buckets,
+        upload::{NewUploadSession, UploadSessions},
+    },
+};
+use ppd_shared::tools::{mb_to_bytes, root_dir};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncSeekExt, AsyncWriteExt},
+};
+use uuid::Uuid;
+
+use crate::{
+    FsResult,
+    auth::create_or_update_asset,
+    errors::Error,
+    opts::{CreateAssetOptions, CreateUploadOptions},
+    utils::get_bucket_size,
+};
+
+/// folder of upload session data, in the install directory.
+const UPLOADS_FOLDER: &str = "uploads";
+
+/// A temporary file that receives an upload chunk by chunk. Writing fails as soon as the total
+/// number of bytes received exceeds `limit`, in which case the partial file is removed.
+pub struct TmpFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    limit: u64,
+}
+
+impl TmpFile {
+    pub async fn create(limit: u64) -> FsResult<Self> {
+        let mut path = std::env::temp_dir();
+        path.push(Uuid::new_v4().to_string());
+
+        let file = File::create(&path).await?;
+        Ok(Self {
+            path,
+            file,
+            size: 0,
+            limit,
+        })
+    }
+
+    /// append a chunk to the file, aborting the upload if it exceeds the size limit.
+    pub async fn write_chunk(&mut self, chunk: &[u8]) -> FsResult<()> {
+        self.size += chunk.len() as u64;
+        if self.size > self.limit {
+            self.remove().await;
+            return Err(Error::ServerError(format!(
+                "upload size exceeded. maximum allowed size is {} bytes.",
+                self.limit
+            )));
+        }
+
+        if let Err(err) = self.file.write_all(chunk).await {
+            self.remove().await;
+            return Err(err.into());
+        }
+
+        Ok(())
+    }
+
+    /// flush the file and return its path and size.
+    pub async fn finish(mut self) -> FsResult<(PathBuf, u64)> {
+        self.file.flush().await?;
+        Ok((self.path, self.size))
+    }
+
+    /// drop an incomplete upload.
+    pub async fn discard(self) {
+        self.remove().await;
+    }
+
+    async fn remove(&self) {
+        if let Err(err) = tokio::fs::remove_file(&self.path).await {
+            tracing::warn!("unable to remove temporary upload file: {err}");
+        }
+    }
+}
+
+/// Maximum number of bytes that can be uploaded to a bucket. This is the lesser of `max_upload`
+/// and the space left in the bucket's partition.
+///
+/// `bucket` may be empty if the upload options are not known yet (i.e the file is sent before
+/// the options), in which case only `max_upload` applies.
+pub async fn upload_limit(db: &RBatis, bucket: &str, max_upload: u64) -> FsResult<u64> {
+    if bucket.is_empty() {
+        return Ok(max_upload);
+    }
+
+    let bucket = Buckets::get_by_pid(db, bucket).await?;
+    let available = bucket_space(&bucket).await?;
+
+    Ok(available.map_or(max_upload, |av| av.min(max_upload)))
+}
+
+/// Space (bytes) left in a bucket's partition. Returns `None` if the bucket has no size limit.
+async fn bucket_space(bucket: &Buckets) -> FsResult<Option<u64>> {
+    match bucket.partition_size() {
+        Some(max_size) => {
+            let used = get_bucket_size(bucket).await?;
+            let available = (mb_to_bytes(*max_size) as u64).saturating_sub(used);
+
+            Ok(Some(available))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Start a resumable upload and return the upload session id. Sessions can receive at most
+/// `max_upload` bytes.
+pub async fn create_session(
+    db: &RBatis,
+    user_id: &u64,
+    opts: &CreateUploadOptions,
+    max_upload: u64,
+) -> FsResult<String> {
+    let CreateUploadOptions { asset, size } = opts;
+
+    let bucket = Buckets::get_by_pid(db, &asset.bucket).await?;
+    if !bucket.validate_write(user_id) {
+        return Err(Error::PermissionError(
+            "you have not permission to write to this bucket".to_string(),
+        ));
+    }
+
+    if let Some(size) = size {
+        check_upload_size(*size, max_upload)?;
+    }
+
+    if let Some(size) = size
+        && let Some(available) = bucket_space(&bucket).await?
+        && *size > available
+    {
+        return Err(Error::ServerError("bucket size exceeded.".to_string()));
+    }
+
+    let options =
+        serde_json::to_string(asset).map_err(|err| Error::ServerError(err.to_string()))?;
+
+    let value = NewUploadSession {
+        user_id: *user_id,
+        bucket_id: bucket.id(),
+        options,
+        size: *size,
+    };
+
+    let pid = UploadSessions::create(db, value).await?;
+    let path = session_path(&pid).await?;
+    File::create(path).await?;
+
+    Ok(pid)
+}
+
+/// Append a chunk of `len` bytes to an upload session and return the new upload offset. `offset`
+/// must match the number of bytes already received by the session. The chunk is streamed to the
+/// session's file, and fails if `chunk` doesn't yield exactly `len` bytes.
+pub async fn append_session_chunk<S, B, E>(
+    db: &RBatis,
+    user_id: &u64,
+    pid: &str,
+    offset: u64,
+    len: u64,
+    mut chunk: S,
+    max_upload: u64,
+) -> FsResult<u64>
+where
+    S: Stream<Item = Result<B, E>> + Unpin,
+    B: AsRef<[u8]>,
+    E: Display,
+{
+    let mut session = UploadSessions::get(db, pid, user_id).await?;
+    if offset != *session.offset() {
+        return Err(offset_mismatch(session.offset()));
+    }
+
+    if len == 0 {
+        return Ok(offset);
+    }
+
+    let new_offset = offset + len;
+    check_upload_size(new_offset, max_upload)?;
+
+    if let Some(size) = session.size()
+        && new_offset > *size
+    {
+        return Err(Error::ServerError(
+            "chunk exceeds the declared upload size.".to_string(),
+        ));
+    }
+
+    let bucket = Buckets::get(db, session.bucket_id()).await?;
+    if let Some(available) = bucket_space(&bucket).await?
+        && new_offset > available
+    {
+        return Err(Error::ServerError("bucket size exceeded.".to_string()));
+    }
+
+    let path = session_path(pid).await?;
+    if !path.is_file() {
+        return Err(Error::NotFound(
+            "upload data for this session no longer exists.".to_string(),
+        ));
+    }
+
+    // claim the range before writing it, so concurrent requests for the same offset can't
+    // both write their chunk
+    if !session.advance_offset(db, offset, new_offset).await? {
+        let session = UploadSessions::get(db, pid, user_id).await?;
+        return Err(offset_mismatch(session.offset()));
+    }
+
+    let written = async {
+        let mut file = OpenOptions::new().write(true).open(&path).await?;
+        file.seek(SeekFrom::Start(offset)).await?;
+
+        let mut received = 0;
+        while let Some(data) = chunk.next().await {
+            let data = data.map_err(|err| Error::ServerError(err.to_string()))?;
+            let data = data.as_ref();
+
+            received += data.len() as u64;
+            if received > len {
+                return Err(Error::ServerError(format!(
+                    "chunk is larger than the declared {len} bytes."
+                )));
+            }
+
+            file.write_all(data).await?;
+        }
+
+        if received != len {
+            return Err(Error::ServerError(format!(
+                "chunk is incomplete. received {received} of {len} bytes."
+            )));
+        }
+
+        file.flush().await?;
+
+        FsResult::Ok(())
+    }
+    .await;
+
+    if let Err(err) = written {
+        match session.advance_offset(db, new_offset, offset).await {
+            Ok(true) => {}
+            Ok(false) => tracing::error!("upload session {pid} moved past a chunk that failed"),
+            Err(err) => tracing::error!("unable to reset offset of upload session {pid}: {err}"),
+        }
+
+        return Err(err);
+    }
+
+    Ok(new_offset)
+}
+
+/// Save the content of an upload session as an asset and close the session.
+pub async fn complete_session(db: &RBatis, user_id: &u64, pid: &str) -> FsResult<()> {
+    let session = UploadSessions::get(db, pid, user_id).await?;
+    let offset = *session.offset();
+
+    if let Some(size) = session.size()
+        && *size != offset
+    {
+        return Err(Error::ServerError(format!(
+            "upload is incomplete. received {offset} of {size} bytes."
+        )));
+    }
+
+    let opts: CreateAssetOptions = serde_json::from_str(session.options())
+        .map_err(|err| Error::ServerError(err.to_string()))?;
+
+    // discard bytes written after the last recorded offset (e.g by a failed request)
+    let path = session_path(pid).await?;
+    let file = OpenOptions::new().write(true).open(&path).await?;
+    file.set_len(offset).await?;
+
+    create_or_update_asset(db, user_id, &opts, &Some(path), &Some(offset)).await?;
+    session.delete(db).await?;
+
+    Ok(())
+}
+
+/// Save a part of a multipart upload session from a temporary file. Parts can be sent in any
+/// order, and a part replaces the one previously saved with the same number. Parts can add up
+/// to at most `max_upload` bytes.
+pub async fn put_session_part(
+    db: &RBatis,
+    user_id: &u64,
+    pid: &str,
+    part_number: u32,
+    tmp: &Path,
+    max_upload: u64,
+) -> FsResult<()> {
+    let mut session = UploadSessions::get(db, pid, user_id).await?;
+
+    let dir = parts_path(pid).await?;
+    let part = dir.join(part_number.to_string());
+
+    let replaced = file_size(&part).await;
+    let received = folder_size(&dir).await?.saturating_sub(replaced) + file_size(tmp).await;
+    if let Err(err) = check_upload_size(received, max_upload) {
+        tokio::fs::remove_file(tmp).await?;
+        return Err(err);
+    }
+
+    let bucket = Buckets::get(db, session.bucket_id()).await?;
+    if let Some(available) = bucket_space(&bucket).await?
+        && received > available
+    {
+        tokio::fs::remove_file(tmp).await?;
+        return Err(Error::ServerError("bucket size exceeded.".to_string()));
+    }
+
+    tokio::fs::rename(tmp, part).await?;
+    session.update_offset(db, received).await?;
+
+    Ok(())
+}
+
+/// Put the given parts of a multipart upload session together (in that order), save the
+/// result as an asset and close the session.
+pub async fn complete_multipart_session(
+    db: &RBatis,
+    user_id: &u64,
+    pid: &str,
+    parts: &[u32],
+) -> FsResult<()> {
+    if parts.is_empty() {
+        return Err(Error::ServerError(
+            "at least one part is required to complete an upload.".to_string(),
+        ));
+    }
+
+    let session = UploadSessions::get(db, pid, user_id).await?;
+    let dir = parts_path(pid).await?;
+    let path = session_path(pid).await?;
+
+    let mut file = File::create(&path).await?;
+    let mut size = 0;
+    for part_number in parts {
+        let part = dir.join(part_number.to_string());
+        if !part.is_file() {
+            return Err(Error::NotFound(format!("upload part {part_number} not found.")));
+        }
+
+        let mut part = File::open(part).await?;
+        size += tokio::io::copy(&mut part, &mut file).await?;
+    }
+    file.flush().await?;
+
+    let opts: CreateAssetOptions = serde_json::from_str(session.options())
+        .map_err(|err| Error::ServerError(err.to_string()))?;
+
+    create_or_update_asset(db, user_id, &opts, &Some(path), &Some(size)).await?;
+    session.delete(db).await?;
+    tokio::fs::remove_dir_all(dir).await?;
+
+    Ok(())
+}
+
+/// Close an upload session and discard the data received so far.
+pub async fn cancel_session(db: &RBatis, user_id: &u64, pid: &str) -> FsResult<()> {
+    let session = UploadSessions::get(db, pid, user_id).await?;
+    session.delete(db).await?;
+
+    let path = session_path(pid).await?;
+    if path.is_file() {
+        tokio::fs::remove_file(path).await?;
+    }
+
+    let parts = parts_path(pid).await?;
+    tokio::fs::remove_dir_all(parts).await?;
+
+    Ok(())
+}
+
+/// Folder holding the data of upload sessions. It's kept in the install directory rather than
+/// the system's temp folder, which may be cleared while uploads are still in progress.
+async fn sessions_dir() -> FsResult<PathBuf> {
+    let dir = root_dir()
+        .map_err(|err| Error::ServerError(err.to_string()))?
+        .join(UPLOADS_FOLDER);
+    tokio::fs::create_dir_all(&dir).await?;
+
+    Ok(dir)
+}
+
+/// Location of an upload session's data. Data is kept on disk so that interrupted uploads
+/// can resume after a service restart.
+async fn session_path(pid: &str) -> FsResult<PathBuf> {
+    Ok(sessions_dir().await?.join(pid))
+}
+
+/// Folder holding the parts of a multipart upload session.
+async fn parts_path(pid: &str) -> FsResult<PathBuf> {
+    let dir = sessions_dir().await?.join(format!("{pid}.parts"));
+    tokio::fs::create_dir_all(&dir).await?;
+
+    Ok(dir)
+}
+
+/// reject an upload session that would exceed `max_upload` bytes.
+fn check_upload_size(size: u64, max_upload: u64) -> FsResult<()> {
+    if size > max_upload {
+        return Err(Error::ServerError(format!(
+            "upload size exceeded. maximum allowed size is {max_upload} bytes."
+        )));
+    }
+
+    Ok(())
+}
+
+fn offset_mismatch(offset: &u64) -> Error {
+    Error::ServerError(format!(
+        "upload offset mismatch. session is currently at offset {offset}."
+    ))
+}
+
+/// size of a file, or 0 if it doesn't exist.
+async fn file_size(path: &Path) -> u64 {
+    tokio::fs::metadata(path)
+        .await
+        .map(|m| m.len())
+        .unwrap_or_default()
+}
+
+async fn folder_size(path: &Path) -> FsResult<u64> {
+    let mut rd = tokio::fs::read_dir(path).await?;
+    let mut size = 0;
+
+    while let Some(entry) = rd.next_entry().await? {
+        size += entry.metadata().await?.len();
+    }
+
+    Ok(size)
+}
@@ -7,12 +7,19 @@ use ppd_bk::{
         bucket::Buckets,
     },
 };
+use tokio::{fs::File, io::AsyncWriteExt};
+use uuid::Uuid;
 
-use crate::{FsResult, errors::Error};
+use crate::{
+    FsResult,
+    errors::Error,
+    storage::{Storage, StorageBackend},
+};
 
 /// create asset's parents (including their records) if they don't exist.
 pub async fn create_asset_parents(
     db: &RBatis,
+    storage: &Storage,
     path: &Path,
     user_id: &u64,
     bucket_id: &u64,
@@ -63,6 +70,8 @@ pub async fn create_asset_parents(
                 asset_type: folder_type,
                 public: is_public.unwrap_or(false),
                 bucket_id: *bucket_id,
+                size: None,
+                mime: None,
             };
 
             assets.push(asset);
@@ -72,49 +81,61 @@ pub async fn create_asset_parents(
             Assets::insert_group(db, assets).await?;
         }
 
-        tokio::fs::create_dir_all(parent).await?;
+        if let Some(parent) = parent.to_str()
+            && !parent.is_empty()
+        {
+            storage.create_folder(parent).await?;
+        }
     }
 
     Ok(())
 }
 
 pub async fn get_bucket_size(bucket: &Buckets) -> FsResult<u64> {
-    let mut size = 0;
-    if let Some(partition) = bucket.partition() {
-        let dir = Path::new(partition);
-        if !dir.exists() {
-            tokio::fs::create_dir_all(dir).await?;
-            return Ok(size);
-        }
+    Storage::for_bucket(bucket).size().await
+}
 
-        get_folder_size(partition, &mut size).await?;
+/// remove the file or folder at `key`, if it exists.
+pub async fn remove_content(storage: &Storage, key: &str, asset_type: AssetType) -> FsResult<()> {
+    if storage.metadata(key).await?.is_none() {
+        return Ok(());
     }
 
-    Ok(size)
+    match asset_type {
+        AssetType::File => storage.remove_file(key).await,
+        AssetType::Folder => storage.remove_folder(key).await,
+    }
 }
 
-/// compute total size (in bytes) of a folder.
-async fn get_folder_size(folder_path: &str, size: &mut u64) -> FsResult<()> {
-    let path = Path::new(folder_path);
+/// copy a file to another (or the same) storage, through a temporary file.
+pub async fn copy_file(source: &Storage, key: &str, dest: &Storage, dest_key: &str) -> FsResult<()> {
+    let size = match source.metadata(key).await? {
+        Some(meta) if meta.is_file => meta.size,
+        _ => {
+            return Err(Error::NotFound(format!(
+                "file '{key}' does not exist in storage."
+            )));
+        }
+    };
 
-    if path.is_file() {
-        return Err(Error::ServerError(
-            "provided path is not a folder path".to_string(),
-        ));
-    }
+    let mut tmp = std::env::temp_dir();
+    tmp.push(Uuid::new_v4().to_string());
 
-    let mut rd = tokio::fs::read_dir(path).await?;
+    let copied = async {
+        let mut reader = source.read(key, 0, size).await?;
+        let mut file = File::create(&tmp).await?;
 
-    while let Ok(Some(entry)) = rd.next_entry().await {
-        let path = entry.path();
+        tokio::io::copy(&mut reader, &mut file).await?;
+        file.flush().await?;
 
-        if path.is_file() {
-            let m = path.metadata()?;
-            *size += m.len()
-        } else if let Some(folder) = path.to_str() {
-            Box::pin(get_folder_size(folder, size)).await?;
-        }
+        dest.put_file(dest_key, &tmp).await
     }
+    .await;
 
-    Ok(())
+    // the temporary file is consumed by a successful write
+    if copied.is_err() && tokio::fs::remove_file(&tmp).await.is_ok() {
+        tracing::debug!("removed temporary copy of '{key}'");
+    }
+
+    copied
 }
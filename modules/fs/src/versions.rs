@@ -0,0 +1,247 @@
+//! versions of file assets. In buckets with versioning, the content replaced by an upload is
+//! moved to [VERSIONS_FOLDER] of the bucket's storage instead of being overwritten, so
+//! versions count toward the size of the bucket.
+
+use ppd_bk::{
+    RBatis,
+    models::{
+        asset::{AssetType, Assets},
+        bucket::Buckets,
+        version::AssetVersions,
+    },
+};
+use ppd_shared::{api::AssetVersion, tools::mb_to_bytes};
+
+use crate::{
+    FileBody, FsResult,
+    errors::Error,
+    opts::PromoteVersionOptions,
+    readable_asset,
+    storage::{Storage, StorageBackend},
+    utils::{copy_file, get_bucket_size, remove_content},
+};
+
+/// folder of versions in storages. The versions of an asset are kept in a sub-folder named
+/// after the asset's id.
+pub const VERSIONS_FOLDER: &str = ".ppdrive_versions";
+
+/// versions of a file of `user_id`, most recent first.
+pub async fn list_versions(
+    db: &RBatis,
+    user_id: &u64,
+    asset_path: &str,
+) -> FsResult<Vec<AssetVersion>> {
+    let asset = owned_file(db, user_id, asset_path).await?;
+    let versions = AssetVersions::get_for_asset(db, &asset.id()).await?;
+
+    let versions = versions
+        .iter()
+        .map(|version| AssetVersion {
+            version: *version.version(),
+            size: *version.size(),
+            mime: version.mime().clone(),
+            created_at: *version.created_at(),
+        })
+        .collect();
+
+    Ok(versions)
+}
+
+/// read a version of a file that `user_id` has access to.
+pub async fn read_version(
+    db: &RBatis,
+    asset_path: &str,
+    user_id: &Option<u64>,
+    version: &u64,
+) -> FsResult<FileBody> {
+    let asset = readable_asset(db, asset_path, &AssetType::File, user_id).await?;
+    let version = get_version(db, &asset, version).await?;
+
+    let bucket = Buckets::get(db, version.bucket_id()).await?;
+    let storage = Storage::for_bucket(&bucket);
+    let key = version_key(version.asset_id(), version.version());
+
+    match storage.metadata(&key).await? {
+        Some(meta) if meta.is_file => Ok(FileBody::version(storage, &key, &asset, meta)),
+        _ => Err(Error::NotFound(format!(
+            "version {} of '{asset_path}' does not exist in storage.",
+            version.version()
+        ))),
+    }
+}
+
+/// make a version of a file its current content. The current content is kept as a new
+/// version, so promoting can be undone.
+pub async fn promote_version(
+    db: &RBatis,
+    user_id: &u64,
+    opts: &PromoteVersionOptions,
+) -> FsResult<()> {
+    let PromoteVersionOptions {
+        asset_path,
+        version,
+    } = opts;
+
+    let mut asset = owned_file(db, user_id, asset_path).await?;
+    let version = get_version(db, &asset, version).await?;
+    let bucket = Buckets::get(db, asset.bucket_id()).await?;
+
+    // the current content stays in the bucket, so the promoted content is added to its size
+    if let (Some(size), Some(max_size)) = (version.size(), bucket.partition_size()) {
+        let used = get_bucket_size(&bucket).await?;
+        if used + size > mb_to_bytes(*max_size) as u64 {
+            return Err(Error::ServerError("bucket size exceeded.".to_string()));
+        }
+    }
+
+    let source = Storage::for_bucket(&Buckets::get(db, version.bucket_id()).await?);
+    let key = version_key(version.asset_id(), version.version());
+
+    let kept = keep_version(db, &bucket, &asset).await?;
+    let storage = Storage::for_bucket(&bucket);
+
+    if let Err(err) = copy_file(&source, &key, &storage, asset.path()).await {
+        if let Some(kept) = kept {
+            restore_kept(db, &bucket, asset.path(), kept).await;
+        }
+
+        return Err(err);
+    }
+
+    asset
+        .set_content(db, *version.size(), version.mime().clone())
+        .await?;
+
+    prune_versions(db, &bucket, &asset.id()).await;
+    Ok(())
+}
+
+/// move the current content of `asset` to its next version, in the storage of `bucket`.
+/// Returns the version, or `None` when the asset has no content to be kept.
+pub(crate) async fn keep_version(
+    db: &RBatis,
+    bucket: &Buckets,
+    asset: &Assets,
+) -> FsResult<Option<AssetVersions>> {
+    let storage = Storage::for_bucket(bucket);
+    match storage.metadata(asset.path()).await? {
+        Some(meta) if meta.is_file => {}
+        _ => return Ok(None),
+    }
+
+    let version = AssetVersions::create(db, asset, bucket.id()).await?;
+    let key = version_key(version.asset_id(), version.version());
+
+    if let Err(err) = storage.rename(asset.path(), &key).await {
+        if let Err(err) = version.delete(db).await {
+            tracing::error!("unable to remove record of version {key}: {err}");
+        }
+
+        return Err(err);
+    }
+
+    Ok(Some(version))
+}
+
+/// move a version created by [keep_version] back to `asset_path`, after the content that
+/// should have replaced it could not be written. Failures are only logged.
+pub(crate) async fn restore_kept(
+    db: &RBatis,
+    bucket: &Buckets,
+    asset_path: &str,
+    version: AssetVersions,
+) {
+    let storage = Storage::for_bucket(bucket);
+    let key = version_key(version.asset_id(), version.version());
+
+    // the record is kept with the content when it can't be moved back
+    if let Err(err) = storage.rename(&key, asset_path).await {
+        tracing::error!("unable to move version {key} back to '{asset_path}': {err}");
+        return;
+    }
+
+    if let Err(err) = version.delete(db).await {
+        tracing::error!("unable to remove record of version {key}: {err}");
+    }
+}
+
+/// remove the oldest versions of an asset that exceed the `max_versions` of `bucket`. Failures
+/// are only logged.
+pub(crate) async fn prune_versions(db: &RBatis, bucket: &Buckets, asset_id: &u64) {
+    let Some(max_versions) = bucket.max_versions() else {
+        return;
+    };
+
+    let versions = match AssetVersions::get_for_asset(db, asset_id).await {
+        Ok(versions) => versions,
+        Err(err) => {
+            tracing::error!("unable to retrieve versions of asset {asset_id}: {err}");
+            return;
+        }
+    };
+
+    for version in versions.iter().skip(*max_versions as usize) {
+        let key = version_key(version.asset_id(), version.version());
+        let removed = async {
+            let bucket = Buckets::get(db, version.bucket_id()).await?;
+            remove_content(&Storage::for_bucket(&bucket), &key, AssetType::File).await?;
+            version.delete(db).await?;
+
+            Ok::<_, Error>(())
+        }
+        .await;
+
+        if let Err(err) = removed {
+            tracing::warn!("unable to remove version {key}: {err}");
+        }
+    }
+}
+
+/// remove the versions of an asset, with their records.
+pub(crate) async fn remove_versions(db: &RBatis, asset_id: &u64) -> FsResult<()> {
+    let versions = AssetVersions::get_for_asset(db, asset_id).await?;
+    let mut buckets: Vec<u64> = versions.iter().map(|v| *v.bucket_id()).collect();
+    buckets.sort();
+    buckets.dedup();
+
+    let folder = format!("{VERSIONS_FOLDER}/{asset_id}");
+    for bucket in buckets {
+        let bucket = Buckets::get(db, &bucket).await?;
+        remove_content(&Storage::for_bucket(&bucket), &folder, AssetType::Folder).await?;
+    }
+
+    AssetVersions::delete_for_asset(db, asset_id).await?;
+    Ok(())
+}
+
+/// storage key of a version.
+fn version_key(asset_id: &u64, version: &u64) -> String {
+    format!("{VERSIONS_FOLDER}/{asset_id}/{version}")
+}
+
+/// retrieve a file of `user_id` that is not in the trash.
+async fn owned_file(db: &RBatis, user_id: &u64, asset_path: &str) -> FsResult<Assets> {
+    let asset = Assets::get_by_path(db, asset_path, &AssetType::File).await?;
+    if asset.deleted_at().is_some() {
+        return Err(Error::NotFound("asset not found".to_string()));
+    }
+
+    if asset.user_id() != user_id {
+        return Err(Error::PermissionError(
+            "you do not have permission to update this resource.".to_string(),
+        ));
+    }
+
+    Ok(asset)
+}
+
+async fn get_version(db: &RBatis, asset: &Assets, version: &u64) -> FsResult<AssetVersions> {
+    AssetVersions::get(db, &asset.id(), version)
+        .await
+        .map_err(|_| {
+            Error::NotFound(format!(
+                "version {version} of '{}' not found.",
+                asset.path()
+            ))
+        })
+}
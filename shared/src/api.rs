@@ -1,9 +1,12 @@
 use std::sync::LazyLock;
 
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use validator::{Validate, ValidationError};
 
+use crate::{AppResult, errors::Error};
+
 #[derive(Deserialize, Serialize, Validate)]
 pub struct CreateClientUser {
     /// Total size of buckets the user can create (MB). This is the total accumulated size, which means
@@ -37,19 +40,68 @@ pub struct LoginTokens {
     pub refresh: Option<(String, i64)>,
 }
 
+/// A refresh token sent to exchange it for new tokens, or to revoke its login.
+#[derive(Serialize, Deserialize)]
+pub struct RefreshTokenOptions {
+    pub token: String,
+}
+
 #[derive(Deserialize, Serialize, Validate)]
 pub struct UserCredentials {
-    #[validate(length(
-        min = 8,
-        max = 120,
-        message = "'username' length must be between 8 to 12 characters."
-    ))]
+    #[validate(
+        length(
+            min = 3,
+            max = 64,
+            message = "'username' length must be between 3 to 64 characters."
+        ),
+        custom(function = "validate_username")
+    )]
     pub username: String,
 
-    #[validate(custom(function = "validate_password_complexity"))]
+    #[validate(
+        length(min = 8, max = 128, message = "'password' length must be between 8 to 128 characters."),
+        custom(function = "validate_password_complexity")
+    )]
     pub password: String,
 }
 
+/// Change the password of a logged in user.
+#[derive(Deserialize, Serialize, Validate)]
+pub struct ChangePassword {
+    pub current_password: String,
+
+    #[validate(
+        length(min = 8, max = 128, message = "'new_password' length must be between 8 to 128 characters."),
+        custom(function = "validate_password_complexity")
+    )]
+    pub new_password: String,
+}
+
+/// Request a password reset token for a user.
+#[derive(Deserialize, Serialize)]
+pub struct ForgotPassword {
+    pub username: String,
+}
+
+/// Set a new password with a reset token.
+#[derive(Deserialize, Serialize, Validate)]
+pub struct ResetPassword {
+    pub token: String,
+
+    #[validate(
+        length(min = 8, max = 128, message = "'password' length must be between 8 to 128 characters."),
+        custom(function = "validate_password_complexity")
+    )]
+    pub password: String,
+}
+
+/// Delete the account of a logged in user. The password is required for accounts that
+/// have one.
+#[derive(Deserialize, Serialize, Default)]
+pub struct DeleteAccount {
+    pub password: Option<String>,
+}
+
 #[derive(Deserialize, Serialize, Default, Validate)]
 pub struct CreateBucketOptions {
     #[validate(length(min=2))]
@@ -70,12 +122,364 @@ pub struct CreateBucketOptions {
     #[validate(length(min=8))]
     pub label: String,
     pub public: Option<bool>,
+
+    /// Storage backend where the bucket's assets are saved. Defaults to the local filesystem.
+    pub backend: Option<BucketBackend>,
+
+    /// Whether overwritten files keep their previous content as versions. Defaults to `false`.
+    pub versioning: Option<bool>,
+
+    /// Number of versions kept for each file. All versions are kept when it's not set.
+    #[validate(range(min = 1))]
+    pub max_versions: Option<u64>,
+}
+
+/// New size limit (MB) of a client's or user's buckets, or of a bucket's partition.
+/// A `null` size removes the limit.
+#[derive(Deserialize, Serialize, Default, Validate)]
+pub struct UpdateQuota {
+    #[validate(range(min = 0.5))]
+    pub size: Option<f64>,
+}
+
+/// Query of a paginated list.
+#[derive(Deserialize, Serialize, Default, Clone, Debug)]
+pub struct PageOptions {
+    /// Number of the page, starting at 1.
+    pub page: Option<u64>,
+
+    /// Number of items per page, up to [MAX_LIST_LIMIT].
+    pub limit: Option<u64>,
+}
+
+impl PageOptions {
+    pub fn page(&self) -> u64 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+            .unwrap_or(DEFAULT_LIST_LIMIT)
+            .clamp(1, MAX_LIST_LIMIT)
+    }
+}
+
+/// A page of a list.
+#[derive(Deserialize, Serialize)]
+pub struct ListPage<T> {
+    pub items: Vec<T>,
+    pub page: u64,
+    pub limit: u64,
+
+    /// number of items in the whole list.
+    pub total: u64,
+}
+
+/// Resources used on a service.
+#[derive(Deserialize, Serialize, Default)]
+pub struct UsageInfo {
+    pub clients: u64,
+    pub users: u64,
+    pub buckets: u64,
+    pub assets: u64,
+
+    /// total size (in bytes) of files saved in buckets.
+    pub storage_size: u64,
+}
+
+/// Storage backends available to buckets.
+#[derive(Deserialize, Serialize, Default, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BucketBackend {
+    /// assets are saved on the local filesystem.
+    #[default]
+    Local,
+
+    /// assets are kept in memory and are lost when the service stops. Useful for testing and
+    /// temporary buckets.
+    Memory,
+
+    /// assets are saved in an S3-compatible object store, configured with `PPDRIVE_S3_*`
+    /// environment variables.
+    S3,
+}
+
+impl From<BucketBackend> for u8 {
+    fn from(value: BucketBackend) -> Self {
+        use BucketBackend::*;
+
+        match value {
+            Local => 0,
+            Memory => 1,
+            S3 => 2,
+        }
+    }
+}
+
+impl From<u8> for BucketBackend {
+    fn from(value: u8) -> Self {
+        use BucketBackend::*;
+
+        match value {
+            1 => Memory,
+            2 => S3,
+            _ => Local,
+        }
+    }
+}
+
+/// Number of entries in a page of a folder listing or paginated list, when `limit` is not set.
+pub const DEFAULT_LIST_LIMIT: u64 = 50;
+
+/// Maximum number of entries in a page of a folder listing or paginated list.
+pub const MAX_LIST_LIMIT: u64 = 500;
+
+/// Sort keys of folder listings.
+#[derive(Deserialize, Serialize, Default, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ListSort {
+    #[default]
+    Name,
+    Size,
+    Created,
+    Updated,
+
+    /// folders first, then by name.
+    Type,
+}
+
+#[derive(Deserialize, Serialize, Default, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
 }
 
+/// Type of a folder entry.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryType {
+    File,
+    Folder,
+}
+
+/// Query of a folder listing.
+#[derive(Deserialize, Serialize, Default, Clone, Debug)]
+pub struct ListFolderOptions {
+    /// `next_cursor` of the previous page. The cursor must be used with the same `sort`
+    /// and `order`.
+    pub cursor: Option<String>,
+
+    /// Number of entries per page, up to [MAX_LIST_LIMIT].
+    pub limit: Option<u64>,
+
+    #[serde(default)]
+    pub sort: ListSort,
+
+    #[serde(default)]
+    pub order: SortOrder,
+
+    /// Only list entries of this type.
+    #[serde(rename = "type")]
+    pub entry_type: Option<EntryType>,
+
+    /// Only list files with this mime type. A type without subtype (`image` or `image/*`)
+    /// matches all its subtypes.
+    pub mime: Option<String>,
+}
+
+impl ListFolderOptions {
+    pub fn limit(&self) -> u64 {
+        self.limit
+            .unwrap_or(DEFAULT_LIST_LIMIT)
+            .clamp(1, MAX_LIST_LIMIT)
+    }
+
+    /// the decoded `cursor`, checked against `sort` and `order`.
+    pub fn after(&self) -> AppResult<Option<ListCursor>> {
+        let Some(cursor) = &self.cursor else {
+            return Ok(None);
+        };
+
+        let cursor = ListCursor::decode(cursor)?;
+        if cursor.sort != self.sort || cursor.order != self.order {
+            return Err(Error::ServerError(
+                "cursor was created with a different sort or order".to_string(),
+            ));
+        }
+
+        Ok(Some(cursor))
+    }
+}
+
+/// Position in a folder listing: sort values and id of the last entry of a page.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct ListCursor {
+    pub sort: ListSort,
+    pub order: SortOrder,
+    pub keys: Vec<CursorKey>,
+    pub id: u64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum CursorKey {
+    Int(i64),
+    Text(String),
+}
+
+impl ListCursor {
+    /// opaque, url-safe representation of the cursor.
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        BASE64_URL_SAFE_NO_PAD.encode(json)
+    }
+
+    pub fn decode(value: &str) -> AppResult<Self> {
+        BASE64_URL_SAFE_NO_PAD
+            .decode(value)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or(Error::ServerError("invalid cursor".to_string()))
+    }
+}
+
+/// An asset in a folder listing.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct FolderEntry {
+    pub name: String,
+    pub path: String,
+
+    #[serde(rename = "type")]
+    pub entry_type: EntryType,
+
+    /// size of files (bytes).
+    pub size: Option<u64>,
+    pub mime: Option<String>,
+
+    /// unix timestamps. Not known for assets created by older PPDRIVE versions.
+    pub created_at: Option<i64>,
+    pub updated_at: Option<i64>,
+
+    pub public: bool,
+
+    /// url path where the asset is read.
+    pub url: String,
+}
+
+/// A page of a folder's entries.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct FolderListing {
+    pub path: String,
+    pub entries: Vec<FolderEntry>,
+
+    /// cursor of the next page. There are no more entries when it's not set.
+    pub next_cursor: Option<String>,
+}
+
+impl FolderListing {
+    /// render the listing as a HTML page of links.
+    pub fn to_html(&self) -> String {
+        let items: Vec<String> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    "<li><a href='{}'>{}</a></li>",
+                    escape_html(&entry.url),
+                    escape_html(&entry.name)
+                )
+            })
+            .collect();
+
+        let content = if items.is_empty() {
+            "<p>No content found.</p>".to_string()
+        } else {
+            format!("<ul>{}</ul>", items.join("\n"))
+        };
+
+        format!("<!DOCTYPE html>\n<html>\n{content}\n</html>\n")
+    }
+}
+
+/// Query of an asset read.
+#[derive(Deserialize, Serialize, Default, Clone, Debug)]
+pub struct ReadAssetOptions {
+    /// version of a file to be read, instead of its current content.
+    pub version: Option<u64>,
+}
+
+/// A previous content of a file.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AssetVersion {
+    pub version: u64,
+
+    /// size of the content (bytes).
+    pub size: Option<u64>,
+    pub mime: Option<String>,
+
+    /// unix timestamp of when the content was uploaded.
+    pub created_at: Option<i64>,
+}
+
+/// Query of an asset deletion.
+#[derive(Deserialize, Serialize, Default, Clone, Debug)]
+pub struct DeleteAssetOptions {
+    /// delete the asset right away, instead of moving it to the trash.
+    #[serde(default)]
+    pub permanent: bool,
+}
+
+/// An asset in the trash of a bucket.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct TrashEntry {
+    pub id: u64,
+
+    /// path the asset was deleted from.
+    pub path: String,
+
+    #[serde(rename = "type")]
+    pub entry_type: EntryType,
+
+    /// size of files (bytes).
+    pub size: Option<u64>,
+
+    /// unix timestamps of the deletion, and of when the asset is purged from the trash.
+    pub deleted_at: i64,
+    pub expires_at: i64,
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+static USERNAME: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[a-zA-Z0-9_.-]+$").unwrap());
 static HAS_NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\d").unwrap());
 static HAS_SPECIAL: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r#"[!@#$%^&*(),.?":{}|<>]"#).unwrap());
 
+fn validate_username(username: &str) -> Result<(), ValidationError> {
+    if !USERNAME.is_match(username) {
+        return Err(ValidationError::new("username_invalid_characters").with_message(
+            "'username' can only contain letters, numbers, '_', '.' and '-'.".into(),
+        ));
+    }
+    Ok(())
+}
+
 fn validate_password_complexity(password: &str) -> Result<(), ValidationError> {
     if !HAS_NUMBER.is_match(password) {
         return Err(ValidationError::new("password_no_number"));
@@ -0,0 +1,266 @@
+//! layered service configuration. A [ServiceConfig] is built from, in order of precedence:
+//! 1. command line flags,
+//! 2. `PPDRIVE_*` environment variables (see each flag's `env` attribute),
+//! 3. the config file (`ppd_config.toml` by default),
+//! 4. default values.
+
+use std::path::{Path, PathBuf};
+
+use clap::{ArgMatches, parser::ValueSource};
+use toml::{Table, Value};
+
+use crate::{AppResult, errors::Error, opts::ServiceConfig};
+
+/// config file read when `--config` is not provided, if it exists in the working directory.
+pub const DEFAULT_CONFIG_FILE: &str = "ppd_config.toml";
+
+/// environment variable holding the path of the config file.
+pub const CONFIG_FILE_ENV: &str = "PPDRIVE_CONFIG";
+
+/// keys of the config file layout used by earlier versions, with the key that replaced them.
+const LEGACY_KEYS: &[(&str, &str, &str)] = &[
+    ("database", "url", "`server.db_url`"),
+    ("database", "manager_port", "the manager's `--port` flag"),
+    ("server", "auth_modes", "`auth.modes`, with lowercase modes"),
+];
+
+/// path of the config file to be loaded. An explicit `path` must exist, while the default
+/// file is skipped when it's missing.
+pub fn config_path(path: Option<&Path>) -> AppResult<Option<PathBuf>> {
+    match path {
+        Some(path) if !path.is_file() => Err(Error::ServerError(format!(
+            "config file '{}' does not exist",
+            path.display()
+        ))),
+        Some(path) => Ok(Some(path.to_path_buf())),
+        None => {
+            let path = PathBuf::from(DEFAULT_CONFIG_FILE);
+            Ok(path.is_file().then_some(path))
+        }
+    }
+}
+
+/// read a config file. Unknown keys are rejected, so typos don't go unnoticed.
+pub fn read_config_file(path: &Path) -> AppResult<Table> {
+    let content = std::fs::read_to_string(path)?;
+    let table: Table = toml::from_str(&content).map_err(|err| {
+        Error::ServerError(format!("invalid config file '{}': {err}", path.display()))
+    })?;
+
+    let legacy = legacy_keys(&table);
+    if !legacy.is_empty() {
+        return Err(Error::ServerError(format!(
+            "config file '{}' uses the old layout: {}",
+            path.display(),
+            legacy.join(", ")
+        )));
+    }
+
+    // check the file on its own, so errors point at the file rather than the merged config
+    Value::Table(table.clone())
+        .try_into::<ServiceConfig>()
+        .map_err(|err| {
+            Error::ServerError(format!("invalid config file '{}': {err}", path.display()))
+        })?;
+
+    Ok(table)
+}
+
+/// keys of `table` from the old config file layout, and how to migrate them.
+fn legacy_keys(table: &Table) -> Vec<String> {
+    let mut keys: Vec<String> = LEGACY_KEYS
+        .iter()
+        .filter(|(section, key, _)| {
+            table
+                .get(*section)
+                .and_then(Value::as_table)
+                .is_some_and(|section| section.contains_key(*key))
+        })
+        .map(|(section, key, new)| format!("`{section}.{key}` was replaced by {new}"))
+        .collect();
+
+    let origins = table
+        .get("server")
+        .and_then(|server| server.get("allowed_origins"));
+
+    if origins.is_some_and(Value::is_str) {
+        keys.push("`server.allowed_origins` is now a list of urls".to_string());
+    }
+
+    keys
+}
+
+/// merge `file` with the values of `parsed` which were set on the command line or by
+/// environment variables. `matches` are the matches `parsed` was created from. Values
+/// clap filled with defaults don't override the file.
+pub fn merge_config(
+    parsed: &ServiceConfig,
+    matches: &ArgMatches,
+    file: Option<Table>,
+) -> AppResult<ServiceConfig> {
+    let mut merged = file.unwrap_or_default();
+
+    let values = Value::try_from(parsed).map_err(|err| Error::ServerError(err.to_string()))?;
+    if let Value::Table(values) = values {
+        merge_tables(&mut merged, explicit_values(&values, matches));
+    }
+
+    let mut config: ServiceConfig = Value::Table(merged)
+        .try_into()
+        .map_err(|err| Error::ServerError(format!("invalid config: {err}")))?;
+
+    config.ty = parsed.ty;
+    config.auto_install = parsed.auto_install;
+    config.reload_deps = parsed.reload_deps;
+
+    Ok(config)
+}
+
+/// build the effective config of a service: the config file at `path` (or the default
+/// file), overridden by environment variables and command line flags.
+pub fn load_config(
+    parsed: &ServiceConfig,
+    matches: &ArgMatches,
+    path: Option<&Path>,
+) -> AppResult<ServiceConfig> {
+    let file = match config_path(path)? {
+        Some(path) => {
+            tracing::debug!("loading config file '{}'", path.display());
+            Some(read_config_file(&path)?)
+        }
+        None => None,
+    };
+
+    merge_config(parsed, matches, file)
+}
+
+/// serialize a config in the config file format.
+pub fn to_toml(config: &ServiceConfig) -> AppResult<String> {
+    toml::to_string_pretty(config).map_err(|err| Error::ServerError(err.to_string()))
+}
+
+/// keep the leaves of `table` whose argument was set on the command line or by an
+/// environment variable. Leaf keys are the ids of the arguments.
+fn explicit_values(table: &Table, matches: &ArgMatches) -> Table {
+    let mut explicit = Table::new();
+
+    for (key, value) in table {
+        match value {
+            Value::Table(sub) => {
+                let sub = explicit_values(sub, matches);
+                if !sub.is_empty() {
+                    explicit.insert(key.clone(), Value::Table(sub));
+                }
+            }
+            _ => {
+                let known = matches.ids().any(|id| id.as_str() == key);
+                if known
+                    && matches!(
+                        matches.value_source(key),
+                        Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+                    )
+                {
+                    explicit.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+
+    explicit
+}
+
+/// merge `overlay` into `base`. Tables are merged recursively, other values are replaced.
+fn merge_tables(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(overlay)) => merge_tables(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{Args, Command, FromArgMatches};
+
+    use super::*;
+    use crate::opts::{ServiceAuthConfig, ServiceAuthMode, ServiceBaseConfig};
+
+    fn parse(args: &[&str]) -> (ServiceConfig, ArgMatches) {
+        let cmd = ServiceAuthConfig::augment_args(ServiceBaseConfig::augment_args(
+            Command::new("launch"),
+        ));
+        let matches = cmd.get_matches_from(std::iter::once("launch").chain(args.iter().copied()));
+
+        let config = ServiceConfig {
+            base: ServiceBaseConfig::from_arg_matches(&matches).unwrap(),
+            auth: ServiceAuthConfig::from_arg_matches(&matches).unwrap(),
+            ..Default::default()
+        };
+
+        (config, matches)
+    }
+
+    const FILE: &str = r#"
+        [server]
+        db_url = "postgres://localhost/ppdrive"
+        port = 6000
+
+        [auth]
+        modes = ["client", "direct"]
+
+        [auth.password]
+        memory = 4096
+    "#;
+
+    #[test]
+    fn test_config_layers() {
+        let file: Table = toml::from_str(FILE).unwrap();
+        let (parsed, matches) = parse(&["--port", "7000", "--argon2-iterations", "4"]);
+        let config = merge_config(&parsed, &matches, Some(file)).unwrap();
+
+        // flags override the file, which overrides defaults
+        assert_eq!(config.base.port, 7000);
+        assert_eq!(config.base.db_url, "postgres://localhost/ppdrive");
+        assert_eq!(
+            config.auth.modes,
+            vec![ServiceAuthMode::Client, ServiceAuthMode::Direct]
+        );
+        assert_eq!(config.auth.password.memory, 4096);
+        assert_eq!(config.auth.password.iterations, 4);
+        assert_eq!(config.auth.access_exp, ServiceAuthConfig::default().access_exp);
+    }
+
+    #[test]
+    fn test_config_unknown_keys() {
+        let file: Table = toml::from_str("[server]\nportt = 6000").unwrap();
+        let (parsed, matches) = parse(&[]);
+
+        assert!(merge_config(&parsed, &matches, Some(file)).is_err());
+    }
+
+    #[test]
+    fn test_config_legacy_keys() {
+        let file: Table = toml::from_str(
+            r#"
+            [database]
+            url = "sqlite://db.sqlite"
+
+            [server]
+            allowed_origins = "*"
+            auth_modes = ["Client"]
+            "#,
+        )
+        .unwrap();
+
+        let keys = legacy_keys(&file);
+        assert_eq!(keys.len(), 3);
+        assert!(keys[0].contains("`database.url`") && keys[0].contains("`server.db_url`"));
+        assert!(keys[1].contains("`server.auth_modes`") && keys[1].contains("`auth.modes`"));
+        assert!(keys[2].contains("`server.allowed_origins`"));
+
+        assert!(legacy_keys(&toml::from_str(FILE).unwrap()).is_empty());
+    }
+}
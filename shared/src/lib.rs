@@ -1,4 +1,5 @@
 use crate::errors::Error;
+pub mod config;
 pub mod errors;
 pub mod opts;
 pub mod plugin;
@@ -7,6 +8,12 @@ pub mod tools;
 #[cfg(feature = "api")]
 pub mod api;
 
+#[cfg(feature = "sigv4")]
+pub mod sigv4;
+
+#[cfg(feature = "sigv4")]
+pub mod xml;
+
 pub type AppResult<T> = Result<T, Error>;
 
 #[cfg(feature = "logger")]
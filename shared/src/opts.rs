@@ -1,18 +1,26 @@
 use bincode::{Decode, Encode, config};
 use clap::{Args, ValueEnum};
 use constants::*;
-use std::fmt::Display;
-use tokio::{io::AsyncWriteExt, net::TcpStream};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, net::IpAddr, str::FromStr};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
 
 use crate::{AppResult, errors::Error};
 
 #[derive(
-    Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Default, Debug, Encode, Decode,
+    Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Default, Debug, Encode, Decode, Serialize, Deserialize,
 )]
+#[serde(rename_all = "lowercase")]
 pub enum ServiceType {
     #[default]
     Rest,
     Grpc,
+
+    /// S3-compatible API, authenticated with client credentials.
+    S3,
 }
 
 impl Display for ServiceType {
@@ -20,6 +28,7 @@ impl Display for ServiceType {
         let o = match self {
             ServiceType::Rest => "rest",
             ServiceType::Grpc => "grpc",
+            ServiceType::S3 => "s3",
         };
 
         write!(f, "{o}")
@@ -27,8 +36,9 @@ impl Display for ServiceType {
 }
 
 #[derive(
-    Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug, Encode, Decode, Default,
+    Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug, Encode, Decode, Default, Serialize, Deserialize,
 )]
+#[serde(rename_all = "lowercase")]
 pub enum ServiceAuthMode {
     #[default]
     Client,
@@ -52,24 +62,90 @@ impl Display for ServiceAuthMode {
     }
 }
 
+/// Permissions granted to a client key. Keys without scopes have all of them.
+#[derive(
+    Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug, Encode, Decode, Serialize, Deserialize,
+)]
+pub enum ClientScope {
+    /// read users created by the client.
+    #[value(name = "users:read")]
+    #[serde(rename = "users:read")]
+    UsersRead,
+
+    /// create, log in and delete users.
+    #[value(name = "users:write")]
+    #[serde(rename = "users:write")]
+    UsersWrite,
+
+    /// create buckets for the client or its users.
+    #[value(name = "buckets:create")]
+    #[serde(rename = "buckets:create")]
+    BucketsCreate,
+
+    /// read assets and uploads.
+    #[value(name = "assets:read")]
+    #[serde(rename = "assets:read")]
+    AssetsRead,
+
+    /// create, upload and delete assets.
+    #[value(name = "assets:write")]
+    #[serde(rename = "assets:write")]
+    AssetsWrite,
+}
+
+impl Display for ClientScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use ClientScope::*;
+
+        let s = match self {
+            UsersRead => "users:read",
+            UsersWrite => "users:write",
+            BucketsCreate => "buckets:create",
+            AssetsRead => "assets:read",
+            AssetsWrite => "assets:write",
+        };
+
+        write!(f, "{s}")
+    }
+}
+
+impl FromStr for ClientScope {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <Self as ValueEnum>::from_str(s, false)
+            .map_err(|_| Error::ServerError(format!("unknown client scope '{s}'")))
+    }
+}
+
 /// configuration for each service created.
-#[derive(Debug, Args, Encode, Decode, Clone)]
+#[derive(Debug, Args, Encode, Decode, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct ServiceBaseConfig {
     /// url of database to be used by ppdrive.
-    #[arg(long("database"), default_value_t = DEFAULT_DB_URL.to_string())]
+    #[arg(long("database"), env = "PPDRIVE_DATABASE_URL", default_value_t = DEFAULT_DB_URL.to_string())]
     pub db_url: String,
 
     /// port on which to run service
-    #[arg(long, default_value_t = DEFAULT_SERVICE_PORT)]
+    #[arg(long, env = "PPDRIVE_PORT", default_value_t = DEFAULT_SERVICE_PORT)]
     pub port: u16,
 
     /// maximum request content size for this service (MB).
-    #[arg(long("max-upload"), default_value_t = DEFAULT_MAX_UPLOAD)]
+    #[arg(long("max-upload"), env = "PPDRIVE_MAX_UPLOAD", default_value_t = DEFAULT_MAX_UPLOAD)]
     pub max_upload_size: f64,
 
     /// urls allowed by CORS policy for this service. if this is not set, we allow all url (*).
-    #[arg(long("allowed-origins"))]
+    #[arg(long("allowed-origins"), env = "PPDRIVE_ALLOWED_ORIGINS", value_delimiter(','))]
     pub allowed_origins: Option<Vec<String>>,
+
+    /// `Cache-Control` header value for public assets. private assets are never cached.
+    #[arg(long("cache-control"), env = "PPDRIVE_CACHE_CONTROL", default_value_t = DEFAULT_CACHE_CONTROL.to_string())]
+    pub cache_control: String,
+
+    /// how long (days) deleted assets are kept in the trash before they are purged. Deleted
+    /// assets are removed right away when this is 0.
+    #[arg(long("trash-retention"), env = "PPDRIVE_TRASH_RETENTION", default_value_t = DEFAULT_TRASH_RETENTION)]
+    pub trash_retention: u64,
 }
 
 impl Default for ServiceBaseConfig {
@@ -79,31 +155,50 @@ impl Default for ServiceBaseConfig {
             port: DEFAULT_SERVICE_PORT,
             max_upload_size: DEFAULT_MAX_UPLOAD,
             allowed_origins: None,
+            cache_control: DEFAULT_CACHE_CONTROL.to_string(),
+            trash_retention: DEFAULT_TRASH_RETENTION,
         }
     }
 }
 
 /// authentication configuration for a service
-#[derive(Debug, Args, Clone, Encode, Decode)]
+#[derive(Debug, Args, Clone, Encode, Decode, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct ServiceAuthConfig {
     /// authentication modes for the service.
-    #[arg(long("auth-modes"), default_values = ["client"], num_args(1..=4), value_delimiter(','))]
+    #[arg(long("auth-modes"), env = "PPDRIVE_AUTH_MODES", default_values = ["client"], num_args(1..=4), value_delimiter(','))]
     pub modes: Vec<ServiceAuthMode>,
 
     /// JWT access token expiration for the service (seconds).
-    #[arg(long, default_value_t = DEFAULT_ACCESS_TOKEN_EXP)]
+    #[arg(long, env = "PPDRIVE_ACCESS_EXP", default_value_t = DEFAULT_ACCESS_TOKEN_EXP)]
     pub access_exp: i64,
 
     /// JWT refresh token expiration for the service (seconds).
-    #[arg(long, default_value_t = DEFAULT_REFRESH_TOKEN_EXP)]
+    #[arg(long, env = "PPDRIVE_REFRESH_EXP", default_value_t = DEFAULT_REFRESH_TOKEN_EXP)]
     pub refresh_exp: i64,
 
     /// external url to be used for authentication.
-    #[arg(long("auth-url"))]
+    #[arg(long("auth-url"), env = "PPDRIVE_AUTH_URL")]
     pub url: Option<String>,
 
-    #[arg(long("jwt-bearer"), default_value_t = DEFAULT_JWT_BEARER.to_string())]
+    /// how long (seconds) users resolved by `--auth-url` are cached.
+    #[arg(long("auth-url-ttl"), env = "PPDRIVE_AUTH_URL_TTL", default_value_t = DEFAULT_AUTH_URL_TTL)]
+    pub url_ttl: u64,
+
+    #[arg(long("jwt-bearer"), env = "PPDRIVE_JWT_BEARER", default_value_t = DEFAULT_JWT_BEARER.to_string())]
     pub bearer: String,
+
+    #[command(flatten)]
+    pub zero: ZeroModeConfig,
+
+    #[command(flatten)]
+    pub oidc: OidcConfig,
+
+    #[command(flatten)]
+    pub password: PasswordHashConfig,
+
+    #[command(flatten)]
+    pub reset: PasswordResetConfig,
 }
 
 impl Default for ServiceAuthConfig {
@@ -113,17 +208,156 @@ impl Default for ServiceAuthConfig {
             access_exp: DEFAULT_ACCESS_TOKEN_EXP,
             refresh_exp: DEFAULT_REFRESH_TOKEN_EXP,
             url: None,
+            url_ttl: DEFAULT_AUTH_URL_TTL,
             bearer: DEFAULT_JWT_BEARER.to_string(),
+            zero: ZeroModeConfig::default(),
+            oidc: OidcConfig::default(),
+            password: PasswordHashConfig::default(),
+            reset: PasswordResetConfig::default(),
+        }
+    }
+}
+
+/// delivery and lifetime of password reset tokens (direct mode).
+#[derive(Debug, Args, Clone, Encode, Decode, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordResetConfig {
+    /// where reset tokens are delivered: `log` (service log), `file:<path>` (JSON lines)
+    /// or a webhook url receiving the token as JSON.
+    #[arg(long("reset-notifier"), env = "PPDRIVE_RESET_NOTIFIER", default_value_t = DEFAULT_RESET_NOTIFIER.to_string())]
+    pub notifier: String,
+
+    /// how long (seconds) reset tokens are valid.
+    #[arg(long("reset-token-exp"), env = "PPDRIVE_RESET_TOKEN_EXP", default_value_t = DEFAULT_RESET_TOKEN_EXP)]
+    pub exp: i64,
+}
+
+impl Default for PasswordResetConfig {
+    fn default() -> Self {
+        Self {
+            notifier: DEFAULT_RESET_NOTIFIER.to_string(),
+            exp: DEFAULT_RESET_TOKEN_EXP,
+        }
+    }
+}
+
+/// Argon2id parameters used to hash user passwords. Hashes created with other parameters
+/// are upgraded when the user logs in.
+#[derive(Debug, Args, Clone, Encode, Decode, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordHashConfig {
+    /// memory cost (KiB).
+    #[arg(long("argon2-memory"), env = "PPDRIVE_ARGON2_MEMORY", default_value_t = DEFAULT_ARGON2_MEMORY)]
+    pub memory: u32,
+
+    /// number of iterations.
+    #[arg(long("argon2-iterations"), env = "PPDRIVE_ARGON2_ITERATIONS", default_value_t = DEFAULT_ARGON2_ITERATIONS)]
+    pub iterations: u32,
+
+    /// degree of parallelism.
+    #[arg(long("argon2-parallelism"), env = "PPDRIVE_ARGON2_PARALLELISM", default_value_t = DEFAULT_ARGON2_PARALLELISM)]
+    pub parallelism: u32,
+}
+
+impl Default for PasswordHashConfig {
+    fn default() -> Self {
+        Self {
+            memory: DEFAULT_ARGON2_MEMORY,
+            iterations: DEFAULT_ARGON2_ITERATIONS,
+            parallelism: DEFAULT_ARGON2_PARALLELISM,
+        }
+    }
+}
+
+/// validation of user tokens issued by an OpenID Connect provider. Enabled when `issuer`
+/// is set.
+#[derive(Debug, Args, Clone, Encode, Decode, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OidcConfig {
+    /// issuer of the tokens, checked against the `iss` claim.
+    #[arg(long("oidc-issuer"), env = "PPDRIVE_OIDC_ISSUER")]
+    pub issuer: Option<String>,
+
+    /// expected `aud` claim. The audience is not checked when it's not set.
+    #[arg(long("oidc-audience"), env = "PPDRIVE_OIDC_AUDIENCE")]
+    pub audience: Option<String>,
+
+    /// url or file path of the issuer's JWKS. Discovered from the issuer's
+    /// `.well-known/openid-configuration` when it's not set.
+    #[arg(long("oidc-jwks"), env = "PPDRIVE_OIDC_JWKS")]
+    pub jwks: Option<String>,
+
+    /// claim that identifies the user.
+    #[arg(long("oidc-user-claim"), env = "PPDRIVE_OIDC_USER_CLAIM", default_value_t = DEFAULT_OIDC_USER_CLAIM.to_string())]
+    pub user_claim: String,
+
+    /// how long (seconds) the JWKS is cached.
+    #[arg(long("oidc-jwks-ttl"), env = "PPDRIVE_OIDC_JWKS_TTL", default_value_t = DEFAULT_OIDC_JWKS_TTL)]
+    pub jwks_ttl: u64,
+}
+
+impl Default for OidcConfig {
+    fn default() -> Self {
+        Self {
+            issuer: None,
+            audience: None,
+            jwks: None,
+            user_claim: DEFAULT_OIDC_USER_CLAIM.to_string(),
+            jwks_ttl: DEFAULT_OIDC_JWKS_TTL,
         }
     }
 }
 
-#[derive(Encode, Decode, Clone, Debug, Default)]
+/// write protections for services running in zero (no auth) mode.
+#[derive(Debug, Args, Clone, Encode, Decode, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ZeroModeConfig {
+    /// folder where zero mode assets are saved.
+    #[arg(long("zero-root"), env = "PPDRIVE_ZERO_ROOT", default_value_t = DEFAULT_ZERO_ROOT.to_string())]
+    pub root: String,
+
+    /// reject all write requests in zero mode.
+    #[arg(long("zero-read-only"), env = "PPDRIVE_ZERO_READ_ONLY", default_value_t = false)]
+    pub read_only: bool,
+
+    /// IP addresses allowed to write in zero mode. Anyone can write when the list is empty.
+    #[arg(long("zero-write-ips"), env = "PPDRIVE_ZERO_WRITE_IPS", value_delimiter(','))]
+    pub write_ips: Vec<IpAddr>,
+}
+
+impl ZeroModeConfig {
+    /// whether a request from `ip` can write assets.
+    pub fn can_write(&self, ip: &IpAddr) -> bool {
+        !self.read_only && (self.write_ips.is_empty() || self.write_ips.contains(ip))
+    }
+}
+
+impl Default for ZeroModeConfig {
+    fn default() -> Self {
+        Self {
+            root: DEFAULT_ZERO_ROOT.to_string(),
+            read_only: false,
+            write_ips: Vec::new(),
+        }
+    }
+}
+
+/// configuration of a service. The `server` and `auth` sections can be loaded from a
+/// config file (see [crate::config]).
+#[derive(Encode, Decode, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct ServiceConfig {
+    #[serde(skip)]
     pub ty: ServiceType,
+
+    #[serde(rename = "server")]
     pub base: ServiceBaseConfig,
     pub auth: ServiceAuthConfig,
+
+    #[serde(skip)]
     pub auto_install: bool,
+
+    #[serde(skip)]
     pub reload_deps: bool,
 }
 
@@ -133,18 +367,35 @@ pub struct ServiceInfo {
     pub port: u16,
     pub auth_modes: Vec<ServiceAuthMode>,
     pub ty: ServiceType,
+
+    /// whether the service is running. Recorded services may be stopped.
+    pub running: bool,
+
+    /// whether the service is launched when the manager starts.
+    pub autostart: bool,
 }
 
 #[derive(Encode, Decode, Debug)]
 /// service management request type
 pub enum ServiceRequest {
-    /// add a new service with the provided config
-    Add(ServiceConfig),
+    /// add a new service with the provided config.
+    ///
+    /// accepts the config and whether the service is launched again when the manager starts.
+    Add(Box<ServiceConfig>, bool),
+
+    /// record a service without starting it. It's launched when the manager starts.
+    Save(Box<ServiceConfig>),
+
+    /// start a recorded service with the given id.
+    Launch(u8),
 
-    /// cancel and remove a service with the given id
-    Cancel(u8),
+    /// stop a service with the given id.
+    ///
+    /// accepts the id and whether the service is removed from recorded services. Otherwise it's
+    /// kept, without autostart, so it can be started again.
+    Cancel(u8, bool),
 
-    /// list running services
+    /// list running and recorded services
     List,
 
     /// stop ppdrive
@@ -163,17 +414,92 @@ pub enum ServiceRequest {
     /// get list of service's clients.
     GetClientList(u8),
 
+    /// create a named key for a client.
+    ///
+    /// accepts `service_id`, `client_id` and the key's options.
+    AddClientKey(u8, String, NewClientKey),
+
+    /// list keys of a client.
+    ///
+    /// accepts `service_id` and `client_id`.
+    ListClientKeys(u8, String),
+
+    /// revoke a client key.
+    ///
+    /// accepts `service_id` and `key_id`.
+    RevokeClientKey(u8, String),
+
+    /// make a new secret key current and re-issue client tokens with it.
+    ///
+    /// accepts the number of previous keys to keep.
+    RotateSecrets(u8),
+
+    /// create an admin user for the service's admin router.
+    ///
+    /// accepts `service_id`, `username` and `password`.
+    CreateAdmin(u8, String, String),
+
     /// check if ppdrive manager is running.
     CheckStatus
 }
 
+/// maximum size (bytes) of an encoded [ServiceRequest].
+const MAX_REQUEST_SIZE: u32 = 1024 * 1024;
+
+impl ServiceRequest {
+    /// encode the request, prefixed with its length as a big-endian `u32`, so it can be read
+    /// whole however it's split over the connection.
+    pub fn to_frame(&self) -> AppResult<Vec<u8>> {
+        let data = bincode::encode_to_vec(self, config::standard())
+            .map_err(|err| Error::ServerError(format!("unable to encode request: {err}")))?;
+
+        let len = u32::try_from(data.len())
+            .ok()
+            .filter(|len| *len <= MAX_REQUEST_SIZE)
+            .ok_or_else(|| Error::ServerError("request is too large".to_string()))?;
+
+        let mut frame = len.to_be_bytes().to_vec();
+        frame.extend(data);
+        Ok(frame)
+    }
+
+    /// read a request written by [ServiceRequest::to_frame]. Returns `None` if the connection
+    /// is closed before a request is sent.
+    pub async fn read(socket: &mut TcpStream) -> AppResult<Option<Self>> {
+        let mut len = [0u8; 4];
+        match socket.read_exact(&mut len).await {
+            Ok(_) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+
+        let len = u32::from_be_bytes(len);
+        if len > MAX_REQUEST_SIZE {
+            return Err(Error::ServerError(format!(
+                "request of {len} bytes exceeds the limit of {MAX_REQUEST_SIZE} bytes"
+            )));
+        }
+
+        let mut data = vec![0u8; len as usize];
+        socket.read_exact(&mut data).await?;
+
+        let (req, _) = bincode::decode_from_slice(&data, config::standard())
+            .map_err(|err| Error::ServerError(format!("invalid request: {err}")))?;
+
+        Ok(Some(req))
+    }
+}
+
 #[derive(Encode, Decode)]
 pub struct ClientDetails {
     id: String,
     token: String,
+
+    /// secret key of S3 requests, with `id` as the access key id.
+    s3_secret: String,
 }
 
-#[derive(Encode, Decode)]
+#[derive(Encode, Decode, Serialize, Deserialize)]
 pub struct ClientInfo {
     pub id: String,
     pub name: String,
@@ -181,21 +507,74 @@ pub struct ClientInfo {
     pub max_bucket_size: Option<f64>
 }
 
+/// options of a new client key.
+#[derive(Encode, Decode, Debug)]
+pub struct NewClientKey {
+    pub name: String,
+
+    /// scopes granted to the key. The key has all scopes when empty.
+    pub scopes: Vec<ClientScope>,
+
+    /// days after which the key can't be used.
+    pub expires_in_days: Option<u32>,
+}
+
+#[derive(Encode, Decode, Serialize, Deserialize)]
+pub struct ClientKeyInfo {
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<ClientScope>,
+
+    /// unix timestamp after which the key can't be used.
+    pub expires_at: Option<i64>,
+
+    /// unix timestamp of the last request made with the key.
+    pub last_used: Option<i64>,
+    pub created_at: String,
+}
+
+/// A client token re-issued with the current secret key.
+#[derive(Encode, Decode)]
+pub struct ReissuedToken {
+    pub service_id: u8,
+    pub client_id: String,
+
+    /// id of the client key, or `None` for the client's own token.
+    pub key_id: Option<String>,
+    pub token: String,
+    pub s3_secret: String,
+}
+
 impl ClientDetails {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
     pub fn token(&self) -> &str {
         &self.token
     }
+
+    pub fn s3_secret(&self) -> &str {
+        &self.s3_secret
+    }
 }
 
-impl From<(String, String)> for ClientDetails {
-    fn from((key, token): (String, String)) -> Self {
-        ClientDetails { id: key, token }
+impl From<(String, String, String)> for ClientDetails {
+    fn from((key, token, s3_secret): (String, String, String)) -> Self {
+        ClientDetails {
+            id: key,
+            token,
+            s3_secret,
+        }
     }
 }
 
 impl Display for ClientDetails {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let s = format!("id: {}\ntoken: {}", self.id, self.token);
+        let s = format!(
+            "id: {}\ntoken: {}\ns3 secret: {}",
+            self.id, self.token, self.s3_secret
+        );
         write!(f, "{s}")
     }
 }
@@ -266,4 +645,15 @@ mod constants {
     pub const DEFAULT_ACCESS_TOKEN_EXP: i64 = 900;
     pub const DEFAULT_REFRESH_TOKEN_EXP: i64 = 86400;
     pub const DEFAULT_JWT_BEARER: &str = "Bearer";
+    pub const DEFAULT_CACHE_CONTROL: &str = "public, max-age=3600";
+    pub const DEFAULT_TRASH_RETENTION: u64 = 30;
+    pub const DEFAULT_ZERO_ROOT: &str = "zero";
+    pub const DEFAULT_AUTH_URL_TTL: u64 = 300;
+    pub const DEFAULT_OIDC_USER_CLAIM: &str = "sub";
+    pub const DEFAULT_OIDC_JWKS_TTL: u64 = 3600;
+    pub const DEFAULT_ARGON2_MEMORY: u32 = 19456;
+    pub const DEFAULT_ARGON2_ITERATIONS: u32 = 2;
+    pub const DEFAULT_ARGON2_PARALLELISM: u32 = 1;
+    pub const DEFAULT_RESET_NOTIFIER: &str = "log";
+    pub const DEFAULT_RESET_TOKEN_EXP: i64 = 900;
 }
@@ -0,0 +1,248 @@
+//! AWS Signature Version 4 helpers, used to sign and verify requests to S3-compatible APIs.
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+pub const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+
+/// Payload hash used when the request body is not signed.
+pub const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+/// A request in the form it's signed.
+pub struct CanonicalRequest<'a> {
+    pub method: &'a str,
+
+    /// uri-encoded path of the request.
+    pub path: &'a str,
+
+    /// decoded query parameters.
+    pub query: &'a [(String, String)],
+
+    /// signed headers. names must be lowercase.
+    pub headers: &'a [(String, String)],
+
+    /// hex encoded sha256 of the request body, or [UNSIGNED_PAYLOAD].
+    pub payload_hash: &'a str,
+}
+
+impl CanonicalRequest<'_> {
+    /// semicolon separated list of signed header names.
+    pub fn signed_headers(&self) -> String {
+        let mut names: Vec<&str> = self.headers.iter().map(|(k, _)| k.as_str()).collect();
+        names.sort_unstable();
+        names.join(";")
+    }
+
+    fn canonical(&self) -> String {
+        let mut query: Vec<(String, String)> = self
+            .query
+            .iter()
+            .map(|(k, v)| (uri_encode(k, true), uri_encode(v, true)))
+            .collect();
+        query.sort();
+
+        let query = query
+            .iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let mut headers: Vec<(&str, String)> = self
+            .headers
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.split_whitespace().collect::<Vec<_>>().join(" ")))
+            .collect();
+        headers.sort();
+
+        let headers: String = headers
+            .iter()
+            .map(|(k, v)| format!("{k}:{v}\n"))
+            .collect();
+
+        format!(
+            "{}\n{}\n{query}\n{headers}\n{}\n{}",
+            self.method,
+            self.path,
+            self.signed_headers(),
+            self.payload_hash
+        )
+    }
+}
+
+/// Scope of a signature, i.e the date, region and service it's valid for.
+pub struct SigningScope<'a> {
+    /// request timestamp in `YYYYMMDDTHHMMSSZ` format.
+    pub amz_date: &'a str,
+    pub region: &'a str,
+    pub service: &'a str,
+}
+
+impl SigningScope<'_> {
+    fn date(&self) -> &str {
+        self.amz_date.get(..8).unwrap_or(self.amz_date)
+    }
+
+    pub fn credential_scope(&self) -> String {
+        format!("{}/{}/{}/aws4_request", self.date(), self.region, self.service)
+    }
+}
+
+/// current time in `YYYYMMDDTHHMMSSZ` format.
+pub fn amz_date() -> String {
+    Utc::now().format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// hex encoded signature of a request.
+pub fn signature(secret_key: &str, scope: &SigningScope, request: &CanonicalRequest) -> String {
+    let mac = signature_mac(secret_key, scope, request);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// checks (in constant time) that `signature` is the hex encoded signature of a request.
+pub fn verify(
+    secret_key: &str,
+    scope: &SigningScope,
+    request: &CanonicalRequest,
+    signature: &str,
+) -> bool {
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+
+    signature_mac(secret_key, scope, request)
+        .verify_slice(&signature)
+        .is_ok()
+}
+
+fn signature_mac(secret_key: &str, scope: &SigningScope, request: &CanonicalRequest) -> Hmac<Sha256> {
+    let string_to_sign = format!(
+        "{ALGORITHM}\n{}\n{}\n{}",
+        scope.amz_date,
+        scope.credential_scope(),
+        sha256_hex(request.canonical().as_bytes())
+    );
+
+    let key = hmac(format!("AWS4{secret_key}").as_bytes(), scope.date().as_bytes());
+    let key = hmac(&key, scope.region.as_bytes());
+    let key = hmac(&key, scope.service.as_bytes());
+    let key = hmac(&key, b"aws4_request");
+
+    let mut mac = new_mac(&key);
+    mac.update(string_to_sign.as_bytes());
+    mac
+}
+
+/// value of the `Authorization` header for a request.
+pub fn authorization(
+    access_key: &str,
+    secret_key: &str,
+    scope: &SigningScope,
+    request: &CanonicalRequest,
+) -> String {
+    format!(
+        "{ALGORITHM} Credential={access_key}/{}, SignedHeaders={}, Signature={}",
+        scope.credential_scope(),
+        request.signed_headers(),
+        signature(secret_key, scope, request)
+    )
+}
+
+/// Parts of an `Authorization` header, as sent by a SigV4 client.
+pub struct Authorization<'a> {
+    pub access_key: &'a str,
+
+    /// date (`YYYYMMDD`) of the credential scope.
+    pub date: &'a str,
+    pub region: &'a str,
+    pub service: &'a str,
+    pub signed_headers: Vec<&'a str>,
+    pub signature: &'a str,
+}
+
+impl<'a> Authorization<'a> {
+    /// parse an `Authorization` header value. returns `None` if it's not a valid SigV4 header.
+    pub fn parse(value: &'a str) -> Option<Self> {
+        let params = value.strip_prefix(ALGORITHM)?.trim();
+
+        let (mut credential, mut signed_headers, mut signature) = (None, None, None);
+        for param in params.split(',') {
+            match param.trim().split_once('=')? {
+                ("Credential", v) => credential = Some(v),
+                ("SignedHeaders", v) => signed_headers = Some(v),
+                ("Signature", v) => signature = Some(v),
+                _ => {}
+            }
+        }
+
+        let mut credential = credential?.split('/');
+        let auth = Authorization {
+            access_key: credential.next()?,
+            date: credential.next()?,
+            region: credential.next()?,
+            service: credential.next()?,
+            signed_headers: signed_headers?.split(';').collect(),
+            signature: signature?,
+        };
+
+        match credential.next() {
+            Some("aws4_request") => Some(auth),
+            _ => None,
+        }
+    }
+}
+
+/// percent-encodes a value as required by SigV4. `/` is left as is if `encode_slash` is false
+/// (i.e when encoding paths).
+pub fn uri_encode(value: &str, encode_slash: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if !encode_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    encoded
+}
+
+/// decodes a percent-encoded value.
+pub fn uri_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn new_mac(key: &[u8]) -> Hmac<Sha256> {
+    // HMAC accepts keys of any length
+    <Hmac<Sha256> as Mac>::new_from_slice(key).expect("invalid hmac key")
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = new_mac(key);
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
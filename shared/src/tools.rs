@@ -1,81 +1,397 @@
-use std::path::{Path, PathBuf};
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
 
 use crate::{AppResult, errors::Error};
-use std::io::SeekFrom;
 
-use chacha20poly1305::{AeadCore, KeyInit, XChaCha20Poly1305, aead::OsRng};
-use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use chacha20poly1305::{KeyInit, XChaCha20Poly1305, aead::OsRng};
+use tokio::io::AsyncWriteExt;
 
 pub const SECRETS_FILENAME: &str = ".ppdrive_secret";
 
-/// App secrets sharable across [AppState](crate::AppState).
-pub struct AppSecrets {
+/// path of the secrets file, instead of [SECRETS_FILENAME] in [root_dir].
+pub const SECRETS_FILE_ENV: &str = "PPDRIVE_SECRETS_FILE";
+
+/// base64 encoded secret key, used with [JWT_SECRET_ENV] instead of a secrets file.
+pub const SECRET_KEY_ENV: &str = "PPDRIVE_SECRET_KEY";
+
+/// base64 encoded jwt secret.
+pub const JWT_SECRET_ENV: &str = "PPDRIVE_JWT_SECRET";
+
+/// id of the key provided with [SECRET_KEY_ENV] and [JWT_SECRET_ENV]. Defaults to 1.
+pub const SECRET_KEY_ID_ENV: &str = "PPDRIVE_SECRET_KEY_ID";
+
+/// directory holding `secret_key`, `jwt_secret` and, optionally, `key_id` files, i.e mounted
+/// container secrets.
+pub const SECRETS_DIR_ENV: &str = "PPDRIVE_SECRETS_DIR";
+
+/// set to `production` to refuse generating secrets that are missing.
+pub const ENV_MODE_ENV: &str = "PPDRIVE_ENV";
+
+/// length (bytes) of secret keys and jwt secrets.
+const SECRET_LEN: usize = 32;
+
+/// first bytes of a secrets file holding a keyring.
+const SECRETS_MAGIC: &[u8; 4] = b"PPDS";
+const SECRETS_VERSION: u8 = 2;
+
+/// length of secret files written by earlier versions: a secret key, a nonce and a jwt secret.
+const LEGACY_SECRETS_LEN: usize = 32 + 24 + 32;
+
+/// id of the key read from a legacy secrets file. Tokens without a key id were issued with it.
+pub const LEGACY_KEY_ID: u32 = 0;
+
+/// A versioned set of secrets. Tokens carry the id of the key they were issued with.
+pub struct SecretKey {
+    id: u32,
     secret_key: Vec<u8>,
-    secret_nonce: Vec<u8>,
     jwt_secret: Vec<u8>,
+
+    /// fixed nonce of legacy client tokens. Only set for [LEGACY_KEY_ID].
+    nonce: Option<Vec<u8>>,
+}
+
+impl SecretKey {
+    fn generate(id: u32) -> Self {
+        Self {
+            id,
+            secret_key: XChaCha20Poly1305::generate_key(&mut OsRng).to_vec(),
+            jwt_secret: XChaCha20Poly1305::generate_key(&mut OsRng).to_vec(),
+            nonce: None,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn secret_key(&self) -> &[u8] {
+        self.secret_key.as_slice()
+    }
+
+    pub fn jwt_secret(&self) -> &[u8] {
+        self.jwt_secret.as_slice()
+    }
+
+    pub fn nonce(&self) -> Option<&[u8]> {
+        self.nonce.as_deref()
+    }
+}
+
+/// App secrets sharable across [AppState](crate::AppState). Holds a keyring: the current key,
+/// used to issue tokens, and previous keys that are still accepted.
+pub struct AppSecrets {
+    keys: Vec<SecretKey>,
 }
 
 impl AppSecrets {
-    /// Read app secrets from secret file
+    /// Read app secrets from the configured [SecretsSource].
     pub async fn read() -> AppResult<Self> {
-        let secret_file = secret_filename()?;
-        let mut secrets = tokio::fs::File::open(&secret_file).await?;
+        match SecretsSource::detect()? {
+            SecretsSource::Env => {
+                let secret_key = env_value(SECRET_KEY_ENV)?;
+                let jwt_secret = env_value(JWT_SECRET_ENV)?;
+                let id = std::env::var(SECRET_KEY_ID_ENV).ok();
+
+                Self::from_values(SECRET_KEY_ENV, &secret_key, &jwt_secret, id.as_deref())
+            }
+            SecretsSource::Dir(dir) => {
+                let secret_key = read_secret(&dir.join("secret_key")).await?;
+                let jwt_secret = read_secret(&dir.join("jwt_secret")).await?;
+                let id = match dir.join("key_id") {
+                    path if path.is_file() => Some(read_secret(&path).await?),
+                    _ => None,
+                };
+
+                let source = dir.display().to_string();
+                Self::from_values(&source, &secret_key, &jwt_secret, id.as_deref())
+            }
+            SecretsSource::File { path, .. } => {
+                let data = tokio::fs::read(&path).await.map_err(|err| {
+                    Error::ServerError(format!(
+                        "unable to read secrets file {}: {err}",
+                        path.display()
+                    ))
+                })?;
+
+                Self::parse(&data)
+            }
+        }
+    }
 
-        let mut secret_key = [0; 32];
-        let mut nonce = [0; 24];
-        let mut jwt_secret = [0; 32];
+    /// secrets with a single key, from base64 encoded values.
+    fn from_values(
+        source: &str,
+        secret_key: &str,
+        jwt_secret: &str,
+        id: Option<&str>,
+    ) -> AppResult<Self> {
+        let id = match id {
+            Some(id) => id.trim().parse().map_err(|_| {
+                Error::ServerError(format!("invalid key id '{id}' in {source}"))
+            })?,
+            None => LEGACY_KEY_ID + 1,
+        };
+
+        let key = SecretKey {
+            id,
+            secret_key: decode_secret(source, "secret key", secret_key)?,
+            jwt_secret: decode_secret(source, "jwt secret", jwt_secret)?,
+            nonce: None,
+        };
+
+        Ok(Self { keys: vec![key] })
+    }
 
-        secrets.read_exact(&mut secret_key).await?;
+    /// create secrets with a single, new key.
+    pub fn generate() -> Self {
+        Self {
+            keys: vec![SecretKey::generate(LEGACY_KEY_ID + 1)],
+        }
+    }
 
-        secrets.seek(SeekFrom::Start(32)).await?;
-        secrets.read_exact(&mut nonce).await?;
+    /// save secrets to secret file. Secrets provided by environment variables or a secrets
+    /// directory are read-only.
+    pub async fn write(&self) -> AppResult<()> {
+        let secret_file = match SecretsSource::detect()? {
+            SecretsSource::File { path, .. } => path,
+            source => {
+                return Err(Error::ServerError(format!(
+                    "secrets are read from {source} and can't be changed by ppdrive"
+                )));
+            }
+        };
+
+        let mut opts = tokio::fs::OpenOptions::new();
+        opts.create(true).truncate(true).write(true);
+
+        // secrets are only readable by their owner
+        #[cfg(unix)]
+        opts.mode(0o600);
+
+        let mut secrets = opts.open(&secret_file).await?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let permissions = std::fs::Permissions::from_mode(0o600);
+            tokio::fs::set_permissions(&secret_file, permissions).await?;
+        }
+
+        secrets.write_all(&self.to_bytes()).await?;
+        Ok(())
+    }
 
-        secrets.seek(SeekFrom::Start(32 + 24)).await?;
-        secrets.read_exact(&mut jwt_secret).await?;
+    /// environment variables providing the current key, i.e for container deployments.
+    pub fn to_env(&self) -> String {
+        let key = self.current();
 
-        Ok(Self {
-            secret_key: Vec::from(secret_key),
-            secret_nonce: Vec::from(nonce),
-            jwt_secret: Vec::from(jwt_secret),
-        })
+        format!(
+            "{SECRET_KEY_ID_ENV}={}\n{SECRET_KEY_ENV}={}\n{JWT_SECRET_ENV}={}",
+            key.id,
+            BASE64_STANDARD.encode(&key.secret_key),
+            BASE64_STANDARD.encode(&key.jwt_secret)
+        )
     }
 
-    pub fn secret_key(&self) -> &[u8] {
-        self.secret_key.as_slice()
+    /// parse the content of a secrets file, in either the keyring or the legacy format.
+    pub fn parse(data: &[u8]) -> AppResult<Self> {
+        let invalid = || Error::ServerError("invalid secrets file".to_string());
+
+        if data.len() == LEGACY_SECRETS_LEN && !data.starts_with(SECRETS_MAGIC) {
+            let key = SecretKey {
+                id: LEGACY_KEY_ID,
+                secret_key: data[..32].to_vec(),
+                nonce: Some(data[32..56].to_vec()),
+                jwt_secret: data[56..].to_vec(),
+            };
+
+            return Ok(Self { keys: vec![key] });
+        }
+
+        let data = data.strip_prefix(SECRETS_MAGIC).ok_or_else(invalid)?;
+        let (version, data) = data.split_first().ok_or_else(invalid)?;
+        if *version != SECRETS_VERSION {
+            return Err(Error::ServerError(format!(
+                "unsupported secrets file version {version}"
+            )));
+        }
+
+        let (count, mut data) = data.split_first().ok_or_else(invalid)?;
+        let mut keys = Vec::with_capacity(*count as usize);
+
+        for _ in 0..*count {
+            let mut take = |len: usize| {
+                let (value, rest) = data.split_at_checked(len).ok_or_else(invalid)?;
+                data = rest;
+                Ok::<_, Error>(value)
+            };
+
+            let id = u32::from_le_bytes(take(4)?.try_into().map_err(|_| invalid())?);
+            let secret_key = take(SECRET_LEN)?.to_vec();
+            let jwt_secret = take(SECRET_LEN)?.to_vec();
+            let nonce = match take(1)?[0] {
+                0 => None,
+                _ => Some(take(24)?.to_vec()),
+            };
+
+            keys.push(SecretKey {
+                id,
+                secret_key,
+                jwt_secret,
+                nonce,
+            });
+        }
+
+        if keys.is_empty() || !data.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(Self { keys })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = SECRETS_MAGIC.to_vec();
+        data.push(SECRETS_VERSION);
+        data.push(self.keys.len() as u8);
+
+        for key in &self.keys {
+            data.extend_from_slice(&key.id.to_le_bytes());
+            data.extend_from_slice(&key.secret_key);
+            data.extend_from_slice(&key.jwt_secret);
+
+            match &key.nonce {
+                Some(nonce) => {
+                    data.push(1);
+                    data.extend_from_slice(nonce);
+                }
+                None => data.push(0),
+            }
+        }
+
+        data
     }
 
-    pub fn nonce(&self) -> &[u8] {
-        self.secret_nonce.as_slice()
+    /// make a new key current, keeping `keep` previous keys. Tokens issued with dropped
+    /// keys are no longer accepted. Returns the id of the new key.
+    pub fn rotate(&mut self, keep: u8) -> u32 {
+        let id = self.keys.iter().map(|k| k.id).max().unwrap_or(LEGACY_KEY_ID) + 1;
+
+        self.keys.insert(0, SecretKey::generate(id));
+        self.keys.truncate(keep as usize + 1);
+
+        id
     }
 
-    pub fn jwt_secret(&self) -> &[u8] {
-        self.jwt_secret.as_slice()
+    /// the key used to issue new tokens.
+    pub fn current(&self) -> &SecretKey {
+        &self.keys[0]
     }
+
+    /// a current or previous key with the given id.
+    pub fn key(&self, id: u32) -> Option<&SecretKey> {
+        self.keys.iter().find(|k| k.id == id)
+    }
+
+    pub fn keys(&self) -> &[SecretKey] {
+        &self.keys
+    }
+}
+
+/// Where app secrets are read from. Environment variables take precedence over a secrets
+/// directory, which takes precedence over the secrets file.
+pub enum SecretsSource {
+    /// [SECRET_KEY_ENV] and [JWT_SECRET_ENV].
+    Env,
+
+    /// [SECRETS_DIR_ENV].
+    Dir(PathBuf),
+
+    /// [SECRETS_FILE_ENV] (`configured`), or [SECRETS_FILENAME] in [root_dir].
+    File { path: PathBuf, configured: bool },
 }
 
+impl SecretsSource {
+    pub fn detect() -> AppResult<Self> {
+        if std::env::var_os(SECRET_KEY_ENV).is_some() || std::env::var_os(JWT_SECRET_ENV).is_some()
+        {
+            return Ok(SecretsSource::Env);
+        }
+
+        if let Some(dir) = std::env::var_os(SECRETS_DIR_ENV) {
+            return Ok(SecretsSource::Dir(PathBuf::from(dir)));
+        }
+
+        let source = match std::env::var_os(SECRETS_FILE_ENV) {
+            Some(path) => SecretsSource::File {
+                path: PathBuf::from(path),
+                configured: true,
+            },
+            None => SecretsSource::File {
+                path: root_dir()?.join(SECRETS_FILENAME),
+                configured: false,
+            },
+        };
+
+        Ok(source)
+    }
+}
+
+impl Display for SecretsSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SecretsSource::Env => write!(f, "environment variables"),
+            SecretsSource::Dir(dir) => write!(f, "secrets directory {}", dir.display()),
+            SecretsSource::File { path, .. } => write!(f, "secrets file {}", path.display()),
+        }
+    }
+}
+
+/// whether ppdrive runs in production mode ([ENV_MODE_ENV]).
+pub fn is_production() -> bool {
+    std::env::var(ENV_MODE_ENV).is_ok_and(|mode| mode.eq_ignore_ascii_case("production"))
+}
+
+fn env_value(name: &str) -> AppResult<String> {
+    std::env::var(name).map_err(|_| Error::ServerError(format!("{name} is not set")))
+}
+
+async fn read_secret(path: &Path) -> AppResult<String> {
+    tokio::fs::read_to_string(path).await.map_err(|err| {
+        Error::ServerError(format!("unable to read secret {}: {err}", path.display()))
+    })
+}
+
+/// decode a base64 encoded secret, validating its length.
+fn decode_secret(source: &str, name: &str, value: &str) -> AppResult<Vec<u8>> {
+    let secret = BASE64_STANDARD.decode(value.trim()).map_err(|err| {
+        Error::ServerError(format!("{name} in {source} is not valid base64: {err}"))
+    })?;
+
+    if secret.len() != SECRET_LEN {
+        return Err(Error::ServerError(format!(
+            "{name} in {source} must be {SECRET_LEN} bytes, found {}",
+            secret.len()
+        )));
+    }
+
+    Ok(secret)
+}
+
+/// path of the secrets file, used when secrets are not provided by environment variables
+/// or a secrets directory.
 pub fn secret_filename() -> AppResult<PathBuf> {
-    let path = root_dir()?.join(SECRETS_FILENAME);
-    Ok(path)
+    match SecretsSource::detect()? {
+        SecretsSource::File { path, .. } => Ok(path),
+        _ => Ok(root_dir()?.join(SECRETS_FILENAME)),
+    }
 }
 
 pub async fn generate_secret_file() -> AppResult<()> {
-    let secret_key = XChaCha20Poly1305::generate_key(&mut OsRng);
-    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
-    let jwt_secret = XChaCha20Poly1305::generate_key(&mut OsRng);
-
-    let secret_file = secret_filename()?;
-    let mut secrets = tokio::fs::OpenOptions::new()
-        .create(true)
-        .truncate(true)
-        .write(true)
-        .open(&secret_file)
-        .await?;
-
-    secrets.write_all(secret_key.as_slice()).await?;
-    secrets.write_all(nonce.as_slice()).await?;
-    secrets.write_all(jwt_secret.as_slice()).await?;
-
-    Ok(())
+    AppSecrets::generate().write().await
 }
 
 pub fn root_dir() -> AppResult<PathBuf> {
@@ -133,13 +449,27 @@ pub fn mb_to_bytes(value: f64) -> usize {
 
 /// If app secret file does not exist, generate it. Mostly useful
 /// for app initialization.
+///
+/// Secrets are never generated when they are expected elsewhere: a configured secrets file,
+/// environment variables, a secrets directory or, in production mode, the default file.
+/// Existing secrets are validated.
 pub async fn init_secrets() -> Result<(), Error> {
-    let path = secret_filename()?;
-    if !path.is_file() {
+    if let SecretsSource::File { path, configured } = SecretsSource::detect()?
+        && !path.is_file()
+    {
+        if configured || is_production() {
+            return Err(Error::ServerError(format!(
+                "secrets file {} does not exist. generate secrets with 'ppdrive secrets generate' or set {SECRET_KEY_ENV} and {JWT_SECRET_ENV}.",
+                path.display()
+            )));
+        }
+
+        tracing::warn!("generating new secrets file {}", path.display());
         generate_secret_file()
             .await
             .map_err(|err| Error::ServerError(err.to_string()))?;
     }
 
+    AppSecrets::read().await?;
     Ok(())
 }